@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Mock sets shipped as a single `.zip` or `.tar.gz` archive.
+//!
+//! The archive is read into memory and unpacked into a private temporary
+//! directory, which is served like a regular mock directory and removed when
+//! blendwerk exits. Entries escaping the archive root are rejected.
+
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Supported archive formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    TarGz,
+}
+
+impl Format {
+    /// Format of a file or URL path by its extension
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+
+    /// Format of archive content by its magic bytes
+    pub fn detect(content: &[u8]) -> Option<Self> {
+        if content.starts_with(b"PK\x03\x04") {
+            Some(Self::Zip)
+        } else if content.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+fn format(path: &Path) -> Option<Format> {
+    Format::from_name(path.file_name()?.to_str()?)
+}
+
+/// Whether `path` names a file in one of the supported archive formats
+pub fn is_archive(path: &Path) -> bool {
+    path.is_file() && format(path).is_some()
+}
+
+/// Unpacked mock archive, deleted on drop
+#[derive(Debug)]
+pub struct UnpackedArchive {
+    dir: TempDir,
+    root: PathBuf,
+}
+
+impl UnpackedArchive {
+    /// Mock directory inside the archive
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Temporary directory everything was unpacked to
+    pub fn temp_dir(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+/// Unpack a `.zip` or `.tar.gz` archive file.
+///
+/// Archives holding a single top-level directory (as produced by
+/// `zip -r mocks.zip mocks/`) are served from inside that directory.
+pub fn unpack(path: &Path) -> Result<UnpackedArchive> {
+    let format = format(path)
+        .with_context(|| format!("'{}' is no .zip or .tar.gz archive", path.display()))?;
+    let content =
+        fs::read(path).with_context(|| format!("Failed to read archive: {}", path.display()))?;
+    let dir = TempDir::with_prefix("blendwerk-")?;
+    let root = extract(content, format, dir.path())
+        .with_context(|| format!("Failed to unpack archive: {}", path.display()))?;
+
+    Ok(UnpackedArchive { dir, root })
+}
+
+/// Unpack an archive held in memory into `dir`, returning the mock root.
+///
+/// Archives holding a single top-level directory are rooted inside it.
+pub fn extract(content: Vec<u8>, format: Format, dir: &Path) -> Result<PathBuf> {
+    match format {
+        Format::Zip => zip::ZipArchive::new(Cursor::new(content))?.extract(dir)?,
+        Format::TarGz => tar::Archive::new(GzDecoder::new(Cursor::new(content))).unpack(dir)?,
+    }
+
+    let root = single_directory(dir)?.unwrap_or_else(|| dir.to_path_buf());
+    if fs::read_dir(&root)?.next().is_none() {
+        bail!("archive is empty");
+    }
+    Ok(root)
+}
+
+fn single_directory(dir: &Path) -> Result<Option<PathBuf>> {
+    let entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    Ok(match entries.as_slice() {
+        [only] if only.is_dir() => Some(only.clone()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    fn tar_gz(path: &Path, files: &[(&str, &str)]) {
+        let encoder = GzEncoder::new(fs::File::create(path).unwrap(), Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_unpack_tar_gz() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("mocks.tar.gz");
+        tar_gz(
+            &path,
+            &[("mocks/api/GET.json", "{}"), ("mocks/users/GET.json", "[]")],
+        );
+
+        assert!(is_archive(&path));
+        let unpacked = unpack(&path).unwrap();
+        assert!(unpacked.path().ends_with("mocks"));
+        assert_eq!(
+            fs::read_to_string(unpacked.path().join("users/GET.json")).unwrap(),
+            "[]"
+        );
+
+        let temp = unpacked.temp_dir().to_path_buf();
+        drop(unpacked);
+        assert!(!temp.exists());
+    }
+
+    #[test]
+    fn test_unpack_zip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("mocks.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        for name in ["GET.json", "api/GET.json"] {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"{}").unwrap();
+        }
+        writer.finish().unwrap();
+
+        let unpacked = unpack(&path).unwrap();
+        assert_eq!(unpacked.path(), unpacked.temp_dir());
+        assert!(unpacked.path().join("api/GET.json").is_file());
+    }
+
+    #[test]
+    fn test_unpack_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("broken.zip");
+        fs::write(&path, "no zip").unwrap();
+        assert!(unpack(&path).is_err());
+        assert!(!is_archive(temp_dir.path()));
+        assert!(unpack(&temp_dir.path().join("mocks.rar")).is_err());
+    }
+}
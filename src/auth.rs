@@ -0,0 +1,253 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Credential checks from the `auth:` frontmatter block.
+
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use base64::Engine;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+const DEFAULT_REALM: &str = "blendwerk";
+
+/// Credentials a route requires, answered with a 401 if they are missing or
+/// wrong
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "AuthConfig")]
+pub struct Auth {
+    scheme: Scheme,
+    realm: String,
+    /// Body of the 401 response
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Scheme {
+    Basic {
+        user: String,
+        pass: String,
+    },
+    Bearer(String),
+    /// Bearer token that must be a validly signed, unexpired JWT carrying
+    /// at least the given claims
+    Jwt {
+        key: DecodingKey,
+        validation: Box<Validation>,
+        claims: Map<String, Value>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthConfig {
+    #[serde(default)]
+    basic: Option<BasicCredentials>,
+    #[serde(default)]
+    bearer: Option<String>,
+    #[serde(default)]
+    jwt: Option<JwtConfig>,
+    #[serde(default)]
+    realm: Option<String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BasicCredentials {
+    user: String,
+    pass: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtConfig {
+    /// HS256 secret
+    #[serde(default)]
+    secret: Option<String>,
+    /// PEM encoded RS256 public key
+    #[serde(default)]
+    public_key: Option<String>,
+    #[serde(default)]
+    claims: Map<String, Value>,
+}
+
+impl TryFrom<JwtConfig> for Scheme {
+    type Error = String;
+
+    fn try_from(config: JwtConfig) -> Result<Self, Self::Error> {
+        let (key, algorithm) = match (config.secret, config.public_key) {
+            (Some(secret), None) => (
+                DecodingKey::from_secret(secret.as_bytes()),
+                Algorithm::HS256,
+            ),
+            (None, Some(public_key)) => (
+                DecodingKey::from_rsa_pem(public_key.as_bytes())
+                    .map_err(|e| format!("invalid jwt public_key: {}", e))?,
+                Algorithm::RS256,
+            ),
+            _ => return Err("jwt needs exactly one of `secret` or `public_key`".to_string()),
+        };
+
+        // Expiry and not-before are checked if present, but not required;
+        // expected claims take the place of audience checks
+        let mut validation = Validation::new(algorithm);
+        validation.required_spec_claims.clear();
+        validation.validate_nbf = true;
+        validation.validate_aud = false;
+
+        Ok(Self::Jwt {
+            key,
+            validation: Box::new(validation),
+            claims: config.claims,
+        })
+    }
+}
+
+impl TryFrom<AuthConfig> for Auth {
+    type Error = String;
+
+    fn try_from(config: AuthConfig) -> Result<Self, Self::Error> {
+        let scheme = match (config.basic, config.bearer, config.jwt) {
+            (Some(BasicCredentials { user, pass }), None, None) => Scheme::Basic { user, pass },
+            (None, Some(token), None) => Scheme::Bearer(token),
+            (None, None, Some(jwt)) => Scheme::try_from(jwt)?,
+            _ => {
+                return Err("auth needs exactly one of `basic`, `bearer` or `jwt`".to_string());
+            }
+        };
+
+        Ok(Self {
+            scheme,
+            realm: config.realm.unwrap_or_else(|| DEFAULT_REALM.to_string()),
+            body: config.body,
+        })
+    }
+}
+
+impl Auth {
+    /// Whether the `Authorization` header carries the expected credentials
+    pub fn accepts(&self, headers: &HeaderMap) -> bool {
+        let Some((scheme, credentials)) = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().split_once(' '))
+        else {
+            return false;
+        };
+        let credentials = credentials.trim();
+
+        match &self.scheme {
+            Scheme::Basic { user, pass } if scheme.eq_ignore_ascii_case("basic") => {
+                base64::engine::general_purpose::STANDARD
+                    .decode(credentials)
+                    .is_ok_and(|decoded| decoded == format!("{}:{}", user, pass).as_bytes())
+            }
+            Scheme::Bearer(token) if scheme.eq_ignore_ascii_case("bearer") => credentials == token,
+            Scheme::Jwt {
+                key,
+                validation,
+                claims,
+            } if scheme.eq_ignore_ascii_case("bearer") => {
+                jsonwebtoken::decode::<Map<String, Value>>(credentials, key, validation).is_ok_and(
+                    |token| {
+                        claims
+                            .iter()
+                            .all(|(name, value)| token.claims.get(name) == Some(value))
+                    },
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// Value of the `WWW-Authenticate` header of the 401 response
+    pub fn challenge(&self) -> String {
+        let scheme = match self.scheme {
+            Scheme::Basic { .. } => "Basic",
+            Scheme::Bearer(_) | Scheme::Jwt { .. } => "Bearer",
+        };
+        format!("{} realm=\"{}\"", scheme, self.realm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn parse(yaml: &str) -> Result<Auth, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    fn authorization(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::try_from(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_basic_credentials() {
+        let auth = parse("basic: {user: alice, pass: secret}").unwrap();
+        assert_eq!(auth.challenge(), "Basic realm=\"blendwerk\"");
+
+        // alice:secret
+        assert!(auth.accepts(&authorization("Basic YWxpY2U6c2VjcmV0")));
+        assert!(auth.accepts(&authorization("basic YWxpY2U6c2VjcmV0")));
+        // alice:wrong
+        assert!(!auth.accepts(&authorization("Basic YWxpY2U6d3Jvbmc=")));
+        assert!(!auth.accepts(&authorization("Bearer YWxpY2U6c2VjcmV0")));
+        assert!(!auth.accepts(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let auth = parse("bearer: abc123\nrealm: api").unwrap();
+        assert_eq!(auth.challenge(), "Bearer realm=\"api\"");
+        assert!(auth.accepts(&authorization("Bearer abc123")));
+        assert!(!auth.accepts(&authorization("Bearer abc1234")));
+        assert!(!auth.accepts(&authorization("Bearer")));
+    }
+
+    #[test]
+    fn test_jwt_signature_expiry_and_claims() {
+        use jsonwebtoken::{EncodingKey, Header, encode};
+        use serde_json::json;
+
+        let auth = parse("jwt:\n  secret: s3cret\n  claims:\n    role: admin").unwrap();
+        let token = |claims: Value, secret: &str| {
+            let token = encode(
+                &Header::default(),
+                &claims,
+                &EncodingKey::from_secret(secret.as_bytes()),
+            )
+            .unwrap();
+            authorization(&format!("Bearer {}", token))
+        };
+        let now = jsonwebtoken::get_current_timestamp();
+
+        assert!(auth.accepts(&token(json!({"sub": "alice", "role": "admin"}), "s3cret")));
+        assert!(auth.accepts(&token(
+            json!({"role": "admin", "exp": now + 3600}),
+            "s3cret"
+        )));
+        assert!(!auth.accepts(&token(
+            json!({"role": "admin", "exp": now - 3600}),
+            "s3cret"
+        )));
+        assert!(!auth.accepts(&token(json!({"role": "user"}), "s3cret")));
+        assert!(!auth.accepts(&token(json!({"role": "admin"}), "other")));
+        assert!(!auth.accepts(&authorization("Bearer not-a-jwt")));
+    }
+
+    #[test]
+    fn test_needs_exactly_one_scheme() {
+        assert!(parse("realm: api").is_err());
+        assert!(parse("bearer: abc\nbasic: {user: a, pass: b}").is_err());
+        assert!(parse("jwt: {secret: a, public_key: b}").is_err());
+        assert!(parse("jwt: {public_key: not-pem}").is_err());
+    }
+}
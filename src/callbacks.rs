@@ -0,0 +1,409 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Outbound requests fired after a route answered (`callbacks:`), to
+//! simulate the webhooks of payment or messaging APIs.
+//!
+//! URL, header values and body are templates: `{{request.body.id}}` and the
+//! like are replaced with values of the request that triggered the callback.
+//! In structured bodies, a string consisting of a single placeholder takes
+//! the JSON value it refers to, so numbers stay numbers.
+
+use crate::delay::Delay;
+use crate::http_client;
+use crate::server::ShutdownSignal;
+use anyhow::anyhow;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, Uri};
+use bytes::Bytes;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Callback {
+    pub url: String,
+    #[serde(default = "default_method", deserialize_with = "deserialize_method")]
+    pub method: Method,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<CallbackBody>,
+    /// Wait after the response before sending
+    #[serde(default)]
+    pub delay: Delay,
+    /// Attempts after the first one when the receiver is unreachable or
+    /// answers with 5xx
+    #[serde(default)]
+    pub retries: u32,
+    /// Milliseconds between attempts
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u64,
+    /// Milliseconds an attempt may take before it counts as failed
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+/// Body written as text or as YAML structure sent as JSON
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CallbackBody {
+    Text(String),
+    Structured(Value),
+}
+
+fn default_method() -> Method {
+    Method::POST
+}
+
+fn default_retry_delay() -> u64 {
+    1000
+}
+
+fn default_timeout() -> u64 {
+    10_000
+}
+
+fn deserialize_method<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Method, D::Error> {
+    let method = String::deserialize(deserializer)?;
+    Method::from_bytes(method.to_ascii_uppercase().as_bytes()).map_err(serde::de::Error::custom)
+}
+
+/// The request a callback was triggered by, as seen by templates
+#[derive(Debug, Clone)]
+pub struct TriggeringRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    /// Values of the route's `[param]` segments
+    pub params: Vec<(String, String)>,
+    /// `;name=value` parameters of the path, with `--matrix-params`
+    pub matrix: Vec<(String, String)>,
+}
+
+impl TriggeringRequest {
+    /// Value of a placeholder like `request.body.items.0.id`, `None` if it
+    /// refers to nothing
+    fn lookup(&self, name: &str) -> Option<Value> {
+        let mut parts = name.split('.');
+        match (parts.next()?, parts.next()) {
+            ("params", Some(param)) => self
+                .params
+                .iter()
+                .find(|(name, _)| name == param)
+                .map(|(_, value)| Value::String(value.clone())),
+            ("matrix", Some(param)) => self
+                .matrix
+                .iter()
+                .find(|(name, _)| name == param)
+                .map(|(_, value)| Value::String(value.clone())),
+            ("request", Some("method")) => Some(Value::String(self.method.clone())),
+            ("request", Some("path")) => Some(Value::String(self.path.clone())),
+            ("request", Some("query")) => {
+                let param = parts.next()?;
+                let params: Vec<(String, String)> =
+                    serde_urlencoded::from_str(self.query.as_deref()?).ok()?;
+                params
+                    .into_iter()
+                    .find(|(name, _)| name == param)
+                    .map(|(_, value)| Value::String(value))
+            }
+            ("request", Some("headers")) => self
+                .headers
+                .get(parts.next()?)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| Value::String(value.to_string())),
+            ("request", Some("body")) => {
+                let path: Vec<&str> = parts.collect();
+                if path.is_empty() {
+                    return Some(Value::String(
+                        String::from_utf8_lossy(&self.body).into_owned(),
+                    ));
+                }
+                let json: Value = serde_json::from_slice(&self.body).ok()?;
+                path.iter()
+                    .try_fold(&json, |value, key| match value {
+                        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+                        _ => value.get(key),
+                    })
+                    .cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Replace all placeholders in `template`; unknown ones become empty
+    fn render(&self, template: &str) -> String {
+        let mut output = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else {
+                break;
+            };
+            output.push_str(&rest[..start]);
+            let name = rest[start + 2..start + end].trim();
+            match self.lookup(name) {
+                Some(Value::String(text)) => output.push_str(&text),
+                Some(value) => output.push_str(&value.to_string()),
+                None => {}
+            }
+            rest = &rest[start + end + 2..];
+        }
+        output.push_str(rest);
+        output
+    }
+
+    fn render_value(&self, value: &Value) -> Value {
+        match value {
+            Value::String(text) => {
+                let trimmed = text.trim();
+                let single = trimmed
+                    .strip_prefix("{{")
+                    .and_then(|inner| inner.strip_suffix("}}"))
+                    .filter(|inner| !inner.contains("{{"));
+                match single {
+                    Some(name) => self.lookup(name.trim()).unwrap_or(Value::Null),
+                    None => Value::String(self.render(text)),
+                }
+            }
+            Value::Array(items) => {
+                Value::Array(items.iter().map(|item| self.render_value(item)).collect())
+            }
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.render_value(value)))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+/// Send the callbacks in the background, each on its own schedule.
+///
+/// Callbacks still waiting or in flight are dropped on shutdown.
+pub fn fire(callbacks: &[Callback], request: TriggeringRequest, shutdown: ShutdownSignal) {
+    let request = Arc::new(request);
+    for callback in callbacks {
+        let callback = callback.clone();
+        let request = request.clone();
+        let mut shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let delivery = async {
+                let delay = callback.delay.sample();
+                if delay > 0 {
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+                send(&callback, &request).await;
+            };
+            tokio::select! {
+                _ = delivery => {}
+                _ = shutdown.wait_for(|stopped| *stopped) => {
+                    info!("Dropping callback {} {} on shutdown", callback.method, callback.url);
+                }
+            }
+        });
+    }
+}
+
+async fn send(callback: &Callback, request: &TriggeringRequest) {
+    let url = request.render(&callback.url);
+    let uri: Uri = match url.parse() {
+        Ok(uri) => uri,
+        Err(e) => {
+            warn!("Callback URL `{}` is invalid: {}", url, e);
+            return;
+        }
+    };
+
+    let mut headers = HeaderMap::new();
+    let body = match &callback.body {
+        Some(CallbackBody::Text(text)) => Bytes::from(request.render(text)),
+        Some(CallbackBody::Structured(value)) => {
+            headers.insert(
+                axum::http::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+            Bytes::from(request.render_value(value).to_string())
+        }
+        None => Bytes::new(),
+    };
+    for (name, value) in &callback.headers {
+        match (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::try_from(request.render(value)),
+        ) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => warn!("Skipping invalid callback header {}", name),
+        }
+    }
+
+    for attempt in 0..=callback.retries {
+        if attempt > 0 {
+            tokio::time::sleep(Duration::from_millis(callback.retry_delay)).await;
+        }
+        let timeout = Duration::from_millis(callback.timeout);
+        let result = tokio::time::timeout(
+            timeout,
+            http_client::send(callback.method.clone(), &uri, headers.clone(), body.clone()),
+        )
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("timed out after {:?}", timeout)));
+        match result {
+            Ok(response) if !response.status().is_server_error() => {
+                info!(
+                    "Callback {} {} answered {}",
+                    callback.method,
+                    uri,
+                    response.status().as_u16()
+                );
+                return;
+            }
+            Ok(response) => warn!(
+                "Callback {} {} answered {} (attempt {} of {})",
+                callback.method,
+                uri,
+                response.status().as_u16(),
+                attempt + 1,
+                callback.retries + 1
+            ),
+            Err(e) => warn!(
+                "Callback {} {} failed: {:#} (attempt {} of {})",
+                callback.method,
+                uri,
+                e,
+                attempt + 1,
+                callback.retries + 1
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::watch;
+
+    fn payment() -> TriggeringRequest {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-tenant", "acme".parse().unwrap());
+        TriggeringRequest {
+            method: "POST".to_string(),
+            path: "/payments/42".to_string(),
+            query: Some("notify=hook%201".to_string()),
+            headers,
+            body: Bytes::from(r#"{"amount": 1200, "items": [{"sku": "book"}]}"#),
+            params: vec![("id".to_string(), "42".to_string())],
+            matrix: vec![("v".to_string(), "2".to_string())],
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let request = payment();
+        assert_eq!(
+            request.render(
+                "{{request.method}} {{ request.path }} {{params.id}} {{request.query.notify}} \
+                 {{request.headers.X-Tenant}} {{request.body.items.0.sku}} [{{request.body.missing}}] {{matrix.v}}"
+            ),
+            "POST /payments/42 42 hook 1 acme book [] 2"
+        );
+        assert_eq!(
+            request.render_value(&serde_json::json!({
+                "id": "pay_{{params.id}}",
+                "amount": "{{request.body.amount}}",
+                "items": "{{request.body.items}}",
+                "missing": "{{request.body.nope}}",
+            })),
+            serde_json::json!({
+                "id": "pay_42",
+                "amount": 1200,
+                "items": [{"sku": "book"}],
+                "missing": null,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let callback: Callback = serde_yaml::from_str(
+            "url: http://localhost/hook\nmethod: put\ndelay: 100\nretries: 2\nbody: {paid: true}",
+        )
+        .unwrap();
+        assert_eq!(callback.method, Method::PUT);
+        assert_eq!(callback.retries, 2);
+        assert_eq!(callback.retry_delay, 1000);
+        assert_eq!(callback.timeout, 10_000);
+        assert!(matches!(callback.body, Some(CallbackBody::Structured(_))));
+        assert!(serde_yaml::from_str::<Callback>("url: x\nretry: 1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let callback: Callback = serde_yaml::from_str(&format!(
+            "url: http://{}/hooks/{{{{params.id}}}}\nretries: 1\nretry_delay: 10\nbody:\n  amount: '{{{{request.body.amount}}}}'",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        fire(&[callback], payment(), shutdown_rx);
+
+        let mut received = Vec::new();
+        for status in ["503 Service Unavailable", "204 No Content"] {
+            let (mut connection, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.ends_with(b"{\"amount\":1200}") {
+                let len = connection.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..len]);
+            }
+            connection
+                .write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes())
+                .await
+                .unwrap();
+            received.push(String::from_utf8(request).unwrap());
+        }
+
+        assert!(received[0].starts_with("POST /hooks/42 HTTP/1.1\r\n"));
+        assert!(received[1].contains("content-type: application/json\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let callback: Callback = serde_yaml::from_str(&format!(
+            "url: http://{}/hook\nretries: 1\nretry_delay: 10\ntimeout: 50",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        fire(&[callback], payment(), shutdown_rx);
+
+        // The receiver never answers: both attempts time out, the second
+        // one is dropped by the shutdown
+        let (mut first, _) = listener.accept().await.unwrap();
+        let (mut second, _) = listener.accept().await.unwrap();
+        let mut buffer = [0; 1024];
+        while first.read(&mut buffer).await.unwrap() > 0 {}
+        shutdown_tx.send(true).unwrap();
+        while second.read(&mut buffer).await.unwrap() > 0 {}
+    }
+}
@@ -0,0 +1,206 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Time-phased route behaviour (`chaos_schedule:` frontmatter).
+//!
+//! A schedule is a list of phases, each lasting `duration` from the moment
+//! blendwerk started, that override the route's `status`, `delay`, `fault`
+//! or `body` while active. Phases without overrides serve the route as is.
+//! After the last phase the route is healthy again, unless the schedule
+//! repeats.
+
+use crate::delay::Delay;
+use crate::fault::Fault;
+use crate::routes::Route;
+use bytes::Bytes;
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawSchedule")]
+pub struct ChaosSchedule {
+    phases: Vec<Phase>,
+    repeat: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSchedule {
+    phases: Vec<Phase>,
+    #[serde(default)]
+    repeat: bool,
+}
+
+impl TryFrom<RawSchedule> for ChaosSchedule {
+    type Error = String;
+
+    fn try_from(raw: RawSchedule) -> Result<Self, Self::Error> {
+        if raw.phases.is_empty() {
+            return Err("chaos_schedule needs at least one phase".to_string());
+        }
+        Ok(Self {
+            phases: raw.phases,
+            repeat: raw.repeat,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Phase {
+    duration: PhaseDuration,
+    #[serde(default)]
+    status: Option<u16>,
+    #[serde(default)]
+    delay: Option<Delay>,
+    #[serde(default)]
+    fault: Option<Fault>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// Length of a phase parsed from strings like `500ms`, `30s`, `2m` or `1h`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+struct PhaseDuration(Duration);
+
+impl TryFrom<String> for PhaseDuration {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let lower = value.trim().to_lowercase();
+        let (number, unit) = if let Some(number) = lower.strip_suffix("ms") {
+            (number, Duration::from_millis(1))
+        } else if let Some(number) = lower.strip_suffix('s') {
+            (number, Duration::from_secs(1))
+        } else if let Some(number) = lower.strip_suffix('m') {
+            (number, Duration::from_secs(60))
+        } else if let Some(number) = lower.strip_suffix('h') {
+            (number, Duration::from_secs(3600))
+        } else {
+            return Err(format!(
+                "invalid phase duration '{}', expected e.g. 500ms, 30s, 2m or 1h",
+                value
+            ));
+        };
+
+        number
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|n| *n > 0)
+            .map(|n| Self(unit * n))
+            .ok_or_else(|| format!("invalid phase duration '{}'", value))
+    }
+}
+
+impl ChaosSchedule {
+    /// The phase active `elapsed` after the start, if any
+    pub fn phase_at(&self, elapsed: Duration) -> Option<&Phase> {
+        let total: Duration = self.phases.iter().map(|phase| phase.duration.0).sum();
+        let mut offset = if self.repeat {
+            Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64)
+        } else {
+            elapsed
+        };
+
+        for phase in &self.phases {
+            if offset < phase.duration.0 {
+                return Some(phase);
+            }
+            offset -= phase.duration.0;
+        }
+        None
+    }
+}
+
+impl Phase {
+    /// Replace the parts of the route's response the phase overrides
+    pub fn apply(&self, route: &mut Route) {
+        let meta = &mut route.response.meta;
+        if let Some(status) = self.status {
+            meta.status = Some(status);
+        }
+        if let Some(delay) = &self.delay {
+            meta.delay = delay.clone();
+        }
+        if let Some(fault) = self.fault {
+            meta.fault = Some(fault);
+        }
+        if let Some(body) = &self.body {
+            meta.chunks = None;
+            meta.events = None;
+            meta.randomize = None;
+            route.response.body = Bytes::from(body.clone());
+            route.body_file = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(yaml: &str) -> ChaosSchedule {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const GAME_DAY: &str = "
+phases:
+  - duration: 2m
+  - duration: 30s
+    status: 503
+    body: Service Unavailable
+  - duration: 1m
+    delay: 2000
+";
+
+    #[test]
+    fn test_phases() {
+        let schedule = schedule(GAME_DAY);
+        let status_at = |secs| {
+            schedule
+                .phase_at(Duration::from_secs(secs))
+                .map(|phase| phase.status)
+        };
+        assert_eq!(status_at(0), Some(None));
+        assert_eq!(status_at(119), Some(None));
+        assert_eq!(status_at(120), Some(Some(503)));
+        assert_eq!(status_at(150).unwrap(), None);
+        assert!(
+            schedule
+                .phase_at(Duration::from_secs(209))
+                .unwrap()
+                .delay
+                .is_some()
+        );
+        assert!(schedule.phase_at(Duration::from_secs(210)).is_none());
+    }
+
+    #[test]
+    fn test_repeat() {
+        let schedule = schedule(&format!("{}repeat: true", GAME_DAY));
+        let phase = schedule.phase_at(Duration::from_secs(210 + 125)).unwrap();
+        assert_eq!(phase.status, Some(503));
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        let parse = |yaml: &str| serde_yaml::from_str::<ChaosSchedule>(yaml);
+        assert!(parse("phases: []").is_err());
+        assert!(parse("phases: [{duration: 0s}]").is_err());
+        assert!(parse("phases: [{duration: 2 minutes}]").is_err());
+        assert!(parse("phases: [{duration: 1s, stauts: 503}]").is_err());
+        assert_eq!(
+            parse("phases: [{duration: 500ms}]").unwrap().phases[0]
+                .duration
+                .0,
+            Duration::from_millis(500)
+        );
+    }
+}
@@ -0,0 +1,79 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::delay::Delay;
+use axum::body::Body;
+use bytes::Bytes;
+use futures_util::stream;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// A single piece of a scripted response body
+#[derive(Debug, Clone, Deserialize)]
+pub struct Chunk {
+    #[serde(default)]
+    pub data: String,
+    /// Pause before this chunk is sent, in milliseconds or as a distribution
+    #[serde(default)]
+    pub delay: Delay,
+}
+
+/// Concatenated data of all chunks, used wherever the full body is needed
+pub fn full_body(chunks: &[Chunk]) -> String {
+    chunks.iter().map(|chunk| chunk.data.as_str()).collect()
+}
+
+/// Build a body that sends every chunk separately after its own delay
+pub fn into_body(chunks: Vec<Chunk>) -> Body {
+    let scripted = stream::unfold(chunks.into_iter(), |mut chunks| async move {
+        let chunk = chunks.next()?;
+
+        let delay = chunk.delay.sample();
+        if delay > 0 {
+            sleep(Duration::from_millis(delay)).await;
+        }
+
+        Some((Ok::<_, Infallible>(Bytes::from(chunk.data)), chunks))
+    });
+
+    Body::from_stream(scripted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_chunks_are_sent_separately_after_their_delay() {
+        let chunks: Vec<Chunk> = serde_yaml::from_str(
+            r#"
+- data: "["
+- data: "1,"
+  delay: 50
+- data: "2]"
+  delay: 50
+"#,
+        )
+        .unwrap();
+        assert_eq!(full_body(&chunks), "[1,2]");
+
+        let started = Instant::now();
+        let sent: Vec<Bytes> = into_body(chunks)
+            .into_data_stream()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(sent, vec!["[", "1,", "2]"]);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Conditional GET (`--etag` and the `etag:` frontmatter field).
+//!
+//! The ETag is a hash of the body, or of size and modification time for
+//! bodies streamed from disk; `Last-Modified` is the modification time of
+//! the route file. Both can be pinned with explicit frontmatter headers.
+
+use crate::routes::Route;
+use axum::http::{
+    HeaderMap,
+    header::{IF_MODIFIED_SINCE, IF_NONE_MATCH},
+};
+use chrono::{DateTime, Utc};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::SystemTime;
+
+/// Validators of a route's current representation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validators {
+    /// Quoted entity tag, e.g. `"5d41402abc4b2a76"`
+    pub etag: String,
+    /// Value of the `Last-Modified` header
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn for_route(route: &Route) -> Self {
+        let header = |name: &str| {
+            route
+                .response
+                .meta
+                .headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .and_then(|(_, value)| value.first())
+                .map(str::to_string)
+        };
+
+        let etag = header("etag").unwrap_or_else(|| {
+            let mut hasher = DefaultHasher::new();
+            match &route.body_file {
+                Some(body_file) => {
+                    body_file.len.hash(&mut hasher);
+                    route.modified.hash(&mut hasher);
+                }
+                None => route.response.body.hash(&mut hasher),
+            }
+            format!("\"{:016x}\"", hasher.finish())
+        });
+        let last_modified = header("last-modified").or_else(|| route.modified.map(http_date));
+
+        Self {
+            etag,
+            last_modified,
+        }
+    }
+
+    /// Whether the request's `If-None-Match`, or `If-Modified-Since` without
+    /// it, says the client's copy is current
+    pub fn not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+            let Ok(if_none_match) = if_none_match.to_str() else {
+                return false;
+            };
+            // Weak comparison: `W/"x"` matches `"x"`
+            let etag = self.etag.trim_start_matches("W/");
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+        }
+
+        let since = headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_http_date);
+        let modified = self.last_modified.as_deref().and_then(parse_http_date);
+        matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+    }
+}
+
+/// Format a time as HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Parse an HTTP date to whole seconds since the epoch
+fn parse_http_date(value: &str) -> Option<i64> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontmatter::parse_frontmatter;
+    use crate::routes::HttpMethod;
+    use axum::http::HeaderValue;
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn route(content: &str) -> Route {
+        Route {
+            method: HttpMethod::Get,
+            path_segments: vec![],
+            response: parse_frontmatter(content).unwrap(),
+            content_type: "application/json".to_string(),
+            body_file: None,
+            websocket: None,
+            script: None,
+            plugin: None,
+            graphql_operation: None,
+            source: PathBuf::from("GET.json"),
+            modified: Some(UNIX_EPOCH + Duration::from_secs(784_111_777)),
+            host: None,
+            includes: Vec::new(),
+        }
+    }
+
+    fn request(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::HeaderName::try_from(name).unwrap(),
+            HeaderValue::try_from(value).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_etag_follows_body() {
+        let validators = Validators::for_route(&route("{\"a\": 1}"));
+        assert_eq!(validators, Validators::for_route(&route("{\"a\": 1}")));
+        assert_ne!(
+            validators.etag,
+            Validators::for_route(&route("{\"a\": 2}")).etag
+        );
+        assert_eq!(
+            validators.last_modified.as_deref(),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+
+        assert!(validators.not_modified(&request("if-none-match", &validators.etag)));
+        let weak = format!("\"other\", W/{}", validators.etag);
+        assert!(validators.not_modified(&request("if-none-match", &weak)));
+        assert!(validators.not_modified(&request("if-none-match", "*")));
+        assert!(!validators.not_modified(&request("if-none-match", "\"other\"")));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let validators = Validators::for_route(&route("{}"));
+        assert_eq!(
+            parse_http_date(validators.last_modified.as_deref().unwrap()),
+            Some(784_111_777)
+        );
+
+        let same = request("if-modified-since", "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert!(validators.not_modified(&same));
+        let earlier = request("if-modified-since", "Sun, 06 Nov 1994 08:49:36 GMT");
+        assert!(!validators.not_modified(&earlier));
+        assert!(!validators.not_modified(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_frontmatter_headers_pin_validators() {
+        let validators = Validators::for_route(&route(
+            "---\nheaders:\n  ETag: '\"v1\"'\n  Last-Modified: Mon, 01 Jan 2024 00:00:00 GMT\n---\n{}",
+        ));
+        assert_eq!(validators.etag, "\"v1\"");
+        assert!(validators.not_modified(&request("if-none-match", "\"v1\"")));
+        assert_eq!(
+            validators.last_modified.as_deref(),
+            Some("Mon, 01 Jan 2024 00:00:00 GMT")
+        );
+    }
+}
@@ -0,0 +1,147 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Per-connection plumbing shared by the HTTP and HTTPS listeners.
+//!
+//! Both listeners wrap accepted TCP streams in a [`FaultStream`] and make a
+//! [`Connection`] handle available to handlers through `ConnectInfo`.
+
+use crate::fault::{FaultStream, FaultTrigger};
+use axum::extract::ConnectInfo;
+use axum::extract::connect_info::Connected;
+use axum::http::Request;
+use axum::serve::{IncomingStream, Listener};
+use axum_server::accept::Accept;
+use std::future::{Ready, ready};
+use std::io;
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tower::Service;
+
+/// Information about the connection a request arrived on
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub fault: FaultTrigger,
+    /// Whether the connection is TLS encrypted; requests that don't carry
+    /// their scheme (HTTP/1.1) are `https` requests then
+    pub secure: bool,
+    /// Address of the peer, which may be a proxy (see `--trusted-proxy`)
+    pub remote_addr: Option<SocketAddr>,
+}
+
+impl Connection {
+    fn new(stream: &FaultStream, secure: bool, remote_addr: Option<SocketAddr>) -> Self {
+        Self {
+            fault: stream.trigger(),
+            secure,
+            remote_addr,
+        }
+    }
+
+    /// Connection without a [`FaultStream`] below it; whoever holds `fault`
+    /// has to carry out armed faults
+    pub fn with_trigger(fault: FaultTrigger) -> Self {
+        Self {
+            fault,
+            secure: false,
+            remote_addr: None,
+        }
+    }
+}
+
+/// Plain HTTP listener producing fault-capable streams
+pub struct ConnectionListener {
+    inner: TcpListener,
+    accept_delay: Option<Duration>,
+}
+
+impl ConnectionListener {
+    pub fn new(inner: TcpListener, accept_delay: Option<Duration>) -> Self {
+        Self {
+            inner,
+            accept_delay,
+        }
+    }
+}
+
+impl Listener for ConnectionListener {
+    type Io = FaultStream;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        let (stream, addr) = Listener::accept(&mut self.inner).await;
+        (
+            FaultStream::new(stream).with_accept_delay(self.accept_delay),
+            addr,
+        )
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Listener::local_addr(&self.inner)
+    }
+}
+
+impl Connected<IncomingStream<'_, ConnectionListener>> for Connection {
+    fn connect_info(stream: IncomingStream<'_, ConnectionListener>) -> Self {
+        Self::new(stream.io(), false, Some(*stream.remote_addr()))
+    }
+}
+
+/// Acceptor for `axum_server` that wraps streams before the TLS handshake
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionAcceptor {
+    pub accept_delay: Option<Duration>,
+}
+
+impl<S> Accept<TcpStream, S> for ConnectionAcceptor {
+    type Stream = FaultStream;
+    type Service = WithConnection<S>;
+    type Future = Ready<io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        let remote_addr = stream.peer_addr().ok();
+        let stream = FaultStream::new(stream).with_accept_delay(self.accept_delay);
+        let connection = Connection::new(&stream, true, remote_addr);
+        ready(Ok((
+            stream,
+            WithConnection {
+                inner: service,
+                connection,
+            },
+        )))
+    }
+}
+
+/// Service wrapper exposing the [`Connection`] as `ConnectInfo` extension
+#[derive(Debug, Clone)]
+pub struct WithConnection<S> {
+    inner: S,
+    connection: Connection,
+}
+
+impl<S, B> Service<Request<B>> for WithConnection<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(self.connection.clone()));
+        self.inner.call(request)
+    }
+}
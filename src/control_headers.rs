@@ -0,0 +1,100 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Per-request overrides through reserved request headers
+//! (`--control-headers`).
+//!
+//! `X-Blendwerk-Status: 503` and `X-Blendwerk-Delay: 2000` replace the
+//! matched route's status and delay for a single request, so a test can
+//! force an error case without touching the mock files.
+
+use crate::delay::Delay;
+use crate::routes::Route;
+use axum::http::{HeaderMap, StatusCode};
+
+pub const STATUS_HEADER: &str = "x-blendwerk-status";
+pub const DELAY_HEADER: &str = "x-blendwerk-delay";
+
+/// Overrides requested by the control headers of a request
+#[derive(Debug, Default, PartialEq)]
+pub struct Overrides {
+    status: Option<u16>,
+    /// Milliseconds
+    delay: Option<u64>,
+}
+
+impl Overrides {
+    /// Read the control headers, failing with a message for invalid values
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, String> {
+        let value = |name: &str| {
+            headers
+                .get(name)
+                .map(|value| value.to_str().unwrap_or_default().trim().to_string())
+        };
+
+        let status = value(STATUS_HEADER)
+            .map(|status| {
+                status
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|code| StatusCode::from_u16(*code).is_ok())
+                    .ok_or_else(|| format!("Invalid {} header: '{}'", STATUS_HEADER, status))
+            })
+            .transpose()?;
+        let delay = value(DELAY_HEADER)
+            .map(|delay| {
+                delay
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid {} header: '{}'", DELAY_HEADER, delay))
+            })
+            .transpose()?;
+
+        Ok(Self { status, delay })
+    }
+
+    /// Replace the parts of the route's response the request overrides
+    pub fn apply(&self, route: &mut Route) {
+        if let Some(status) = self.status {
+            route.response.meta.status = Some(status);
+        }
+        if let Some(delay) = self.delay {
+            route.response.meta.delay = Delay::Fixed(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(headers: &[(&'static str, &'static str)]) -> Result<Overrides, String> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, value.parse().unwrap());
+        }
+        Overrides::from_headers(&map)
+    }
+
+    #[test]
+    fn test_from_headers() {
+        assert_eq!(overrides(&[]), Ok(Overrides::default()));
+        assert_eq!(
+            overrides(&[
+                ("X-Blendwerk-Status", "503"),
+                ("X-Blendwerk-Delay", " 2000 ")
+            ]),
+            Ok(Overrides {
+                status: Some(503),
+                delay: Some(2000),
+            })
+        );
+        assert!(overrides(&[("X-Blendwerk-Status", "error")]).is_err());
+        assert!(overrides(&[("X-Blendwerk-Status", "42")]).is_err());
+        assert!(overrides(&[("X-Blendwerk-Delay", "-1")]).is_err());
+    }
+}
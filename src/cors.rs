@@ -0,0 +1,228 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! CORS mode (`--cors`).
+//!
+//! Allowed origins are echoed back instead of answering with `*`, so
+//! requests with credentials work as well. Headers set by a route file take
+//! precedence over the generated ones.
+
+use axum::http::{
+    HeaderMap, HeaderName, HeaderValue, Method,
+    header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+        ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD,
+        ORIGIN, VARY,
+    },
+};
+
+/// Response headers scripts can read without being exposed explicitly
+const SAFELISTED_RESPONSE_HEADERS: &[&str] = &[
+    "cache-control",
+    "content-language",
+    "content-length",
+    "content-type",
+    "expires",
+    "last-modified",
+    "pragma",
+];
+
+/// How long browsers may cache a preflight answer, in seconds
+const PREFLIGHT_MAX_AGE: &str = "600";
+
+#[derive(Debug, Clone, Default)]
+pub struct Cors {
+    /// Allowed origins; empty or `*` allows any
+    pub origins: Vec<String>,
+    /// Methods announced to preflights; empty echoes the requested method
+    pub methods: Vec<String>,
+    /// Request headers announced to preflights; empty echoes the requested
+    /// ones
+    pub headers: Vec<String>,
+}
+
+impl Cors {
+    /// Whether the request is a preflight to be answered on the route's behalf
+    pub fn is_preflight(method: &Method, headers: &HeaderMap) -> bool {
+        method == Method::OPTIONS
+            && headers.contains_key(ORIGIN)
+            && headers.contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+    }
+
+    /// CORS headers for the response to a request with the given headers.
+    ///
+    /// Empty for requests without an allowed `Origin`, and for any header
+    /// already present in `response_headers`.
+    pub fn response_headers(
+        &self,
+        method: &Method,
+        request_headers: &HeaderMap,
+        response_headers: &HeaderMap,
+    ) -> Vec<(HeaderName, HeaderValue)> {
+        let Some(origin) = request_headers
+            .get(ORIGIN)
+            .filter(|origin| self.allows(origin))
+        else {
+            return Vec::new();
+        };
+
+        let mut headers = vec![
+            (ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone()),
+            (
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            ),
+            (VARY, HeaderValue::from_static("Origin")),
+        ];
+
+        if Self::is_preflight(method, request_headers) {
+            let methods = match self.methods.is_empty() {
+                true => request_headers.get(ACCESS_CONTROL_REQUEST_METHOD).cloned(),
+                false => HeaderValue::try_from(self.methods.join(", ")).ok(),
+            };
+            let allowed_headers = match self.headers.is_empty() {
+                true => request_headers.get(ACCESS_CONTROL_REQUEST_HEADERS).cloned(),
+                false => HeaderValue::try_from(self.headers.join(", ")).ok(),
+            };
+
+            headers.extend(methods.map(|value| (ACCESS_CONTROL_ALLOW_METHODS, value)));
+            headers.extend(allowed_headers.map(|value| (ACCESS_CONTROL_ALLOW_HEADERS, value)));
+            headers.push((
+                ACCESS_CONTROL_MAX_AGE,
+                HeaderValue::from_static(PREFLIGHT_MAX_AGE),
+            ));
+        } else {
+            // Let scripts read custom headers such as X-Total-Count
+            let exposed: Vec<&str> = response_headers
+                .keys()
+                .map(HeaderName::as_str)
+                .filter(|name| {
+                    !SAFELISTED_RESPONSE_HEADERS.contains(name)
+                        && !name.starts_with("access-control-")
+                })
+                .collect();
+            if !exposed.is_empty()
+                && let Ok(value) = HeaderValue::try_from(exposed.join(", "))
+            {
+                headers.push((ACCESS_CONTROL_EXPOSE_HEADERS, value));
+            }
+        }
+
+        headers.retain(|(name, _)| !response_headers.contains_key(name));
+        headers
+    }
+
+    fn allows(&self, origin: &HeaderValue) -> bool {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        self.origins.is_empty()
+            || self
+                .origins
+                .iter()
+                .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)]) -> HeaderMap {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::try_from(*name).unwrap(),
+                    HeaderValue::try_from(*value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn get(headers: &[(HeaderName, HeaderValue)], name: HeaderName) -> Option<&str> {
+        headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value.to_str().unwrap())
+    }
+
+    #[test]
+    fn test_preflight_echoes_request() {
+        let cors = Cors::default();
+        let headers = request(&[
+            ("origin", "http://localhost:3000"),
+            ("access-control-request-method", "PUT"),
+            ("access-control-request-headers", "authorization"),
+        ]);
+        assert!(Cors::is_preflight(&Method::OPTIONS, &headers));
+
+        let cors_headers = cors.response_headers(&Method::OPTIONS, &headers, &HeaderMap::new());
+        assert_eq!(
+            get(&cors_headers, ACCESS_CONTROL_ALLOW_ORIGIN),
+            Some("http://localhost:3000")
+        );
+        assert_eq!(
+            get(&cors_headers, ACCESS_CONTROL_ALLOW_METHODS),
+            Some("PUT")
+        );
+        assert_eq!(
+            get(&cors_headers, ACCESS_CONTROL_ALLOW_HEADERS),
+            Some("authorization")
+        );
+    }
+
+    #[test]
+    fn test_configured_lists_and_origin_check() {
+        let cors = Cors {
+            origins: vec!["https://app.example.com".to_string()],
+            methods: vec!["GET".to_string(), "POST".to_string()],
+            headers: vec![],
+        };
+
+        let preflight = request(&[
+            ("origin", "https://app.example.com"),
+            ("access-control-request-method", "DELETE"),
+        ]);
+        let cors_headers = cors.response_headers(&Method::OPTIONS, &preflight, &HeaderMap::new());
+        assert_eq!(
+            get(&cors_headers, ACCESS_CONTROL_ALLOW_METHODS),
+            Some("GET, POST")
+        );
+
+        let foreign = request(&[("origin", "https://evil.example.com")]);
+        assert!(
+            cors.response_headers(&Method::GET, &foreign, &HeaderMap::new())
+                .is_empty()
+        );
+        assert!(
+            cors.response_headers(&Method::GET, &HeaderMap::new(), &HeaderMap::new())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_exposes_custom_headers_and_keeps_route_headers() {
+        let cors = Cors::default();
+        let headers = request(&[("origin", "http://localhost:3000")]);
+        let response = request(&[
+            ("content-type", "application/json"),
+            ("x-total-count", "2"),
+            ("access-control-allow-origin", "*"),
+        ]);
+
+        let cors_headers = cors.response_headers(&Method::GET, &headers, &response);
+        assert_eq!(
+            get(&cors_headers, ACCESS_CONTROL_EXPOSE_HEADERS),
+            Some("x-total-count")
+        );
+        // The route file sets its own Allow-Origin
+        assert_eq!(get(&cors_headers, ACCESS_CONTROL_ALLOW_ORIGIN), None);
+    }
+}
@@ -0,0 +1,163 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Route coverage (`--coverage`).
+//!
+//! Counts how often each route file answered and which requests no route
+//! matched, reported at `/__blendwerk/coverage` and summarized on shutdown.
+//! Hits are counted per file, so they survive reloads of the tree.
+
+use crate::routes::Route;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Distinct unmatched requests kept; further ones aren't counted, so
+/// scanners probing random paths can't grow the report without bound
+const MAX_UNMATCHED: usize = 1000;
+
+#[derive(Debug, Default)]
+pub struct Coverage {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Matches by route file
+    hits: HashMap<PathBuf, u64>,
+    /// Requests without route by method and path
+    unmatched: HashMap<(String, String), u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// Number of loaded routes
+    pub total: usize,
+    /// Routes that answered at least once
+    pub hit: usize,
+    pub routes: Vec<RouteHits>,
+    pub never_hit: Vec<RouteHits>,
+    /// Most frequent first
+    pub unmatched: Vec<Unmatched>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteHits {
+    pub method: String,
+    pub route: String,
+    pub file: PathBuf,
+    pub hits: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Unmatched {
+    pub method: String,
+    pub path: String,
+    pub count: u64,
+}
+
+impl Coverage {
+    /// Count a request answered by `route`
+    pub fn hit(&self, route: &Route) {
+        let mut state = self.state.lock().unwrap();
+        *state.hits.entry(route.source.clone()).or_default() += 1;
+    }
+
+    /// Count a request no route answered
+    pub fn miss(&self, method: &str, path: &str) {
+        let mut state = self.state.lock().unwrap();
+        let key = (method.to_string(), path.to_string());
+        if let Some(count) = state.unmatched.get_mut(&key) {
+            *count += 1;
+        } else if state.unmatched.len() < MAX_UNMATCHED {
+            state.unmatched.insert(key, 1);
+        }
+    }
+
+    /// Hits of the currently loaded `routes` and the unmatched requests
+    pub fn report(&self, routes: &[Route]) -> Report {
+        let state = self.state.lock().unwrap();
+        let mut entries: Vec<RouteHits> = routes
+            .iter()
+            .map(|route| RouteHits {
+                method: route.method.as_str().to_string(),
+                route: route.display_path(),
+                file: route.source.clone(),
+                hits: state.hits.get(&route.source).copied().unwrap_or(0),
+            })
+            .collect();
+        entries.sort_by(|a, b| (&a.route, &a.method).cmp(&(&b.route, &b.method)));
+
+        let mut unmatched: Vec<Unmatched> = state
+            .unmatched
+            .iter()
+            .map(|((method, path), count)| Unmatched {
+                method: method.clone(),
+                path: path.clone(),
+                count: *count,
+            })
+            .collect();
+        unmatched.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| (&a.path, &a.method).cmp(&(&b.path, &b.method)))
+        });
+
+        let never_hit: Vec<RouteHits> = entries.iter().filter(|e| e.hits == 0).cloned().collect();
+        Report {
+            total: entries.len(),
+            hit: entries.len() - never_hit.len(),
+            routes: entries,
+            never_hit,
+            unmatched,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scan_directory;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_report() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("users")).unwrap();
+        fs::write(temp_dir.path().join("users/GET.json"), "[]").unwrap();
+        fs::write(temp_dir.path().join("users/POST.json"), "{}").unwrap();
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        let get = routes.iter().find(|r| r.method.as_str() == "GET").unwrap();
+
+        let coverage = Coverage::default();
+        coverage.hit(get);
+        coverage.hit(get);
+        coverage.miss("GET", "/missing");
+        coverage.miss("GET", "/other");
+        coverage.miss("GET", "/missing");
+
+        let report = coverage.report(&routes);
+        assert_eq!(report.total, 2);
+        assert_eq!(report.hit, 1);
+        assert_eq!(report.routes[0].method, "GET");
+        assert_eq!(report.routes[0].hits, 2);
+        assert_eq!(report.never_hit.len(), 1);
+        assert_eq!(report.never_hit[0].method, "POST");
+        assert_eq!(
+            report.unmatched[0],
+            Unmatched {
+                method: "GET".to_string(),
+                path: "/missing".to_string(),
+                count: 2,
+            }
+        );
+        assert_eq!(report.unmatched.len(), 2);
+    }
+}
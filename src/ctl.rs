@@ -0,0 +1,169 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Client for the admin API of a running instance (`blendwerk ctl`).
+//!
+//! Every command is one or a few requests to `/__blendwerk/` below the
+//! given URL, which includes the `--base-path` of the instance if it has
+//! one. Scenarios are the instance's tags: setting one enables exactly the
+//! given tags.
+
+use crate::http_client;
+use crate::mime_types;
+use anyhow::{Context, Result, bail};
+use axum::http::{HeaderMap, Method, Response, StatusCode, Uri};
+use bytes::Bytes;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Admin API of one instance
+pub struct Client {
+    url: String,
+}
+
+impl Client {
+    /// Client for the instance at `url`, e.g. `http://localhost:8080`
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The loaded routes, with method, path and source
+    pub async fn routes(&self) -> Result<Value> {
+        self.request(Method::GET, "routes", Bytes::new()).await
+    }
+
+    /// The requests the instance received, oldest first
+    pub async fn requests(&self) -> Result<Value> {
+        self.request(Method::GET, "requests", Bytes::new()).await
+    }
+
+    /// Forget received requests, restart progressions and remove stubs
+    pub async fn reset(&self) -> Result<()> {
+        // Received requests are only kept with `--request-history`
+        let response = self.send(Method::DELETE, "requests", Bytes::new()).await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            check(&response)?;
+        }
+        self.request(Method::DELETE, "progression", Bytes::new())
+            .await?;
+        self.request(Method::DELETE, "stubs", Bytes::new()).await?;
+        Ok(())
+    }
+
+    /// Enable exactly the tags of a scenario, disabling no others
+    pub async fn set_scenario(&self, tags: &[String]) -> Result<Value> {
+        let selection = json!({ "enabled": tags, "disabled": [] });
+        self.request(Method::PUT, "tags", Bytes::from(selection.to_string()))
+            .await
+    }
+
+    /// Answer `method` requests to `path` like the route `file`, whose
+    /// extension sets the content type
+    pub async fn add_stub(&self, method: &str, path: &str, file: &Path) -> Result<()> {
+        let content =
+            fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let extension = file.extension().and_then(|s| s.to_str()).unwrap_or("");
+        let query = serde_urlencoded::to_string([
+            ("method", method),
+            ("path", path),
+            (
+                "content_type",
+                &mime_types::for_extension(extension, &HashMap::new()),
+            ),
+        ])?;
+        self.request(
+            Method::POST,
+            &format!("stubs?{}", query),
+            Bytes::from(content),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Send a request and parse the JSON answer, failing on error statuses
+    async fn request(&self, method: Method, endpoint: &str, body: Bytes) -> Result<Value> {
+        let response = self.send(method, endpoint, body).await?;
+        check(&response)?;
+        if response.body().is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(response.body())
+            .with_context(|| format!("/__blendwerk/{} did not answer with JSON", endpoint))
+    }
+
+    async fn send(&self, method: Method, endpoint: &str, body: Bytes) -> Result<Response<Bytes>> {
+        let url = format!("{}/__blendwerk/{}", self.url, endpoint);
+        let uri: Uri = url
+            .parse()
+            .with_context(|| format!("Invalid URL: {}", url))?;
+        http_client::send(method.clone(), &uri, HeaderMap::new(), body)
+            .await
+            .with_context(|| format!("{} {} failed", method, url))
+    }
+}
+
+fn check(response: &Response<Bytes>) -> Result<()> {
+    let status = response.status();
+    if !status.is_success() {
+        bail!(
+            "instance answered {}: {}",
+            status,
+            String::from_utf8_lossy(response.body()).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::MockServer;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_client() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("users")).unwrap();
+        fs::write(temp_dir.path().join("users/GET.json"), "[]").unwrap();
+        let stub = temp_dir.path().join("stub.json");
+        fs::write(&stub, "---\nstatus: 202\n---\n{\"stubbed\": true}").unwrap();
+        let server = MockServer::builder()
+            .directory(temp_dir.path())
+            .start()
+            .await
+            .unwrap();
+        let client = Client::new(&server.url());
+
+        let routes = client.routes().await.unwrap();
+        assert_eq!(routes[0]["method"], "GET");
+        assert_eq!(routes[0]["path"], "/users");
+
+        client.add_stub("GET", "/users", &stub).await.unwrap();
+        let users: Uri = format!("{}/users", server.url()).parse().unwrap();
+        let response = http_client::send(Method::GET, &users, HeaderMap::new(), Bytes::new())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        assert_eq!(client.requests().await.unwrap()[0]["path"], "/users");
+
+        let tags = client.set_scenario(&["outage".to_string()]).await.unwrap();
+        assert_eq!(tags["enabled"], json!(["outage"]));
+
+        client.reset().await.unwrap();
+        assert_eq!(client.requests().await.unwrap(), json!([]));
+        assert_eq!(client.routes().await.unwrap().as_array().unwrap().len(), 1);
+
+        let error = client.add_stub("FETCH", "/users", &stub).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("unsupported method FETCH"));
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use rand::Rng;
+use serde::Deserialize;
+use std::f64::consts::PI;
+
+/// Response delay in milliseconds, either fixed or sampled per request
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Delay {
+    Fixed(u64),
+    Distribution(DelayDistribution),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DelayDistribution {
+    /// Uniformly distributed between `[min, max]`
+    Uniform([u64; 2]),
+    /// Normally distributed, clamped at zero
+    Normal { mean: f64, stddev: f64 },
+    /// Pareto distributed: mostly close to `scale`, with a long tail
+    Pareto { scale: f64, shape: f64 },
+}
+
+impl Default for Delay {
+    fn default() -> Self {
+        Self::Fixed(0)
+    }
+}
+
+impl PartialEq<u64> for Delay {
+    fn eq(&self, other: &u64) -> bool {
+        matches!(self, Self::Fixed(delay) if delay == other)
+    }
+}
+
+impl Delay {
+    /// Determine the delay for a single request
+    pub fn sample(&self) -> u64 {
+        match self {
+            Self::Fixed(delay) => *delay,
+            Self::Distribution(distribution) => distribution.sample(&mut rand::rng()),
+        }
+    }
+}
+
+impl DelayDistribution {
+    fn sample(&self, rng: &mut impl Rng) -> u64 {
+        let value = match self {
+            Self::Uniform([min, max]) => {
+                let (low, high) = if min <= max {
+                    (*min, *max)
+                } else {
+                    (*max, *min)
+                };
+                return rng.random_range(low..=high);
+            }
+            Self::Normal { mean, stddev } => {
+                // Box-Muller transform; 1 - u avoids ln(0)
+                let u1: f64 = 1.0 - rng.random::<f64>();
+                let u2: f64 = rng.random();
+                mean + stddev * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+            }
+            Self::Pareto { scale, shape } => {
+                // Inverse transform sampling; 1 - u keeps the base in (0, 1]
+                let u: f64 = 1.0 - rng.random::<f64>();
+                scale / u.powf(1.0 / shape)
+            }
+        };
+
+        if value.is_finite() {
+            value.max(0.0).round() as u64
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Delay {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_fixed_delay() {
+        let delay = parse("150");
+        assert_eq!(delay, 150);
+        assert_eq!(delay.sample(), 150);
+    }
+
+    #[test]
+    fn test_uniform_delay() {
+        let delay = parse("uniform: [50, 200]");
+        for _ in 0..100 {
+            assert!((50..=200).contains(&delay.sample()));
+        }
+    }
+
+    #[test]
+    fn test_normal_delay_is_never_negative() {
+        let delay = parse("normal: { mean: 10, stddev: 100 }");
+        let samples: Vec<u64> = (0..1000).map(|_| delay.sample()).collect();
+        assert!(samples.contains(&0));
+        assert!(samples.iter().any(|sample| *sample > 10));
+    }
+
+    #[test]
+    fn test_pareto_delay_respects_scale() {
+        let delay = parse("pareto: { scale: 100, shape: 2.5 }");
+        for _ in 0..100 {
+            assert!(delay.sample() >= 100);
+        }
+    }
+
+    #[test]
+    fn test_unknown_distribution_is_rejected() {
+        assert!(serde_yaml::from_str::<Delay>("poisson: 3").is_err());
+    }
+}
@@ -0,0 +1,181 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Detection of duplicate requests (`--duplicate-window`).
+//!
+//! A request is a duplicate if an identical one (same method, path, query
+//! and body) arrived less than the window before it. Duplicates are logged
+//! as warnings and reported at `/__blendwerk/duplicates`, to catch client
+//! retry storms and double submits.
+
+use axum::http::{Method, Uri};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct Duplicates {
+    window: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Requests seen within the window, by hash of method, URI and body
+    seen: HashMap<u64, Seen>,
+    last_pruned: Instant,
+}
+
+#[derive(Debug)]
+struct Seen {
+    method: String,
+    uri: String,
+    last: Instant,
+    duplicates: u64,
+}
+
+/// Requests that were repeated within the window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub window_ms: u128,
+    pub duplicates: Vec<Duplicate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Duplicate {
+    pub method: String,
+    pub uri: String,
+    /// Number of repetitions, not counting the first request
+    pub count: u64,
+}
+
+impl Duplicates {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            state: Mutex::new(State {
+                seen: HashMap::new(),
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Record a request, returning whether it repeats an earlier one
+    pub fn check(&self, method: &Method, uri: &Uri, body: &[u8]) -> bool {
+        self.check_at(method, uri, body, Instant::now())
+    }
+
+    fn check_at(&self, method: &Method, uri: &Uri, body: &[u8], now: Instant) -> bool {
+        let mut hasher = DefaultHasher::new();
+        method.as_str().hash(&mut hasher);
+        uri.path().hash(&mut hasher);
+        uri.query().hash(&mut hasher);
+        body.hash(&mut hasher);
+        let key = hasher.finish();
+
+        let mut state = self.state.lock().unwrap();
+        // Requests that were never repeated are forgotten once their window
+        // has passed; reported ones stay for the report
+        if now.duration_since(state.last_pruned) > self.window {
+            let window = self.window;
+            state
+                .seen
+                .retain(|_, seen| seen.duplicates > 0 || now.duration_since(seen.last) <= window);
+            state.last_pruned = now;
+        }
+
+        match state.seen.get_mut(&key) {
+            Some(seen) => {
+                let duplicate = now.duration_since(seen.last) <= self.window;
+                if duplicate {
+                    seen.duplicates += 1;
+                    tracing::warn!(
+                        "Duplicate request: {} {} repeated {} time(s) within {} ms",
+                        seen.method,
+                        seen.uri,
+                        seen.duplicates,
+                        self.window.as_millis()
+                    );
+                }
+                seen.last = now;
+                duplicate
+            }
+            None => {
+                state.seen.insert(
+                    key,
+                    Seen {
+                        method: method.to_string(),
+                        uri: uri.to_string(),
+                        last: now,
+                        duplicates: 0,
+                    },
+                );
+                false
+            }
+        }
+    }
+
+    pub fn report(&self) -> Report {
+        let state = self.state.lock().unwrap();
+        let mut duplicates: Vec<Duplicate> = state
+            .seen
+            .values()
+            .filter(|seen| seen.duplicates > 0)
+            .map(|seen| Duplicate {
+                method: seen.method.clone(),
+                uri: seen.uri.clone(),
+                count: seen.duplicates,
+            })
+            .collect();
+        duplicates.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.uri.cmp(&b.uri)));
+
+        Report {
+            window_ms: self.window.as_millis(),
+            duplicates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicates_within_window() {
+        let duplicates = Duplicates::new(Duration::from_millis(500));
+        let uri: Uri = "/orders?page=1".parse().unwrap();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert!(!duplicates.check_at(&Method::POST, &uri, b"{}", at(0)));
+        assert!(duplicates.check_at(&Method::POST, &uri, b"{}", at(100)));
+        // Each repetition extends the window
+        assert!(duplicates.check_at(&Method::POST, &uri, b"{}", at(550)));
+        assert!(!duplicates.check_at(&Method::POST, &uri, b"{}", at(1100)));
+
+        // Other bodies, methods and queries are different requests
+        assert!(!duplicates.check_at(&Method::POST, &uri, b"{\"a\":1}", at(1100)));
+        assert!(!duplicates.check_at(&Method::PUT, &uri, b"{}", at(1100)));
+        let other: Uri = "/orders?page=2".parse().unwrap();
+        assert!(!duplicates.check_at(&Method::POST, &other, b"{}", at(1100)));
+
+        assert_eq!(
+            duplicates.report(),
+            Report {
+                window_ms: 500,
+                duplicates: vec![Duplicate {
+                    method: "POST".to_string(),
+                    uri: "/orders?page=1".to_string(),
+                    count: 2,
+                }],
+            }
+        );
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Routes answering with the request they received (`mode: echo`).
+//!
+//! The body is a JSON document with the method, path, query parameters,
+//! headers and body of the request, for seeing what a client actually sends
+//! without enabling the request log. Repeated query parameters and headers
+//! are listed as arrays; bodies that aren't UTF-8 are base64 encoded, as in
+//! request log entries. The client's address and protocol and the route that
+//! answered are added as `client` and `route`.
+
+use crate::connection::Connection;
+use crate::forwarded::Forwarded;
+use crate::request_logger::LoggedBody;
+use crate::routes::Route;
+use axum::http::Version;
+use axum::http::request::Parts;
+use bytes::Bytes;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Serialize)]
+struct Echo<'a> {
+    method: &'a str,
+    path: &'a str,
+    query: Map<String, Value>,
+    headers: Map<String, Value>,
+    #[serde(flatten)]
+    body: LoggedBody,
+    client: Client,
+    route: MatchedRoute<'a>,
+}
+
+/// Who sent the request, and how
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Client {
+    /// The client behind `--trusted-proxy` proxies; unknown for connections
+    /// without a peer address
+    ip: Option<String>,
+    /// Only known if the client connected directly
+    port: Option<u16>,
+    ip_version: Option<u8>,
+    /// `http/1.1`, `h2` or `h3`
+    protocol: &'static str,
+}
+
+impl Client {
+    pub fn new(connection: &Connection, forwarded: &Forwarded, version: Version) -> Self {
+        let ip = forwarded.client_ip.map(|ip| ip.to_canonical());
+        Self {
+            ip: ip.map(|ip| ip.to_string()),
+            port: connection
+                .remote_addr
+                .filter(|peer| Some(peer.ip().to_canonical()) == ip)
+                .map(|peer| peer.port()),
+            ip_version: ip.map(|ip| if ip.is_ipv4() { 4 } else { 6 }),
+            protocol: protocol(version),
+        }
+    }
+}
+
+fn protocol(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "http/0.9",
+        Version::HTTP_10 => "http/1.0",
+        Version::HTTP_2 => "h2",
+        Version::HTTP_3 => "h3",
+        _ => "http/1.1",
+    }
+}
+
+/// The route file that answered
+#[derive(Serialize)]
+struct MatchedRoute<'a> {
+    /// Display path, e.g. `/users/:id`
+    path: String,
+    file: String,
+    tags: &'a [String],
+}
+
+/// Replace the route's body with the JSON description of the request
+pub fn apply(route: &mut Route, parts: &Parts, body: &Bytes, client: Client) {
+    let query_params: Vec<(String, String)> = parts
+        .uri
+        .query()
+        .and_then(|query| serde_urlencoded::from_str(query).ok())
+        .unwrap_or_default();
+    let echo = Echo {
+        method: parts.method.as_str(),
+        path: parts.uri.path(),
+        query: collect(query_params),
+        headers: collect(parts.headers.iter().map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })),
+        body: LoggedBody::Content(body.clone()),
+        client,
+        route: MatchedRoute {
+            path: route.display_path(),
+            file: route.source.display().to_string(),
+            tags: &route.response.meta.tags,
+        },
+    };
+    let echo = serde_json::to_vec_pretty(&echo).unwrap_or_default();
+
+    let meta = &mut route.response.meta;
+    meta.chunks = None;
+    meta.events = None;
+    meta.randomize = None;
+    route.content_type = "application/json".to_string();
+    route.response.body = Bytes::from(echo);
+    route.body_file = None;
+}
+
+/// Map of names to their value, or to the list of values if repeated
+fn collect(pairs: impl IntoIterator<Item = (String, String)>) -> Map<String, Value> {
+    let mut map = Map::new();
+    for (name, value) in pairs {
+        match map.get_mut(&name) {
+            Some(Value::Array(values)) => values.push(Value::String(value)),
+            Some(first) => *first = Value::Array(vec![first.take(), Value::String(value)]),
+            None => {
+                map.insert(name, Value::String(value));
+            }
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::FaultTrigger;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn test_collect() {
+        let map = collect([
+            ("tag".to_string(), "a".to_string()),
+            ("page".to_string(), "2".to_string()),
+            ("tag".to_string(), "b".to_string()),
+            ("tag".to_string(), "c".to_string()),
+        ]);
+        assert_eq!(
+            Value::Object(map),
+            serde_json::json!({"tag": ["a", "b", "c"], "page": "2"})
+        );
+    }
+
+    #[test]
+    fn test_client() {
+        let mut connection = Connection::with_trigger(FaultTrigger::default());
+        connection.remote_addr = Some(SocketAddr::new("::ffff:10.0.0.7".parse().unwrap(), 40000));
+        let forwarded = |client_ip: &str| Forwarded {
+            scheme: "http".to_string(),
+            host: "localhost".to_string(),
+            client_ip: Some(client_ip.parse().unwrap()),
+        };
+
+        assert_eq!(
+            Client::new(&connection, &forwarded("10.0.0.7"), Version::HTTP_2),
+            Client {
+                ip: Some("10.0.0.7".to_string()),
+                port: Some(40000),
+                ip_version: Some(4),
+                protocol: "h2",
+            }
+        );
+        // Behind a proxy, the port is the proxy's
+        assert_eq!(
+            Client::new(&connection, &forwarded("2001:db8::1"), Version::HTTP_11),
+            Client {
+                ip: Some("2001:db8::1".to_string()),
+                port: None,
+                ip_version: Some(6),
+                protocol: "http/1.1",
+            }
+        );
+    }
+}
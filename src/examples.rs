@@ -0,0 +1,199 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Example requests embedded in frontmatter (`examples:`) and the runner
+//! behind `blendwerk test`.
+//!
+//! Every example is sent through the regular router in-process and must be
+//! answered by the file declaring it, optionally with a given status.
+
+use crate::connection::Connection;
+use crate::fault::FaultTrigger;
+use crate::routes::{self, PathSegment, Route};
+use crate::server::{self, AppState, MatchedRoute};
+use anyhow::Result;
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{HeaderName, HeaderValue, Method, Request},
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{RwLock, watch};
+use tower::ServiceExt;
+
+/// A request that should be answered by the route declaring it
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Example {
+    /// Request path; defaults to the route's path, required if it has
+    /// `[param]` segments
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Expected response status; without it only the match is checked
+    #[serde(default)]
+    pub status: Option<u16>,
+}
+
+/// Result of running a single example
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// File declaring the example, relative to the mock directory
+    pub source: PathBuf,
+    /// Method and path of the sent request
+    pub request: String,
+    /// Why the example failed; `None` if it passed
+    pub failure: Option<String>,
+}
+
+/// Send the examples of all routes below `base_dir` to an in-process server
+/// built from them
+pub async fn run(base_dir: &Path) -> Result<Vec<Outcome>> {
+    let routes = routes::scan_directory(base_dir)?;
+    let relative = |path: &Path| path.strip_prefix(base_dir).unwrap_or(path).to_path_buf();
+
+    let examples: Vec<(Route, Example)> = routes
+        .iter()
+        .flat_map(|route| {
+            route
+                .response
+                .meta
+                .examples
+                .iter()
+                .map(move |example| (route.clone(), example.clone()))
+        })
+        .collect();
+
+    // Never fires; examples don't outlive the run
+    let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+    let router = server::create_router(Arc::new(AppState::new(
+        Arc::new(RwLock::new(routes)),
+        shutdown_rx,
+    )));
+
+    let mut outcomes = Vec::new();
+    for (route, example) in examples {
+        let path = example.path.clone().unwrap_or_else(|| route.display_path());
+        let request = format!("{} {}", route.method.as_str(), path);
+
+        let failure = match build_request(&route, &example, &path) {
+            Ok(request) => {
+                let response = router.clone().oneshot(request).await.unwrap();
+                let matched = response.extensions().get::<MatchedRoute>();
+                let status = response.status().as_u16();
+
+                match (matched, example.status) {
+                    (Some(MatchedRoute(source)), _) if *source != route.source => {
+                        Some(format!("answered by {}", relative(source).display()))
+                    }
+                    (None, _) => Some(format!("matched no route (status {})", status)),
+                    (_, Some(expected)) if expected != status => {
+                        Some(format!("expected status {}, got {}", expected, status))
+                    }
+                    _ => None,
+                }
+            }
+            Err(reason) => Some(reason),
+        };
+
+        outcomes.push(Outcome {
+            source: relative(&route.source),
+            request,
+            failure,
+        });
+    }
+
+    Ok(outcomes)
+}
+
+fn build_request(route: &Route, example: &Example, path: &str) -> Result<Request<Body>, String> {
+    let has_parameters = route
+        .path_segments
+        .iter()
+        .any(|segment| matches!(segment, PathSegment::Dynamic(_)));
+    if example.path.is_none() && has_parameters {
+        return Err("needs a `path`, the route has parameters".to_string());
+    }
+
+    let method = Method::from_bytes(route.method.as_str().as_bytes())
+        .map_err(|e| format!("invalid method: {}", e))?;
+    let mut request = Request::builder().method(method).uri(path);
+    // Routes forcing HTTP/2 would reject the default HTTP/1.1
+    if let Some(version) = route.response.meta.http_version {
+        request = request.version(version.version());
+    }
+    for (name, value) in &example.headers {
+        let name = HeaderName::try_from(name.as_str())
+            .map_err(|e| format!("invalid header {}: {}", name, e))?;
+        let value = HeaderValue::try_from(value.as_str())
+            .map_err(|e| format!("invalid value for header {}: {}", name, e))?;
+        request = request.header(name, value);
+    }
+
+    let mut request = request
+        .body(Body::from(example.body.clone().unwrap_or_default()))
+        .map_err(|e| format!("invalid request: {}", e))?;
+
+    // Faults are armed as usual but there is no connection to break
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(Connection::with_trigger(
+            FaultTrigger::default(),
+        )));
+
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_examples_are_checked_against_their_route() {
+        let temp_dir = TempDir::new().unwrap();
+        let users_dir = temp_dir.path().join("users");
+        fs::create_dir_all(users_dir.join("[id]")).unwrap();
+
+        fs::write(
+            users_dir.join("GET.json"),
+            "---\nexamples:\n  - status: 200\n  - status: 201\n---\n[]",
+        )
+        .unwrap();
+        fs::write(
+            users_dir.join("[id]/GET.json"),
+            "---\nexamples:\n  - path: /users/42\n  - path: /users\n  - path: /posts\n  - {}\n---\n{}",
+        )
+        .unwrap();
+
+        let outcomes = run(temp_dir.path()).await.unwrap();
+        let results: Vec<(&str, Option<&str>)> = outcomes
+            .iter()
+            .map(|outcome| (outcome.request.as_str(), outcome.failure.as_deref()))
+            .collect();
+
+        assert_eq!(results.len(), 6);
+        assert!(results.contains(&("GET /users", None)));
+        assert!(results.contains(&("GET /users", Some("expected status 201, got 200"))));
+        assert!(results.contains(&("GET /users/42", None)));
+        assert!(results.iter().any(|(request, failure)| {
+            *request == "GET /users" && *failure == Some("answered by users/GET.json")
+        }));
+        assert!(results.contains(&("GET /posts", Some("matched no route (status 404)"))));
+        assert!(results.contains(&(
+            "GET /users/:id",
+            Some("needs a `path`, the route has parameters")
+        )));
+    }
+}
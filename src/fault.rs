@@ -0,0 +1,268 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Connection-level fault injection.
+//!
+//! Faults cannot be expressed as HTTP responses, so they bypass axum and act
+//! on the TCP stream itself: the handler arms a [`FaultTrigger`] shared with
+//! the connection's [`FaultStream`], which then replaces the next write (the
+//! regular response) with the fault and terminates the connection.
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::{Sleep, sleep};
+
+/// Status line and headers announcing a chunked body, followed by a chunk
+/// with an invalid size line
+const MALFORMED_CHUNK: &[u8] =
+    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nnot a valid chunk\r\n";
+
+const RANDOM_GARBAGE_BYTES: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fault {
+    /// Abort the connection with a TCP reset
+    ConnectionReset,
+    /// Close the connection without sending anything
+    EmptyResponse,
+    /// Send a valid status line, then an invalid chunk, then close
+    MalformedChunk,
+    /// Send random bytes that are not HTTP at all, then close
+    RandomGarbage,
+}
+
+/// Shared switch between a request handler and its connection's stream
+#[derive(Debug, Clone, Default)]
+pub struct FaultTrigger(Arc<Mutex<Option<Fault>>>);
+
+impl FaultTrigger {
+    /// Replace the next write on the connection with the given fault
+    pub fn arm(&self, fault: Fault) {
+        *self.0.lock().unwrap() = Some(fault);
+    }
+
+    /// Remove and return the armed fault, if any
+    pub fn take(&self) -> Option<Fault> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+enum State {
+    Normal,
+    /// Writing the fault payload; the connection fails once it is written
+    Injecting {
+        payload: Vec<u8>,
+        written: usize,
+    },
+    Failed(io::ErrorKind),
+}
+
+/// TCP stream that can be told to misbehave through a [`FaultTrigger`]
+pub struct FaultStream {
+    inner: TcpStream,
+    trigger: FaultTrigger,
+    state: State,
+    /// Nothing is read from the client before this has elapsed
+    accept_delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl FaultStream {
+    pub fn new(inner: TcpStream) -> Self {
+        Self {
+            inner,
+            trigger: FaultTrigger::default(),
+            state: State::Normal,
+            accept_delay: None,
+        }
+    }
+
+    /// Leave the client waiting for `delay` before reading anything, which
+    /// also holds up the TLS handshake
+    pub fn with_accept_delay(mut self, delay: Option<Duration>) -> Self {
+        self.accept_delay = delay.map(|delay| Box::pin(sleep(delay)));
+        self
+    }
+
+    pub fn trigger(&self) -> FaultTrigger {
+        self.trigger.clone()
+    }
+
+    fn start_fault(&mut self, fault: Fault) {
+        self.state = match fault {
+            Fault::ConnectionReset => {
+                // A zero linger time makes the close send RST instead of FIN
+                let _ = self.inner.set_linger(Some(Duration::ZERO));
+                State::Failed(io::ErrorKind::ConnectionReset)
+            }
+            Fault::EmptyResponse => State::Failed(io::ErrorKind::BrokenPipe),
+            Fault::MalformedChunk => State::Injecting {
+                payload: MALFORMED_CHUNK.to_vec(),
+                written: 0,
+            },
+            Fault::RandomGarbage => {
+                let mut payload = vec![0; RANDOM_GARBAGE_BYTES];
+                rand::rng().fill_bytes(&mut payload);
+                State::Injecting {
+                    payload,
+                    written: 0,
+                }
+            }
+        };
+    }
+}
+
+impl AsyncRead for FaultStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Some(delay) = &mut self.accept_delay {
+            ready!(delay.as_mut().poll(cx));
+            self.accept_delay = None;
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for FaultStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+
+        if matches!(this.state, State::Normal)
+            && let Some(fault) = this.trigger.take()
+        {
+            this.start_fault(fault);
+        }
+
+        loop {
+            match &mut this.state {
+                State::Normal => return Pin::new(&mut this.inner).poll_write(cx, buf),
+                State::Injecting { payload, written } => {
+                    if *written == payload.len() {
+                        this.state = State::Failed(io::ErrorKind::BrokenPipe);
+                        continue;
+                    }
+                    let count =
+                        ready!(Pin::new(&mut this.inner).poll_write(cx, &payload[*written..]))?;
+                    *written += count;
+                }
+                State::Failed(kind) => {
+                    return Poll::Ready(Err(io::Error::new(*kind, "injected fault")));
+                }
+            }
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    async fn connected_pair() -> (FaultStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (FaultStream::new(server), client)
+    }
+
+    #[tokio::test]
+    async fn test_passthrough_without_fault() {
+        let (mut server, mut client) = connected_pair().await;
+
+        server.write_all(b"hello").await.unwrap();
+        drop(server);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_empty_response() {
+        let (mut server, mut client) = connected_pair().await;
+
+        server.trigger().arm(Fault::EmptyResponse);
+        assert!(server.write_all(b"HTTP/1.1 200 OK\r\n").await.is_err());
+        drop(server);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert!(received.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_chunk_replaces_response() {
+        let (mut server, mut client) = connected_pair().await;
+
+        server.trigger().arm(Fault::MalformedChunk);
+        assert!(
+            server
+                .write_all(b"HTTP/1.1 204 No Content\r\n")
+                .await
+                .is_err()
+        );
+        drop(server);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, MALFORMED_CHUNK);
+    }
+
+    #[tokio::test]
+    async fn test_connection_reset() {
+        let (mut server, mut client) = connected_pair().await;
+
+        server.trigger().arm(Fault::ConnectionReset);
+        assert!(server.write_all(b"HTTP/1.1 200 OK\r\n").await.is_err());
+        drop(server);
+
+        let mut received = Vec::new();
+        let error = client.read_to_end(&mut received).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+    }
+
+    #[tokio::test]
+    async fn test_accept_delay_holds_back_reads() {
+        let (server, mut client) = connected_pair().await;
+        let start = std::time::Instant::now();
+        let mut server = server.with_accept_delay(Some(Duration::from_millis(200)));
+
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let mut received = [0; 4];
+        server.read_exact(&mut received).await.unwrap();
+
+        assert_eq!(&received, b"GET ");
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
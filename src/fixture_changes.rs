@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Routes changed by a hot reload (`--mark-changed-fixtures`).
+//!
+//! The first response a changed route sends after a reload carries
+//! `X-Blendwerk-Fixture-Changed: true`, so tests editing fixtures while
+//! blendwerk runs can tell their change took effect.
+
+use crate::routes::Route;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+pub const HEADER: &str = "x-blendwerk-fixture-changed";
+
+/// Method and path pattern identifying a route across reloads
+type RouteKey = (String, String);
+
+fn key(route: &Route) -> RouteKey {
+    (route.method.as_str().to_string(), route.display_path())
+}
+
+#[derive(Debug, Default)]
+pub struct ChangedFixtures {
+    pending: Mutex<HashSet<RouteKey>>,
+}
+
+impl ChangedFixtures {
+    /// Remember the routes of `new` that are new or differ from `old`,
+    /// returning how many there are
+    pub fn record(&self, old: &[Route], new: &[Route]) -> usize {
+        let old: HashMap<RouteKey, &Route> = old.iter().map(|route| (key(route), route)).collect();
+        let changed: Vec<RouteKey> = new
+            .iter()
+            .filter(|route| {
+                old.get(&key(route)).is_none_or(|previous| {
+                    previous.source != route.source
+                        || previous.modified != route.modified
+                        || previous.response.body != route.response.body
+                })
+            })
+            .map(key)
+            .collect();
+
+        let count = changed.len();
+        self.pending.lock().unwrap().extend(changed);
+        count
+    }
+
+    /// Whether `route` changed since it last answered, clearing the mark
+    pub fn take(&self, route: &Route) -> bool {
+        self.pending.lock().unwrap().remove(&key(route))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scan_directory;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_changed_routes_are_marked_once() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("users")).unwrap();
+        fs::write(temp_dir.path().join("users/GET.json"), "[]").unwrap();
+        fs::write(temp_dir.path().join("users/POST.json"), "{}").unwrap();
+        let old = scan_directory(temp_dir.path()).unwrap();
+
+        fs::write(temp_dir.path().join("users/GET.json"), "[1]").unwrap();
+        fs::write(temp_dir.path().join("GET.json"), "{}").unwrap();
+        let new = scan_directory(temp_dir.path()).unwrap();
+
+        let changes = ChangedFixtures::default();
+        assert_eq!(changes.record(&old, &new), 2);
+
+        let route = |method: &str, path: &str| {
+            new.iter()
+                .find(|route| route.method.as_str() == method && route.display_path() == path)
+                .unwrap()
+        };
+        assert!(changes.take(route("GET", "/users")));
+        assert!(!changes.take(route("GET", "/users")));
+        assert!(!changes.take(route("POST", "/users")));
+        assert!(changes.take(route("GET", "/")));
+    }
+}
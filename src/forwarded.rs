@@ -0,0 +1,321 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Scheme, host and client address behind reverse proxies
+//! (`--trusted-proxy`).
+//!
+//! `Forwarded` and `X-Forwarded-*` headers are only believed if the
+//! connection comes from a trusted proxy; otherwise anyone could pick the
+//! address and host blendwerk sees. The client is the last address in the
+//! chain that isn't a trusted proxy itself.
+
+use crate::connection::Connection;
+use anyhow::{Context, bail};
+use axum::http::{HeaderMap, Uri, header::HOST};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+/// Network given with `--trusted-proxy`, e.g. `10.0.0.0/8` or `::1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+        let address = IpAddr::from_str(address)
+            .with_context(|| format!("Invalid IP address: {}", address))?
+            .to_canonical();
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .with_context(|| format!("Invalid prefix length: {}", prefix))?,
+            None => max,
+        };
+        if prefix > max {
+            bail!("Prefix length {} is longer than {}", prefix, max);
+        }
+        Ok(Self { address, prefix })
+    }
+}
+
+/// Proxies whose forwarding headers are believed
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(pub Vec<Network>);
+
+/// Where a request was sent to and who sent it, after proxies
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forwarded {
+    pub scheme: String,
+    pub host: String,
+    /// Unknown for connections without a peer address (e.g. in tests) and
+    /// for obfuscated `for=` identifiers
+    pub client_ip: Option<IpAddr>,
+}
+
+impl Forwarded {
+    /// `scheme://host` the client sent the request to
+    pub fn origin(&self) -> String {
+        format!("{}://{}", self.scheme, self.host)
+    }
+}
+
+impl TrustedProxies {
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|network| network.contains(ip))
+    }
+
+    pub fn resolve(&self, uri: &Uri, headers: &HeaderMap, connection: &Connection) -> Forwarded {
+        let peer = connection.remote_addr.map(|addr| addr.ip().to_canonical());
+        let direct = Forwarded {
+            scheme: uri
+                .scheme_str()
+                .unwrap_or(if connection.secure { "https" } else { "http" })
+                .to_string(),
+            host: uri
+                .authority()
+                .map(|authority| authority.as_str())
+                .or_else(|| header(headers, HOST.as_str()))
+                .unwrap_or("localhost")
+                .to_string(),
+            client_ip: peer,
+        };
+        if !peer.is_some_and(|peer| self.trusts(peer)) {
+            return direct;
+        }
+
+        let elements = forwarded_elements(headers);
+        let (chain, scheme, host) = if elements.is_empty() {
+            let chain: Vec<Option<IpAddr>> = headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(parse_node)
+                .collect();
+            let host = header(headers, "x-forwarded-host").map(|host| {
+                match header(headers, "x-forwarded-port") {
+                    Some(port) if !host.contains(':') => format!("{}:{}", host, port),
+                    _ => host.to_string(),
+                }
+            });
+            (
+                chain,
+                header(headers, "x-forwarded-proto").map(str::to_string),
+                host,
+            )
+        } else {
+            let chain = elements
+                .iter()
+                .filter_map(|element| element.get("for"))
+                .map(|node| parse_node(node))
+                .collect();
+            let first = &elements[0];
+            (
+                chain,
+                first.get("proto").cloned(),
+                first.get("host").cloned(),
+            )
+        };
+
+        // Walk back from the proxy that connected to us, past other trusted
+        // proxies
+        let mut client_ip = peer;
+        for node in chain.into_iter().rev() {
+            client_ip = node;
+            if !node.is_some_and(|ip| self.trusts(ip)) {
+                break;
+            }
+        }
+
+        Forwarded {
+            scheme: scheme.unwrap_or(direct.scheme).to_ascii_lowercase(),
+            host: host.unwrap_or(direct.host),
+            client_ip,
+        }
+    }
+}
+
+/// First value of a comma-separated header
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .split(',')
+        .next()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Elements of the RFC 7239 `Forwarded` headers, with lowercase keys and
+/// unquoted values
+fn forwarded_elements(headers: &HeaderMap) -> Vec<std::collections::HashMap<String, String>> {
+    headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| {
+                    (
+                        key.trim().to_ascii_lowercase(),
+                        value.trim().trim_matches('"').to_string(),
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Address of a `for=` node or `X-Forwarded-For` entry, which may carry a
+/// port (`192.0.2.1:4711`, `[2001:db8::1]:4711`)
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim();
+    IpAddr::from_str(node)
+        .or_else(|_| SocketAddr::from_str(node).map(|addr| addr.ip()))
+        .or_else(|_| IpAddr::from_str(node.trim_start_matches('[').trim_end_matches(']')))
+        .ok()
+        .map(|ip| ip.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::FaultTrigger;
+    use axum::http::HeaderValue;
+
+    fn connection(peer: &str) -> Connection {
+        let mut connection = Connection::with_trigger(FaultTrigger::default());
+        connection.remote_addr = Some(SocketAddr::new(peer.parse().unwrap(), 40000));
+        connection
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("mock:8080"));
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::try_from(*value).unwrap());
+        }
+        headers
+    }
+
+    fn proxies(networks: &[&str]) -> TrustedProxies {
+        TrustedProxies(networks.iter().map(|n| n.parse().unwrap()).collect())
+    }
+
+    #[test]
+    fn test_networks() {
+        let network: Network = "10.0.0.0/8".parse().unwrap();
+        assert!(network.contains("10.1.2.3".parse().unwrap()));
+        assert!(network.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!network.contains("11.0.0.1".parse().unwrap()));
+        assert!(
+            "::1"
+                .parse::<Network>()
+                .unwrap()
+                .contains("::1".parse().unwrap())
+        );
+        assert!("0.0.0.0/0".parse::<Network>().is_ok());
+        assert!("10.0.0.0/33".parse::<Network>().is_err());
+        assert!("proxy".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn test_untrusted_peer_is_the_client() {
+        let request = headers(&[
+            ("x-forwarded-for", "1.2.3.4"),
+            ("x-forwarded-proto", "https"),
+        ]);
+        let forwarded = proxies(&["10.0.0.0/8"]).resolve(
+            &Uri::from_static("/"),
+            &request,
+            &connection("192.0.2.9"),
+        );
+        assert_eq!(
+            forwarded,
+            Forwarded {
+                scheme: "http".to_string(),
+                host: "mock:8080".to_string(),
+                client_ip: Some("192.0.2.9".parse().unwrap()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_x_forwarded_headers() {
+        let request = headers(&[
+            ("x-forwarded-for", "1.2.3.4, 10.0.0.2"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "staging.example.com"),
+            ("x-forwarded-port", "8443"),
+        ]);
+        let forwarded = proxies(&["10.0.0.0/8"]).resolve(
+            &Uri::from_static("/"),
+            &request,
+            &connection("10.0.0.1"),
+        );
+        assert_eq!(forwarded.origin(), "https://staging.example.com:8443");
+        assert_eq!(forwarded.client_ip, Some("1.2.3.4".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_forwarded_header() {
+        let request = headers(&[(
+            "forwarded",
+            "for=\"[2001:db8::1]:4711\";proto=https;host=api.example.com, for=10.0.0.2",
+        )]);
+        let forwarded = proxies(&["10.0.0.0/8"]).resolve(
+            &Uri::from_static("/"),
+            &request,
+            &connection("10.0.0.1"),
+        );
+        assert_eq!(forwarded.origin(), "https://api.example.com");
+        assert_eq!(forwarded.client_ip, Some("2001:db8::1".parse().unwrap()));
+
+        // Spoofed entries left of an untrusted address are ignored
+        let request = headers(&[("x-forwarded-for", "127.0.0.1, 1.2.3.4")]);
+        let forwarded = proxies(&["10.0.0.0/8"]).resolve(
+            &Uri::from_static("/"),
+            &request,
+            &connection("10.0.0.1"),
+        );
+        assert_eq!(forwarded.client_ip, Some("1.2.3.4".parse().unwrap()));
+    }
+}
@@ -6,30 +6,261 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use crate::auth::Auth;
+use crate::callbacks::Callback;
+use crate::chaos::ChaosSchedule;
+use crate::chunks::{Chunk, full_body};
+use crate::delay::Delay;
+use crate::examples::Example;
+use crate::fault::Fault;
+use crate::progression::Progression;
+use crate::randomize::Randomize;
+use crate::route_match::RouteMatch;
+use crate::routes::TrailingSlash;
+use crate::session::Session;
+use crate::sse::{self, SseEvent};
+use crate::throttle::Throttle;
+use crate::webdav::{MultistatusEntry, render_multistatus};
 use anyhow::{Context, Result};
-use serde::Deserialize;
-use std::collections::HashMap;
+use axum::http::Version;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ResponseMeta {
-    #[serde(default = "default_status")]
-    pub status: u16,
+    /// Status code; see [`ResponseMeta::status`]
     #[serde(default)]
-    pub headers: HashMap<String, String>,
+    pub status: Option<u16>,
     #[serde(default)]
-    pub delay: u64,
+    pub headers: HashMap<String, HeaderValues>,
+    #[serde(default)]
+    pub delay: Delay,
+    #[serde(default)]
+    pub multistatus: Option<Vec<MultistatusEntry>>,
+    /// XML namespaces of prefixes used in `multistatus` properties
+    #[serde(default)]
+    pub namespaces: BTreeMap<String, String>,
+    #[serde(default)]
+    pub fault: Option<Fault>,
+    #[serde(default)]
+    pub throttle: Option<Throttle>,
+    #[serde(default)]
+    pub chunks: Option<Vec<Chunk>>,
+    #[serde(default)]
+    pub events: Option<Vec<SseEvent>>,
+    #[serde(default = "default_keep_open")]
+    pub keep_open: bool,
+    /// Variables a GraphQL request must carry for an operation file to match
+    #[serde(default)]
+    pub variables: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Requests `blendwerk test` expects this file to answer
+    #[serde(default)]
+    pub examples: Vec<Example>,
+    /// Key/values recorded on the tracing span of requests served by this
+    /// file
+    #[serde(default)]
+    pub trace_fields: BTreeMap<String, String>,
+    /// Credentials requests must carry to get this response instead of a 401
+    #[serde(default)]
+    pub auth: Option<Auth>,
+    /// Session state the request must have and the state it moves to
+    #[serde(default)]
+    pub session: Option<Session>,
+    /// HTTP version requests must use; others get a 505
+    #[serde(default)]
+    pub http_version: Option<RouteHttpVersion>,
+    /// Media types the request's Content-Type must be one of; others get a
+    /// 415
+    #[serde(default)]
+    pub require_content_type: Option<HeaderValues>,
+    /// Send `ETag`/`Last-Modified` and answer conditional GETs with 304;
+    /// defaults to `--etag`
+    #[serde(default)]
+    pub etag: Option<bool>,
+    /// Generators replacing values of the JSON body on every request
+    #[serde(default)]
+    pub randomize: Option<Randomize>,
+    /// Phases overriding status, delay, fault or body over time
+    #[serde(default)]
+    pub chaos_schedule: Option<ChaosSchedule>,
+    /// Responses advancing with every request to the same path
+    #[serde(default)]
+    pub progression: Option<Progression>,
+    /// Outbound requests sent after the response, e.g. webhooks
+    #[serde(default)]
+    pub callbacks: Vec<Callback>,
+    /// Only serve this file while one of these tags is enabled
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// How the response body is produced
+    #[serde(default)]
+    pub mode: ResponseMode,
+    /// Conditions on the request, e.g. the client's address
+    #[serde(default, rename = "match")]
+    pub route_match: Option<RouteMatch>,
+    /// Treatment of request paths ending in `/`; defaults to
+    /// `--trailing-slash`
+    #[serde(default)]
+    pub trailing_slash: Option<TrailingSlash>,
+    /// Match static path segments ignoring case; defaults to
+    /// `--case-insensitive-paths`
+    #[serde(default)]
+    pub case_insensitive: Option<bool>,
+    /// WebAssembly module transforming the response, relative to the file
+    #[serde(default)]
+    pub plugin: Option<PathBuf>,
 }
 
-fn default_status() -> u16 {
-    200
+/// Value of a frontmatter header; a list sends the header once per value,
+/// e.g. for several `Set-Cookie` headers
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum HeaderValues {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HeaderValues {
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        let values = match self {
+            Self::One(value) => std::slice::from_ref(value),
+            Self::Many(values) => values.as_slice(),
+        };
+        values.iter().map(String::as_str)
+    }
+
+    /// The first value, which is the only one for most headers
+    pub fn first(&self) -> Option<&str> {
+        self.iter().next()
+    }
+
+    /// Add another value, turning a single value into a list
+    pub fn push(&mut self, value: String) {
+        match self {
+            Self::One(first) => *self = Self::Many(vec![std::mem::take(first), value]),
+            Self::Many(values) => values.push(value),
+        }
+    }
+}
+
+impl From<String> for HeaderValues {
+    fn from(value: String) -> Self {
+        Self::One(value)
+    }
+}
+
+impl PartialEq<str> for HeaderValues {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, Self::One(value) if value == other)
+    }
+}
+
+/// Source of a route's response body
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseMode {
+    /// The body of the route file
+    #[default]
+    File,
+    /// A JSON description of the received request
+    Echo,
+}
+
+/// HTTP version a single route is served in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "serde_yaml::Value")]
+pub enum RouteHttpVersion {
+    /// HTTP/1.x requests, answered as HTTP/1.0 (no chunked encoding, the
+    /// connection is closed afterwards)
+    Http10,
+    Http11,
+    Http2,
+}
+
+impl TryFrom<serde_yaml::Value> for RouteHttpVersion {
+    type Error = String;
+
+    fn try_from(value: serde_yaml::Value) -> Result<Self, Self::Error> {
+        // Unquoted versions are YAML numbers
+        let text = match &value {
+            serde_yaml::Value::String(text) => text.clone(),
+            serde_yaml::Value::Number(number) => number.to_string(),
+            _ => String::new(),
+        };
+        match text.as_str() {
+            "1.0" | "1" => Ok(Self::Http10),
+            "1.1" => Ok(Self::Http11),
+            "2" | "2.0" => Ok(Self::Http2),
+            _ => Err(format!(
+                "invalid http_version {:?}, expected 1.0, 1.1 or 2",
+                value
+            )),
+        }
+    }
+}
+
+impl RouteHttpVersion {
+    /// Whether requests in `version` can be answered in this version
+    pub fn accepts(self, version: Version) -> bool {
+        match self {
+            Self::Http10 | Self::Http11 => version <= Version::HTTP_11,
+            Self::Http2 => version == Version::HTTP_2,
+        }
+    }
+
+    pub fn version(self) -> Version {
+        match self {
+            Self::Http10 => Version::HTTP_10,
+            Self::Http11 => Version::HTTP_11,
+            Self::Http2 => Version::HTTP_2,
+        }
+    }
+}
+
+fn default_keep_open() -> bool {
+    true
+}
+
+impl ResponseMeta {
+    /// Status code to answer with, 200 unless given
+    pub fn status(&self) -> u16 {
+        self.status.unwrap_or(200)
+    }
 }
 
 impl Default for ResponseMeta {
     fn default() -> Self {
         Self {
-            status: 200,
+            status: None,
             headers: HashMap::new(),
-            delay: 0,
+            delay: Delay::default(),
+            multistatus: None,
+            namespaces: BTreeMap::new(),
+            fault: None,
+            throttle: None,
+            chunks: None,
+            events: None,
+            keep_open: true,
+            variables: None,
+            examples: Vec::new(),
+            trace_fields: BTreeMap::new(),
+            auth: None,
+            session: None,
+            http_version: None,
+            require_content_type: None,
+            etag: None,
+            randomize: None,
+            chaos_schedule: None,
+            progression: None,
+            callbacks: Vec::new(),
+            tags: Vec::new(),
+            mode: ResponseMode::default(),
+            route_match: None,
+            trailing_slash: None,
+            case_insensitive: None,
+            plugin: None,
         }
     }
 }
@@ -37,19 +268,61 @@ impl Default for ResponseMeta {
 #[derive(Debug, Clone)]
 pub struct ParsedResponse {
     pub meta: ResponseMeta,
-    pub body: String,
+    pub body: Bytes,
 }
 
 pub fn parse_frontmatter(content: &str) -> Result<ParsedResponse> {
+    let (meta, body_start) = parse_frontmatter_header(content)?;
+    build_response(meta, &content[body_start..])
+}
+
+/// Combine metadata and the written body into the response, letting
+/// frontmatter generating a body (`multistatus`, `chunks`, `events`) replace
+/// it
+pub fn build_response(mut meta: ResponseMeta, body: &str) -> Result<ParsedResponse> {
+    // A WebDAV multistatus definition replaces the body and, unless another
+    // status was given explicitly, answers with 207 Multi-Status
+    let body = match &meta.multistatus {
+        Some(entries) => {
+            if meta.status.is_none() {
+                meta.status = Some(207);
+            }
+            render_multistatus(entries, &meta.namespaces)?
+        }
+        None => body.to_string(),
+    };
+
+    // Scripted chunks replace the body as well; the concatenation is kept
+    // for logging and exports while responses are sent chunk by chunk
+    let body = match (&meta.chunks, &meta.events) {
+        (Some(chunks), _) => full_body(chunks),
+        (None, Some(events)) => sse::full_body(events),
+        (None, None) => body,
+    };
+
+    if meta.randomize.is_some() {
+        serde_json::from_str::<serde_json::Value>(&body).context("randomize: needs a JSON body")?;
+    }
+
+    Ok(ParsedResponse {
+        meta,
+        body: Bytes::from(body),
+    })
+}
+
+/// Parse the frontmatter and locate the body without copying it.
+///
+/// Returns the metadata and the byte offset of the body in `content`. The
+/// content only needs to extend past the closing delimiter, which allows
+/// parsing the beginning of files too large to be read into memory.
+pub fn parse_frontmatter_header(content: &str) -> Result<(ResponseMeta, usize)> {
+    let leading_whitespace = content.len() - content.trim_start().len();
     let content = content.trim_start();
 
     // Check if content starts with frontmatter delimiter
     if !content.starts_with("---") {
         // No frontmatter, entire content is body
-        return Ok(ParsedResponse {
-            meta: ResponseMeta::default(),
-            body: content.to_string(),
-        });
+        return Ok((ResponseMeta::default(), leading_whitespace));
     }
 
     // Find the closing delimiter
@@ -61,10 +334,11 @@ pub fn parse_frontmatter(content: &str) -> Result<ParsedResponse> {
     let yaml_content = &after_first[..closing_pos].trim();
     let body_start = 3 + closing_pos + 4; // Skip "---" + yaml + "\n---"
 
-    let body = if body_start < content.len() {
-        content[body_start..].trim_start_matches('\n').to_string()
+    let body_start = if body_start < content.len() {
+        let body = &content[body_start..];
+        body_start + body.len() - body.trim_start_matches('\n').len()
     } else {
-        String::new()
+        content.len()
     };
 
     let meta: ResponseMeta = if yaml_content.is_empty() {
@@ -73,7 +347,7 @@ pub fn parse_frontmatter(content: &str) -> Result<ParsedResponse> {
         serde_yaml::from_str(yaml_content).context("Failed to parse YAML frontmatter")?
     };
 
-    Ok(ParsedResponse { meta, body })
+    Ok((meta, leading_whitespace + body_start))
 }
 
 #[cfg(test)]
@@ -84,7 +358,7 @@ mod tests {
     fn test_no_frontmatter() {
         let content = r#"{"hello": "world"}"#;
         let result = parse_frontmatter(content).unwrap();
-        assert_eq!(result.meta.status, 200);
+        assert_eq!(result.meta.status(), 200);
         assert_eq!(result.body, r#"{"hello": "world"}"#);
     }
 
@@ -98,19 +372,44 @@ delay: 100
 ---
 {"created": true}"#;
         let result = parse_frontmatter(content).unwrap();
-        assert_eq!(result.meta.status, 201);
+        assert_eq!(result.meta.status(), 201);
         assert_eq!(result.meta.delay, 100);
         assert_eq!(result.meta.headers.get("X-Custom").unwrap(), "value");
         assert_eq!(result.body, r#"{"created": true}"#);
     }
 
+    #[test]
+    fn test_repeated_header_values() {
+        let content = r#"---
+headers:
+  Set-Cookie:
+    - session=abc; Path=/
+    - theme=dark
+---
+"#;
+        let result = parse_frontmatter(content).unwrap();
+        let values: Vec<&str> = result.meta.headers["Set-Cookie"].iter().collect();
+        assert_eq!(values, ["session=abc; Path=/", "theme=dark"]);
+    }
+
+    #[test]
+    fn test_delay_distribution_frontmatter() {
+        let content = r#"---
+delay:
+  uniform: [50, 200]
+---
+body"#;
+        let result = parse_frontmatter(content).unwrap();
+        assert!((50..=200).contains(&result.meta.delay.sample()));
+    }
+
     #[test]
     fn test_empty_frontmatter() {
         let content = r#"---
 ---
 body content"#;
         let result = parse_frontmatter(content).unwrap();
-        assert_eq!(result.meta.status, 200);
+        assert_eq!(result.meta.status(), 200);
         assert_eq!(result.body, "body content");
     }
 
@@ -121,8 +420,114 @@ status: 404
 ---
 Not found"#;
         let result = parse_frontmatter(content).unwrap();
-        assert_eq!(result.meta.status, 404);
+        assert_eq!(result.meta.status(), 404);
         assert!(result.meta.headers.is_empty());
         assert_eq!(result.body, "Not found");
     }
+
+    #[test]
+    fn test_header_body_offset() {
+        let content = "\n  ---\nstatus: 201\n---\n\nbody";
+        let (meta, offset) = parse_frontmatter_header(content).unwrap();
+        assert_eq!(meta.status(), 201);
+        assert_eq!(&content[offset..], "body");
+
+        let (_, offset) = parse_frontmatter_header("  plain").unwrap();
+        assert_eq!(offset, 2);
+    }
+
+    #[test]
+    fn test_fault_frontmatter() {
+        let content = r#"---
+fault: connection-reset
+---
+"#;
+        let result = parse_frontmatter(content).unwrap();
+        assert_eq!(result.meta.fault, Some(Fault::ConnectionReset));
+    }
+
+    #[test]
+    fn test_chunks_frontmatter() {
+        let content = r#"---
+chunks:
+  - data: "{\"progress\": 50}\n"
+  - data: "{\"progress\": 100}\n"
+    delay: 500
+---
+ignored"#;
+        let result = parse_frontmatter(content).unwrap();
+        let chunks = result.meta.chunks.unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].delay, 500);
+        assert_eq!(result.body, "{\"progress\": 50}\n{\"progress\": 100}\n");
+    }
+
+    #[test]
+    fn test_events_frontmatter() {
+        let content = r#"---
+keep_open: false
+events:
+  - event: token
+    data: Hello
+  - data: World
+    delay: 100
+---
+"#;
+        let result = parse_frontmatter(content).unwrap();
+        assert!(!result.meta.keep_open);
+        assert_eq!(result.meta.events.unwrap().len(), 2);
+        assert_eq!(result.body, "event: token\ndata: Hello\n\ndata: World\n\n");
+    }
+
+    #[test]
+    fn test_multistatus_frontmatter() {
+        let content = r#"---
+multistatus:
+  - href: /files/
+    props:
+      displayname: files
+---
+ignored"#;
+        let result = parse_frontmatter(content).unwrap();
+        assert_eq!(result.meta.status(), 207);
+        let body = String::from_utf8_lossy(&result.body);
+        assert!(body.contains("<D:href>/files/</D:href>"));
+        assert!(body.contains("<D:displayname>files</D:displayname>"));
+
+        let explicit = parse_frontmatter("---\nstatus: 200\nmultistatus:\n  - href: /\n---\n");
+        assert_eq!(explicit.unwrap().meta.status(), 200);
+        let undeclared =
+            parse_frontmatter("---\nmultistatus:\n  - href: /\n    props: {X:a: b}\n---\n");
+        assert!(undeclared.is_err());
+    }
+
+    #[test]
+    fn test_http_version_frontmatter() {
+        let version = |yaml: &str| {
+            parse_frontmatter(&format!("---\nhttp_version: {}\n---\n", yaml))
+                .map(|result| result.meta.http_version)
+        };
+        assert_eq!(version("1.0").unwrap(), Some(RouteHttpVersion::Http10));
+        assert_eq!(version("\"1.1\"").unwrap(), Some(RouteHttpVersion::Http11));
+        assert_eq!(version("2").unwrap(), Some(RouteHttpVersion::Http2));
+        assert!(version("3").is_err());
+
+        assert!(RouteHttpVersion::Http10.accepts(Version::HTTP_11));
+        assert!(!RouteHttpVersion::Http11.accepts(Version::HTTP_2));
+        assert!(!RouteHttpVersion::Http2.accepts(Version::HTTP_11));
+    }
+
+    #[test]
+    fn test_trace_fields_frontmatter() {
+        let content = r#"---
+trace_fields:
+  test_case: login-42
+  attempt: 3
+---
+{}"#;
+        let result = parse_frontmatter(content).unwrap();
+        let fields = result.meta.trace_fields;
+        assert_eq!(fields["test_case"], "login-42");
+        assert_eq!(fields["attempt"], "3");
+    }
 }
@@ -0,0 +1,212 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Matching of GraphQL requests by operation name and variables.
+//!
+//! Files inside a directory named `graphql/` whose stem is not an HTTP
+//! method are operation files: `graphql/GetUser.json` answers `POST
+//! /graphql` requests for the `GetUser` operation.
+
+use crate::routes::HttpMethod;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Name of the directories holding operation files
+pub const GRAPHQL_DIR: &str = "graphql";
+
+/// Operation name and variables of a GraphQL request body
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphqlRequest {
+    pub operation_name: Option<String>,
+    pub variables: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestBody {
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    operation_name: Option<String>,
+    #[serde(default)]
+    variables: Option<Map<String, Value>>,
+}
+
+impl GraphqlRequest {
+    /// Parse a JSON request body; `None` if it isn't a GraphQL request
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let body: RequestBody = serde_json::from_slice(body).ok()?;
+
+        // Clients may omit operationName for single-operation documents
+        let operation_name = body
+            .operation_name
+            .filter(|name| !name.is_empty())
+            .or_else(|| body.query.as_deref().and_then(operation_name_from_query));
+
+        Some(Self {
+            operation_name,
+            variables: body.variables.unwrap_or_default(),
+        })
+    }
+
+    /// Whether the request is for `operation` and carries at least the
+    /// `expected` variables with equal values
+    pub fn matches(&self, operation: &str, expected: Option<&Map<String, Value>>) -> bool {
+        if self.operation_name.as_deref() != Some(operation) {
+            return false;
+        }
+
+        expected.is_none_or(|expected| {
+            expected
+                .iter()
+                .all(|(name, value)| self.variables.get(name) == Some(value))
+        })
+    }
+}
+
+/// Name of the first operation in a GraphQL document, skipping fragment
+/// definitions; `None` for anonymous operations
+fn operation_name_from_query(query: &str) -> Option<String> {
+    let is_name_char = |c: char| c == '_' || c.is_ascii_alphanumeric();
+    let mut depth = 0usize;
+    let mut chars = query.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '#' => {
+                // Comment until the end of the line
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                let mut escaped = false;
+                for (_, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth = depth.saturating_sub(1),
+            c if depth == 0 && (c == '_' || c.is_ascii_alphabetic()) => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !is_name_char(c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+
+                if matches!(&query[start..end], "query" | "mutation" | "subscription") {
+                    let after = query[end..].trim_start();
+                    let name_len = after
+                        .find(|c: char| !is_name_char(c))
+                        .unwrap_or(after.len());
+                    return (name_len > 0).then(|| after[..name_len].to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Operation name of an operation file, e.g. `GetUser` for
+/// `graphql/GetUser.json` or `graphql/GetUser.admin.json`
+pub fn operation_name(file_path: &Path) -> Option<String> {
+    let in_graphql_dir = file_path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .is_some_and(|name| name == GRAPHQL_DIR);
+    if !in_graphql_dir {
+        return None;
+    }
+
+    let stem = file_path.file_stem()?.to_str()?;
+    if HttpMethod::from_str(stem).is_some() {
+        return None;
+    }
+
+    stem.split('.')
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_operation_name() {
+        let explicit = GraphqlRequest::parse(
+            br#"{"query": "query A { a }", "operationName": "GetUser", "variables": {"id": "1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(explicit.operation_name.as_deref(), Some("GetUser"));
+        assert_eq!(explicit.variables["id"], "1");
+
+        let from_query =
+            GraphqlRequest::parse(br#"{"query": "mutation CreateUser($name: String) { x }"}"#)
+                .unwrap();
+        assert_eq!(from_query.operation_name.as_deref(), Some("CreateUser"));
+
+        let with_fragment = GraphqlRequest::parse(
+            br##"{"query": "# list\nfragment F on User { id } query ListUsers { users { ...F } }"}"##,
+        )
+        .unwrap();
+        assert_eq!(with_fragment.operation_name.as_deref(), Some("ListUsers"));
+
+        let anonymous = GraphqlRequest::parse(br#"{"query": "{ users { id } }"}"#).unwrap();
+        assert_eq!(anonymous.operation_name, None);
+
+        assert!(GraphqlRequest::parse(b"not json").is_none());
+    }
+
+    #[test]
+    fn test_variables_are_matched_as_subset() {
+        let request = GraphqlRequest {
+            operation_name: Some("GetUser".to_string()),
+            variables: json!({"id": "1", "locale": "de"})
+                .as_object()
+                .unwrap()
+                .clone(),
+        };
+
+        assert!(request.matches("GetUser", None));
+        assert!(!request.matches("ListUsers", None));
+
+        let id_1 = json!({"id": "1"}).as_object().unwrap().clone();
+        let id_2 = json!({"id": "2"}).as_object().unwrap().clone();
+        assert!(request.matches("GetUser", Some(&id_1)));
+        assert!(!request.matches("GetUser", Some(&id_2)));
+    }
+
+    #[test]
+    fn test_operation_file_names() {
+        assert_eq!(
+            operation_name(Path::new("mocks/graphql/GetUser.json")).as_deref(),
+            Some("GetUser")
+        );
+        assert_eq!(
+            operation_name(Path::new("mocks/api/graphql/GetUser.admin.json")).as_deref(),
+            Some("GetUser")
+        );
+        assert_eq!(operation_name(Path::new("mocks/graphql/POST.json")), None);
+        assert_eq!(operation_name(Path::new("mocks/users/GetUser.json")), None);
+    }
+}
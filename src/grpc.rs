@@ -0,0 +1,511 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! gRPC mocks served from `.proto` descriptors and YAML stubs.
+//!
+//! Everything lives in the `grpc/` directory of the mock tree: the `.proto`
+//! files describe the services, stub files map methods to response messages
+//! written as JSON-style YAML.
+
+use crate::delay::Delay;
+use crate::server::ShutdownSignal;
+use anyhow::{Context, Result, anyhow, bail};
+use axum::{
+    Router,
+    body::Body,
+    extract::State,
+    http::{HeaderMap, HeaderName, HeaderValue, Request, StatusCode},
+    response::Response,
+};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::stream;
+use http_body::Frame;
+use http_body_util::StreamBody;
+use prost_reflect::prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+use tower_http::trace::{self, TraceLayer};
+use tracing::Level;
+
+/// Directory inside the mock tree holding `.proto` files and stubs
+pub const GRPC_DIR: &str = "grpc";
+
+/// gRPC status codes by their canonical names
+const STATUS_CODES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+const INVALID_ARGUMENT: u32 = 3;
+const UNIMPLEMENTED: u32 = 12;
+
+/// gRPC status code, given as number or canonical name (`NOT_FOUND`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(try_from = "serde_yaml::Value")]
+pub struct GrpcStatus(u32);
+
+impl TryFrom<serde_yaml::Value> for GrpcStatus {
+    type Error = String;
+
+    fn try_from(value: serde_yaml::Value) -> Result<Self, Self::Error> {
+        let code = match &value {
+            serde_yaml::Value::Number(number) => number.as_u64().map(|n| n as usize),
+            serde_yaml::Value::String(name) => STATUS_CODES
+                .iter()
+                .position(|code| code.eq_ignore_ascii_case(&name.replace('-', "_"))),
+            _ => None,
+        };
+
+        match code {
+            Some(code) if code < STATUS_CODES.len() => Ok(Self(code as u32)),
+            _ => Err(format!("invalid gRPC status {:?}", value)),
+        }
+    }
+}
+
+/// A stub file: responses for the methods of one service
+#[derive(Debug, Deserialize)]
+struct StubFile {
+    service: String,
+    methods: HashMap<String, MethodStub>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MethodStub {
+    #[serde(default)]
+    status: GrpcStatus,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+    #[serde(default)]
+    delay: Delay,
+    /// Single response message of a unary call
+    #[serde(default)]
+    response: Option<serde_json::Value>,
+    /// Messages of a server-streaming call
+    #[serde(default)]
+    stream: Vec<StreamedMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamedMessage {
+    message: serde_json::Value,
+    #[serde(default)]
+    delay: Delay,
+}
+
+/// Encoded response of a stubbed method
+#[derive(Debug, Clone)]
+struct GrpcResponse {
+    status: GrpcStatus,
+    message: String,
+    metadata: HashMap<String, String>,
+    delay: Delay,
+    messages: Vec<(Bytes, Delay)>,
+}
+
+#[derive(Debug, Clone)]
+struct GrpcMethod {
+    input: MessageDescriptor,
+    response: Option<GrpcResponse>,
+}
+
+/// All methods described by the `.proto` files, keyed by their request
+/// path (`/package.Service/Method`)
+#[derive(Debug, Clone, Default)]
+pub struct GrpcMocks {
+    methods: HashMap<String, GrpcMethod>,
+}
+
+impl GrpcMocks {
+    pub fn method_count(&self) -> usize {
+        self.methods.len()
+    }
+
+    pub fn stubbed_count(&self) -> usize {
+        self.methods
+            .values()
+            .filter(|method| method.response.is_some())
+            .count()
+    }
+}
+
+/// Compile the `.proto` files and encode the stubs in `<base_dir>/grpc/`
+pub fn load(base_dir: &Path) -> Result<GrpcMocks> {
+    let grpc_dir = base_dir.join(GRPC_DIR);
+    if !grpc_dir.is_dir() {
+        bail!("gRPC directory does not exist: {}", grpc_dir.display());
+    }
+
+    let mut protos = Vec::new();
+    collect_protos(&grpc_dir, &grpc_dir, &mut protos)?;
+    if protos.is_empty() {
+        bail!("No .proto files found in {}", grpc_dir.display());
+    }
+
+    let pool = protox::Compiler::new([&grpc_dir])?
+        .include_imports(true)
+        .open_files(&protos)
+        .map_err(|e| anyhow!("{}", e))
+        .context("Failed to compile .proto files")?
+        .descriptor_pool();
+
+    let mut methods = HashMap::new();
+    for service in pool.services() {
+        for method in service.methods() {
+            methods.insert(
+                format!("/{}/{}", service.full_name(), method.name()),
+                GrpcMethod {
+                    input: method.input(),
+                    response: None,
+                },
+            );
+        }
+    }
+
+    let mut stub_files: Vec<PathBuf> = fs::read_dir(&grpc_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && matches!(
+                    path.extension().and_then(|ext| ext.to_str()),
+                    Some("yaml" | "yml")
+                )
+        })
+        .collect();
+    stub_files.sort();
+
+    for stub_file in stub_files {
+        load_stub_file(&pool, &stub_file, &mut methods)
+            .with_context(|| format!("Failed to load gRPC stub: {}", stub_file.display()))?;
+    }
+
+    Ok(GrpcMocks { methods })
+}
+
+/// Collect `.proto` files relative to the include directory
+fn collect_protos(include_dir: &Path, dir: &Path, protos: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_protos(include_dir, &path, protos)?;
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some("proto") {
+            protos.push(path.strip_prefix(include_dir)?.to_path_buf());
+        }
+    }
+    protos.sort();
+    Ok(())
+}
+
+fn load_stub_file(
+    pool: &DescriptorPool,
+    stub_file: &Path,
+    methods: &mut HashMap<String, GrpcMethod>,
+) -> Result<()> {
+    let content = fs::read_to_string(stub_file)?;
+    let stubs: StubFile = serde_yaml::from_str(&content).context("Failed to parse YAML")?;
+
+    let service = pool
+        .get_service_by_name(&stubs.service)
+        .ok_or_else(|| anyhow!("Unknown service '{}'", stubs.service))?;
+
+    for (name, stub) in stubs.methods {
+        let method = service
+            .methods()
+            .find(|method| method.name() == name)
+            .ok_or_else(|| anyhow!("Unknown method '{}' of {}", name, stubs.service))?;
+
+        if !method.is_server_streaming() && stub.stream.len() > 1 {
+            bail!("{} is not server-streaming but has several messages", name);
+        }
+
+        let output = method.output();
+        let mut messages = Vec::new();
+        if let Some(response) = stub.response {
+            messages.push((encode(&output, response)?, Delay::default()));
+        }
+        for streamed in stub.stream {
+            messages.push((encode(&output, streamed.message)?, streamed.delay));
+        }
+
+        let path = format!("/{}/{}", service.full_name(), name);
+        if let Some(grpc_method) = methods.get_mut(&path) {
+            grpc_method.response = Some(GrpcResponse {
+                status: stub.status,
+                message: stub.message,
+                metadata: stub.metadata,
+                delay: stub.delay,
+                messages,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Encode a message given in its JSON mapping
+fn encode(desc: &MessageDescriptor, value: serde_json::Value) -> Result<Bytes> {
+    let message = DynamicMessage::deserialize(desc.clone(), value)
+        .with_context(|| format!("Invalid {} message", desc.full_name()))?;
+    Ok(Bytes::from(message.encode_to_vec()))
+}
+
+/// Prefix a message with the gRPC length-prefixed framing
+fn frame(message: &[u8]) -> Bytes {
+    let mut framed = BytesMut::with_capacity(5 + message.len());
+    framed.put_u8(0); // not compressed
+    framed.put_u32(message.len() as u32);
+    framed.put_slice(message);
+    framed.freeze()
+}
+
+pub async fn run_grpc_server(
+    mocks: GrpcMocks,
+    listener: std::net::TcpListener,
+    mut shutdown: ShutdownSignal,
+) -> Result<()> {
+    let router = Router::new()
+        .fallback(grpc_handler)
+        .with_state(Arc::new(mocks))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        );
+    let listener = TcpListener::from_std(listener)?;
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.changed().await;
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn grpc_handler(State(mocks): State<Arc<GrpcMocks>>, request: Request<Body>) -> Response {
+    let path = request.uri().path().to_string();
+
+    let Some(method) = mocks.methods.get(&path) else {
+        return error_response(UNIMPLEMENTED, &format!("Unknown method {}", path));
+    };
+    let Some(response) = &method.response else {
+        return error_response(UNIMPLEMENTED, &format!("No stub for {}", path));
+    };
+
+    // Reject requests that don't decode as the method's input message
+    let body = match axum::body::to_bytes(request.into_body(), usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => return error_response(INVALID_ARGUMENT, &e.to_string()),
+    };
+    if body.len() >= 5 {
+        let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+        let message = body.slice(5..(5 + len).min(body.len()));
+        if let Err(e) = DynamicMessage::decode(method.input.clone(), message) {
+            return error_response(INVALID_ARGUMENT, &format!("Invalid request: {}", e));
+        }
+    }
+
+    let delay = response.delay.sample();
+    if delay > 0 {
+        sleep(Duration::from_millis(delay)).await;
+    }
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &response.metadata {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::try_from(value.as_str()),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+
+    let trailers = status_headers(response.status.0, &response.message);
+    let messages = response.messages.clone();
+    let frames = stream::unfold(
+        (messages.into_iter(), Some(trailers)),
+        |(mut messages, trailers)| async move {
+            match messages.next() {
+                Some((message, delay)) => {
+                    let delay = delay.sample();
+                    if delay > 0 {
+                        sleep(Duration::from_millis(delay)).await;
+                    }
+                    let data = Frame::data(frame(&message));
+                    Some((Ok::<_, Infallible>(data), (messages, trailers)))
+                }
+                None => {
+                    let trailers = Frame::trailers(trailers?);
+                    Some((Ok(trailers), (messages, None)))
+                }
+            }
+        },
+    );
+
+    let mut builder = Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/grpc");
+    if let Some(response_headers) = builder.headers_mut() {
+        response_headers.extend(headers);
+    }
+    builder.body(Body::new(StreamBody::new(frames))).unwrap()
+}
+
+fn status_headers(code: u32, message: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("grpc-status", HeaderValue::from(code));
+    if !message.is_empty()
+        && let Ok(value) = HeaderValue::try_from(percent_encode(message))
+    {
+        headers.insert("grpc-message", value);
+    }
+    headers
+}
+
+/// Percent-encode a status message as required for `grpc-message`
+fn percent_encode(message: &str) -> String {
+    message
+        .bytes()
+        .map(|byte| match byte {
+            b' '..=b'~' if byte != b'%' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// A response without messages, carrying the status in its headers
+/// ("Trailers-Only")
+fn error_response(code: u32, message: &str) -> Response {
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/grpc")
+        .body(Body::empty())
+        .unwrap();
+    response.headers_mut().extend(status_headers(code, message));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PROTO: &str = r#"
+syntax = "proto3";
+package users.v1;
+
+message GetUserRequest { int64 id = 1; }
+message User { int64 id = 1; string name = 2; }
+
+service UserService {
+  rpc GetUser(GetUserRequest) returns (User);
+  rpc ListUsers(GetUserRequest) returns (stream User);
+  rpc DeleteUser(GetUserRequest) returns (User);
+}
+"#;
+
+    fn mock_tree(stubs: &str) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let grpc_dir = temp_dir.path().join(GRPC_DIR);
+        fs::create_dir(&grpc_dir).unwrap();
+        fs::write(grpc_dir.join("users.proto"), PROTO).unwrap();
+        fs::write(grpc_dir.join("users.yaml"), stubs).unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_load_stubs() {
+        let temp_dir = mock_tree(
+            r#"
+service: users.v1.UserService
+methods:
+  GetUser:
+    response: { id: 1, name: Alice }
+  ListUsers:
+    stream:
+      - message: { id: 1, name: Alice }
+      - message: { id: 2, name: Bob }
+        delay: 100
+  DeleteUser:
+    status: NOT_FOUND
+    message: user not found
+"#,
+        );
+
+        let mocks = load(temp_dir.path()).unwrap();
+        assert_eq!(mocks.method_count(), 3);
+        assert_eq!(mocks.stubbed_count(), 3);
+
+        let get_user = mocks.methods["/users.v1.UserService/GetUser"]
+            .response
+            .as_ref()
+            .unwrap();
+        assert_eq!(get_user.messages.len(), 1);
+        assert_eq!(get_user.messages[0].0.as_ref(), b"\x08\x01\x12\x05Alice");
+
+        let list_users = mocks.methods["/users.v1.UserService/ListUsers"]
+            .response
+            .as_ref()
+            .unwrap();
+        assert_eq!(list_users.messages.len(), 2);
+
+        let delete_user = mocks.methods["/users.v1.UserService/DeleteUser"]
+            .response
+            .as_ref()
+            .unwrap();
+        assert_eq!(delete_user.status, GrpcStatus(5));
+    }
+
+    #[test]
+    fn test_invalid_stubs_are_rejected() {
+        let unknown_field = mock_tree(
+            "service: users.v1.UserService\nmethods:\n  GetUser:\n    response: { nickname: x }\n",
+        );
+        assert!(load(unknown_field.path()).is_err());
+
+        let unknown_method =
+            mock_tree("service: users.v1.UserService\nmethods:\n  Missing:\n    response: {}\n");
+        assert!(load(unknown_method.path()).is_err());
+
+        let unary_stream = mock_tree(
+            "service: users.v1.UserService\nmethods:\n  GetUser:\n    stream:\n      - message: {}\n      - message: {}\n",
+        );
+        assert!(load(unary_stream.path()).is_err());
+    }
+
+    #[test]
+    fn test_framing_and_status_message() {
+        assert_eq!(frame(b"ab").as_ref(), b"\x00\x00\x00\x00\x02ab");
+        assert_eq!(percent_encode("not found: 100%"), "not found: 100%25");
+    }
+}
@@ -0,0 +1,465 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::frontmatter::HeaderValues;
+use crate::routes::HttpMethod;
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tracing::{info, warn};
+
+/// Headers describing the transfer rather than the response itself. The HAR
+/// body is already decoded, so replaying them would produce broken responses.
+const SKIPPED_HEADERS: &[&str] = &[
+    "connection",
+    "content-encoding",
+    "content-length",
+    "date",
+    "keep-alive",
+    "transfer-encoding",
+];
+
+#[derive(Debug, Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarEntry {
+    request: HarRequest,
+    response: HarResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarRequest {
+    method: String,
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarResponse {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    content: HarContent,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarContent {
+    #[serde(default)]
+    mime_type: String,
+    text: Option<String>,
+    encoding: Option<String>,
+}
+
+/// Convert all entries of a HAR capture into route files below `target_dir`.
+///
+/// Returns the number of written route files. The first response for a
+/// (method, path) pair wins, mirroring blendwerk's own matching order.
+/// Nothing is written if any of the files exists already, unless `force`
+/// is given.
+pub fn import(har_file: &Path, target_dir: &Path, force: bool) -> Result<usize> {
+    let content = fs::read_to_string(har_file)
+        .with_context(|| format!("Failed to read HAR file: {}", har_file.display()))?;
+    let har: Har = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse HAR file: {}", har_file.display()))?;
+
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+
+    for entry in har.log.entries {
+        let Some(method) = HttpMethod::from_str(&entry.request.method) else {
+            warn!(
+                "Skipping unsupported method {} {}",
+                entry.request.method, entry.request.url
+            );
+            continue;
+        };
+
+        let path = url_path(&entry.request.url);
+        let file_path = match route_file_path(
+            target_dir,
+            &path,
+            &method,
+            &entry.response.content.mime_type,
+        ) {
+            Ok(file_path) => file_path,
+            Err(e) => {
+                warn!("Skipping {} {}: {}", method.as_str(), path, e);
+                continue;
+            }
+        };
+        if !seen.insert(file_path.with_extension("")) {
+            warn!(
+                "Skipping repeated {} {}, keeping the first response",
+                method.as_str(),
+                path
+            );
+            continue;
+        }
+
+        let Some(body) = decode_body(&entry.response.content) else {
+            warn!(
+                "Skipping {} {}: binary response bodies are not supported",
+                method.as_str(),
+                path
+            );
+            continue;
+        };
+
+        let response = CapturedResponse {
+            status: entry.response.status,
+            headers: entry
+                .response
+                .headers
+                .iter()
+                .map(|header| (header.name.as_str(), header.value.as_str()))
+                .collect(),
+            mime_type: &entry.response.content.mime_type,
+            body: &body,
+        };
+        let file_content = render_route_file(&response)?;
+
+        info!("  {} {} -> {}", method.as_str(), path, file_path.display());
+        files.push((file_path, file_content));
+    }
+
+    if !force && let Some((file_path, _)) = files.iter().find(|(path, _)| path.exists()) {
+        bail!(
+            "Refusing to overwrite existing file {} (use --force)",
+            file_path.display()
+        );
+    }
+    for (file_path, file_content) in &files {
+        write_file(file_path, file_content)?;
+    }
+
+    Ok(files.len())
+}
+
+/// Extract the path of an absolute URL; query strings do not affect matching
+fn url_path(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = without_scheme
+        .find('/')
+        .map_or("/", |index| &without_scheme[index..]);
+
+    path.split(['?', '#']).next().unwrap_or("/").to_string()
+}
+
+/// A recorded response to be written as route file
+pub struct CapturedResponse<'a> {
+    pub status: u16,
+    /// Header names and values as received; repeated names become a list
+    pub headers: Vec<(&'a str, &'a str)>,
+    pub mime_type: &'a str,
+    pub body: &'a str,
+}
+
+/// Write `response` as the route file answering `method` requests to `path`
+/// below `target_dir`, returning the file's path
+pub fn write_route_file(
+    target_dir: &Path,
+    path: &str,
+    method: &HttpMethod,
+    response: &CapturedResponse,
+    force: bool,
+) -> Result<PathBuf> {
+    let file_path = route_file_path(target_dir, path, method, response.mime_type)?;
+    if file_path.exists() && !force {
+        bail!(
+            "Refusing to overwrite existing file {} (use --force)",
+            file_path.display()
+        );
+    }
+
+    write_file(&file_path, &render_route_file(response)?)?;
+    Ok(file_path)
+}
+
+fn write_file(file_path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    fs::write(file_path, content)
+        .with_context(|| format!("Failed to write file: {}", file_path.display()))
+}
+
+fn route_file_path(
+    target_dir: &Path,
+    path: &str,
+    method: &HttpMethod,
+    mime_type: &str,
+) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        relative.push(directory_name(segment)?);
+    }
+    relative.push(format!("{}.{}", method.as_str(), extension(mime_type)));
+
+    // Paths come from clients (promote endpoint) or captured traffic, so a
+    // segment must never climb out of or replace `target_dir`
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!("path {} leaves the mock directory", path);
+    }
+    let file_path = target_dir.join(relative);
+    if !file_path.starts_with(target_dir) {
+        bail!("path {} leaves the mock directory", path);
+    }
+    Ok(file_path)
+}
+
+/// Directory name matching the request path segment `segment`.
+///
+/// Requests are matched percent-decoded, so the name is too. A name the
+/// scanner would read as a parameter is escaped (`[id]` becomes `[[id]]`),
+/// and segments that can't be a single directory, such as `..` or an
+/// encoded `/`, are refused.
+fn directory_name(segment: &str) -> Result<String> {
+    let name = percent_decode_str(segment)
+        .decode_utf8()
+        .with_context(|| format!("path segment {} is not valid UTF-8", segment))?;
+    if name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        bail!("path segment {} can't be written as a directory", segment);
+    }
+    if name.starts_with('[') && name.ends_with(']') {
+        return Ok(format!("[{}]", name));
+    }
+    Ok(name.into_owned())
+}
+
+fn extension(mime_type: &str) -> &'static str {
+    let essence = mime_type.split(';').next().unwrap_or("").trim();
+    match essence {
+        "text/html" => "html",
+        "application/xml" | "text/xml" => "xml",
+        "text/css" => "css",
+        "application/javascript" | "text/javascript" => "js",
+        "text/plain" => "txt",
+        _ if essence.ends_with("json") => "json",
+        _ => "txt",
+    }
+}
+
+fn decode_body(content: &HarContent) -> Option<String> {
+    let text = content.text.clone().unwrap_or_default();
+
+    match content.encoding.as_deref() {
+        Some("base64") => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(text.trim())
+                .ok()?;
+            String::from_utf8(bytes).ok()
+        }
+        _ => Some(text),
+    }
+}
+
+fn render_route_file(response: &CapturedResponse) -> Result<String> {
+    let mut headers: BTreeMap<String, HeaderValues> = BTreeMap::new();
+    for &(name, value) in &response.headers {
+        let lower = name.to_lowercase();
+        // HTTP/2 pseudo headers and transfer details are not replayable
+        if lower.starts_with(':') || SKIPPED_HEADERS.contains(&lower.as_str()) {
+            continue;
+        }
+
+        // Repeated headers such as Set-Cookie become a list
+        match headers.get_mut(name) {
+            Some(values) => values.push(value.to_string()),
+            None => {
+                headers.insert(name.to_string(), value.to_string().into());
+            }
+        }
+    }
+
+    let mut meta = serde_yaml::Mapping::new();
+    if response.status != 200 {
+        meta.insert("status".into(), response.status.into());
+    }
+    if !headers.is_empty() {
+        meta.insert("headers".into(), serde_yaml::to_value(&headers)?);
+    }
+
+    if meta.is_empty() {
+        return Ok(response.body.to_string());
+    }
+
+    let yaml = serde_yaml::to_string(&meta).context("Failed to serialize frontmatter")?;
+    Ok(format!("---\n{}---\n{}", yaml, response.body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::{PathSegment, scan_directory};
+    use tempfile::TempDir;
+
+    const HAR: &str = r#"{
+  "log": {
+    "entries": [
+      {
+        "request": { "method": "GET", "url": "https://api.example.com/users/42?verbose=1" },
+        "response": {
+          "status": 404,
+          "headers": [
+            { "name": "Content-Type", "value": "application/json" },
+            { "name": "Content-Length", "value": "20" },
+            { "name": "X-Trace", "value": "abc" },
+            { "name": "Set-Cookie", "value": "a=1" },
+            { "name": "Set-Cookie", "value": "b=2" }
+          ],
+          "content": { "mimeType": "application/json", "text": "{\"error\": \"missing\"}" }
+        }
+      },
+      {
+        "request": { "method": "GET", "url": "https://api.example.com/users/42" },
+        "response": {
+          "status": 200,
+          "headers": [],
+          "content": { "mimeType": "application/json", "text": "{}" }
+        }
+      },
+      {
+        "request": { "method": "POST", "url": "https://api.example.com/" },
+        "response": {
+          "status": 200,
+          "headers": [],
+          "content": { "mimeType": "text/plain", "text": "aGVsbG8=", "encoding": "base64" }
+        }
+      }
+    ]
+  }
+}"#;
+
+    #[test]
+    fn test_import_har() {
+        let temp_dir = TempDir::new().unwrap();
+        let har_file = temp_dir.path().join("capture.har");
+        let mocks = temp_dir.path().join("mocks");
+        fs::write(&har_file, HAR).unwrap();
+
+        assert_eq!(import(&har_file, &mocks, false).unwrap(), 2);
+
+        let routes = scan_directory(&mocks).unwrap();
+        let user = routes
+            .iter()
+            .find(|r| r.display_path() == "/users/42")
+            .unwrap();
+        assert_eq!(user.response.meta.status(), 404);
+        assert_eq!(user.response.meta.headers.get("X-Trace").unwrap(), "abc");
+        assert_eq!(
+            user.response.meta.headers["Set-Cookie"],
+            HeaderValues::Many(vec!["a=1".to_string(), "b=2".to_string()])
+        );
+        assert!(!user.response.meta.headers.contains_key("Content-Length"));
+        assert_eq!(user.response.body, r#"{"error": "missing"}"#);
+
+        let root = routes
+            .iter()
+            .find(|r| r.method == HttpMethod::Post)
+            .unwrap();
+        assert_eq!(root.display_path(), "/");
+        assert_eq!(root.response.body, "hello");
+    }
+
+    #[test]
+    fn test_import_refuses_overwrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let har_file = temp_dir.path().join("capture.har");
+        let mocks = temp_dir.path().join("mocks");
+        fs::write(&har_file, HAR).unwrap();
+
+        import(&har_file, &mocks, false).unwrap();
+        assert!(import(&har_file, &mocks, false).is_err());
+        assert!(import(&har_file, &mocks, true).is_ok());
+    }
+
+    #[test]
+    fn test_import_sanitizes_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let har_file = temp_dir.path().join("capture.har");
+        let mocks = temp_dir.path().join("mocks");
+        let entry = |url: &str| {
+            format!(
+                r#"{{"request": {{"method": "GET", "url": "{}"}},
+                    "response": {{"status": 200, "content": {{"mimeType": "text/plain", "text": "{}"}}}}}}"#,
+                url, url
+            )
+        };
+        let urls = [
+            "https://example.com/../escaped",
+            "https://example.com/a/%2E%2E/%2E%2E/escaped",
+            "https://example.com/a%2Fb",
+            "https://example.com/files/[id]",
+            "https://example.com/caf%C3%A9",
+        ];
+        let entries: Vec<String> = urls.iter().map(|url| entry(url)).collect();
+        fs::write(
+            &har_file,
+            format!(r#"{{"log": {{"entries": [{}]}}}}"#, entries.join(",")),
+        )
+        .unwrap();
+
+        assert_eq!(import(&har_file, &mocks, false).unwrap(), 2);
+        assert!(!temp_dir.path().join("escaped").exists());
+        assert!(mocks.join("files/[[id]]/GET.txt").exists());
+
+        let routes = scan_directory(&mocks).unwrap();
+        let literal = routes
+            .iter()
+            .find(|r| r.display_path() == "/files/[id]")
+            .unwrap();
+        assert!(matches!(literal.path_segments[1], PathSegment::Static(_)));
+        assert!(routes.iter().any(|r| r.display_path() == "/café"));
+    }
+
+    #[test]
+    fn test_import_checks_conflicts_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let har_file = temp_dir.path().join("capture.har");
+        let mocks = temp_dir.path().join("mocks");
+        fs::write(&har_file, HAR).unwrap();
+        fs::create_dir_all(&mocks).unwrap();
+        fs::write(mocks.join("POST.txt"), "existing").unwrap();
+
+        assert!(import(&har_file, &mocks, false).is_err());
+        assert!(!mocks.join("users").exists());
+        assert_eq!(
+            fs::read_to_string(mocks.join("POST.txt")).unwrap(),
+            "existing"
+        );
+    }
+
+    #[test]
+    fn test_url_path() {
+        assert_eq!(url_path("https://example.com"), "/");
+        assert_eq!(url_path("https://example.com/a/b?c=d#e"), "/a/b");
+        assert_eq!(url_path("/relative/path"), "/relative/path");
+    }
+}
@@ -0,0 +1,245 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Experimental HTTP/3 listener.
+//!
+//! Requests arriving over QUIC are handed to the same router as on the HTTP
+//! and HTTPS ports. There is no TCP connection to break, so response faults
+//! reset the request stream instead, or close the whole QUIC connection for
+//! `connection-reset`.
+
+use crate::connection::Connection;
+use crate::fault::{Fault, FaultTrigger};
+use crate::server::{self, AppState, ShutdownSignal};
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    body::Body,
+    extract::ConnectInfo,
+    http::{Request, Response},
+};
+use axum_server::tls_rustls::RustlsConfig;
+use bytes::{BufMut, Bytes, BytesMut};
+use h3::error::Code;
+use h3::quic::BidiStream;
+use h3::server::RequestStream;
+use http_body_util::BodyExt;
+use quinn::crypto::rustls::QuicServerConfig;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// ALPN protocol identifier of HTTP/3
+const ALPN_H3: &[u8] = b"h3";
+
+/// Bind a QUIC endpoint on the given UDP port, using the HTTPS certificate
+pub fn bind(port: u16, tls_config: &RustlsConfig) -> Result<quinn::Endpoint> {
+    quinn::Endpoint::server(
+        server_config(tls_config)?,
+        SocketAddr::from(([0, 0, 0, 0], port)),
+    )
+    .with_context(|| format!("Failed to bind UDP port {}", port))
+}
+
+/// Switch a running endpoint to the current HTTPS certificate; existing
+/// connections keep the one they were established with
+pub fn reload(endpoint: &quinn::Endpoint, tls_config: &RustlsConfig) -> Result<()> {
+    endpoint.set_server_config(Some(server_config(tls_config)?));
+    Ok(())
+}
+
+fn server_config(tls_config: &RustlsConfig) -> Result<quinn::ServerConfig> {
+    let mut server_config = (*tls_config.get_inner()).clone();
+    server_config.alpn_protocols = vec![ALPN_H3.to_vec()];
+    let crypto = QuicServerConfig::try_from(server_config)
+        .context("TLS configuration can't be used for QUIC")?;
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+}
+
+pub async fn run_http3_server(
+    state: Arc<AppState>,
+    endpoint: quinn::Endpoint,
+    mut shutdown: ShutdownSignal,
+) -> Result<()> {
+    let router = server::create_router(state);
+
+    loop {
+        tokio::select! {
+            incoming = endpoint.accept() => {
+                let Some(incoming) = incoming else {
+                    break;
+                };
+                let router = router.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve_connection(router, incoming).await {
+                        tracing::debug!("HTTP/3 connection ended: {}", e);
+                    }
+                });
+            }
+            _ = shutdown.changed() => break,
+        }
+    }
+
+    endpoint.close(0u32.into(), b"server shutting down");
+    endpoint.wait_idle().await;
+
+    Ok(())
+}
+
+async fn serve_connection(router: Router, incoming: quinn::Incoming) -> Result<()> {
+    let quic = incoming.await?;
+    let mut connection: h3::server::Connection<h3_quinn::Connection, Bytes> =
+        h3::server::Connection::new(h3_quinn::Connection::new(quic.clone())).await?;
+
+    while let Some(resolver) = connection.accept().await? {
+        let router = router.clone();
+        let quic = quic.clone();
+        tokio::spawn(async move {
+            let result = match resolver.resolve_request().await {
+                Ok((request, stream)) => serve_request(router, &quic, request, stream).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                tracing::debug!("HTTP/3 request failed: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn serve_request<S>(
+    router: Router,
+    quic: &quinn::Connection,
+    request: Request<()>,
+    mut stream: RequestStream<S, Bytes>,
+) -> Result<()>
+where
+    S: BidiStream<Bytes>,
+{
+    let mut body = BytesMut::new();
+    while let Some(chunk) = stream.recv_data().await? {
+        body.put(chunk);
+    }
+
+    let fault = FaultTrigger::default();
+    let mut request = request.map(|()| Body::from(body.freeze()));
+    let mut connection = Connection::with_trigger(fault.clone());
+    connection.remote_addr = Some(quic.remote_address());
+    request.extensions_mut().insert(ConnectInfo(connection));
+
+    let response = router.oneshot(request).await?;
+
+    match fault.take() {
+        Some(Fault::ConnectionReset) => {
+            quic.close(Code::H3_INTERNAL_ERROR.value().try_into()?, b"");
+            return Ok(());
+        }
+        Some(_) => {
+            stream.stop_stream(Code::H3_INTERNAL_ERROR);
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let (parts, mut body) = response.into_parts();
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+
+    while let Some(frame) = body.frame().await {
+        match frame?.into_data() {
+            Ok(data) => stream.send_data(data).await?,
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    stream.send_trailers(trailers).await?;
+                }
+            }
+        }
+    }
+    stream.finish().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes;
+    use axum::http::Version;
+    use rcgen::{CertifiedKey, generate_simple_self_signed};
+    use std::fs;
+    use tempfile::TempDir;
+    use tokio::sync::{RwLock, watch};
+
+    #[tokio::test]
+    async fn test_serves_routes_over_http3() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("hello")).unwrap();
+        fs::write(temp_dir.path().join("hello/GET.json"), r#"{"hello": "h3"}"#).unwrap();
+
+        let CertifiedKey { cert, signing_key } =
+            generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let tls_config = RustlsConfig::from_pem(
+            cert.pem().into_bytes(),
+            signing_key.serialize_pem().into_bytes(),
+        )
+        .await
+        .unwrap();
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let routes = routes::scan_directory(temp_dir.path()).unwrap();
+        let state = Arc::new(AppState::new(
+            Arc::new(RwLock::new(routes)),
+            shutdown_rx.clone(),
+        ));
+        let endpoint = bind(0, &tls_config).unwrap();
+        let port = endpoint.local_addr().unwrap().port();
+        let server = tokio::spawn(run_http3_server(state, endpoint, shutdown_rx));
+
+        // Client trusting the generated certificate
+        let mut roots = quinn::rustls::RootCertStore::empty();
+        roots.add(cert.der().clone()).unwrap();
+        let mut client_crypto = quinn::rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_crypto.alpn_protocols = vec![ALPN_H3.to_vec()];
+        let client_config = quinn::ClientConfig::new(Arc::new(
+            quinn::crypto::rustls::QuicClientConfig::try_from(client_crypto).unwrap(),
+        ));
+        let mut client = quinn::Endpoint::client(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        client.set_default_client_config(client_config);
+        let quic = client
+            .connect(SocketAddr::from(([127, 0, 0, 1], port)), "localhost")
+            .unwrap()
+            .await
+            .unwrap();
+
+        let (mut driver, mut send_request) = h3::client::new(h3_quinn::Connection::new(quic))
+            .await
+            .unwrap();
+        tokio::spawn(async move { driver.wait_idle().await });
+
+        let request = Request::get("https://localhost/hello").body(()).unwrap();
+        let mut stream = send_request.send_request(request).await.unwrap();
+        stream.finish().await.unwrap();
+
+        let response = stream.recv_response().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.version(), Version::HTTP_3);
+
+        let mut body = BytesMut::new();
+        while let Some(chunk) = stream.recv_data().await.unwrap() {
+            body.put(chunk);
+        }
+        assert_eq!(&body[..], br#"{"hello": "h3"}"#);
+
+        shutdown_tx.send(true).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
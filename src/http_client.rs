@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Minimal HTTP/1.1 client for the requests blendwerk sends itself
+//! (remote mock sources, callbacks). HTTPS uses the webpki root
+//! certificates; every request opens its own connection.
+
+use anyhow::{Context, Result, anyhow, bail};
+use axum::http::{HeaderMap, Method, Request, Response, Uri, header::HOST, header::USER_AGENT};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper_util::rt::TokioIo;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore, pki_types::ServerName};
+
+/// Send a request to an `http://` or `https://` URL and read the whole
+/// response
+pub async fn send(
+    method: Method,
+    uri: &Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response<Bytes>> {
+    send_limited(method, uri, headers, body, usize::MAX).await
+}
+
+/// Like [`send`], failing once the response body exceeds `max_body` bytes
+pub async fn send_limited(
+    method: Method,
+    uri: &Uri,
+    headers: HeaderMap,
+    body: Bytes,
+    max_body: usize,
+) -> Result<Response<Bytes>> {
+    let host = uri.host().context("URL has no host")?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let https = match uri.scheme_str() {
+        Some("https") => true,
+        Some("http") => false,
+        _ => bail!("unsupported URL scheme: {}", uri),
+    };
+    let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+    let stream = TcpStream::connect((host, port))
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+
+    let authority = uri.authority().map(|a| a.as_str()).unwrap_or(host);
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let mut request = Request::builder()
+        .method(method)
+        .uri(path)
+        .header(HOST, authority)
+        .header(USER_AGENT, concat!("blendwerk/", env!("CARGO_PKG_VERSION")))
+        .body(Full::new(body))?;
+    request.headers_mut().extend(headers);
+
+    if https {
+        let stream = tls_connector()
+            .connect(ServerName::try_from(host.to_string())?, stream)
+            .await
+            .with_context(|| format!("TLS handshake with {} failed", host))?;
+        exchange(stream, request, max_body).await
+    } else {
+        exchange(stream, request, max_body).await
+    }
+}
+
+async fn exchange<S>(
+    stream: S,
+    request: Request<Full<Bytes>>,
+    max_body: usize,
+) -> Result<Response<Bytes>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+
+    let (parts, body) = sender.send_request(request).await?.into_parts();
+    let body = match Limited::new(body, max_body).collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            bail!("response body exceeds {} bytes", max_body)
+        }
+        Err(e) => return Err(anyhow!(e)),
+    };
+    Ok(Response::from_parts(parts, body))
+}
+
+fn tls_connector() -> TlsConnector {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .expect("default protocol versions are supported")
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
@@ -0,0 +1,145 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Shared fragments composed into response bodies (`{{> partials/page.json}}`).
+//!
+//! Include paths are relative to the mock directory. They are resolved at
+//! scan time, also inside included files, so a fragment like a pagination
+//! envelope lives in one file. Routes remember the files they include, and
+//! the watcher reloads them whenever one of those changes.
+
+use crate::interpolate;
+use anyhow::{Context, Result, anyhow, bail};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const OPEN: &str = "{{>";
+const CLOSE: &str = "}}";
+
+/// Whether `body` includes other files at all
+pub fn has_includes(body: &str) -> bool {
+    body.contains(OPEN)
+}
+
+/// Replace every include in `body` with the content of the included file,
+/// returning the result and all files read.
+///
+/// A single trailing newline of an included file is dropped, so fragments
+/// can be embedded inline. Environment placeholders in included files are
+/// resolved like in route files.
+pub fn resolve(base_dir: &Path, body: &str) -> Result<(String, Vec<PathBuf>)> {
+    let mut files = Vec::new();
+    let resolved = expand(base_dir, body, &mut Vec::new(), &mut files)?;
+    Ok((resolved, files))
+}
+
+fn expand(
+    base_dir: &Path,
+    text: &str,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(OPEN) {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + OPEN.len()..];
+        let end = after
+            .find(CLOSE)
+            .ok_or_else(|| anyhow!("unterminated include `{}`", &rest[start..]))?;
+        let name = after[..end].trim();
+        let path = include_path(base_dir, name)?;
+
+        if stack.contains(&path) {
+            bail!("include cycle through {}", name);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read include: {}", path.display()))?;
+        let content = interpolate::interpolate_env(&content, &path);
+        let content = content
+            .strip_suffix('\n')
+            .map(|content| content.strip_suffix('\r').unwrap_or(content))
+            .unwrap_or(&content);
+
+        if !files.contains(&path) {
+            files.push(path.clone());
+        }
+        stack.push(path);
+        output.push_str(&expand(base_dir, content, stack, files)?);
+        stack.pop();
+
+        rest = &after[end + CLOSE.len()..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Included files have to stay inside the mock directory
+fn include_path(base_dir: &Path, name: &str) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    if name.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!(
+            "include `{}` has to be a path inside the mock directory",
+            name
+        );
+    }
+    Ok(base_dir.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path();
+        fs::create_dir_all(base.join("partials")).unwrap();
+        fs::write(
+            base.join("partials/page.json"),
+            "{\"page\": 1, \"total\": {{> partials/total.json }}}\n",
+        )
+        .unwrap();
+        fs::write(base.join("partials/total.json"), "42\n").unwrap();
+
+        let (body, files) =
+            resolve(base, "{\"items\": [], \"meta\": {{> partials/page.json}}}").unwrap();
+        assert_eq!(
+            body,
+            "{\"items\": [], \"meta\": {\"page\": 1, \"total\": 42}}"
+        );
+        assert_eq!(
+            files,
+            [
+                base.join("partials/page.json"),
+                base.join("partials/total.json")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path();
+        fs::write(base.join("a.json"), "{{> b.json}}").unwrap();
+        fs::write(base.join("b.json"), "{{> a.json}}").unwrap();
+
+        let error = resolve(base, "{{> a.json}}").unwrap_err();
+        assert_eq!(error.to_string(), "include cycle through a.json");
+        assert!(resolve(base, "{{> ../secret}}").is_err());
+        assert!(resolve(base, "{{> missing.json}}").is_err());
+        assert!(resolve(base, "{{> a.json").is_err());
+    }
+}
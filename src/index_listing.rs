@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! HTML index of the routes below a path (`--index-listing`).
+//!
+//! Browsers requesting a path without a route file get a page listing the
+//! routes below it with their methods. GET routes are linked; `[param]`
+//! segments are filled from the requested path, the route's first
+//! `examples:` entry, or `1`.
+
+use crate::routes::{HttpMethod, PathSegment, Route};
+use std::collections::BTreeMap;
+
+/// Value linked for parameters nothing else provides one for
+const SAMPLE_PARAM: &str = "1";
+
+/// Page listing the routes below `path`, or `None` if there are none.
+/// Links are prefixed with `base_path`.
+pub fn render(routes: &[Route], path: &str, base_path: &str) -> Option<String> {
+    let requested: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let mut entries: BTreeMap<String, (Option<String>, Vec<&str>)> = BTreeMap::new();
+    for route in routes.iter().filter(|route| is_below(route, &requested)) {
+        let entry = entries.entry(route.display_path()).or_default();
+        entry.1.push(route.method.as_str());
+        if route.method == HttpMethod::Get {
+            entry.0 = Some(format!("{}{}", base_path, link(route, &requested)));
+        }
+    }
+    if entries.is_empty() {
+        return None;
+    }
+
+    let items: String = entries
+        .into_iter()
+        .map(|(display, (href, mut methods))| {
+            methods.sort_unstable();
+            methods.dedup();
+            let label = match href {
+                Some(href) => format!("<a href=\"{}\">{}</a>", escape(&href), escape(&display)),
+                None => escape(&display),
+            };
+            format!("<li><code>{}</code> {}</li>\n", methods.join(" "), label)
+        })
+        .collect();
+
+    let title = escape(&format!("{}{}", base_path, path));
+    Some(format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body>\n<h1>Index of {title}</h1>\n<ul>\n{items}</ul>\n</body>\n</html>\n"
+    ))
+}
+
+/// Whether the route lies strictly below the requested segments
+fn is_below(route: &Route, requested: &[&str]) -> bool {
+    route.method != HttpMethod::Ws
+        && route.path_segments.len() > requested.len()
+        && route
+            .path_segments
+            .iter()
+            .zip(requested)
+            .all(|(segment, requested)| match segment {
+                PathSegment::Static(name) => name == requested,
+                PathSegment::Dynamic(_) => true,
+            })
+}
+
+/// Path to request the route with, keeping the requested segments
+fn link(route: &Route, requested: &[&str]) -> String {
+    let example = route
+        .response
+        .meta
+        .examples
+        .iter()
+        .filter_map(|example| example.path.as_deref())
+        .map(|path| path.split('?').next().unwrap_or_default())
+        .find(|path| route.matches(path));
+    let example: Vec<&str> = example
+        .map(|path| path.split('/').filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+
+    let segments: Vec<&str> = route
+        .path_segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| match segment {
+            PathSegment::Static(name) => name.as_str(),
+            PathSegment::Dynamic(_) => requested
+                .get(i)
+                .or_else(|| example.get(i))
+                .copied()
+                .unwrap_or(SAMPLE_PARAM),
+        })
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scan_directory;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("api/users/[id]")).unwrap();
+        fs::create_dir_all(root.join("api/orders/[orderId]")).unwrap();
+        fs::create_dir_all(root.join("health")).unwrap();
+        fs::write(root.join("api/users/GET.json"), "[]").unwrap();
+        fs::write(root.join("api/users/POST.json"), "{}").unwrap();
+        fs::write(root.join("api/users/[id]/GET.json"), "{}").unwrap();
+        fs::write(
+            root.join("api/orders/[orderId]/GET.json"),
+            "---\nexamples:\n  - path: /api/orders/A-7\n---\n{}",
+        )
+        .unwrap();
+        fs::write(root.join("api/orders/[orderId]/DELETE.json"), "").unwrap();
+        fs::write(root.join("health/GET.json"), "{}").unwrap();
+        let routes = scan_directory(root).unwrap();
+
+        let html = render(&routes, "/api", "/mocks").unwrap();
+        assert!(html.contains("<title>Index of /mocks/api</title>"));
+        assert!(html.contains(
+            "<li><code>GET POST</code> <a href=\"/mocks/api/users\">/api/users</a></li>"
+        ));
+        assert!(html.contains("<a href=\"/mocks/api/users/1\">/api/users/:id</a>"));
+        assert!(html.contains(
+            "<code>DELETE GET</code> <a href=\"/mocks/api/orders/A-7\">/api/orders/:orderId</a>"
+        ));
+        assert!(!html.contains("/health"));
+
+        assert!(render(&routes, "/api/users/42", "").is_none());
+        let html = render(&routes, "/", "").unwrap();
+        assert!(html.contains("<a href=\"/health\">/health</a>"));
+        assert!(render(&routes, "/nothing", "").is_none());
+    }
+}
@@ -0,0 +1,393 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Starter mock trees for `blendwerk init`.
+//!
+//! The generated files carry comments in their frontmatter explaining the
+//! fields they use, so the tree doubles as a reference for new users.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// How the scaffolded API authenticates its clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AuthStyle {
+    /// No authentication examples
+    None,
+    /// Token endpoint and a 401 response asking for a bearer token
+    Bearer,
+    /// 401 response asking for HTTP basic credentials
+    Basic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitOptions {
+    /// URL prefix of all generated routes, without leading or trailing `/`
+    pub base_path: String,
+    /// Collection names, each getting list, create, show and delete routes
+    pub resources: Vec<String>,
+    pub auth: AuthStyle,
+}
+
+/// Ask for every option that wasn't given on the command line.
+///
+/// Empty answers and the end of `input` select the default shown in
+/// brackets, so piping nothing in scaffolds the defaults.
+pub fn prompt(
+    base_path: Option<String>,
+    resources: Option<Vec<String>>,
+    auth: Option<AuthStyle>,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<InitOptions> {
+    let base_path = match base_path {
+        Some(base_path) => base_path,
+        None => ask(input, output, "Base path", "/api")?,
+    };
+
+    let resources = match resources {
+        Some(resources) => resources,
+        None => ask(input, output, "Resources (comma separated)", "users")?
+            .split(',')
+            .map(String::from)
+            .collect(),
+    };
+
+    let auth = match auth {
+        Some(auth) => auth,
+        None => loop {
+            let answer = ask(input, output, "Auth style (none, bearer, basic)", "none")?;
+            match AuthStyle::from_str(&answer, true) {
+                Ok(auth) => break auth,
+                Err(_) => writeln!(output, "Unknown auth style '{}'", answer)?,
+            }
+        },
+    };
+
+    Ok(InitOptions {
+        base_path: base_path.trim().trim_matches('/').to_string(),
+        resources: resources
+            .iter()
+            .map(|resource| resource.trim().trim_matches('/').to_string())
+            .filter(|resource| !resource.is_empty())
+            .collect(),
+        auth,
+    })
+}
+
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: &str,
+) -> Result<String> {
+    write!(output, "{} [{}]: ", question, default)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input
+        .read_line(&mut answer)
+        .context("Failed to read answer")?;
+
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// Write the starter tree below `target_dir`.
+///
+/// Returns the written files. Existing files are only replaced with `force`.
+pub fn scaffold(target_dir: &Path, options: &InitOptions, force: bool) -> Result<Vec<PathBuf>> {
+    let files = starter_files(options);
+
+    if !force
+        && let Some((path, _)) = files
+            .iter()
+            .find(|(path, _)| target_dir.join(path).exists())
+    {
+        anyhow::bail!(
+            "Refusing to overwrite existing file {} (use --force)",
+            target_dir.join(path).display()
+        );
+    }
+
+    let mut written = Vec::new();
+    for (path, content) in files {
+        let file_path = target_dir.join(path);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        fs::write(&file_path, content)
+            .with_context(|| format!("Failed to write file: {}", file_path.display()))?;
+        written.push(file_path);
+    }
+
+    Ok(written)
+}
+
+/// A 1x1 transparent PNG
+const PIXEL_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// Relative paths and contents of all files of the starter tree
+fn starter_files(options: &InitOptions) -> Vec<(PathBuf, Vec<u8>)> {
+    let base = Path::new(&options.base_path);
+    let mut files = Vec::new();
+
+    for resource in &options.resources {
+        let collection = base.join(resource);
+        let url = match options.base_path.as_str() {
+            "" => format!("/{}", resource),
+            base_path => format!("/{}/{}", base_path, resource),
+        };
+
+        files.push((collection.join("GET.json"), list_response(resource)));
+        files.push((
+            collection.join("POST.json"),
+            create_response(resource, &url),
+        ));
+        files.push((
+            collection.join("[id]").join("GET.json"),
+            show_response(resource, &url),
+        ));
+        files.push((
+            collection.join("[id]").join("DELETE.json"),
+            delete_response(),
+        ));
+    }
+
+    files.push((base.join("health/GET.txt"), health_response()));
+
+    match options.auth {
+        AuthStyle::None => {}
+        AuthStyle::Bearer => {
+            files.push((base.join("auth/token/POST.json"), token_response()));
+            files.push((
+                base.join("unauthorized/GET.json"),
+                unauthorized_response("Bearer realm=\"blendwerk\""),
+            ));
+        }
+        AuthStyle::Basic => {
+            files.push((
+                base.join("unauthorized/GET.json"),
+                unauthorized_response("Basic realm=\"blendwerk\""),
+            ));
+        }
+    }
+
+    let mut files: Vec<(PathBuf, Vec<u8>)> = files
+        .into_iter()
+        .map(|(path, content)| (path, content.into_bytes()))
+        .collect();
+    files.push((base.join("images/pixel.png/GET.png"), image_response()));
+    files
+}
+
+fn list_response(resource: &str) -> String {
+    format!(
+        r#"---
+# Everything between the `---` lines is optional YAML frontmatter; the rest
+# of the file is sent as the response body. The directory path is the URL,
+# the file name (GET) the method and the extension (.json) the Content-Type.
+status: 200
+headers:
+  X-Total-Count: "2"
+---
+[
+  {{"id": 1, "name": "{resource} 1"}},
+  {{"id": 2, "name": "{resource} 2"}}
+]
+"#
+    )
+}
+
+fn create_response(resource: &str, url: &str) -> String {
+    format!(
+        r#"---
+status: 201
+headers:
+  Location: {url}/3
+# Wait 150 ms before responding
+delay: 150
+---
+{{"id": 3, "name": "{resource} 3"}}
+"#
+    )
+}
+
+fn show_response(resource: &str, url: &str) -> String {
+    format!(
+        r#"---
+# A `[id]` directory matches any single path segment, e.g. {url}/42.
+# Responses are static, so every id gets this body.
+status: 200
+# Delays may also be sampled per request from a distribution
+delay:
+  uniform: [50, 250]
+---
+{{"id": 1, "name": "{resource} 1"}}
+"#
+    )
+}
+
+fn delete_response() -> String {
+    r#"---
+# No body below the frontmatter: an empty response
+status: 204
+---
+"#
+    .to_string()
+}
+
+fn health_response() -> String {
+    r#"---
+# A static route: always the same plain text body (.txt), no parameters
+headers:
+  Cache-Control: no-cache
+---
+ok
+"#
+    .to_string()
+}
+
+fn image_response() -> Vec<u8> {
+    let mut content = r#"---
+# Bodies don't have to be text: everything after the frontmatter, here a
+# PNG image, is sent byte for byte. The directory pixel.png makes the URL
+# end in .png; the Content-Type comes from the header below.
+headers:
+  Content-Type: image/png
+  Cache-Control: max-age=3600
+---
+"#
+    .as_bytes()
+    .to_vec();
+    content.extend_from_slice(PIXEL_PNG);
+    content
+}
+
+fn token_response() -> String {
+    r#"---
+headers:
+  Cache-Control: no-store
+---
+{"access_token": "blendwerk-example-token", "token_type": "Bearer", "expires_in": 3600}
+"#
+    .to_string()
+}
+
+fn unauthorized_response(challenge: &str) -> String {
+    format!(
+        r#"---
+# A fixed 401 to test how clients handle a rejected request. To check
+# credentials on another route, give it an `auth:` block, e.g.
+# `auth: {{bearer: blendwerk-example-token}}`
+status: 401
+headers:
+  WWW-Authenticate: '{challenge}'
+---
+{{"error": "unauthorized"}}
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::{self, HttpMethod};
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_prompt_uses_answers_and_defaults() {
+        let mut input = Cursor::new("v1/\nusers, orders\nkerberos\nbearer\n");
+        let mut output = Vec::new();
+
+        let options = prompt(None, None, None, &mut input, &mut output).unwrap();
+        assert_eq!(options.base_path, "v1");
+        assert_eq!(options.resources, vec!["users", "orders"]);
+        assert_eq!(options.auth, AuthStyle::Bearer);
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("Unknown auth style")
+        );
+
+        let defaults = prompt(None, None, None, &mut Cursor::new(""), &mut Vec::new()).unwrap();
+        assert_eq!(defaults.base_path, "api");
+        assert_eq!(defaults.resources, vec!["users"]);
+        assert_eq!(defaults.auth, AuthStyle::None);
+    }
+
+    #[test]
+    fn test_scaffold_creates_loadable_routes() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = InitOptions {
+            base_path: "api".to_string(),
+            resources: vec!["users".to_string()],
+            auth: AuthStyle::Bearer,
+        };
+
+        let written = scaffold(temp_dir.path(), &options, false).unwrap();
+        assert_eq!(written.len(), 8);
+
+        let routes = routes::scan_directory(temp_dir.path()).unwrap();
+        let find = |method: HttpMethod, path: &str| {
+            routes
+                .iter()
+                .find(|r| r.method == method && r.display_path() == path)
+                .unwrap()
+        };
+
+        assert_eq!(
+            find(HttpMethod::Get, "/api/users").response.meta.status(),
+            200
+        );
+        assert_eq!(
+            find(HttpMethod::Post, "/api/users").response.meta.status(),
+            201
+        );
+        assert_eq!(
+            find(HttpMethod::Get, "/api/users/:id")
+                .response
+                .meta
+                .status(),
+            200
+        );
+        assert!(
+            find(HttpMethod::Delete, "/api/users/:id")
+                .response
+                .body
+                .is_empty()
+        );
+        assert_eq!(
+            find(HttpMethod::Get, "/api/unauthorized")
+                .response
+                .meta
+                .status(),
+            401
+        );
+
+        assert_eq!(find(HttpMethod::Get, "/api/health").response.body, "ok\n");
+        let image = find(HttpMethod::Get, "/api/images/pixel.png");
+        assert_eq!(image.response.body, PIXEL_PNG);
+        assert_eq!(image.response.meta.headers["Content-Type"], *"image/png");
+
+        // A second run must not silently replace the files
+        assert!(scaffold(temp_dir.path(), &options, false).is_err());
+        assert!(scaffold(temp_dir.path(), &options, true).is_ok());
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Environment variable placeholders in mock files.
+//!
+//! `${NAME}` is replaced with the value of the environment variable `NAME`
+//! while the tree is scanned, `${NAME:-fallback}` uses `fallback` if it is
+//! unset or empty. Only upper case names (`A-Z`, `0-9`, `_`) are
+//! placeholders, so template literals in JavaScript bodies stay untouched;
+//! `$${NAME}` keeps a literal `${NAME}`. Placeholders of unset variables
+//! without fallback are kept as they are, with a warning.
+
+use std::borrow::Cow;
+use std::path::Path;
+use tracing::warn;
+
+/// Replace the placeholders in `text` with values from `lookup`.
+///
+/// Placeholders of variables that are unset and have no fallback stay in
+/// the text; their names are returned alongside.
+pub fn interpolate<'a>(
+    text: &'a str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> (Cow<'a, str>, Vec<&'a str>) {
+    let mut unset = Vec::new();
+    if !text.contains("${") {
+        return (Cow::Borrowed(text), unset);
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some((name, fallback, len)) = placeholder(&rest[start + 2..]) else {
+            result.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        };
+
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str(&rest[start..start + 2 + len]);
+        } else {
+            result.push_str(&rest[..start]);
+            let value = lookup(name).filter(|value| !value.is_empty());
+            match (value, fallback) {
+                (Some(value), _) => result.push_str(&value),
+                (None, Some(fallback)) => result.push_str(fallback),
+                (None, None) => {
+                    result.push_str(&rest[start..start + 2 + len]);
+                    unset.push(name);
+                }
+            }
+        }
+        rest = &rest[start + 2 + len..];
+    }
+    result.push_str(rest);
+
+    (Cow::Owned(result), unset)
+}
+
+/// Interpolate with the environment of the process, warning about unset
+/// variables of `file`
+pub fn interpolate_env<'a>(text: &'a str, file: &Path) -> Cow<'a, str> {
+    let (result, unset) = interpolate(text, |name| std::env::var(name).ok());
+    for name in unset {
+        warn!(
+            "Environment variable {} is not set in: {}, keeping ${{{}}}",
+            name,
+            file.display(),
+            name
+        );
+    }
+    result
+}
+
+/// Name, fallback and length up to the closing brace of the placeholder
+/// following `${`, or `None` if it isn't one
+fn placeholder(text: &str) -> Option<(&str, Option<&str>, usize)> {
+    let end = text.find('}')?;
+    let inner = &text[..end];
+    let (name, fallback) = match inner.split_once(":-") {
+        Some((name, fallback)) => (name, Some(fallback)),
+        None => (inner, None),
+    };
+
+    let valid = name.starts_with(|c: char| c.is_ascii_uppercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    valid.then_some((name, fallback, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "API_URL" => Some("https://ci.example.com".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let resolved = |text| interpolate(text, lookup).0;
+        assert_eq!(
+            resolved("url: ${API_URL}/users"),
+            "url: https://ci.example.com/users"
+        );
+        assert_eq!(resolved("${TENANT:-local} ${EMPTY:-none}"), "local none");
+        assert_eq!(resolved("$${API_URL}"), "${API_URL}");
+        assert_eq!(
+            resolved("`${user.name}` ${ x } ${"),
+            "`${user.name}` ${ x } ${"
+        );
+        assert!(matches!(resolved("{}"), Cow::Borrowed(_)));
+
+        let (text, unset) = interpolate("${TENANT}/${API_URL}/${EMPTY}", lookup);
+        assert_eq!(text, "${TENANT}/https://ci.example.com/${EMPTY}");
+        assert_eq!(unset, ["TENANT", "EMPTY"]);
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! File-based mock HTTP server.
+//!
+//! The `blendwerk` binary is a thin command line wrapper around this crate.
+//! Rust integration tests can embed the server with [`MockServer`] instead
+//! of spawning the binary:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use blendwerk::{MockServer, Stub};
+//!
+//! let server = MockServer::builder().directory("tests/mocks").start().await?;
+//! server.stub(Stub::get("/api/health").status(204)).await;
+//!
+//! let url = format!("{}/api/users", server.url());
+//! // ... exercise the code under test against `url` ...
+//!
+//! assert_eq!(server.received_requests()[0].path, "/api/users");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The modules behind it implement the server and the CLI. Those the
+//! binary needs are public but hidden from the documentation; their API
+//! follows the needs of the binary and may change between minor versions.
+
+#[doc(hidden)]
+pub mod archive;
+mod auth;
+mod callbacks;
+mod chaos;
+mod chunks;
+mod conditional;
+mod connection;
+mod control_headers;
+#[doc(hidden)]
+pub mod cors;
+mod coverage;
+#[doc(hidden)]
+pub mod ctl;
+mod delay;
+#[doc(hidden)]
+pub mod duplicates;
+mod echo;
+#[doc(hidden)]
+pub mod examples;
+mod fault;
+#[doc(hidden)]
+pub mod fixture_changes;
+#[doc(hidden)]
+pub mod forwarded;
+mod frontmatter;
+mod graphql;
+#[doc(hidden)]
+pub mod grpc;
+#[doc(hidden)]
+pub mod har;
+#[doc(hidden)]
+pub mod http3;
+mod http_client;
+mod includes;
+mod index_listing;
+#[doc(hidden)]
+pub mod init;
+mod interpolate;
+#[doc(hidden)]
+pub mod log_filter;
+#[doc(hidden)]
+pub mod logging;
+#[doc(hidden)]
+pub mod mime_types;
+#[doc(hidden)]
+pub mod mock_server;
+#[doc(hidden)]
+pub mod oidc;
+#[doc(hidden)]
+pub mod openapi;
+mod plugin;
+#[doc(hidden)]
+pub mod ports;
+mod progression;
+#[doc(hidden)]
+pub mod promote;
+mod randomize;
+#[doc(hidden)]
+pub mod reload_events;
+#[doc(hidden)]
+pub mod remote;
+#[doc(hidden)]
+pub mod request_id;
+#[doc(hidden)]
+pub mod request_logger;
+mod route_match;
+#[doc(hidden)]
+pub mod routes;
+mod routes_file;
+mod scripting;
+#[doc(hidden)]
+pub mod sequence;
+#[doc(hidden)]
+pub mod server;
+mod session;
+mod sse;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod tags;
+mod throttle;
+#[doc(hidden)]
+pub mod tls;
+mod transfer;
+mod verification;
+#[doc(hidden)]
+pub mod vhost;
+#[doc(hidden)]
+pub mod watcher;
+mod webdav;
+mod websocket;
+mod wiremock;
+
+pub use mock_server::{MockServer, MockServerBuilder, ReceivedRequest, Stub};
+pub use verification::{Received, RequestMatcher};
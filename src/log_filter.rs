@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Which requests end up in the request log (`--log-only-path`,
+//! `--log-skip-status`, `--log-only-unmatched`).
+//!
+//! Path patterns match the requested path segment by segment: `*` matches
+//! any characters within a segment, a `**` segment any number of segments.
+//! `/api/**` thus covers `/api` and everything below it.
+
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathPattern(Vec<String>);
+
+impl FromStr for PathPattern {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        if !pattern.starts_with('/') {
+            return Err(format!("path pattern '{}' must start with /", pattern));
+        }
+        Ok(Self(segments(pattern).map(String::from).collect()))
+    }
+}
+
+impl PathPattern {
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<&str> = segments(path).collect();
+        let pattern: Vec<&str> = self.0.iter().map(String::as_str).collect();
+        match_segments(&pattern, &path)
+    }
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => path.split_first().is_some_and(|(first, path_rest)| {
+            match_wildcards(segment, first) && match_segments(rest, path_rest)
+        }),
+    }
+}
+
+/// Match a single segment where `*` stands for any run of characters
+fn match_wildcards(pattern: &str, text: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(text) = text.strip_prefix(prefix) else {
+        return false;
+    };
+    (0..=text.len())
+        .filter(|i| text.is_char_boundary(*i))
+        .any(|i| match_wildcards(rest, &text[i..]))
+}
+
+/// A status code (`404`) or class (`2xx`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusPattern {
+    Exact(u16),
+    Class(u16),
+}
+
+impl FromStr for StatusPattern {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let lower = value.trim().to_ascii_lowercase();
+        let invalid = || format!("invalid status '{}', expected e.g. 404 or 2xx", value);
+        match lower.strip_suffix("xx") {
+            Some(class) => match class.parse::<u16>() {
+                Ok(class @ 1..=5) => Ok(Self::Class(class)),
+                _ => Err(invalid()),
+            },
+            None => match lower.parse::<u16>() {
+                Ok(status @ 100..=599) => Ok(Self::Exact(status)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+impl StatusPattern {
+    pub fn matches(&self, status: u16) -> bool {
+        match self {
+            Self::Exact(expected) => status == *expected,
+            Self::Class(class) => status / 100 == *class,
+        }
+    }
+}
+
+/// Include and exclude rules of the request log; all of them have to pass
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Only log requests to these paths (any path if empty)
+    pub only_paths: Vec<PathPattern>,
+    /// Don't log responses with these statuses
+    pub skip_statuses: Vec<StatusPattern>,
+    /// Only log requests no route answered
+    pub only_unmatched: bool,
+}
+
+impl LogFilter {
+    /// Whether a request to `path` answered with `status` gets logged
+    pub fn accepts(&self, path: &str, status: u16, matched: bool) -> bool {
+        (self.only_paths.is_empty() || self.only_paths.iter().any(|p| p.matches(path)))
+            && !self.skip_statuses.iter().any(|s| s.matches(status))
+            && !(self.only_unmatched && matched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(pattern: &str) -> PathPattern {
+        pattern.parse().unwrap()
+    }
+
+    #[test]
+    fn test_path_patterns() {
+        assert!(path("/api/**").matches("/api"));
+        assert!(path("/api/**").matches("/api/users/1"));
+        assert!(!path("/api/**").matches("/apis"));
+        assert!(path("/api/*/orders").matches("/api/42/orders"));
+        assert!(!path("/api/*/orders").matches("/api/orders"));
+        assert!(path("/**/health").matches("/health"));
+        assert!(path("/**/health").matches("/v1/internal/health"));
+        assert!(path("/files/*.json").matches("/files/users.json"));
+        assert!(!path("/files/*.json").matches("/files/users.yaml"));
+        assert!(path("/").matches("/"));
+        assert!(!path("/").matches("/api"));
+        assert!("api/**".parse::<PathPattern>().is_err());
+    }
+
+    #[test]
+    fn test_status_patterns() {
+        assert_eq!("2xx".parse(), Ok(StatusPattern::Class(2)));
+        assert_eq!("404".parse(), Ok(StatusPattern::Exact(404)));
+        assert!("6xx".parse::<StatusPattern>().is_err());
+        assert!("20".parse::<StatusPattern>().is_err());
+        assert!(StatusPattern::Class(2).matches(204));
+        assert!(!StatusPattern::Class(2).matches(304));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = LogFilter {
+            only_paths: vec![path("/api/**")],
+            skip_statuses: vec![StatusPattern::Class(2)],
+            only_unmatched: false,
+        };
+        assert!(filter.accepts("/api/users", 500, true));
+        assert!(!filter.accepts("/api/users", 200, true));
+        assert!(!filter.accepts("/health", 500, true));
+
+        let unmatched = LogFilter {
+            only_unmatched: true,
+            ..LogFilter::default()
+        };
+        assert!(unmatched.accepts("/anything", 404, false));
+        assert!(!unmatched.accepts("/anything", 200, true));
+        assert!(LogFilter::default().accepts("/", 200, true));
+    }
+}
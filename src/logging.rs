@@ -0,0 +1,209 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Console log and the access log line written for every request.
+//!
+//! The log is printed as compact text, or with `--log-json` as one JSON
+//! object per line carrying the event's fields as keys.
+
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Install the global log subscriber
+pub fn init(json: bool) {
+    let builder = tracing_subscriber::fmt().with_target(false);
+    if json {
+        builder.event_format(JsonFormat).init();
+    } else {
+        builder.compact().init();
+    }
+}
+
+/// A request as shown in the access log
+pub struct Access<'a> {
+    pub method: &'a str,
+    /// Path the client requested
+    pub path: &'a str,
+    pub matched_route: Option<&'a str>,
+    pub status: u16,
+    /// Response body bytes sent
+    pub bytes: u64,
+    /// From receiving the request until the body was sent
+    pub duration: Duration,
+    /// The route's `trace_fields`
+    pub trace_fields: &'a BTreeMap<String, String>,
+}
+
+impl Access<'_> {
+    /// Write the access log line
+    pub fn log(&self) {
+        let trace_fields = (!self.trace_fields.is_empty()).then(|| {
+            self.trace_fields
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+
+        tracing::info!(
+            method = %self.method,
+            path = %self.path,
+            route = self.matched_route.map(tracing::field::display),
+            status = self.status,
+            bytes = self.bytes,
+            duration_ms = duration_ms(self.duration),
+            trace_fields = trace_fields.map(tracing::field::display),
+        );
+    }
+}
+
+/// Milliseconds with microsecond precision
+pub fn duration_ms(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// Formats events as a JSON object per line
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        writeln!(writer, "{}", Value::Object(json_line(event)))
+    }
+}
+
+fn json_line(event: &Event<'_>) -> Map<String, Value> {
+    let mut line = Map::new();
+    line.insert(
+        "timestamp".to_string(),
+        chrono::Utc::now()
+            .to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+            .into(),
+    );
+    line.insert(
+        "level".to_string(),
+        event.metadata().level().as_str().into(),
+    );
+    event.record(&mut JsonFields(&mut line));
+    line
+}
+
+struct JsonFields<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonFields<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_json_access_line() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let sink = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(JsonFormat)
+            .with_writer(move || SharedWriter(sink.clone()))
+            .finish();
+
+        let trace_fields = BTreeMap::from([("case".to_string(), "42".to_string())]);
+        tracing::subscriber::with_default(subscriber, || {
+            Access {
+                method: "GET",
+                path: "/api/users/1",
+                matched_route: Some("/api/users/:id"),
+                status: 200,
+                bytes: 27,
+                duration: Duration::from_micros(1500),
+                trace_fields: &trace_fields,
+            }
+            .log();
+            Access {
+                method: "GET",
+                path: "/missing",
+                matched_route: None,
+                status: 404,
+                bytes: 9,
+                duration: Duration::ZERO,
+                trace_fields: &BTreeMap::new(),
+            }
+            .log();
+        });
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["method"], "GET");
+        assert_eq!(lines[0]["path"], "/api/users/1");
+        assert_eq!(lines[0]["route"], "/api/users/:id");
+        assert_eq!(lines[0]["status"], 200);
+        assert_eq!(lines[0]["bytes"], 27);
+        assert_eq!(lines[0]["duration_ms"], 1.5);
+        assert_eq!(lines[0]["trace_fields"], "case=42");
+        assert!(lines[0].get("message").is_none());
+        assert!(lines[1].get("route").is_none());
+        assert!(lines[1].get("trace_fields").is_none());
+    }
+
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -6,48 +6,83 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-mod frontmatter;
-mod request_logger;
-mod routes;
-mod server;
-mod tls;
-mod watcher;
-
-use clap::{Parser, ValueEnum};
+use anyhow::Context;
+use blendwerk::{
+    archive, cors, ctl, duplicates, examples, fixture_changes, forwarded, grpc, har, http3, init,
+    log_filter, logging, mime_types, mock_server, oidc, openapi, ports, promote, reload_events,
+    remote, request_id, request_logger, routes, sequence, server, stats, tags, tls, vhost, watcher,
+};
+use clap::{Parser, Subcommand, ValueEnum};
 use pid1::Pid1Settings;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{RwLock, watch};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Clone, ValueEnum)]
 enum CertMode {
     /// No HTTPS, HTTP only
     None,
-    /// Generate self-signed certificate on startup
+    /// Generate a certificate on startup, signed by a local CA kept in the
+    /// state directory
     SelfSigned,
     /// Use custom certificate files
     Custom,
 }
 
+#[derive(Debug, Clone, ValueEnum)]
+enum RuntimeMode {
+    /// Work-stealing multi-threaded runtime shared by all connections
+    MultiThread,
+    /// One single-threaded runtime per core, each with its own listener
+    /// (SO_REUSEPORT, unix only)
+    ThreadPerCore,
+}
+
 #[derive(Parser, Debug)]
 #[command(name = "blendwerk")]
 #[command(about = "A file-based mock HTTP/HTTPS server for testing")]
 #[command(version)]
 #[command(author)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// Directory containing mock responses
-    directory: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Directory containing mock responses, a `.zip`/`.tar.gz` archive of
+    /// one, or a git or archive URL to fetch it from
+    #[arg(required = true)]
+    directory: Option<PathBuf>,
 
-    /// HTTP port
+    /// HTTP port (0 lets the OS pick a free one)
     #[arg(short = 'p', long, default_value = "8080")]
     http_port: u16,
 
-    /// HTTPS port
+    /// HTTPS port (0 lets the OS pick a free one)
     #[arg(short = 's', long, default_value = "8443")]
     https_port: u16,
 
+    /// Serve gRPC mocks from the `grpc/` directory on this port (HTTP/2
+    /// without TLS)
+    #[arg(long, value_name = "PORT")]
+    grpc_port: Option<u16>,
+
+    /// Serve HTTP/3 on this UDP port, using the HTTPS certificate
+    /// (experimental)
+    #[arg(long, value_name = "PORT")]
+    http3_port: Option<u16>,
+
+    /// Print the bound ports as a JSON line to stdout once all servers
+    /// listen, e.g. `{"listening":{"http":41234,...}}`
+    #[arg(long)]
+    print_ports: bool,
+
+    /// Write the bound ports as JSON to this file once all servers listen;
+    /// removed on shutdown
+    #[arg(long, value_name = "FILE")]
+    port_file: Option<PathBuf>,
+
     /// Only serve HTTP (no HTTPS)
     #[arg(long, conflicts_with = "https_only")]
     http_only: bool,
@@ -56,6 +91,171 @@ struct Args {
     #[arg(long, conflicts_with = "http_only")]
     https_only: bool,
 
+    /// HTTP version answered on the HTTP and HTTPS ports; requests in other
+    /// versions get a 505
+    #[arg(long, value_enum, default_value = "auto", value_name = "VERSION")]
+    http_version: server::HttpVersion,
+
+    /// Wait this many milliseconds before reading from new connections on
+    /// the HTTP and HTTPS ports, stalling the request or TLS handshake
+    #[arg(long, value_name = "MS")]
+    accept_delay: Option<u64>,
+
+    /// Answer requests whose path and query are longer than this many bytes
+    /// with 414 URI Too Long
+    #[arg(long, value_name = "BYTES")]
+    max_uri_length: Option<usize>,
+
+    /// Answer requests whose header names and values add up to more than
+    /// this many bytes with 431 Request Header Fields Too Large
+    #[arg(long, value_name = "BYTES")]
+    max_header_bytes: Option<usize>,
+
+    /// Answer requests whose body is longer than this many bytes with 413
+    /// Payload Too Large
+    #[arg(long, value_name = "BYTES")]
+    max_body_bytes: Option<usize>,
+
+    /// Body format of the errors blendwerk answers itself, such as 404 for
+    /// unknown routes or 405 for unsupported methods
+    #[arg(long, value_enum, default_value = "text", value_name = "FORMAT")]
+    error_format: server::ErrorFormat,
+
+    /// Believe the Forwarded and X-Forwarded-* headers of requests from
+    /// these addresses or networks (e.g. 10.0.0.0/8) for the client address
+    /// and the scheme and host of generated URLs
+    #[arg(long, value_delimiter = ',', value_name = "NETWORK")]
+    trusted_proxy: Vec<forwarded::Network>,
+
+    /// Serve the mock tree below this path prefix (e.g. /mocks), for
+    /// mounting blendwerk under a path of an ingress; requests outside it
+    /// get a 404
+    #[arg(long, value_name = "PATH", value_parser = parse_base_path)]
+    base_path: Option<String>,
+
+    /// Serve requests for HOST (Host header, without port) from the mock
+    /// directory DIR instead of the main one; repeatable
+    #[arg(long, value_name = "HOST=DIR")]
+    vhost: Vec<vhost::VirtualHost>,
+
+    /// Header carrying the correlation ID of requests (e.g. X-Request-Id);
+    /// IDs are echoed on responses and used as request ID in the request log
+    #[arg(long, value_name = "NAME")]
+    request_id_header: Option<axum::http::HeaderName>,
+
+    /// What to do with requests that don't carry an ID in
+    /// --request-id-header
+    #[arg(
+        long,
+        value_enum,
+        default_value = "generate",
+        value_name = "POLICY",
+        requires = "request_id_header"
+    )]
+    missing_request_id: request_id::MissingRequestId,
+
+    /// Answer CORS preflights and add CORS headers to all responses
+    #[arg(long)]
+    cors: bool,
+
+    /// Origins allowed with --cors (default: any)
+    #[arg(long, value_delimiter = ',', value_name = "ORIGIN", requires = "cors")]
+    cors_origins: Vec<String>,
+
+    /// Methods allowed in preflights with --cors (default: the requested one)
+    #[arg(long, value_delimiter = ',', value_name = "METHOD", requires = "cors")]
+    cors_methods: Vec<String>,
+
+    /// Request headers allowed in preflights with --cors (default: the
+    /// requested ones)
+    #[arg(long, value_delimiter = ',', value_name = "HEADER", requires = "cors")]
+    cors_headers: Vec<String>,
+
+    /// Send ETag and Last-Modified headers and answer conditional GET and
+    /// HEAD requests with 304 Not Modified (routes can opt out with `etag:
+    /// false`)
+    #[arg(long)]
+    etag: bool,
+
+    /// Add `X-Blendwerk-Fixture-Changed: true` to the first response of
+    /// each route a hot reload changed
+    #[arg(long)]
+    mark_changed_fixtures: bool,
+
+    /// Detect changes to the mock directories by scanning them every this
+    /// many milliseconds, for bind mounts and network file systems that do
+    /// not deliver file system events
+    #[arg(long, value_name = "MS")]
+    watch_poll: Option<u64>,
+
+    /// Fetch a remote mock source (git or archive URL) again every this many
+    /// seconds; SIGHUP fetches it at any time
+    #[arg(long, value_name = "SECS")]
+    remote_refresh: Option<u64>,
+
+    /// POST a JSON summary of the added, removed and changed routes to this
+    /// http:// URL after every hot reload
+    #[arg(long, value_name = "URL", value_parser = reload_events::parse_webhook)]
+    reload_webhook: Option<axum::http::Uri>,
+
+    /// Answer browsers (Accept: text/html) requesting a path without route
+    /// file with an HTML index of the routes below it
+    #[arg(long)]
+    index_listing: bool,
+
+    /// Let the X-Blendwerk-Status and X-Blendwerk-Delay request headers
+    /// override the status and delay of the matched route
+    #[arg(long)]
+    control_headers: bool,
+
+    /// How request paths ending in `/` are matched; route files can
+    /// override it with `trailing_slash:`
+    #[arg(long, value_enum, value_name = "MODE", default_value = "ignore")]
+    trailing_slash: routes::TrailingSlash,
+
+    /// Match static path segments ignoring case; route files can override
+    /// it with `case_insensitive:`
+    #[arg(long)]
+    case_insensitive_paths: bool,
+
+    /// Match path segments as sent instead of percent-decoding them first
+    /// (`my%20report` only matching a `my%20report` directory)
+    #[arg(long)]
+    raw_paths: bool,
+
+    /// Ignore `;name=value` matrix parameters of path segments when
+    /// matching; they are available to callbacks as {{matrix.NAME}}
+    #[arg(long)]
+    matrix_params: bool,
+
+    /// Accept request log entries at POST /__blendwerk/promote and write
+    /// them as route files into the mock directory
+    #[arg(long)]
+    promote_endpoint: bool,
+
+    /// Act as OpenID Connect provider: serve discovery, JWKS, authorize and
+    /// token endpoints for paths without a route file
+    #[arg(long)]
+    oidc: bool,
+
+    /// Issuer of tokens from --oidc (default: the URL the request was sent
+    /// to)
+    #[arg(long, value_name = "URL", requires = "oidc")]
+    oidc_issuer: Option<String>,
+
+    /// Subject of --oidc logins without `login_hint` or `username`
+    #[arg(long, default_value = "user", value_name = "SUB", requires = "oidc")]
+    oidc_subject: String,
+
+    /// Claim added to tokens from --oidc; values are parsed as JSON if
+    /// possible (repeatable)
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_claim, requires = "oidc")]
+    oidc_claim: Vec<(String, serde_json::Value)>,
+
+    /// Lifetime of tokens from --oidc, in seconds
+    #[arg(long, default_value = "3600", value_name = "SECS", requires = "oidc")]
+    oidc_token_ttl: u64,
+
     /// Certificate mode
     #[arg(long, value_enum, default_value = "self-signed")]
     cert_mode: CertMode,
@@ -68,6 +268,39 @@ struct Args {
     #[arg(long, required_if_eq("cert_mode", "custom"))]
     key_file: Option<PathBuf>,
 
+    /// Additional hostname or IP address the self-signed certificate is
+    /// valid for, next to localhost, 127.0.0.1 and ::1
+    #[arg(long, value_delimiter = ',', value_name = "NAME")]
+    cert_san: Vec<String>,
+
+    /// Common name of the self-signed certificate (default: localhost)
+    #[arg(long, value_name = "NAME")]
+    cert_cn: Option<String>,
+
+    /// Directory keeping the local CA of the self-signed cert mode
+    /// (default: $XDG_STATE_HOME/blendwerk or ~/.local/state/blendwerk)
+    #[arg(long, value_name = "DIR")]
+    state_dir: Option<PathBuf>,
+
+    /// Runtime used to serve requests
+    #[arg(long, value_enum, default_value = "multi-thread")]
+    runtime: RuntimeMode,
+
+    /// Stream response bodies of files larger than this many bytes from disk
+    /// instead of keeping them in memory
+    #[arg(long, value_name = "BYTES")]
+    stream_threshold: Option<u64>,
+
+    /// Content type of route files with an extension, e.g.
+    /// `geojson=application/geo+json`; may be repeated
+    #[arg(long, value_name = "EXT=TYPE", value_parser = mime_types::parse_mapping)]
+    mime_type: Vec<(String, String)>,
+
+    /// Print the console log, including the access log line of every
+    /// request, as JSON lines
+    #[arg(long)]
+    log_json: bool,
+
     /// Directory to log all incoming requests
     #[arg(long)]
     request_log: Option<PathBuf>,
@@ -75,6 +308,258 @@ struct Args {
     /// Format for request logs
     #[arg(long, default_value = "json", value_enum)]
     request_log_format: request_logger::LogFormat,
+
+    /// Maximum number of requests waiting to be written to the request log;
+    /// requests beyond this are dropped and counted
+    #[arg(long, default_value = "10000", value_name = "N")]
+    request_log_queue: usize,
+
+    /// Buffer request log entries and append them in batches of this size
+    /// to one file per minute instead of writing a file per request
+    #[arg(long, value_name = "N")]
+    request_log_batch: Option<usize>,
+
+    /// Longest time batched request log entries are buffered before being
+    /// written, in milliseconds
+    #[arg(
+        long,
+        default_value = "1000",
+        value_name = "MS",
+        requires = "request_log_batch"
+    )]
+    request_log_flush_interval: u64,
+
+    /// Cut request and response bodies in the request log after this many
+    /// bytes, marking them as truncated
+    #[arg(long, value_name = "BYTES")]
+    request_log_max_body: Option<usize>,
+
+    /// Compress request log files
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    request_log_compress: Option<request_logger::LogCompression>,
+
+    /// Only write requests to paths matching these patterns to the request
+    /// log (`*` within a segment, `**` for any number of segments, e.g.
+    /// /api/**)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "PATTERN",
+        requires = "request_log"
+    )]
+    log_only_path: Vec<log_filter::PathPattern>,
+
+    /// Don't write requests answered with these statuses or classes (e.g.
+    /// 2xx, 304) to the request log
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "STATUS",
+        requires = "request_log"
+    )]
+    log_skip_status: Vec<log_filter::StatusPattern>,
+
+    /// Headers whose values are written as `<redacted>` to the request log;
+    /// replaces the defaults, an empty value logs all headers as they are
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "NAME",
+        default_value = "authorization,proxy-authorization,cookie,set-cookie"
+    )]
+    log_redact_header: Vec<String>,
+
+    /// Only write requests no route answered to the request log
+    #[arg(long, requires = "request_log")]
+    log_only_unmatched: bool,
+
+    /// Warn about requests repeating an identical one (same method, path,
+    /// query and body) within this many milliseconds and report them at
+    /// /__blendwerk/duplicates
+    #[arg(long, value_name = "MS")]
+    duplicate_window: Option<u64>,
+
+    /// Count how often each route answers and which requests match none;
+    /// reported at /__blendwerk/coverage and summarized on shutdown
+    #[arg(long)]
+    coverage: bool,
+
+    /// Keep the last this many requests for /__blendwerk/wait-for-request,
+    /// which blocks until a matching one arrives; 0 disables the endpoint
+    #[arg(long, value_name = "N", default_value = "1000")]
+    request_history: usize,
+
+    /// Serve the route files tagged with one of these tags (`tags:`
+    /// frontmatter); they take precedence over untagged files
+    #[arg(long, value_delimiter = ',', value_name = "TAG")]
+    enable_tags: Vec<String>,
+
+    /// Don't serve route files tagged with one of these tags, even if
+    /// another of their tags is enabled
+    #[arg(long, value_delimiter = ',', value_name = "TAG")]
+    disable_tags: Vec<String>,
+
+    /// YAML file with the order in which requests have to arrive; requests
+    /// out of order are flagged or rejected with 409
+    #[arg(long, value_name = "FILE")]
+    sequence: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Export the mock tree as an OpenAPI document
+    Export {
+        /// Directory containing mock responses
+        directory: PathBuf,
+
+        /// Write the document to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Create mock files from captured traffic
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+
+    /// Turn a request log entry back into a route file
+    Promote {
+        /// Request log file (JSON, YAML, JSON Lines or NDJSON, optionally
+        /// gzip- or zstd-compressed)
+        file: PathBuf,
+
+        /// Mock directory to write the route file to
+        directory: PathBuf,
+
+        /// Request ID of the entry to promote, for files holding several
+        #[arg(long, value_name = "ID")]
+        request_id: Option<String>,
+
+        /// Overwrite an existing route file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Check that the `examples` in the frontmatter of all route files are
+    /// answered by their file
+    Test {
+        /// Directory containing mock responses
+        directory: PathBuf,
+    },
+
+    /// Scaffold a starter mock tree, asking for anything not given as option
+    Init {
+        /// Directory to create the mock files in
+        #[arg(default_value = "mocks")]
+        directory: PathBuf,
+
+        /// URL prefix of the generated routes
+        #[arg(long)]
+        base_path: Option<String>,
+
+        /// Resources to generate routes for
+        #[arg(long, value_delimiter = ',')]
+        resources: Option<Vec<String>>,
+
+        /// Authentication examples to generate
+        #[arg(long, value_enum)]
+        auth: Option<init::AuthStyle>,
+
+        /// Overwrite existing route files
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Manage the local CA of the self-signed cert mode
+    Cert {
+        #[command(subcommand)]
+        command: CertCommand,
+    },
+
+    /// Control a running instance through its admin API
+    Ctl {
+        /// URL of the instance, including its `--base-path`
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+
+        #[command(subcommand)]
+        command: CtlCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CtlCommand {
+    /// List the loaded routes
+    Routes,
+
+    /// Print the received requests as JSON (needs `--request-history`)
+    Requests,
+
+    /// Forget received requests, restart progressions and remove stubs
+    Reset,
+
+    /// Switch scenarios, which are route tags
+    Scenario {
+        #[command(subcommand)]
+        command: ScenarioCommand,
+    },
+
+    /// Manage routes added at runtime
+    Stub {
+        #[command(subcommand)]
+        command: StubCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ScenarioCommand {
+    /// Enable exactly these tags, none if no tag is given
+    Set { tags: Vec<String> },
+}
+
+#[derive(Subcommand, Debug)]
+enum StubCommand {
+    /// Answer requests like a route file, ahead of all other routes
+    Add {
+        /// HTTP method, e.g. GET
+        method: String,
+
+        /// Path like `/users/[id]`
+        path: String,
+
+        /// Route file (frontmatter and body); its extension sets the
+        /// content type
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CertCommand {
+    /// Print the PEM of the local CA, creating it if there is none yet, to
+    /// add it to the trust store of browsers and clients
+    ExportCa {
+        /// Directory keeping the local CA (default: $XDG_STATE_HOME/blendwerk
+        /// or ~/.local/state/blendwerk)
+        #[arg(long, value_name = "DIR")]
+        state_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ImportSource {
+    /// Convert a browser-exported HAR capture into route files
+    Har {
+        /// HAR file to import
+        file: PathBuf,
+
+        /// Mock directory to write the route files to
+        directory: PathBuf,
+
+        /// Overwrite existing route files
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
@@ -89,28 +574,69 @@ fn main() -> anyhow::Result<()> {
 
 #[tokio::main]
 async fn main_inner() -> anyhow::Result<()> {
-    // Initialize tracing subscriber for request logging
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .compact()
-        .init();
-
     let args = Args::parse();
+    logging::init(args.log_json);
 
-    // Validate directory exists
-    if !args.directory.exists() {
-        anyhow::bail!("Directory '{}' does not exist", args.directory.display());
+    if let Some(command) = args.command {
+        return run_command(command).await;
     }
 
-    if !args.directory.is_dir() {
-        anyhow::bail!("'{}' is not a directory", args.directory.display());
+    let directory = args
+        .directory
+        .expect("clap requires a directory without subcommand");
+    // Remote sources and archives are copied into temporary directories,
+    // which are removed when the server exits
+    let remote = match directory.to_str().and_then(remote::RemoteSource::parse) {
+        Some(source) => {
+            info!("Fetching mocks from {}...", source);
+            Some(Arc::new(remote::RemoteMocks::fetch(source).await?))
+        }
+        None => None,
+    };
+    if args.remote_refresh.is_some() && remote.is_none() {
+        anyhow::bail!("--remote-refresh requires a git or archive URL as mock source");
+    }
+    let archive = (remote.is_none() && archive::is_archive(&directory))
+        .then(|| archive::unpack(&directory))
+        .transpose()?;
+    let (directory, origin) = match (&remote, &archive) {
+        (Some(remote), _) => (remote.path(), Some(directory)),
+        (None, Some(archive)) => (archive.path().to_path_buf(), Some(directory)),
+        (None, None) => (directory, None),
+    };
+    validate_directory(&directory)?;
+    for vhost in &args.vhost {
+        validate_directory(&vhost.directory)?;
     }
 
     info!("Starting blendwerk...");
-    info!("  Directory: {}", args.directory.display());
+    info!("  Directory: {}", directory.display());
+    if let Some(origin) = &origin {
+        info!("  Copied from: {}", origin.display());
+    }
+    if let Some(interval) = args.remote_refresh {
+        info!("  Re-fetching every {}s", interval);
+    }
+    for vhost in &args.vhost {
+        info!(
+            "  Virtual host: {} → {}",
+            vhost.host,
+            vhost.directory.display()
+        );
+    }
     info!("  HTTP port: {}", args.http_port);
     info!("  HTTPS port: {}", args.https_port);
     info!("  Cert mode: {:?}", args.cert_mode);
+    info!("  Runtime: {:?}", args.runtime);
+    if let Some(base_path) = &args.base_path {
+        info!("  Base path: {}", base_path);
+    }
+    if let Some(header) = &args.request_id_header {
+        info!(
+            "  Request IDs: {} ({:?} when missing)",
+            header, args.missing_request_id
+        );
+    }
 
     let run_http = !args.https_only;
     let run_https = !args.http_only && !matches!(args.cert_mode, CertMode::None);
@@ -125,14 +651,49 @@ async fn main_inner() -> anyhow::Result<()> {
         anyhow::bail!("No server to run (both HTTP and HTTPS disabled)");
     }
 
+    if args.http3_port.is_some() && !run_https {
+        anyhow::bail!("HTTP/3 needs a certificate; --http3-port requires HTTPS to be enabled");
+    }
+
+    let scan_options = routes::ScanOptions {
+        stream_threshold: args.stream_threshold,
+        mime_types: args.mime_type.iter().cloned().collect(),
+    };
+    if let Some(threshold) = scan_options.stream_threshold {
+        info!("  Streaming bodies larger than {} bytes", threshold);
+    }
+
     // Scan directory for routes
-    let routes = routes::scan_directory(&args.directory)?;
-    info!("  Loaded {} routes", routes.len());
+    let routes = vhost::scan(&directory, &args.vhost, &scan_options)?;
+    let memory = stats::memory_report(&routes);
+    info!(
+        "  Loaded {} routes ({} body bytes, {} after deduplication)",
+        routes.len(),
+        memory.total_body_bytes,
+        memory.unique_body_bytes
+    );
 
     for route in &routes {
-        info!("    {:?} {}", route.method, route.display_path());
+        match &route.host {
+            Some(host) => info!("    {:?} {}{}", route.method, host, route.display_path()),
+            None => info!("    {:?} {}", route.method, route.display_path()),
+        }
     }
 
+    // Load gRPC mocks if enabled
+    let grpc_mocks = match args.grpc_port {
+        Some(_) => {
+            let mocks = grpc::load(&directory)?;
+            info!(
+                "  Loaded {} gRPC methods ({} stubbed)",
+                mocks.method_count(),
+                mocks.stubbed_count()
+            );
+            Some(mocks)
+        }
+        None => None,
+    };
+
     // Create shared routes for hot-reload
     let shared_routes = Arc::new(RwLock::new(routes));
 
@@ -140,18 +701,135 @@ async fn main_inner() -> anyhow::Result<()> {
     let request_logger = args.request_log.as_ref().map(|log_dir| {
         info!("  Request logging: {}", log_dir.display());
         info!("  Log format: {:?}", args.request_log_format);
-        request_logger::RequestLogger::new(log_dir.clone(), args.request_log_format.clone())
+        if let Some(compression) = args.request_log_compress {
+            info!("  Log compression: {:?}", compression);
+        }
+        let batch = args
+            .request_log_batch
+            .map(|size| request_logger::BatchOptions {
+                size: size.max(1),
+                interval: Duration::from_millis(args.request_log_flush_interval.max(1)),
+            });
+        if let Some(batch) = &batch {
+            info!(
+                "  Log batching: {} entries or {:?}",
+                batch.size, batch.interval
+            );
+        }
+        request_logger::RequestLogger::new(
+            log_dir.clone(),
+            args.request_log_format.clone(),
+            args.request_log_queue,
+            batch,
+            args.request_log_compress,
+        )
+        .with_max_body_bytes(args.request_log_max_body)
+        .with_redacted_headers(
+            args.log_redact_header
+                .iter()
+                .filter(|name| !name.is_empty())
+                .cloned()
+                .collect(),
+        )
+        .with_filter(log_filter::LogFilter {
+            only_paths: args.log_only_path.clone(),
+            skip_statuses: args.log_skip_status.clone(),
+            only_unmatched: args.log_only_unmatched,
+        })
     });
 
+    let oidc = match args.oidc {
+        true => {
+            info!("  Generating OIDC signing key...");
+            Some(oidc::Oidc::new(oidc::OidcConfig {
+                issuer: args.oidc_issuer.clone(),
+                subject: args.oidc_subject.clone(),
+                claims: args.oidc_claim.iter().cloned().collect(),
+                token_ttl: Duration::from_secs(args.oidc_token_ttl),
+            })?)
+        }
+        false => None,
+    };
+
+    let sequence = match &args.sequence {
+        Some(path) => {
+            let sequence = sequence::Sequence::load(path)?;
+            info!("  Expected sequence: {}", path.display());
+            Some(sequence)
+        }
+        None => None,
+    };
+
+    if !args.enable_tags.is_empty() {
+        info!("  Enabled tags: {}", args.enable_tags.join(", "));
+    }
+    if !args.disable_tags.is_empty() {
+        info!("  Disabled tags: {}", args.disable_tags.join(", "));
+    }
+
+    // Create shutdown signal
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let changed_fixtures = args
+        .mark_changed_fixtures
+        .then(|| Arc::new(fixture_changes::ChangedFixtures::default()));
+    let reload_events = Arc::new(reload_events::ReloadEvents::new(
+        args.reload_webhook.clone(),
+    ));
+
     // Create application state
     let app_state = Arc::new(server::AppState {
-        routes: shared_routes.clone(),
         request_logger,
+        http_version: args.http_version,
+        cors: args.cors.then(|| cors::Cors {
+            origins: args.cors_origins.clone(),
+            methods: args.cors_methods.clone(),
+            headers: args.cors_headers.clone(),
+        }),
+        limits: server::RequestLimits {
+            max_uri_length: args.max_uri_length,
+            max_header_bytes: args.max_header_bytes,
+            max_body_bytes: args.max_body_bytes,
+        },
+        accept_delay: args.accept_delay.map(Duration::from_millis),
+        oidc,
+        tags: tags::Tags::new(tags::TagSelection {
+            enabled: args.enable_tags.iter().cloned().collect(),
+            disabled: args.disable_tags.iter().cloned().collect(),
+        }),
+        etag: args.etag,
+        error_format: args.error_format,
+        duplicates: args
+            .duplicate_window
+            .map(|ms| duplicates::Duplicates::new(Duration::from_millis(ms))),
+        sequence,
+        trusted_proxies: forwarded::TrustedProxies(args.trusted_proxy.clone()),
+        base_path: args.base_path.clone(),
+        request_ids: args
+            .request_id_header
+            .clone()
+            .map(|header| request_id::RequestIds::new(header, args.missing_request_id)),
+        changed_fixtures: changed_fixtures.clone(),
+        index_listing: args.index_listing,
+        control_headers: args.control_headers,
+        path_matching: routes::PathMatching {
+            trailing_slash: args.trailing_slash,
+            case_insensitive: args.case_insensitive_paths,
+            percent_decode: !args.raw_paths,
+            matrix_params: args.matrix_params,
+        },
+        promote_dir: args.promote_endpoint.then(|| directory.clone()),
+        coverage: args.coverage.then(Default::default),
+        vhosts: args.vhost.clone(),
+        received_requests: (args.request_history > 0).then(|| {
+            Arc::new(mock_server::ReceivedRequests::with_capacity(
+                args.request_history,
+            ))
+        }),
+        reload_events: reload_events.clone(),
+        ..server::AppState::new(shared_routes.clone(), shutdown_rx.clone())
     });
 
-    // Create shutdown signal
-    let (shutdown_tx, shutdown_rx) = watch::channel(false);
-
     // Set up signal handler for graceful shutdown
     let signal_tx = shutdown_tx.clone();
     tokio::spawn(async move {
@@ -186,7 +864,9 @@ async fn main_inner() -> anyhow::Result<()> {
         Some(match args.cert_mode {
             CertMode::SelfSigned => {
                 info!("  Generating self-signed certificate...");
-                tls::create_self_signed_config().await?
+                let state_dir = args.state_dir.clone().or_else(tls::default_state_dir);
+                let names = tls::ServerNames::new(&args.cert_san, args.cert_cn.as_deref());
+                tls::create_self_signed_config(state_dir.as_deref(), &names).await?
             }
             CertMode::Custom => {
                 let cert_file = args.cert_file.as_ref().unwrap();
@@ -204,44 +884,389 @@ async fn main_inner() -> anyhow::Result<()> {
         None
     };
 
+    // Re-fetched remote mocks are applied by the file watcher
+    if let Some(remote) = &remote {
+        tokio::spawn(remote::refresh_loop(
+            remote.clone(),
+            args.remote_refresh
+                .map(|interval| Duration::from_secs(interval.max(1))),
+            shutdown_rx.clone(),
+        ));
+    }
+
     // Spawn file watcher for hot-reload
     let watcher_routes = shared_routes.clone();
-    let watcher_dir = args.directory.clone();
+    let watcher_dir = directory.clone();
+    let watcher_vhosts = args.vhost.clone();
     let watcher_shutdown = shutdown_rx.clone();
+    let watcher_read_bodies = app_state.routes_read_bodies.clone();
     tokio::spawn(async move {
-        if let Err(e) =
-            watcher::watch_directory(watcher_dir, watcher_routes, watcher_shutdown).await
+        if let Err(e) = watcher::watch_directory(
+            watcher_dir,
+            watcher_vhosts,
+            scan_options,
+            args.watch_poll
+                .map(|interval| Duration::from_millis(interval.max(1))),
+            watcher_routes,
+            watcher::ReloadListeners {
+                changed_fixtures,
+                events: reload_events,
+                routes_read_bodies: watcher_read_bodies,
+            },
+            watcher_shutdown,
+        )
+        .await
         {
             error!("Watcher error: {}", e);
         }
     });
 
-    // Spawn servers
-    let mut handles = vec![];
+    let mut ports = ports::BoundPorts::default();
+
+    // Spawn the gRPC server next to the HTTP servers, on the main runtime
+    let grpc_handle = match (grpc_mocks, args.grpc_port) {
+        (Some(mocks), Some(port)) => {
+            let listener = server::bind_listener(port, false)?;
+            info!("gRPC server listening on {}", listener.local_addr()?);
+            ports.grpc = Some(listener.local_addr()?.port());
+            let shutdown = shutdown_rx.clone();
+            Some(tokio::spawn(async move {
+                grpc::run_grpc_server(mocks, listener, shutdown).await
+            }))
+        }
+        _ => None,
+    };
+
+    // HTTP/3 runs on the main runtime as well, sharing the HTTPS certificate
+    let mut http3_endpoint = None;
+    let http3_handle = match (&tls_config, args.http3_port) {
+        (Some(tls), Some(port)) => {
+            let endpoint = http3::bind(port, tls)?;
+            info!(
+                "HTTP/3 server listening on https://{} (UDP)",
+                endpoint.local_addr()?
+            );
+            ports.http3 = Some(endpoint.local_addr()?.port());
+            http3_endpoint = Some(endpoint.clone());
+            let state = app_state.clone();
+            let shutdown = shutdown_rx.clone();
+            Some(tokio::spawn(async move {
+                http3::run_http3_server(state, endpoint, shutdown).await
+            }))
+        }
+        _ => None,
+    };
 
-    if run_http {
-        let state = app_state.clone();
+    // Custom certificates are reloaded when they change on disk, so rotated
+    // certificates don't need a restart
+    if let (Some(tls), CertMode::Custom) = (&tls_config, &args.cert_mode) {
+        let cert_file = args.cert_file.clone().unwrap();
+        let key_file = args.key_file.clone().unwrap();
+        let tls = tls.clone();
         let shutdown = shutdown_rx.clone();
-        let port = args.http_port;
-        handles.push(tokio::spawn(async move {
-            server::run_http_server(state, port, shutdown).await
-        }));
+        tokio::spawn(async move {
+            if let Err(e) =
+                watcher::watch_certificates(cert_file, key_file, tls, http3_endpoint, shutdown)
+                    .await
+            {
+                error!("Certificate watcher error: {}", e);
+            }
+        });
     }
 
-    if run_https {
-        let state = app_state.clone();
-        let shutdown = shutdown_rx.clone();
-        let port = args.https_port;
-        let tls = tls_config.unwrap();
-        handles.push(tokio::spawn(async move {
-            server::run_https_server(state, port, tls, shutdown).await
-        }));
+    // Spawn servers
+    match args.runtime {
+        RuntimeMode::MultiThread => {
+            let mut handles = vec![];
+
+            if run_http {
+                let state = app_state.clone();
+                let shutdown = shutdown_rx.clone();
+                let listener = server::bind_listener(args.http_port, false)?;
+                info!("HTTP server listening on http://{}", listener.local_addr()?);
+                ports.http = Some(listener.local_addr()?.port());
+                handles.push(tokio::spawn(async move {
+                    server::run_http_server(state, listener, shutdown).await
+                }));
+            }
+
+            if run_https {
+                let state = app_state.clone();
+                let shutdown = shutdown_rx.clone();
+                let listener = server::bind_listener(args.https_port, false)?;
+                info!(
+                    "HTTPS server listening on https://{}",
+                    listener.local_addr()?
+                );
+                ports.https = Some(listener.local_addr()?.port());
+                let tls = tls_config.unwrap();
+                handles.push(tokio::spawn(async move {
+                    server::run_https_server(state, listener, tls, shutdown).await
+                }));
+            }
+
+            ports.announce(args.print_ports, args.port_file.as_deref())?;
+
+            // Wait for servers to finish (they'll stop when shutdown signal is sent)
+            for handle in handles {
+                let _ = handle.await;
+            }
+        }
+        RuntimeMode::ThreadPerCore => {
+            let cores = std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1);
+            let mut threads = vec![];
+
+            // With port 0 the first listener picks the port the others share
+            for index in 0..cores {
+                let http_listener = if run_http {
+                    let listener =
+                        server::bind_listener(ports.http.unwrap_or(args.http_port), true)?;
+                    ports.http = Some(listener.local_addr()?.port());
+                    Some(listener)
+                } else {
+                    None
+                };
+                let https_listener = match &tls_config {
+                    Some(tls) if run_https => {
+                        let listener =
+                            server::bind_listener(ports.https.unwrap_or(args.https_port), true)?;
+                        ports.https = Some(listener.local_addr()?.port());
+                        Some((listener, tls.clone()))
+                    }
+                    _ => None,
+                };
+
+                threads.push(server::spawn_core_thread(
+                    index,
+                    app_state.clone(),
+                    http_listener,
+                    https_listener,
+                    shutdown_rx.clone(),
+                )?);
+            }
+
+            if let Some(port) = ports.http {
+                info!(
+                    "HTTP server listening on http://0.0.0.0:{} ({} threads)",
+                    port, cores
+                );
+            }
+            if let Some(port) = ports.https {
+                info!(
+                    "HTTPS server listening on https://0.0.0.0:{} ({} threads)",
+                    port, cores
+                );
+            }
+            ports.announce(args.print_ports, args.port_file.as_deref())?;
+
+            // Wait for all core threads to finish
+            for thread in threads {
+                let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+            }
+        }
     }
 
-    // Wait for servers to finish (they'll stop when shutdown signal is sent)
-    for handle in handles {
+    if let Some(handle) = grpc_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = http3_handle {
         let _ = handle.await;
     }
 
+    if let Some(logger) = &app_state.request_logger {
+        // Write out whatever is still queued or buffered in a batch
+        logger.flush().await;
+        let stats = logger.stats();
+        if stats.dropped > 0 {
+            warn!(
+                "Request log dropped {} of {} requests because the queue was full",
+                stats.dropped,
+                stats.written + stats.dropped + stats.failed + stats.queued as u64
+            );
+        }
+    }
+
+    if let Some(port_file) = &args.port_file {
+        let _ = std::fs::remove_file(port_file);
+    }
+
+    if let Some(coverage) = &app_state.coverage {
+        let report = coverage.report(&shared_routes.read().await);
+        info!("Coverage: {} of {} routes hit", report.hit, report.total);
+        for route in &report.never_hit {
+            warn!(
+                "  Never hit: {} {} ({})",
+                route.method,
+                route.route,
+                route.file.display()
+            );
+        }
+        for request in &report.unmatched {
+            warn!(
+                "  Unmatched: {} {} ({} times)",
+                request.method, request.path, request.count
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_directory(directory: &Path) -> anyhow::Result<()> {
+    if !directory.exists() {
+        anyhow::bail!("Directory '{}' does not exist", directory.display());
+    }
+
+    if !directory.is_dir() {
+        anyhow::bail!("'{}' is not a directory", directory.display());
+    }
+
+    Ok(())
+}
+
+/// Normalize a base path to a leading and no trailing slash
+fn parse_base_path(input: &str) -> Result<String, String> {
+    let path = input.trim_matches('/');
+    if path.is_empty() {
+        return Err("the base path must not be empty".to_string());
+    }
+    Ok(format!("/{}", path))
+}
+
+/// Parse a `NAME=VALUE` claim, taking the value as JSON if it is valid JSON
+fn parse_claim(input: &str) -> Result<(String, serde_json::Value), String> {
+    let (name, value) = input
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got `{}`", input))?;
+    let value = serde_json::from_str(value)
+        .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+    Ok((name.to_string(), value))
+}
+
+async fn run_command(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Export { directory, output } => {
+            validate_directory(&directory)?;
+            let routes = routes::scan_directory(&directory)?;
+            let document = serde_json::to_string_pretty(&openapi::generate(&routes))?;
+
+            match output {
+                Some(path) => std::fs::write(&path, document).with_context(|| {
+                    format!("Failed to write OpenAPI document: {}", path.display())
+                })?,
+                None => println!("{}", document),
+            }
+        }
+        Command::Import {
+            source:
+                ImportSource::Har {
+                    file,
+                    directory,
+                    force,
+                },
+        } => {
+            info!("Importing {} into {}", file.display(), directory.display());
+            let count = har::import(&file, &directory, force)?;
+            info!("Imported {} routes", count);
+        }
+        Command::Promote {
+            file,
+            directory,
+            request_id,
+            force,
+        } => {
+            let written = promote::promote(&file, &directory, request_id.as_deref(), force)?;
+            info!("Promoted {} to {}", file.display(), written.display());
+        }
+        Command::Test { directory } => {
+            validate_directory(&directory)?;
+            let outcomes = examples::run(&directory).await?;
+
+            let mut failed = 0;
+            for outcome in &outcomes {
+                let source = outcome.source.display();
+                match &outcome.failure {
+                    None => println!("ok   {} ({})", outcome.request, source),
+                    Some(reason) => {
+                        failed += 1;
+                        println!("FAIL {} ({}): {}", outcome.request, source, reason);
+                    }
+                }
+            }
+
+            println!("{} examples, {} failed", outcomes.len(), failed);
+            if failed > 0 {
+                anyhow::bail!("{} of {} examples failed", failed, outcomes.len());
+            }
+        }
+        Command::Init {
+            directory,
+            base_path,
+            resources,
+            auth,
+            force,
+        } => {
+            let options = init::prompt(
+                base_path,
+                resources,
+                auth,
+                &mut std::io::stdin().lock(),
+                &mut std::io::stdout(),
+            )?;
+            for file in init::scaffold(&directory, &options, force)? {
+                info!("  Created {}", file.display());
+            }
+            info!("Run `blendwerk {}` to serve the mocks", directory.display());
+        }
+        Command::Cert {
+            command: CertCommand::ExportCa { state_dir },
+        } => {
+            let state_dir = state_dir
+                .or_else(tls::default_state_dir)
+                .context("No state directory; set --state-dir")?;
+            print!("{}", tls::LocalCa::load_or_create(&state_dir)?.cert_pem);
+        }
+        Command::Ctl { url, command } => {
+            let client = ctl::Client::new(&url);
+            match command {
+                CtlCommand::Routes => {
+                    let routes = client.routes().await?;
+                    for route in routes.as_array().into_iter().flatten() {
+                        println!(
+                            "{:7} {} ({})",
+                            route["method"].as_str().unwrap_or_default(),
+                            route["path"].as_str().unwrap_or_default(),
+                            route["source"].as_str().unwrap_or_default()
+                        );
+                    }
+                }
+                CtlCommand::Requests => {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&client.requests().await?)?
+                    );
+                }
+                CtlCommand::Reset => {
+                    client.reset().await?;
+                    info!("Reset {}", url);
+                }
+                CtlCommand::Scenario {
+                    command: ScenarioCommand::Set { tags },
+                } => {
+                    client.set_scenario(&tags).await?;
+                    info!("Enabled tags: {}", tags.join(", "));
+                }
+                CtlCommand::Stub {
+                    command: StubCommand::Add { method, path, file },
+                } => {
+                    client.add_stub(&method, &path, &file).await?;
+                    info!("Added stub {} {}", method, path);
+                }
+            }
+        }
+    }
+
     Ok(())
 }
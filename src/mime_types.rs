@@ -0,0 +1,95 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Content types of route files by their extension.
+//!
+//! Mappings given with `--mime-type` come first, then blendwerk's own
+//! defaults (kept stable for existing mock trees), then the `mime_guess`
+//! database. Unknown extensions are `application/octet-stream`.
+
+use crate::sse;
+use std::collections::HashMap;
+
+/// Types blendwerk always used, or that the database lacks or names
+/// differently
+const DEFAULTS: &[(&str, &str)] = &[
+    ("json", "application/json"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("xml", "application/xml"),
+    ("txt", "text/plain"),
+    ("css", "text/css"),
+    ("js", "application/javascript"),
+    ("sse", sse::CONTENT_TYPE),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("ndjson", "application/x-ndjson"),
+    ("proto", "text/plain"),
+];
+
+const FALLBACK: &str = "application/octet-stream";
+
+/// Content type of a file with `extension`, `custom` mapping lowercase
+/// extensions to types
+pub fn for_extension(extension: &str, custom: &HashMap<String, String>) -> String {
+    let extension = extension.to_ascii_lowercase();
+    if let Some(content_type) = custom.get(&extension) {
+        return content_type.clone();
+    }
+    DEFAULTS
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, content_type)| *content_type)
+        .or_else(|| mime_guess::from_ext(&extension).first_raw())
+        .unwrap_or(FALLBACK)
+        .to_string()
+}
+
+/// Parse a `--mime-type` mapping like `EXT=TYPE`
+pub fn parse_mapping(value: &str) -> Result<(String, String), String> {
+    let (extension, content_type) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected EXT=TYPE, got '{}'", value))?;
+    let extension = extension
+        .trim()
+        .trim_start_matches('.')
+        .to_ascii_lowercase();
+    let content_type = content_type.trim();
+    if extension.is_empty() || !content_type.contains('/') {
+        return Err(format!("expected EXT=TYPE, got '{}'", value));
+    }
+    Ok((extension, content_type.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_extension() {
+        let custom = HashMap::from([("geojson".to_string(), "application/geo+json".to_string())]);
+        assert_eq!(for_extension("JSON", &custom), "application/json");
+        assert_eq!(for_extension("xml", &custom), "application/xml");
+        assert_eq!(for_extension("svg", &custom), "image/svg+xml");
+        assert_eq!(for_extension("wasm", &custom), "application/wasm");
+        assert_eq!(for_extension("csv", &custom), "text/csv");
+        assert_eq!(for_extension("geojson", &custom), "application/geo+json");
+        assert_eq!(for_extension("unknown-ext", &custom), FALLBACK);
+    }
+
+    #[test]
+    fn test_parse_mapping() {
+        assert_eq!(
+            parse_mapping(".GeoJSON=application/geo+json"),
+            Ok(("geojson".to_string(), "application/geo+json".to_string()))
+        );
+        assert!(parse_mapping("geojson").is_err());
+        assert!(parse_mapping("=text/plain").is_err());
+        assert!(parse_mapping("txt=plain").is_err());
+    }
+}
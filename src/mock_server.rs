@@ -0,0 +1,433 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Embedded server for Rust integration tests.
+//!
+//! [`MockServer`] serves a mock directory over plain HTTP on a free port of
+//! `127.0.0.1`, records the requests it receives and accepts stubs added
+//! from code. Stubs take precedence over route files. The server shuts
+//! down when the handle is dropped.
+
+use crate::delay::Delay;
+use crate::frontmatter::{self, HeaderValues, ParsedResponse, ResponseMeta};
+use crate::request_logger::LoggedBody;
+use crate::routes::{self, HttpMethod, PathSegment, Route};
+use crate::server::{self, AppState, RoutesReadBodies, SharedRoutes};
+use crate::verification::{self, Received, RequestMatcher};
+use anyhow::{Context, Result, bail};
+use axum::http::HeaderMap;
+use bytes::Bytes;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, VecDeque};
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Notify, RwLock, watch};
+use tokio::task::JoinHandle;
+
+/// Requests a [`MockServer`] received, in order of arrival
+#[derive(Debug, Default)]
+pub struct ReceivedRequests {
+    requests: Mutex<VecDeque<ReceivedRequest>>,
+    /// Oldest requests are forgotten beyond this many, unlimited if `None`
+    capacity: Option<usize>,
+    /// Woken for every recorded request
+    arrived: Notify,
+}
+
+impl ReceivedRequests {
+    /// Keep only the last `capacity` requests
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&self, request: ReceivedRequest) {
+        let mut requests = self.requests.lock().unwrap();
+        requests.push_back(request);
+        if let Some(capacity) = self.capacity
+            && requests.len() > capacity
+        {
+            requests.pop_front();
+        }
+        drop(requests);
+        self.arrived.notify_waiters();
+    }
+
+    pub fn snapshot(&self) -> Vec<ReceivedRequest> {
+        self.requests.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.requests.lock().unwrap().clear();
+    }
+
+    /// The first request matching `matcher`, waiting until `deadline` (or
+    /// forever without one) for it to arrive; on timeout, all requests
+    /// received instead
+    pub async fn wait_for(
+        &self,
+        matcher: &RequestMatcher,
+        deadline: Option<tokio::time::Instant>,
+    ) -> std::result::Result<ReceivedRequest, Vec<ReceivedRequest>> {
+        loop {
+            // Registered before looking, so no request slips through
+            let arrived = self.arrived.notified();
+            tokio::pin!(arrived);
+            arrived.as_mut().enable();
+
+            let requests = self.snapshot();
+            if let Some(request) = requests.iter().find(|request| matcher.matches(request)) {
+                return Ok(request.clone());
+            }
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, arrived).await.is_err() {
+                        return Err(requests);
+                    }
+                }
+                None => arrived.await,
+            }
+        }
+    }
+}
+
+/// A request as received by a [`MockServer`]
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub method: String,
+    /// Path as requested
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    /// Display path of the answering route (e.g. `/users/:id`), if any
+    pub matched_route: Option<String>,
+    pub status: u16,
+}
+
+/// Serialized like request log entries, binary bodies base64 encoded
+impl Serialize for ReceivedRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Entry<'a> {
+            method: &'a str,
+            path: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            query: Option<&'a str>,
+            headers: BTreeMap<&'a str, &'a str>,
+            #[serde(flatten)]
+            body: LoggedBody,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            matched_route: Option<&'a str>,
+            status: u16,
+        }
+
+        Entry {
+            method: &self.method,
+            path: &self.path,
+            query: self.query.as_deref(),
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.to_str().unwrap_or("<binary>")))
+                .collect(),
+            body: LoggedBody::Content(self.body.clone()),
+            matched_route: self.matched_route.as_deref(),
+            status: self.status,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MockServerBuilder {
+    directory: Option<PathBuf>,
+    stubs: Vec<Stub>,
+}
+
+impl MockServerBuilder {
+    /// Serve the route files of this mock directory
+    pub fn directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Answer with `stub` from the start
+    pub fn stub(mut self, stub: Stub) -> Self {
+        self.stubs.push(stub);
+        self
+    }
+
+    pub async fn start(self) -> Result<MockServer> {
+        let mut routes = match &self.directory {
+            Some(directory) => routes::scan_directory(directory)?,
+            None => Vec::new(),
+        };
+        routes.splice(0..0, self.stubs.into_iter().rev().map(Stub::into_route));
+        let routes: SharedRoutes = Arc::new(RwLock::new(routes));
+        let received = Arc::new(ReceivedRequests::default());
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let state = Arc::new(AppState {
+            received_requests: Some(received.clone()),
+            ..AppState::new(routes.clone(), shutdown_rx.clone())
+        });
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).context("Failed to bind 127.0.0.1")?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let routes_read_bodies = state.routes_read_bodies.clone();
+        let task = tokio::spawn(server::run_http_server(state, listener, shutdown_rx));
+
+        Ok(MockServer {
+            address,
+            routes,
+            routes_read_bodies,
+            received,
+            shutdown: shutdown_tx,
+            task: Some(task),
+        })
+    }
+}
+
+/// A running server; shut down on drop
+#[derive(Debug)]
+pub struct MockServer {
+    address: SocketAddr,
+    routes: SharedRoutes,
+    routes_read_bodies: RoutesReadBodies,
+    received: Arc<ReceivedRequests>,
+    shutdown: watch::Sender<bool>,
+    task: Option<JoinHandle<Result<()>>>,
+}
+
+impl MockServer {
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder::default()
+    }
+
+    /// Address the server listens on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// `http://127.0.0.1:<port>`, to put paths behind
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Requests received so far, in order of arrival
+    pub fn received_requests(&self) -> Vec<ReceivedRequest> {
+        self.received.snapshot()
+    }
+
+    /// Forget the requests received so far
+    pub fn clear_received_requests(&self) {
+        self.received.clear();
+    }
+
+    /// Requests received so far with this method to this path, to assert
+    /// on; `[param]` segments match any value
+    pub fn received(&self, method: impl AsRef<str>, path: &str) -> Received {
+        Received::new(RequestMatcher::new(method, path), self.received_requests())
+    }
+
+    /// The first request matching `matcher`, waiting up to `timeout` for it
+    /// to arrive; timeouts too long to represent wait forever
+    pub async fn wait_for_request(
+        &self,
+        matcher: RequestMatcher,
+        timeout: Duration,
+    ) -> Result<ReceivedRequest> {
+        let deadline = tokio::time::Instant::now().checked_add(timeout);
+        self.received
+            .wait_for(&matcher, deadline)
+            .await
+            .or_else(|requests| {
+                bail!(
+                    "no request {} within {:?}\n{}",
+                    matcher,
+                    timeout,
+                    verification::summary(&requests)
+                )
+            })
+    }
+
+    /// Answer matching requests with `stub`, ahead of route files and
+    /// earlier stubs
+    pub async fn stub(&self, stub: Stub) {
+        let mut routes = self.routes.write().await;
+        routes.insert(0, stub.into_route());
+        self.routes_read_bodies.update(&routes);
+    }
+
+    /// Stop accepting requests and wait for open ones to finish
+    pub async fn shutdown(mut self) -> Result<()> {
+        let _ = self.shutdown.send(true);
+        match self.task.take() {
+            Some(task) => task.await?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+/// Start of the `source` of stub routes, which have no file
+const STUB_SOURCE_PREFIX: &str = "<stub ";
+
+/// A route defined in code, matching like a route file of the same path:
+/// `/users/[id]` matches any user ID
+#[derive(Debug, Clone)]
+pub struct Stub {
+    method: HttpMethod,
+    path: String,
+    meta: ResponseMeta,
+    content_type: String,
+    body: Bytes,
+}
+
+impl Stub {
+    /// Stub answering `method` requests to `path` with an empty 200.
+    ///
+    /// Panics for methods blendwerk has no route files for.
+    pub fn new(method: &str, path: &str) -> Self {
+        Self {
+            method: HttpMethod::from_str(method)
+                .unwrap_or_else(|| panic!("unsupported method {}", method)),
+            path: path.to_string(),
+            meta: ResponseMeta::default(),
+            content_type: "text/plain".to_string(),
+            body: Bytes::new(),
+        }
+    }
+
+    pub fn get(path: &str) -> Self {
+        Self::new("GET", path)
+    }
+
+    pub fn post(path: &str) -> Self {
+        Self::new("POST", path)
+    }
+
+    pub fn put(path: &str) -> Self {
+        Self::new("PUT", path)
+    }
+
+    pub fn patch(path: &str) -> Self {
+        Self::new("PATCH", path)
+    }
+
+    pub fn delete(path: &str) -> Self {
+        Self::new("DELETE", path)
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.meta.status = Some(status);
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        match self.meta.headers.get_mut(name) {
+            Some(values) => values.push(value.to_string()),
+            None => {
+                self.meta
+                    .headers
+                    .insert(name.to_string(), HeaderValues::One(value.to_string()));
+            }
+        }
+        self
+    }
+
+    /// Plain text body, unless a `Content-Type` header says otherwise
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// JSON body, sent as `application/json`
+    pub fn json(mut self, body: &serde_json::Value) -> Self {
+        self.body = Bytes::from(body.to_string());
+        self.content_type = "application/json".to_string();
+        self
+    }
+
+    /// Wait this many milliseconds before answering
+    pub fn delay_ms(mut self, delay: u64) -> Self {
+        self.meta.delay = Delay::Fixed(delay);
+        self
+    }
+
+    /// Stub answering like a route file with `content` (frontmatter and
+    /// body) of the given content type, for `/__blendwerk/stubs`
+    pub(crate) fn from_route_file(
+        method: &str,
+        path: &str,
+        content_type: String,
+        content: &str,
+    ) -> Result<Self> {
+        let method = HttpMethod::from_str(method)
+            .filter(|method| *method != HttpMethod::Ws)
+            .with_context(|| format!("unsupported method {}", method))?;
+        let response = frontmatter::parse_frontmatter(content)?;
+        Ok(Self {
+            method,
+            path: path.to_string(),
+            meta: response.meta,
+            content_type,
+            body: response.body,
+        })
+    }
+
+    /// Whether `route` was added as stub rather than loaded from a file
+    pub(crate) fn is_stub(route: &Route) -> bool {
+        route
+            .source
+            .to_string_lossy()
+            .starts_with(STUB_SOURCE_PREFIX)
+    }
+
+    pub(crate) fn into_route(self) -> Route {
+        Route {
+            path_segments: self
+                .path
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(PathSegment::parse)
+                .collect(),
+            source: PathBuf::from(format!(
+                "{}{} {}>",
+                STUB_SOURCE_PREFIX,
+                self.method.as_str(),
+                self.path
+            )),
+            method: self.method,
+            response: ParsedResponse {
+                meta: self.meta,
+                body: self.body,
+            },
+            content_type: self.content_type,
+            body_file: None,
+            websocket: None,
+            script: None,
+            plugin: None,
+            graphql_operation: None,
+            modified: None,
+            host: None,
+            includes: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,506 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Built-in OpenID Connect provider (`--oidc`).
+//!
+//! Serves discovery, JWKS, authorize and token endpoints for paths without a
+//! route file. Every client and every login is accepted: the authorize
+//! endpoint redirects straight back with a code, and the token endpoint
+//! issues RS256 signed tokens for codes, client credentials, passwords and
+//! refresh tokens. Codes and refresh tokens carry their grant themselves, so
+//! nothing is stored between requests. The signing key is generated on
+//! startup.
+
+use axum::http::{
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri,
+    header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, LOCATION},
+};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use bytes::Bytes;
+use jsonwebtoken::jwk::{Jwk, JwkSet, PublicKeyUse};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rcgen::{KeyPair, PKCS_RSA_SHA256};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+pub const JWKS_PATH: &str = "/oidc/jwks";
+pub const AUTHORIZE_PATH: &str = "/oidc/authorize";
+pub const TOKEN_PATH: &str = "/oidc/token";
+
+/// `client_id` of requests that don't name a client
+const DEFAULT_CLIENT_ID: &str = "blendwerk";
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    /// `iss` of issued tokens; the origin of the request if unset
+    pub issuer: Option<String>,
+    /// `sub` of logins without a `login_hint` or `username`
+    pub subject: String,
+    /// Claims added to every access and ID token, replacing generated ones
+    pub claims: Map<String, Value>,
+    /// Lifetime of access and ID tokens
+    pub token_ttl: Duration,
+}
+
+pub struct Oidc {
+    config: OidcConfig,
+    key: EncodingKey,
+    key_id: String,
+    jwks: Value,
+}
+
+/// Answer of one of the provider's endpoints
+#[derive(Debug)]
+pub struct Reply {
+    pub status: StatusCode,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub body: Bytes,
+}
+
+impl Reply {
+    fn json(status: StatusCode, body: &Value) -> Self {
+        Self {
+            status,
+            headers: vec![(CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+            body: Bytes::from(body.to_string()),
+        }
+    }
+
+    /// OAuth error response of the token endpoint
+    fn error(error: &str, description: &str) -> Self {
+        Self::json(
+            StatusCode::BAD_REQUEST,
+            &json!({ "error": error, "error_description": description }),
+        )
+    }
+
+    fn redirect(location: &str) -> Self {
+        match HeaderValue::try_from(location) {
+            Ok(location) => Self {
+                status: StatusCode::FOUND,
+                headers: vec![(LOCATION, location)],
+                body: Bytes::new(),
+            },
+            Err(_) => Self::error(
+                "invalid_request",
+                "redirect_uri is not a valid header value",
+            ),
+        }
+    }
+}
+
+/// What a code or refresh token grants
+#[derive(Debug, Serialize, Deserialize)]
+struct Grant {
+    sub: String,
+    client_id: String,
+    scope: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+}
+
+impl Grant {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(token: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(token).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+impl Oidc {
+    pub fn new(config: OidcConfig) -> anyhow::Result<Self> {
+        let key_pair = KeyPair::generate_for(&PKCS_RSA_SHA256)?;
+        let key = EncodingKey::from_rsa_pem(key_pair.serialize_pem().as_bytes())?;
+        let key_id = ulid::Ulid::new().to_string().to_lowercase();
+
+        let mut jwk = Jwk::from_encoding_key(&key, Algorithm::RS256)?;
+        jwk.common.key_id = Some(key_id.clone());
+        jwk.common.public_key_use = Some(PublicKeyUse::Signature);
+        let jwks = serde_json::to_value(JwkSet { keys: vec![jwk] })?;
+
+        Ok(Self {
+            config,
+            key,
+            key_id,
+            jwks,
+        })
+    }
+
+    /// Answer the request if it is for one of the provider's endpoints.
+    ///
+    /// `origin` is the `scheme://host` the client sent the request to, for
+    /// the endpoint URLs and the default issuer.
+    pub fn respond(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        body: &[u8],
+        origin: &str,
+    ) -> Option<Reply> {
+        let reply = match (method, uri.path()) {
+            (&Method::GET, DISCOVERY_PATH) => Reply::json(StatusCode::OK, &self.discovery(origin)),
+            (&Method::GET, JWKS_PATH) => Reply::json(StatusCode::OK, &self.jwks),
+            (&Method::GET, AUTHORIZE_PATH) => {
+                self.authorize(&parse_form(uri.query().unwrap_or_default().as_bytes()))
+            }
+            (&Method::POST, TOKEN_PATH) => self.token(origin, &parse_form(body), headers),
+            _ => return None,
+        };
+        Some(reply)
+    }
+
+    fn issuer(&self, origin: &str) -> String {
+        self.config
+            .issuer
+            .clone()
+            .unwrap_or_else(|| origin.to_string())
+    }
+
+    fn discovery(&self, origin: &str) -> Value {
+        json!({
+            "issuer": self.issuer(origin),
+            "authorization_endpoint": format!("{}{}", origin, AUTHORIZE_PATH),
+            "token_endpoint": format!("{}{}", origin, TOKEN_PATH),
+            "jwks_uri": format!("{}{}", origin, JWKS_PATH),
+            "response_types_supported": ["code"],
+            "grant_types_supported": [
+                "authorization_code",
+                "client_credentials",
+                "password",
+                "refresh_token",
+            ],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+            "scopes_supported": ["openid", "profile", "email", "offline_access"],
+            "token_endpoint_auth_methods_supported": [
+                "client_secret_basic",
+                "client_secret_post",
+                "none",
+            ],
+            "code_challenge_methods_supported": ["S256", "plain"],
+        })
+    }
+
+    /// Log in without asking and redirect back with a code
+    fn authorize(&self, params: &HashMap<String, String>) -> Reply {
+        let Some(redirect_uri) = params.get("redirect_uri") else {
+            return Reply::error("invalid_request", "redirect_uri is required");
+        };
+
+        let mut response = vec![];
+        match params.get("response_type").map(String::as_str) {
+            Some("code") => {
+                let grant = Grant {
+                    sub: params
+                        .get("login_hint")
+                        .cloned()
+                        .unwrap_or_else(|| self.config.subject.clone()),
+                    client_id: params
+                        .get("client_id")
+                        .cloned()
+                        .unwrap_or_else(|| DEFAULT_CLIENT_ID.to_string()),
+                    scope: params
+                        .get("scope")
+                        .cloned()
+                        .unwrap_or_else(|| "openid".to_string()),
+                    nonce: params.get("nonce").cloned(),
+                };
+                response.push(("code", grant.encode()));
+            }
+            _ => response.push(("error", "unsupported_response_type".to_string())),
+        }
+        if let Some(state) = params.get("state") {
+            response.push(("state", state.clone()));
+        }
+
+        let separator = if redirect_uri.contains('?') { '&' } else { '?' };
+        let query = serde_urlencoded::to_string(&response).unwrap_or_default();
+        Reply::redirect(&format!("{}{}{}", redirect_uri, separator, query))
+    }
+
+    fn token(&self, origin: &str, params: &HashMap<String, String>, headers: &HeaderMap) -> Reply {
+        let client_id = params
+            .get("client_id")
+            .cloned()
+            .or_else(|| basic_auth_user(headers))
+            .unwrap_or_else(|| DEFAULT_CLIENT_ID.to_string());
+        let scope = params.get("scope").cloned().unwrap_or_default();
+
+        let grant_type = params.get("grant_type").map(String::as_str);
+        let grant = match grant_type {
+            Some("authorization_code") => params.get("code").and_then(|code| Grant::decode(code)),
+            Some("refresh_token") => params
+                .get("refresh_token")
+                .and_then(|token| Grant::decode(token))
+                .map(|grant| Grant {
+                    nonce: None,
+                    ..grant
+                }),
+            Some("client_credentials") => Some(Grant {
+                sub: client_id.clone(),
+                client_id,
+                scope,
+                nonce: None,
+            }),
+            Some("password") => Some(Grant {
+                sub: params
+                    .get("username")
+                    .cloned()
+                    .unwrap_or_else(|| self.config.subject.clone()),
+                client_id,
+                scope,
+                nonce: None,
+            }),
+            Some(other) => {
+                return Reply::error(
+                    "unsupported_grant_type",
+                    &format!("grant_type {} is not supported", other),
+                );
+            }
+            None => return Reply::error("invalid_request", "grant_type is required"),
+        };
+        let Some(grant) = grant else {
+            return Reply::error("invalid_grant", "the code or refresh token is invalid");
+        };
+
+        match self.tokens(origin, &grant, grant_type != Some("client_credentials")) {
+            Ok(tokens) => {
+                let mut reply = Reply::json(StatusCode::OK, &tokens);
+                reply
+                    .headers
+                    .push((CACHE_CONTROL, HeaderValue::from_static("no-store")));
+                reply
+            }
+            Err(e) => Reply::json(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &json!({ "error": "server_error", "error_description": e.to_string() }),
+            ),
+        }
+    }
+
+    /// Token response for the grant; clients acting for themselves get
+    /// neither an ID nor a refresh token
+    fn tokens(&self, origin: &str, grant: &Grant, for_user: bool) -> anyhow::Result<Value> {
+        let now = jsonwebtoken::get_current_timestamp();
+        let ttl = self.config.token_ttl.as_secs();
+        let claims = |extra: Value| {
+            let mut claims = Map::new();
+            claims.insert("iss".to_string(), json!(self.issuer(origin)));
+            claims.insert("sub".to_string(), json!(grant.sub));
+            claims.insert("aud".to_string(), json!(grant.client_id));
+            claims.insert("iat".to_string(), json!(now));
+            claims.insert("exp".to_string(), json!(now + ttl));
+            if let Value::Object(extra) = extra {
+                claims.extend(extra);
+            }
+            claims.extend(self.config.claims.clone());
+            claims
+        };
+
+        let mut response = json!({
+            "access_token": self.sign(&claims(json!({
+                "client_id": grant.client_id,
+                "scope": grant.scope,
+            })))?,
+            "token_type": "Bearer",
+            "expires_in": ttl,
+            "scope": grant.scope,
+        });
+
+        if for_user {
+            response["refresh_token"] = json!(grant.encode());
+            if grant
+                .scope
+                .split_whitespace()
+                .any(|scope| scope == "openid")
+            {
+                let mut id_claims = claims(json!({ "auth_time": now }));
+                if let Some(nonce) = &grant.nonce {
+                    id_claims.insert("nonce".to_string(), json!(nonce));
+                }
+                response["id_token"] = json!(self.sign(&id_claims)?);
+            }
+        }
+
+        Ok(response)
+    }
+
+    fn sign(&self, claims: &Map<String, Value>) -> anyhow::Result<String> {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.key_id.clone());
+        Ok(jsonwebtoken::encode(&header, claims, &self.key)?)
+    }
+}
+
+fn parse_form(input: &[u8]) -> HashMap<String, String> {
+    serde_urlencoded::from_bytes(input).unwrap_or_default()
+}
+
+/// Client ID of `client_secret_basic` authentication; the secret isn't
+/// checked
+fn basic_auth_user(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(credentials.trim()).ok()?).ok()?;
+    decoded.split_once(':').map(|(user, _)| user.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{DecodingKey, Validation};
+
+    fn provider() -> Oidc {
+        Oidc::new(OidcConfig {
+            issuer: None,
+            subject: "user".to_string(),
+            claims: [("email".to_string(), json!("user@example.com"))]
+                .into_iter()
+                .collect(),
+            token_ttl: Duration::from_secs(60),
+        })
+        .unwrap()
+    }
+
+    fn json_body(reply: &Reply) -> Value {
+        serde_json::from_slice(&reply.body).unwrap()
+    }
+
+    fn post_token(oidc: &Oidc, form: &str) -> Reply {
+        let uri = Uri::from_static(TOKEN_PATH);
+        oidc.respond(
+            &Method::POST,
+            &uri,
+            &HeaderMap::new(),
+            form.as_bytes(),
+            "http://localhost:8080",
+        )
+        .unwrap()
+    }
+
+    /// Claims of `token` after checking it against the served JWKS
+    fn verify(oidc: &Oidc, token: &str) -> Map<String, Value> {
+        let jwks: JwkSet = serde_json::from_value(oidc.jwks.clone()).unwrap();
+        let key = DecodingKey::from_jwk(&jwks.keys[0]).unwrap();
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&["app"]);
+        jsonwebtoken::decode::<Map<String, Value>>(token, &key, &validation)
+            .unwrap()
+            .claims
+    }
+
+    #[test]
+    fn test_discovery_uses_request_origin() {
+        let oidc = provider();
+        let uri = Uri::from_static(DISCOVERY_PATH);
+        let reply = oidc
+            .respond(
+                &Method::GET,
+                &uri,
+                &HeaderMap::new(),
+                &[],
+                "https://idp.test:8443",
+            )
+            .unwrap();
+
+        let document = json_body(&reply);
+        assert_eq!(document["issuer"], "https://idp.test:8443");
+        assert_eq!(document["jwks_uri"], "https://idp.test:8443/oidc/jwks");
+
+        let other = Uri::from_static("/users");
+        assert!(
+            oidc.respond(
+                &Method::GET,
+                &other,
+                &HeaderMap::new(),
+                &[],
+                "http://localhost"
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_authorization_code_flow() {
+        let oidc = provider();
+        let uri: Uri = "/oidc/authorize?response_type=code&client_id=app&scope=openid%20email\
+                        &redirect_uri=http%3A%2F%2Fapp.test%2Fcb&state=xyz&nonce=n-1\
+                        &login_hint=alice"
+            .parse()
+            .unwrap();
+        let reply = oidc
+            .respond(
+                &Method::GET,
+                &uri,
+                &HeaderMap::new(),
+                &[],
+                "http://localhost",
+            )
+            .unwrap();
+        assert_eq!(reply.status, StatusCode::FOUND);
+
+        let location: Uri = reply.headers[0].1.to_str().unwrap().parse().unwrap();
+        assert_eq!(location.path(), "/cb");
+        let params = parse_form(location.query().unwrap().as_bytes());
+        assert_eq!(params["state"], "xyz");
+
+        let reply = post_token(
+            &oidc,
+            &format!("grant_type=authorization_code&code={}", params["code"]),
+        );
+        let tokens = json_body(&reply);
+        let id_claims = verify(&oidc, tokens["id_token"].as_str().unwrap());
+        assert_eq!(id_claims["iss"], "http://localhost:8080");
+        assert_eq!(id_claims["sub"], "alice");
+        assert_eq!(id_claims["nonce"], "n-1");
+        assert_eq!(id_claims["email"], "user@example.com");
+
+        let access_claims = verify(&oidc, tokens["access_token"].as_str().unwrap());
+        assert_eq!(access_claims["scope"], "openid email");
+
+        let refresh = format!(
+            "grant_type=refresh_token&refresh_token={}",
+            tokens["refresh_token"].as_str().unwrap()
+        );
+        let refreshed = json_body(&post_token(&oidc, &refresh));
+        let id_claims = verify(&oidc, refreshed["id_token"].as_str().unwrap());
+        assert_eq!(id_claims["sub"], "alice");
+        assert!(id_claims.get("nonce").is_none());
+    }
+
+    #[test]
+    fn test_client_credentials_and_errors() {
+        let oidc = provider();
+        let reply = post_token(&oidc, "grant_type=client_credentials&client_id=app");
+        let tokens = json_body(&reply);
+        assert_eq!(
+            verify(&oidc, tokens["access_token"].as_str().unwrap())["sub"],
+            "app"
+        );
+        assert!(tokens.get("id_token").is_none());
+        assert!(tokens.get("refresh_token").is_none());
+
+        let reply = post_token(&oidc, "grant_type=device_code");
+        assert_eq!(reply.status, StatusCode::BAD_REQUEST);
+        assert_eq!(json_body(&reply)["error"], "unsupported_grant_type");
+
+        let reply = post_token(&oidc, "grant_type=authorization_code&code=bogus");
+        assert_eq!(json_body(&reply)["error"], "invalid_grant");
+    }
+}
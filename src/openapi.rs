@@ -0,0 +1,201 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::routes::{HttpMethod, PathSegment, Route};
+use serde_json::{Map, Value, json};
+
+/// Generate an OpenAPI 3 document describing the given routes
+pub fn generate(routes: &[Route]) -> Value {
+    let mut paths = Map::new();
+
+    for route in routes {
+        // OpenAPI has no notion of extension methods such as WebDAV's
+        if !is_openapi_method(&route.method) {
+            continue;
+        }
+
+        let path_item = paths
+            .entry(openapi_path(route))
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        let Value::Object(operations) = path_item else {
+            unreachable!("path items are always objects");
+        };
+
+        // First match wins, so a later route for the same method and path is
+        // never served and must not overwrite the documented one
+        let method = route.method.as_str().to_lowercase();
+        if !operations.contains_key(&method) {
+            operations.insert(method, operation(route));
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "blendwerk mock API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+    })
+}
+
+fn is_openapi_method(method: &HttpMethod) -> bool {
+    matches!(
+        method,
+        HttpMethod::Get
+            | HttpMethod::Post
+            | HttpMethod::Put
+            | HttpMethod::Delete
+            | HttpMethod::Patch
+            | HttpMethod::Head
+            | HttpMethod::Options
+    )
+}
+
+/// Convert the route to OpenAPI path syntax (e.g., "/users/{id}")
+fn openapi_path(route: &Route) -> String {
+    let parts: Vec<String> = route
+        .path_segments
+        .iter()
+        .map(|segment| match segment {
+            PathSegment::Static(s) => s.clone(),
+            PathSegment::Dynamic(name) => format!("{{{}}}", name),
+        })
+        .collect();
+
+    format!("/{}", parts.join("/"))
+}
+
+fn operation(route: &Route) -> Value {
+    let parameters: Vec<Value> = route
+        .path_segments
+        .iter()
+        .filter_map(|segment| match segment {
+            PathSegment::Dynamic(name) => Some(json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" },
+            })),
+            PathSegment::Static(_) => None,
+        })
+        .collect();
+
+    let mut response = Map::new();
+    response.insert(
+        "description".to_string(),
+        Value::String(format!("Mocked {} response", route.response.meta.status())),
+    );
+
+    let headers: Map<String, Value> = route
+        .response
+        .meta
+        .headers
+        .keys()
+        .filter(|name| !name.eq_ignore_ascii_case("content-type"))
+        .map(|name| (name.clone(), json!({ "schema": { "type": "string" } })))
+        .collect();
+    if !headers.is_empty() {
+        response.insert("headers".to_string(), Value::Object(headers));
+    }
+
+    if !route.response.body.is_empty() {
+        let content_type = effective_content_type(route);
+        response.insert(
+            "content".to_string(),
+            json!({ content_type.clone(): { "example": example(&content_type, &String::from_utf8_lossy(&route.response.body)) } }),
+        );
+    }
+
+    let mut operation = Map::new();
+    if !parameters.is_empty() {
+        operation.insert("parameters".to_string(), Value::Array(parameters));
+    }
+    operation.insert(
+        "responses".to_string(),
+        json!({ route.response.meta.status().to_string(): response }),
+    );
+
+    Value::Object(operation)
+}
+
+/// Content-Type as served, honoring an override from the frontmatter headers
+fn effective_content_type(route: &Route) -> String {
+    route
+        .response
+        .meta
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .and_then(|(_, value)| value.first())
+        .map_or_else(|| route.content_type.clone(), str::to_string)
+}
+
+/// Embed JSON bodies as structured examples, everything else as a string
+fn example(content_type: &str, body: &str) -> Value {
+    if content_type.contains("json")
+        && let Ok(value) = serde_json::from_str(body)
+    {
+        return value;
+    }
+
+    Value::String(body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scan_directory;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_paths_and_parameters() {
+        let temp_dir = TempDir::new().unwrap();
+        let user_dir = temp_dir.path().join("users").join("[id]");
+        fs::create_dir_all(&user_dir).unwrap();
+        fs::write(user_dir.join("GET.json"), r#"{"id": 1}"#).unwrap();
+        fs::write(user_dir.join("DELETE.txt"), "---\nstatus: 204\n---\n").unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        let document = generate(&routes);
+
+        let item = &document["paths"]["/users/{id}"];
+        assert_eq!(item["get"]["parameters"][0]["name"], "id");
+        assert_eq!(
+            item["get"]["responses"]["200"]["content"]["application/json"]["example"]["id"],
+            1
+        );
+        assert!(item["delete"]["responses"]["204"].get("content").is_none());
+    }
+
+    #[test]
+    fn test_content_type_override_and_headers() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("GET.json"),
+            r#"---
+status: 201
+headers:
+  Content-Type: text/plain
+  X-Custom: value
+---
+plain body"#,
+        )
+        .unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        let document = generate(&routes);
+
+        let response = &document["paths"]["/"]["get"]["responses"]["201"];
+        assert_eq!(response["content"]["text/plain"]["example"], "plain body");
+        assert!(response["headers"].get("X-Custom").is_some());
+        assert!(response["headers"].get("Content-Type").is_none());
+    }
+}
@@ -0,0 +1,221 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! WebAssembly plugins transforming or generating responses (`plugin:`
+//! frontmatter).
+//!
+//! A plugin is a WASI preview 1 command module, e.g. built for
+//! `wasm32-wasip1`. For every request it reads a JSON document from stdin
+//! with the `request` (as scripts see it) and the `response` the route
+//! would send, and writes the response to send to stdout, following the
+//! rules of scripts: an object with `status`, `headers` and `body`, parts
+//! left out staying as they are, or just the body. Writing nothing keeps
+//! the response.
+//!
+//! Plugins get no file system, network or environment access, and run with
+//! limited fuel and memory. Modules are compiled when the route is scanned
+//! and reloaded with it whenever the `.wasm` file changes.
+
+use crate::routes::Route;
+use crate::scripting::{self, ScriptRequest, ScriptResponse};
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+use wasmi::{Config, Engine, Linker, Module, StoreLimits, StoreLimitsBuilder};
+use wasmi_wasi::wasi_common::pipe::{ReadPipe, WritePipe};
+use wasmi_wasi::{WasiCtx, WasiCtxBuilder};
+
+/// Instructions, roughly, a plugin may run per request
+const MAX_FUEL: u64 = 100_000_000;
+
+/// Linear memory a plugin may grow to
+const MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    Engine::new(&config)
+});
+
+/// Compiled module of a plugin
+#[derive(Debug)]
+pub struct Plugin(Module);
+
+/// State of a plugin instance
+struct Store {
+    wasi: WasiCtx,
+    limits: StoreLimits,
+}
+
+/// Read and compile a plugin module (`.wasm`, or `.wat` text)
+pub fn load(path: &Path) -> Result<Plugin> {
+    let wasm =
+        fs::read(path).with_context(|| format!("Failed to read plugin: {}", path.display()))?;
+    Ok(Plugin(Module::new(&ENGINE, wasm)?))
+}
+
+/// Run the plugin of a route for a request
+pub async fn run(
+    plugin: Arc<Plugin>,
+    request: &ScriptRequest,
+    route: &Route,
+) -> Result<ScriptResponse> {
+    let input = json!({
+        "request": request.to_value(),
+        "response": response_value(route),
+    });
+    tokio::task::spawn_blocking(move || plugin.call(input.to_string().into_bytes(), MAX_FUEL))
+        .await?
+}
+
+/// The response a route would send, as plugins see it
+fn response_value(route: &Route) -> Value {
+    let meta = &route.response.meta;
+    let mut headers = serde_json::to_value(&meta.headers).unwrap_or_default();
+    if let Value::Object(headers) = &mut headers {
+        headers.insert(
+            "content-type".to_string(),
+            Value::String(route.content_type.clone()),
+        );
+    }
+    json!({
+        "status": meta.status(),
+        "headers": headers,
+        "body": String::from_utf8_lossy(&route.response.body),
+    })
+}
+
+impl Plugin {
+    fn call(&self, input: Vec<u8>, fuel: u64) -> Result<ScriptResponse> {
+        let stdout = WritePipe::new_in_memory();
+        let stderr = WritePipe::new_in_memory();
+        let wasi = WasiCtxBuilder::new()
+            .stdin(Box::new(ReadPipe::from(input)))
+            .stdout(Box::new(stdout.clone()))
+            .stderr(Box::new(stderr.clone()))
+            .build();
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = wasmi::Store::new(&ENGINE, Store { wasi, limits });
+        store.limiter(|store| &mut store.limits);
+        store.set_fuel(fuel)?;
+
+        let mut linker = Linker::new(&ENGINE);
+        wasmi_wasi::add_to_linker(&mut linker, |store: &mut Store| &mut store.wasi)?;
+        let instance = linker.instantiate_and_start(&mut store, &self.0)?;
+        let start = instance
+            .get_typed_func::<(), ()>(&store, "_start")
+            .context("plugin has no `_start` function, expected a WASI command module")?;
+        let result = start.call(&mut store, ());
+        // The pipes are only ours again once the instance is gone
+        drop(store);
+
+        match result {
+            Ok(()) => {}
+            Err(e) if e.i32_exit_status() == Some(0) => {}
+            Err(e) => {
+                let stderr = String::from_utf8_lossy(&contents(stderr)).into_owned();
+                let stderr = stderr.trim();
+                if stderr.is_empty() {
+                    bail!("plugin failed: {}", e);
+                }
+                bail!("plugin failed: {}: {}", e, stderr);
+            }
+        }
+
+        let output = contents(stdout);
+        if output.trim_ascii().is_empty() {
+            return Ok(ScriptResponse::default());
+        }
+        let value = serde_json::from_slice(&output).context("plugin output is not JSON")?;
+        scripting::response(value)
+    }
+}
+
+fn contents(pipe: WritePipe<Cursor<Vec<u8>>>) -> Vec<u8> {
+    pipe.try_into_inner()
+        .map(Cursor::into_inner)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Command module writing the data segment at offset 16 to stdout, then
+    /// exiting with `status`
+    fn writer(output: &str, status: i32) -> String {
+        format!(
+            r#"(module
+              (import "wasi_snapshot_preview1" "fd_write"
+                (func $fd_write (param i32 i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 0) "\10\00\00\00{len}\00\00\00")
+              (data (i32.const 16) "{output}")
+              (func (export "_start")
+                (drop (call $fd_write (i32.const {fd}) (i32.const 0) (i32.const 1) (i32.const 8)))
+                (call $proc_exit (i32.const {status}))))"#,
+            len = format_args!("\\{:02x}", output.len()),
+            output = output.replace('"', "\\\""),
+            fd = if status == 0 { 1 } else { 2 },
+            status = status,
+        )
+    }
+
+    /// Command module copying stdin to stdout
+    const ECHO: &str = r#"(module
+      (import "wasi_snapshot_preview1" "fd_read"
+        (func $fd_read (param i32 i32 i32 i32) (result i32)))
+      (import "wasi_snapshot_preview1" "fd_write"
+        (func $fd_write (param i32 i32 i32 i32) (result i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "\10\00\00\00\00\f0\00\00")
+      (func (export "_start")
+        (drop (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+        (i32.store (i32.const 4) (i32.load (i32.const 8)))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))"#;
+
+    fn call(module: &str, input: &str) -> Result<ScriptResponse> {
+        Plugin(Module::new(&ENGINE, module).unwrap()).call(input.as_bytes().to_vec(), 1_000_000)
+    }
+
+    #[test]
+    fn test_output() {
+        let response = call(&writer(r#"{"status": 201, "body": {"ok": true}}"#, 0), "").unwrap();
+        assert_eq!(
+            response,
+            scripting::response(json!({"status": 201, "body": {"ok": true}})).unwrap()
+        );
+
+        let response = call(ECHO, r#"{"headers": {"X-Plugin": "echo"}}"#).unwrap();
+        assert_eq!(
+            response,
+            scripting::response(json!({"headers": {"X-Plugin": "echo"}})).unwrap()
+        );
+        assert_eq!(call(&writer("", 0), "").unwrap(), ScriptResponse::default());
+    }
+
+    #[test]
+    fn test_failures() {
+        let error = call(&writer("bad input", 3), "").unwrap_err();
+        assert!(format!("{:#}", error).contains("bad input"));
+        assert!(call(&writer("not json", 0), "").is_err());
+        assert!(call("(module (func (export \"_start\") (loop (br 0))))", "").is_err());
+        assert!(call("(module)", "").is_err());
+        assert!(
+            call(
+                "(module (import \"env\" \"open\" (func)) (func (export \"_start\")))",
+                ""
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,68 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Ports the servers actually listen on (`--print-ports`, `--port-file`).
+//!
+//! With port 0 the OS picks a free port; test harnesses learn it from a JSON
+//! line on stdout or a file, both written once every listener is bound.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct BoundPorts {
+    pub http: Option<u16>,
+    pub https: Option<u16>,
+    pub http3: Option<u16>,
+    pub grpc: Option<u16>,
+}
+
+impl BoundPorts {
+    /// Print the ports to stdout and/or write them to `port_file`.
+    ///
+    /// The file is written to a temporary name and renamed, so it never
+    /// exists half-written for a harness polling for it.
+    pub fn announce(&self, print: bool, port_file: Option<&Path>) -> Result<()> {
+        if print {
+            println!("{}", serde_json::json!({ "listening": self }));
+        }
+        if let Some(port_file) = port_file {
+            let temp_file = port_file.with_extension("tmp");
+            fs::write(&temp_file, serde_json::to_string(self)? + "\n")
+                .and_then(|()| fs::rename(&temp_file, port_file))
+                .with_context(|| format!("Failed to write port file: {}", port_file.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_port_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let port_file = temp_dir.path().join("ports.json");
+        let ports = BoundPorts {
+            http: Some(41234),
+            grpc: Some(50051),
+            ..BoundPorts::default()
+        };
+
+        ports.announce(false, Some(&port_file)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&port_file).unwrap(),
+            "{\"http\":41234,\"https\":null,\"http3\":null,\"grpc\":50051}\n"
+        );
+        assert!(!temp_dir.path().join("ports.tmp").exists());
+    }
+}
@@ -0,0 +1,206 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Responses advancing with every request (`progression:` frontmatter),
+//! for job and status endpoints that clients poll.
+//!
+//! Each step answers `times` requests and overrides the route's `status`,
+//! `delay`, `headers` or `body` while active. Requests are counted per
+//! method and request path, so `/jobs/1` and `/jobs/2` progress on their
+//! own. After the last step the route stays in it, unless the progression
+//! repeats. Counters survive reloads and are reset through
+//! `/__blendwerk/progression`.
+
+use crate::delay::Delay;
+use crate::frontmatter::HeaderValues;
+use crate::routes::Route;
+use bytes::Bytes;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawProgression")]
+pub struct Progression {
+    steps: Vec<Step>,
+    repeat: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProgression {
+    steps: Vec<Step>,
+    #[serde(default)]
+    repeat: bool,
+}
+
+impl TryFrom<RawProgression> for Progression {
+    type Error = String;
+
+    fn try_from(raw: RawProgression) -> Result<Self, Self::Error> {
+        if raw.steps.is_empty() {
+            return Err("progression needs at least one step".to_string());
+        }
+        if raw.steps.iter().any(|step| step.times == 0) {
+            return Err("progression steps need `times` of at least 1".to_string());
+        }
+        Ok(Self {
+            steps: raw.steps,
+            repeat: raw.repeat,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// Number of requests answered by this step
+    #[serde(default = "default_times")]
+    times: u64,
+    #[serde(default)]
+    status: Option<u16>,
+    #[serde(default)]
+    delay: Option<Delay>,
+    /// Added to (or replacing) the route's headers
+    #[serde(default)]
+    headers: HashMap<String, HeaderValues>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+fn default_times() -> u64 {
+    1
+}
+
+impl Progression {
+    /// The step answering the request after `count` earlier ones
+    pub fn step_at(&self, count: u64) -> &Step {
+        let total: u64 = self.steps.iter().map(|step| step.times).sum();
+        let mut offset = if self.repeat { count % total } else { count };
+
+        for step in &self.steps {
+            if offset < step.times {
+                return step;
+            }
+            offset -= step.times;
+        }
+        self.steps.last().expect("progressions have steps")
+    }
+}
+
+impl Step {
+    /// Replace the parts of the route's response the step overrides
+    pub fn apply(&self, route: &mut Route) {
+        let meta = &mut route.response.meta;
+        if let Some(status) = self.status {
+            meta.status = Some(status);
+        }
+        if let Some(delay) = &self.delay {
+            meta.delay = delay.clone();
+        }
+        meta.headers.extend(self.headers.clone());
+        if let Some(body) = &self.body {
+            meta.chunks = None;
+            meta.events = None;
+            meta.randomize = None;
+            route.response.body = Bytes::from(body.clone());
+            route.body_file = None;
+        }
+    }
+}
+
+/// Requests counted so far, by `METHOD /path`
+#[derive(Debug, Clone, Default)]
+pub struct Progressions(Arc<Mutex<HashMap<String, u64>>>);
+
+impl Progressions {
+    /// Count a request, returning the number of requests before it
+    pub fn advance(&self, method: &str, path: &str) -> u64 {
+        let mut counts = self.0.lock().unwrap();
+        let count = counts.entry(format!("{} {}", method, path)).or_default();
+        *count += 1;
+        *count - 1
+    }
+
+    /// Start the progressions of `path` (all paths with `None`) over,
+    /// returning how many were reset
+    pub fn reset(&self, path: Option<&str>) -> usize {
+        let mut counts = self.0.lock().unwrap();
+        let before = counts.len();
+        match path {
+            Some(path) => counts.retain(|key, _| key.split_once(' ').map(|(_, p)| p) != Some(path)),
+            None => counts.clear(),
+        }
+        before - counts.len()
+    }
+
+    /// Requests counted per `METHOD /path`
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, count)| (key.clone(), *count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progression(yaml: &str) -> Progression {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const JOB: &str = "
+steps:
+  - times: 3
+    status: 202
+    body: '{\"status\": \"pending\"}'
+  - body: '{\"status\": \"done\"}'
+";
+
+    #[test]
+    fn test_steps() {
+        let progression = progression(JOB);
+        let status_at = |count| progression.step_at(count).status;
+        assert_eq!(status_at(0), Some(202));
+        assert_eq!(status_at(2), Some(202));
+        assert_eq!(status_at(3), None);
+        assert_eq!(status_at(100), None);
+
+        let repeating = self::progression(&format!("{}repeat: true", JOB));
+        assert_eq!(repeating.step_at(4).status, Some(202));
+        assert_eq!(repeating.step_at(7).status, None);
+    }
+
+    #[test]
+    fn test_invalid_progressions() {
+        let parse = |yaml: &str| serde_yaml::from_str::<Progression>(yaml);
+        assert!(parse("steps: []").is_err());
+        assert!(parse("steps: [{times: 0}]").is_err());
+        assert!(parse("steps: [{stauts: 500}]").is_err());
+        assert!(parse("steps: [{status: 500}]").is_ok());
+    }
+
+    #[test]
+    fn test_counters() {
+        let progressions = Progressions::default();
+        assert_eq!(progressions.advance("GET", "/jobs/1"), 0);
+        assert_eq!(progressions.advance("GET", "/jobs/1"), 1);
+        assert_eq!(progressions.advance("GET", "/jobs/2"), 0);
+        assert_eq!(progressions.advance("DELETE", "/jobs/1"), 0);
+
+        assert_eq!(progressions.reset(Some("/jobs/1")), 2);
+        assert_eq!(progressions.advance("GET", "/jobs/1"), 0);
+        assert_eq!(progressions.snapshot().get("GET /jobs/2"), Some(&1));
+        assert_eq!(progressions.reset(None), 2);
+        assert!(progressions.snapshot().is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Turn request log entries back into route files (`blendwerk promote` and
+//! `POST /__blendwerk/promote`).
+//!
+//! The logged response becomes the route file for the literal request path
+//! and method, written like a HAR import. Entries whose body was truncated
+//! or isn't text can't be replayed and are refused; redacted headers are
+//! left out.
+
+use crate::fixture_changes;
+use crate::har::{self, CapturedResponse};
+use crate::request_logger::LogCompression;
+use crate::routes::HttpMethod;
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use flate2::read::MultiGzDecoder;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// The parts of a request log entry needed to recreate its response
+#[derive(Debug, Deserialize)]
+pub struct LogEntry {
+    #[serde(default)]
+    metadata: Option<EntryMetadata>,
+    request: EntryRequest,
+    response: EntryResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct EntryMetadata {
+    request_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EntryRequest {
+    method: String,
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EntryResponse {
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    body_encoding: Option<String>,
+    #[serde(default)]
+    body_truncated: bool,
+}
+
+/// Promote the entry of `log_file` to a route file below `target_dir`.
+///
+/// Files holding several entries (batches, NDJSON) need `request_id` to pick
+/// one.
+pub fn promote(
+    log_file: &Path,
+    target_dir: &Path,
+    request_id: Option<&str>,
+    force: bool,
+) -> Result<PathBuf> {
+    let entries = read_entries(log_file)?;
+    let entry = match request_id {
+        Some(id) => entries
+            .into_iter()
+            .find(|entry| entry.metadata.as_ref().is_some_and(|m| m.request_id == id))
+            .with_context(|| {
+                format!("No entry with request ID {} in {}", id, log_file.display())
+            })?,
+        None if entries.len() == 1 => entries.into_iter().next().unwrap(),
+        None => bail!(
+            "{} holds {} entries, pick one with --request-id",
+            log_file.display(),
+            entries.len()
+        ),
+    };
+    promote_entry(&entry, target_dir, force)
+}
+
+/// Write the route file answering like `entry` did, returning its path
+pub fn promote_entry(entry: &LogEntry, target_dir: &Path, force: bool) -> Result<PathBuf> {
+    let method = HttpMethod::from_str(&entry.request.method)
+        .with_context(|| format!("Unsupported method {}", entry.request.method))?;
+    let response = &entry.response;
+    if response.body_truncated {
+        bail!("The response body was truncated by --request-log-max-body");
+    }
+
+    let body = match response.body_encoding.as_deref() {
+        Some("base64") => {
+            let bytes = STANDARD
+                .decode(response.body.trim())
+                .context("Invalid base64 response body")?;
+            String::from_utf8(bytes).context("Binary response bodies are not supported")?
+        }
+        Some(encoding) => bail!("Unknown body encoding {}", encoding),
+        None => response.body.clone(),
+    };
+
+    let mut headers: Vec<(&str, &str)> = Vec::new();
+    for (name, value) in &response.headers {
+        if value == "<redacted>" {
+            warn!("Leaving out redacted header {}", name);
+        } else if !name.eq_ignore_ascii_case(fixture_changes::HEADER) {
+            headers.push((name, value));
+        }
+    }
+    headers.sort_unstable();
+    let mime_type = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map_or("", |(_, value)| value);
+
+    har::write_route_file(
+        target_dir,
+        &entry.request.path,
+        &method,
+        &CapturedResponse {
+            status: response.status,
+            headers,
+            mime_type,
+            body: &body,
+        },
+        force,
+    )
+}
+
+/// Read the entries of a log file in any of the request log formats,
+/// gzip- or zstd-compressed or not
+fn read_entries(log_file: &Path) -> Result<Vec<LogEntry>> {
+    let mut file = File::open(log_file)
+        .with_context(|| format!("Failed to open log file: {}", log_file.display()))?;
+    let file_name = log_file.to_string_lossy();
+    let (name, compression) = if let Some(name) = file_name.strip_suffix(".gz") {
+        (name, Some(LogCompression::Gzip))
+    } else if let Some(name) = file_name.strip_suffix(".zst") {
+        (name, Some(LogCompression::Zstd))
+    } else {
+        (file_name.as_ref(), None)
+    };
+
+    let mut content = String::new();
+    let read = match compression {
+        Some(LogCompression::Gzip) => MultiGzDecoder::new(file).read_to_string(&mut content),
+        Some(LogCompression::Zstd) => {
+            zstd::Decoder::new(file).and_then(|mut decoder| decoder.read_to_string(&mut content))
+        }
+        None => file.read_to_string(&mut content),
+    };
+    read.with_context(|| format!("Failed to read log file: {}", log_file.display()))?;
+
+    let parse_error = || format!("Failed to parse log file: {}", log_file.display());
+    if name.ends_with(".yaml") {
+        serde_yaml::Deserializer::from_str(&content)
+            .map(LogEntry::deserialize)
+            .collect::<Result<_, _>>()
+            .with_context(parse_error)
+    } else if name.ends_with(".jsonl") || name.ends_with(".ndjson") {
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .with_context(parse_error)
+    } else {
+        Ok(vec![
+            serde_json::from_str(&content).with_context(parse_error)?,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scan_directory;
+    use flate2::{Compression, write::GzEncoder};
+    use std::fs;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    const ENTRY: &str = r#"{
+  "metadata": {"timestamp": "2025-01-28T15-30-45.123456Z", "request_id": "a", "duration_ms": 1.2},
+  "request": {"method": "GET", "uri": "/api/users/42?x=1", "path": "/api/users/42", "headers": {}},
+  "response": {
+    "status": 404,
+    "headers": {
+      "content-type": "application/json",
+      "content-length": "20",
+      "set-cookie": "<redacted>",
+      "x-trace": "abc"
+    },
+    "body": "{\"error\": \"missing\"}",
+    "delay_ms": 0
+  }
+}"#;
+
+    #[test]
+    fn test_promote_json_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_file = temp_dir.path().join("entry.json");
+        let mocks = temp_dir.path().join("mocks");
+        fs::write(&log_file, ENTRY).unwrap();
+
+        let file = promote(&log_file, &mocks, None, false).unwrap();
+        assert_eq!(file, mocks.join("api/users/42/GET.json"));
+        assert!(promote(&log_file, &mocks, None, false).is_err());
+
+        let routes = scan_directory(&mocks).unwrap();
+        let meta = &routes[0].response.meta;
+        assert_eq!(meta.status(), 404);
+        assert_eq!(meta.headers.get("x-trace").unwrap(), "abc");
+        assert!(!meta.headers.contains_key("set-cookie"));
+        assert!(!meta.headers.contains_key("content-length"));
+        assert_eq!(routes[0].response.body, r#"{"error": "missing"}"#);
+    }
+
+    #[test]
+    fn test_promote_from_compressed_batch() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_file = temp_dir.path().join("2025-01-28T15-30.jsonl.gz");
+        let second = r#"{"metadata": {"request_id": "b"}, "request": {"method": "POST", "path": "/echo"}, "response": {"status": 201, "headers": {}, "body": "aGk=", "body_encoding": "base64"}}"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        writeln!(encoder, "{}", ENTRY.replace('\n', "")).unwrap();
+        writeln!(encoder, "{}", second).unwrap();
+        fs::write(&log_file, encoder.finish().unwrap()).unwrap();
+
+        let mocks = temp_dir.path().join("mocks");
+        assert!(promote(&log_file, &mocks, None, false).is_err());
+        let file = promote(&log_file, &mocks, Some("b"), false).unwrap();
+        assert_eq!(file, mocks.join("echo/POST.txt"));
+        assert_eq!(
+            fs::read_to_string(file).unwrap(),
+            "---\nstatus: 201\n---\nhi"
+        );
+
+        let log_file = temp_dir.path().join("2025-01-28T15-31.jsonl.zst");
+        fs::write(&log_file, zstd::encode_all(second.as_bytes(), 0).unwrap()).unwrap();
+        let file = promote(&log_file, &mocks, None, true).unwrap();
+        assert_eq!(file, mocks.join("echo/POST.txt"));
+    }
+
+    #[test]
+    fn test_unreplayable_bodies() {
+        let entry = |response: &str| -> LogEntry {
+            serde_json::from_str(&format!(
+                r#"{{"request": {{"method": "GET", "path": "/"}}, "response": {}}}"#,
+                response
+            ))
+            .unwrap()
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let truncated = entry(r#"{"status": 200, "body": "ab", "body_truncated": true}"#);
+        assert!(promote_entry(&truncated, temp_dir.path(), false).is_err());
+        let binary = entry(r#"{"status": 200, "body": "/wCA", "body_encoding": "base64"}"#);
+        assert!(promote_entry(&binary, temp_dir.path(), false).is_err());
+    }
+}
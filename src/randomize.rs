@@ -0,0 +1,272 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Per-request random values in JSON bodies (`randomize:` frontmatter).
+//!
+//! Each entry maps a JSONPath to a generator:
+//!
+//! ```yaml
+//! randomize:
+//!   $.id: uuid
+//!   $.items[*].price: { int: [1, 100] }
+//!   $.status: { choice: [active, suspended] }
+//! ```
+//!
+//! Paths support `.key`, `['key']`, `[index]` and the `*` wildcard; paths
+//! that don't exist in the body are skipped.
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "BTreeMap<String, Generator>")]
+pub struct Randomize(Vec<(JsonPath, Generator)>);
+
+impl TryFrom<BTreeMap<String, Generator>> for Randomize {
+    type Error = String;
+
+    fn try_from(entries: BTreeMap<String, Generator>) -> Result<Self, Self::Error> {
+        entries
+            .into_iter()
+            .map(|(path, generator)| Ok((JsonPath::parse(&path)?, generator)))
+            .collect::<Result<_, String>>()
+            .map(Self)
+    }
+}
+
+impl Randomize {
+    /// Apply the generators to a copy of `body`
+    pub fn apply(&self, body: &Value) -> Value {
+        let mut body = body.clone();
+        let mut rng = rand::rng();
+        for (path, generator) in &self.0 {
+            path.for_each_mut(&mut body, &mut |value| {
+                *value = generator.generate(&mut rng);
+            });
+        }
+        body
+    }
+}
+
+/// Source of the random values for one path
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "RawGenerator")]
+pub enum Generator {
+    /// Random version 4 UUID
+    Uuid,
+    /// Integer between both bounds, inclusive
+    Int(i64, i64),
+    /// One of the values
+    Choice(Vec<Value>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawGenerator {
+    Name(String),
+    Int { int: [i64; 2] },
+    Choice { choice: Vec<Value> },
+}
+
+impl TryFrom<RawGenerator> for Generator {
+    type Error = String;
+
+    fn try_from(raw: RawGenerator) -> Result<Self, Self::Error> {
+        match raw {
+            RawGenerator::Name(name) if name == "uuid" => Ok(Self::Uuid),
+            RawGenerator::Name(name) => Err(format!("unknown generator '{}'", name)),
+            RawGenerator::Int { int: [min, max] } if min <= max => Ok(Self::Int(min, max)),
+            RawGenerator::Int { int: [min, max] } => {
+                Err(format!("int range [{}, {}] is empty", min, max))
+            }
+            RawGenerator::Choice { choice } if choice.is_empty() => {
+                Err("choice needs at least one value".to_string())
+            }
+            RawGenerator::Choice { choice } => Ok(Self::Choice(choice)),
+        }
+    }
+}
+
+impl Generator {
+    fn generate(&self, rng: &mut impl Rng) -> Value {
+        match self {
+            Self::Uuid => {
+                let mut bytes: [u8; 16] = rng.random();
+                bytes[6] = (bytes[6] & 0x0f) | 0x40;
+                bytes[8] = (bytes[8] & 0x3f) | 0x80;
+                let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                Value::String(format!(
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                ))
+            }
+            Self::Int(min, max) => Value::from(rng.random_range(*min..=*max)),
+            Self::Choice(values) => values.choose(rng).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// The JSONPath subset accepted by `randomize:`
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath(Vec<Segment>);
+
+impl JsonPath {
+    fn parse(path: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid JSONPath '{}'", path);
+        let mut rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+        let mut segments = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                let segment = match &after_dot[..end] {
+                    "" => return Err(invalid()),
+                    "*" => Segment::Wildcard,
+                    key => Segment::Key(key.to_string()),
+                };
+                segments.push(segment);
+                rest = &after_dot[end..];
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket.find(']').ok_or_else(invalid)?;
+                let inner = after_bracket[..end].trim();
+                let segment = if inner == "*" {
+                    Segment::Wildcard
+                } else if let Ok(index) = inner.parse() {
+                    Segment::Index(index)
+                } else {
+                    let key = inner
+                        .strip_prefix('\'')
+                        .and_then(|key| key.strip_suffix('\''))
+                        .or_else(|| {
+                            inner
+                                .strip_prefix('"')
+                                .and_then(|key| key.strip_suffix('"'))
+                        })
+                        .ok_or_else(invalid)?;
+                    Segment::Key(key.to_string())
+                };
+                segments.push(segment);
+                rest = &after_bracket[end + 1..];
+            } else {
+                return Err(invalid());
+            }
+        }
+
+        if segments.is_empty() {
+            return Err(format!("JSONPath '{}' would replace the whole body", path));
+        }
+        Ok(Self(segments))
+    }
+
+    /// Call `f` on every value the path selects
+    fn for_each_mut(&self, value: &mut Value, f: &mut impl FnMut(&mut Value)) {
+        visit(&self.0, value, f);
+    }
+}
+
+fn visit(segments: &[Segment], value: &mut Value, f: &mut impl FnMut(&mut Value)) {
+    let Some((segment, rest)) = segments.split_first() else {
+        f(value);
+        return;
+    };
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(object)) => {
+            if let Some(child) = object.get_mut(key) {
+                visit(rest, child, f);
+            }
+        }
+        (Segment::Index(index), Value::Array(array)) => {
+            if let Some(child) = array.get_mut(*index) {
+                visit(rest, child, f);
+            }
+        }
+        (Segment::Wildcard, Value::Array(array)) => {
+            for child in array {
+                visit(rest, child, f);
+            }
+        }
+        (Segment::Wildcard, Value::Object(object)) => {
+            for child in object.values_mut() {
+                visit(rest, child, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn randomize(yaml: &str) -> Randomize {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_generators() {
+        let randomize = randomize(
+            "$.id: uuid\n\
+             $.items[*].price: { int: [1, 3] }\n\
+             $['meta'].status: { choice: [active] }\n\
+             $.missing.field: uuid",
+        );
+        let body = json!({
+            "id": "fixed",
+            "items": [{"price": 0}, {"price": 0}],
+            "meta": {"status": "draft"},
+        });
+
+        let first = randomize.apply(&body);
+        let id = first["id"].as_str().unwrap();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        for item in first["items"].as_array().unwrap() {
+            assert!((1..=3).contains(&item["price"].as_i64().unwrap()));
+        }
+        assert_eq!(first["meta"]["status"], "active");
+        assert!(first.get("missing").is_none());
+
+        assert_ne!(randomize.apply(&body)["id"], first["id"]);
+        assert_eq!(body["id"], "fixed");
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        let parse = |yaml: &str| serde_yaml::from_str::<Randomize>(yaml);
+        assert!(parse("$.count: { int: [5, 1] }").is_err());
+        assert!(parse("$.status: { choice: [] }").is_err());
+        assert!(parse("$.id: guid").is_err());
+        assert!(parse("id: uuid").is_err());
+        assert!(parse("$: uuid").is_err());
+        assert!(parse("$.items[0: uuid").is_err());
+        assert_eq!(
+            JsonPath::parse("$.items[0]['a b'].*").unwrap(),
+            JsonPath(vec![
+                Segment::Key("items".to_string()),
+                Segment::Index(0),
+                Segment::Key("a b".to_string()),
+                Segment::Wildcard,
+            ])
+        );
+    }
+}
@@ -0,0 +1,286 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Notifications about hot reloads (`/__blendwerk/events`,
+//! `--reload-webhook`).
+//!
+//! Every reload produces an event listing the routes it added, removed and
+//! changed. It is streamed to all clients of the server-sent events endpoint
+//! and POSTed as JSON to the webhook, so e.g. frontend dev servers can
+//! refresh when mocks change.
+
+use crate::routes::Route;
+use crate::server::ShutdownSignal;
+use crate::sse::SseEvent;
+use anyhow::{Context, Result};
+use axum::body::Body;
+use axum::http::header::{CONTENT_TYPE, HOST};
+use axum::http::{Request, Uri};
+use bytes::Bytes;
+use futures_util::stream;
+use http_body_util::Full;
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+/// Idle streams get a comment this often, so proxies keep them open
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Routes a reload added, removed or changed, each as `GET /users/:id`
+/// (prefixed with the virtual host, if any)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReloadEvent {
+    /// Number of routes after the reload
+    pub routes: usize,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl ReloadEvent {
+    pub fn between(old: &[Route], new: &[Route]) -> Self {
+        let old_routes: HashMap<String, &Route> =
+            old.iter().map(|route| (name(route), route)).collect();
+        let new_routes: HashMap<String, &Route> =
+            new.iter().map(|route| (name(route), route)).collect();
+
+        let mut event = Self {
+            routes: new.len(),
+            ..Self::default()
+        };
+        for (name, route) in &new_routes {
+            match old_routes.get(name) {
+                None => event.added.push(name.clone()),
+                Some(previous)
+                    if previous.source != route.source
+                        || previous.modified != route.modified
+                        || previous.response.body != route.response.body =>
+                {
+                    event.changed.push(name.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        event.removed = old_routes
+            .into_keys()
+            .filter(|name| !new_routes.contains_key(name))
+            .collect();
+
+        event.added.sort();
+        event.removed.sort();
+        event.changed.sort();
+        event
+    }
+}
+
+fn name(route: &Route) -> String {
+    format!(
+        "{} {}{}",
+        route.method.as_str(),
+        route.host.as_deref().unwrap_or_default(),
+        route.display_path()
+    )
+}
+
+/// Fans reload events out to the event stream clients and the webhook
+#[derive(Debug)]
+pub struct ReloadEvents {
+    sender: broadcast::Sender<ReloadEvent>,
+    webhook: Option<Uri>,
+}
+
+impl Default for ReloadEvents {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl ReloadEvents {
+    pub fn new(webhook: Option<Uri>) -> Self {
+        let (sender, _) = broadcast::channel(16);
+        Self { sender, webhook }
+    }
+
+    /// Send `event` to all connected clients and, in the background, to the
+    /// webhook
+    pub fn publish(&self, event: ReloadEvent) {
+        if let Some(webhook) = &self.webhook {
+            let webhook = webhook.clone();
+            let body = serde_json::to_string(&event).unwrap_or_default();
+            tokio::spawn(async move {
+                match tokio::time::timeout(WEBHOOK_TIMEOUT, post(&webhook, body)).await {
+                    Ok(Ok(status)) => debug!("Reload webhook {} answered {}", webhook, status),
+                    Ok(Err(e)) => warn!("Reload webhook {} failed: {:#}", webhook, e),
+                    Err(_) => warn!("Reload webhook {} timed out", webhook),
+                }
+            });
+        }
+        // Without connected clients there is nobody to miss the event
+        let _ = self.sender.send(event);
+    }
+
+    /// `text/event-stream` body with an `event: reload` per reload, open
+    /// until the server shuts down
+    pub fn stream(&self, shutdown: ShutdownSignal) -> Body {
+        let events = stream::unfold(
+            (self.sender.subscribe(), shutdown),
+            |(mut receiver, mut shutdown)| async move {
+                let chunk = tokio::select! {
+                    received = receiver.recv() => match received {
+                        Ok(event) => SseEvent {
+                            id: None,
+                            event: Some("reload".to_string()),
+                            data: serde_json::to_string(&event).unwrap_or_default(),
+                            retry: None,
+                            delay: Default::default(),
+                        }
+                        .render(),
+                        Err(RecvError::Lagged(missed)) => format!(": missed {} reloads\n\n", missed),
+                        Err(RecvError::Closed) => return None,
+                    },
+                    _ = tokio::time::sleep(KEEP_ALIVE_INTERVAL) => ": keep-alive\n\n".to_string(),
+                    _ = shutdown.wait_for(|stopped| *stopped) => return None,
+                };
+                Some((
+                    Ok::<_, Infallible>(Bytes::from(chunk)),
+                    (receiver, shutdown),
+                ))
+            },
+        );
+        Body::from_stream(events)
+    }
+}
+
+/// Parse a `--reload-webhook` URL; only plain HTTP is supported
+pub fn parse_webhook(input: &str) -> Result<Uri, String> {
+    let uri: Uri = input.parse().map_err(|e| format!("{}", e))?;
+    if uri.scheme_str() != Some("http") || uri.host().is_none() {
+        return Err(format!("expected an http:// URL, got `{}`", input));
+    }
+    Ok(uri)
+}
+
+async fn post(webhook: &Uri, body: String) -> Result<u16> {
+    let host = webhook.host().context("URL has no host")?;
+    let port = webhook.port_u16().unwrap_or(80);
+    let stream = TcpStream::connect((host.trim_start_matches('[').trim_end_matches(']'), port))
+        .await
+        .context("Failed to connect")?;
+
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+
+    let authority = webhook.authority().map(|a| a.as_str()).unwrap_or(host);
+    let path = webhook.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let request = Request::post(path)
+        .header(HOST, authority)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)))?;
+    let response = sender.send_request(request).await?;
+    Ok(response.status().as_u16())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scan_directory;
+    use http_body_util::BodyExt;
+    use std::fs;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::watch;
+
+    #[test]
+    fn test_between() {
+        let temp_dir = TempDir::new().unwrap();
+        for dir in ["users", "orders", "health"] {
+            fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+            fs::write(temp_dir.path().join(dir).join("GET.json"), "{}").unwrap();
+        }
+        let old = scan_directory(temp_dir.path()).unwrap();
+
+        fs::remove_dir_all(temp_dir.path().join("orders")).unwrap();
+        fs::write(temp_dir.path().join("users/GET.json"), "[]").unwrap();
+        fs::write(temp_dir.path().join("users/POST.json"), "{}").unwrap();
+        let new = scan_directory(temp_dir.path()).unwrap();
+
+        assert_eq!(
+            ReloadEvent::between(&old, &new),
+            ReloadEvent {
+                routes: 3,
+                added: vec!["POST /users".to_string()],
+                removed: vec!["GET /orders".to_string()],
+                changed: vec!["GET /users".to_string()],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_and_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let webhook = parse_webhook(&format!(
+            "http://{}/reload?source=blendwerk",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let events = ReloadEvents::new(Some(webhook));
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut stream = events.stream(shutdown_rx).into_data_stream();
+
+        let event = ReloadEvent {
+            routes: 1,
+            added: vec!["GET /users".to_string()],
+            ..ReloadEvent::default()
+        };
+        events.publish(event);
+
+        let chunk = stream.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(
+            chunk,
+            "event: reload\ndata: {\"routes\":1,\"added\":[\"GET /users\"],\"removed\":[],\"changed\":[]}\n\n"
+        );
+
+        let (mut connection, _) = listener.accept().await.unwrap();
+        let mut request = String::new();
+        while !request.ends_with('}') {
+            let mut buffer = [0; 4096];
+            let len = connection.read(&mut buffer).await.unwrap();
+            request.push_str(&String::from_utf8_lossy(&buffer[..len]));
+        }
+        assert!(request.starts_with("POST /reload?source=blendwerk HTTP/1.1\r\n"));
+        assert!(
+            request.ends_with(
+                "{\"routes\":1,\"added\":[\"GET /users\"],\"removed\":[],\"changed\":[]}"
+            )
+        );
+        connection
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .await
+            .unwrap();
+
+        shutdown_tx.send(true).unwrap();
+        assert!(stream.frame().await.is_none());
+    }
+
+    #[test]
+    fn test_parse_webhook() {
+        assert!(parse_webhook("http://localhost:5173/__mocks_changed").is_ok());
+        assert!(parse_webhook("https://example.test/hook").is_err());
+        assert!(parse_webhook("/hook").is_err());
+    }
+}
@@ -0,0 +1,410 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Mock sets fetched from a git repository or an archive URL.
+//!
+//! The source is fetched into a staging directory first and then synced
+//! into the served directory file by file, so a re-fetch only touches what
+//! changed and the regular hot reload picks it up. A failed re-fetch keeps
+//! the previous mocks.
+
+use crate::archive::{self, Format};
+use crate::http_client;
+use crate::server::ShutdownSignal;
+use anyhow::{Context, Result, anyhow, bail};
+use axum::http::{HeaderMap, Method, Uri, header::LOCATION};
+use bytes::Bytes;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Maximum time a single fetch may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Redirects followed when downloading an archive
+const MAX_REDIRECTS: usize = 5;
+
+/// Largest archive downloaded, so a wrong URL can't fill memory
+const MAX_ARCHIVE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Directory in the checkout holding git's own data, never served
+const GIT_DIR: &str = ".git";
+
+/// Where mocks are fetched from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteSource {
+    /// Repository cloned with the `git` command, optionally at a branch or
+    /// tag given as URL fragment (`#main`)
+    Git {
+        url: String,
+        reference: Option<String>,
+    },
+    /// `.zip` or `.tar.gz` archive downloaded via HTTP(S)
+    Archive(Uri),
+}
+
+impl RemoteSource {
+    /// Recognize a remote source; returns `None` for local paths.
+    ///
+    /// `git+https://`, `ssh://`, `git://`, `git@host:path` and HTTP(S) URLs
+    /// ending in `.git` are git repositories, other HTTP(S) URLs archives.
+    pub fn parse(input: &str) -> Option<Self> {
+        let git = |url: &str| {
+            let (url, reference) = match url.split_once('#') {
+                Some((url, reference)) => (url, Some(reference.to_string())),
+                None => (url, None),
+            };
+            Some(Self::Git {
+                url: url.to_string(),
+                reference: reference.filter(|reference| !reference.is_empty()),
+            })
+        };
+
+        if let Some(url) = input.strip_prefix("git+") {
+            return git(url);
+        }
+        if ["ssh://", "git://", "git@"]
+            .iter()
+            .any(|prefix| input.starts_with(prefix))
+        {
+            return git(input);
+        }
+        if !["http://", "https://"]
+            .iter()
+            .any(|prefix| input.starts_with(prefix))
+        {
+            return None;
+        }
+        if input
+            .split('#')
+            .next()
+            .is_some_and(|url| url.ends_with(".git"))
+        {
+            return git(input);
+        }
+        input.parse().ok().map(Self::Archive)
+    }
+}
+
+impl fmt::Display for RemoteSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Git {
+                url,
+                reference: Some(reference),
+            } => write!(f, "{} ({})", url, reference),
+            Self::Git { url, .. } => write!(f, "{}", url),
+            Self::Archive(uri) => write!(f, "{}", uri),
+        }
+    }
+}
+
+/// Local copy of a remote mock source, deleted on drop
+#[derive(Debug)]
+pub struct RemoteMocks {
+    source: RemoteSource,
+    dir: TempDir,
+}
+
+impl RemoteMocks {
+    /// Fetch `source` for the first time
+    pub async fn fetch(source: RemoteSource) -> Result<Self> {
+        let dir = TempDir::with_prefix("blendwerk-")?;
+        fs::create_dir(dir.path().join("mocks"))?;
+        let mocks = Self { source, dir };
+        mocks.refresh().await?;
+        Ok(mocks)
+    }
+
+    /// Directory serving the fetched mocks
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join("mocks")
+    }
+
+    /// Fetch the source again and apply the differences to `path()`
+    pub async fn refresh(&self) -> Result<()> {
+        let staging = TempDir::with_prefix_in("staging-", self.dir.path())?;
+        let root = tokio::time::timeout(FETCH_TIMEOUT, fetch_into(&self.source, staging.path()))
+            .await
+            .map_err(|_| anyhow!("timed out after {:?}", FETCH_TIMEOUT))?
+            .with_context(|| format!("Failed to fetch {}", self.source))?;
+
+        let target = self.path();
+        tokio::task::spawn_blocking(move || sync_dir(&root, &target)).await??;
+        Ok(())
+    }
+}
+
+/// Re-fetch every `interval` and, on unix, on SIGHUP until shutdown
+pub async fn refresh_loop(
+    mocks: Arc<RemoteMocks>,
+    interval: Option<Duration>,
+    mut shutdown: ShutdownSignal,
+) {
+    #[cfg(unix)]
+    let mut sighup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(signal) => Some(signal),
+        Err(e) => {
+            warn!("Failed to install SIGHUP handler: {}", e);
+            None
+        }
+    };
+
+    loop {
+        let sleep = async {
+            match interval {
+                Some(interval) => tokio::time::sleep(interval).await,
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(unix)]
+        let hangup = async {
+            match &mut sighup {
+                Some(signal) => signal.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(not(unix))]
+        let hangup = std::future::pending::<Option<()>>();
+
+        tokio::select! {
+            _ = sleep => {}
+            _ = hangup => info!("Received SIGHUP, re-fetching {}", mocks.source),
+            _ = shutdown.wait_for(|stopped| *stopped) => return,
+        }
+
+        match mocks.refresh().await {
+            Ok(()) => info!("Re-fetched {}", mocks.source),
+            Err(e) => warn!("{:#}; keeping the previous mocks", e),
+        }
+    }
+}
+
+/// Fetch `source` into `dir`, returning the mock root inside it
+async fn fetch_into(source: &RemoteSource, dir: &Path) -> Result<PathBuf> {
+    match source {
+        RemoteSource::Git { url, reference } => {
+            let checkout = dir.join("checkout");
+            let mut command = Command::new("git");
+            command.args(["clone", "--quiet", "--depth", "1"]);
+            if let Some(reference) = reference {
+                command.args(["--branch", reference]);
+            }
+            // A URL starting with `-` must not be taken for an option
+            let output = command
+                .arg("--")
+                .arg(url)
+                .arg(&checkout)
+                .kill_on_drop(true)
+                .output()
+                .await
+                .context("Failed to run git")?;
+            if !output.status.success() {
+                bail!(
+                    "git clone failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            fs::remove_dir_all(checkout.join(GIT_DIR))?;
+            Ok(checkout)
+        }
+        RemoteSource::Archive(uri) => {
+            let content = download(uri, MAX_ARCHIVE_BYTES).await?;
+            let format = Format::from_name(uri.path())
+                .or_else(|| Format::detect(&content))
+                .ok_or_else(|| anyhow!("response is no .zip or .tar.gz archive"))?;
+            let dir = dir.to_path_buf();
+            tokio::task::spawn_blocking(move || archive::extract(content.to_vec(), format, &dir))
+                .await?
+        }
+    }
+}
+
+/// Download `uri`, following redirects, refusing more than `max_bytes`
+async fn download(uri: &Uri, max_bytes: usize) -> Result<Bytes> {
+    let mut uri = uri.clone();
+    for _ in 0..=MAX_REDIRECTS {
+        let response =
+            http_client::send_limited(Method::GET, &uri, HeaderMap::new(), Bytes::new(), max_bytes)
+                .await
+                .with_context(|| format!("Failed to download {}", uri))?;
+        let status = response.status();
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        match location {
+            Some(location) if status.is_redirection() => {
+                // Relative redirects resolve against the current URL
+                uri = match location.parse::<Uri>() {
+                    Ok(next) if next.scheme().is_some() => next,
+                    _ => Uri::builder()
+                        .scheme(uri.scheme_str().unwrap_or("https"))
+                        .authority(uri.authority().map(|a| a.as_str()).unwrap_or_default())
+                        .path_and_query(location)
+                        .build()?,
+                };
+            }
+            _ if status.is_success() => return Ok(response.into_body()),
+            _ => bail!("{} answered {}", uri, status),
+        }
+    }
+    bail!("too many redirects")
+}
+
+/// Make `target` a copy of `source`, moving only new and changed files and
+/// removing those that are gone
+fn sync_dir(source: &Path, target: &Path) -> Result<()> {
+    for entry in fs::read_dir(target)? {
+        let path = entry?.path();
+        let counterpart = source.join(path.file_name().unwrap_or_default());
+        if path.is_dir() && !counterpart.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else if !path.is_dir() && !counterpart.is_file() {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    for entry in fs::read_dir(source)? {
+        let path = entry?.path();
+        let counterpart = target.join(path.file_name().unwrap_or_default());
+        if path.is_dir() {
+            if !counterpart.exists() {
+                fs::create_dir(&counterpart)?;
+            }
+            sync_dir(&path, &counterpart)?;
+        } else if fs::read(&counterpart).ok() != Some(fs::read(&path)?) {
+            fs::rename(&path, &counterpart)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(RemoteSource::parse("./mocks"), None);
+        assert_eq!(RemoteSource::parse("mocks.tar.gz"), None);
+        assert_eq!(
+            RemoteSource::parse("https://example.test/team/mocks.git#v2"),
+            Some(RemoteSource::Git {
+                url: "https://example.test/team/mocks.git".to_string(),
+                reference: Some("v2".to_string()),
+            })
+        );
+        assert_eq!(
+            RemoteSource::parse("git+https://example.test/team/mocks"),
+            Some(RemoteSource::Git {
+                url: "https://example.test/team/mocks".to_string(),
+                reference: None,
+            })
+        );
+        assert!(matches!(
+            RemoteSource::parse("git@example.test:team/mocks.git"),
+            Some(RemoteSource::Git { .. })
+        ));
+        assert_eq!(
+            RemoteSource::parse("https://example.test/mocks.tar.gz"),
+            Some(RemoteSource::Archive(
+                "https://example.test/mocks.tar.gz".parse().unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_sync_dir() {
+        let source = TempDir::new().unwrap();
+        let target = TempDir::new().unwrap();
+        fs::create_dir_all(source.path().join("users")).unwrap();
+        fs::write(source.path().join("users/GET.json"), "[]").unwrap();
+        fs::create_dir_all(target.path().join("orders")).unwrap();
+        fs::write(target.path().join("orders/GET.json"), "{}").unwrap();
+        fs::write(target.path().join("GET.json"), "old").unwrap();
+
+        sync_dir(source.path(), target.path()).unwrap();
+
+        assert!(!target.path().join("orders").exists());
+        assert!(!target.path().join("GET.json").exists());
+        assert_eq!(
+            fs::read_to_string(target.path().join("users/GET.json")).unwrap(),
+            "[]"
+        );
+    }
+
+    /// Serve `archive` once behind a redirect
+    async fn serve(archive: Vec<u8>) -> Uri {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for response in [
+                b"HTTP/1.1 302 Found\r\nLocation: /mocks.tar.gz\r\nContent-Length: 0\r\n\r\n"
+                    .to_vec(),
+                [
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                        archive.len()
+                    )
+                    .into_bytes(),
+                    archive.clone(),
+                ]
+                .concat(),
+            ] {
+                let (mut connection, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let len = connection.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..len]);
+                }
+                connection.write_all(&response).await.unwrap();
+            }
+        });
+        format!("http://{}/latest", address).parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_archive() {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "mocks-main/api/GET.json", &b"{}"[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let mocks = RemoteMocks::fetch(RemoteSource::Archive(serve(archive).await))
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(mocks.path().join("api/GET.json")).unwrap(),
+            "{}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_limit() {
+        let uri = serve(vec![0; 64]).await;
+        let error = download(&uri, 32).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("exceeds 32 bytes"));
+    }
+}
@@ -0,0 +1,118 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Correlation IDs of requests (`--request-id-header`).
+//!
+//! IDs clients send in the configured header are always echoed on the
+//! response and become the request's ID in the request log. What happens to
+//! requests without one depends on `--missing-request-id`.
+
+use axum::http::{HeaderMap, HeaderName};
+use clap::ValueEnum;
+
+/// What happens to requests without an ID
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MissingRequestId {
+    /// Generate a ULID and send it with the response
+    #[default]
+    Generate,
+    /// Only echo IDs clients send; responses to others carry none
+    Echo,
+    /// Answer with 400 Bad Request
+    Reject,
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestIds {
+    header: HeaderName,
+    missing: MissingRequestId,
+}
+
+/// A request without ID with `--missing-request-id reject`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingId(pub HeaderName);
+
+impl std::fmt::Display for MissingId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Missing request ID header {}", self.0)
+    }
+}
+
+impl RequestIds {
+    pub fn new(header: HeaderName, missing: MissingRequestId) -> Self {
+        Self { header, missing }
+    }
+
+    pub fn header(&self) -> &HeaderName {
+        &self.header
+    }
+
+    /// ID of a request: the one it carries, a generated one or none
+    pub fn resolve(&self, headers: &HeaderMap) -> Result<Option<String>, MissingId> {
+        let sent = headers
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|id| !id.is_empty());
+        match (sent, self.missing) {
+            (Some(id), _) => Ok(Some(id.to_string())),
+            (None, MissingRequestId::Generate) => Ok(Some(ulid::Ulid::new().to_string())),
+            (None, MissingRequestId::Echo) => Ok(None),
+            (None, MissingRequestId::Reject) => Err(MissingId(self.header.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn request_ids(missing: MissingRequestId) -> RequestIds {
+        RequestIds::new(HeaderName::from_static("x-correlation-id"), missing)
+    }
+
+    #[test]
+    fn test_sent_ids_are_kept() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-correlation-id", HeaderValue::from_static("order-42"));
+        for missing in [
+            MissingRequestId::Generate,
+            MissingRequestId::Echo,
+            MissingRequestId::Reject,
+        ] {
+            assert_eq!(
+                request_ids(missing).resolve(&headers),
+                Ok(Some("order-42".to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_ids() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", HeaderValue::from_static("other-header"));
+
+        let generated = request_ids(MissingRequestId::Generate)
+            .resolve(&headers)
+            .unwrap()
+            .unwrap();
+        assert!(generated.parse::<ulid::Ulid>().is_ok());
+        assert_eq!(
+            request_ids(MissingRequestId::Echo).resolve(&headers),
+            Ok(None)
+        );
+        assert_eq!(
+            request_ids(MissingRequestId::Reject)
+                .resolve(&headers)
+                .unwrap_err()
+                .to_string(),
+            "Missing request ID header x-correlation-id"
+        );
+    }
+}
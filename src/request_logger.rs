@@ -6,33 +6,66 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use crate::fault::Fault;
+use crate::log_filter::LogFilter;
+use crate::logging;
+use crate::transfer::TransferStats;
 use anyhow::{Context, Result};
-use axum::body::Body;
 use axum::http::{HeaderMap, Method, Uri};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use bytes::Bytes;
 use clap::ValueEnum;
-use serde::Serialize;
-use std::collections::HashMap;
+use flate2::{Compression, write::GzEncoder};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tokio::fs;
-use tracing::error;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
+use tokio::time::{MissedTickBehavior, interval};
+use tracing::{error, warn};
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum LogFormat {
+    /// One JSON file per request, below its path and method
     Json,
+    /// One YAML file per request, below its path and method
     Yaml,
+    /// One JSON line per request, appended to a single `requests.ndjson`
+    Ndjson,
 }
 
+/// The file all entries of the `ndjson` format are appended to
+const NDJSON_FILE: &str = "requests.ndjson";
+
 impl LogFormat {
     fn extension(&self) -> &'static str {
         match self {
             Self::Json => "json",
             Self::Yaml => "yaml",
+            Self::Ndjson => "ndjson",
+        }
+    }
+
+    /// Extension of batch files, which hold many entries each
+    fn batch_extension(&self) -> &'static str {
+        match self {
+            Self::Json => "jsonl",
+            Self::Yaml => "yaml",
+            Self::Ndjson => "ndjson",
         }
     }
 
     fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
         match self {
             Self::Json => serde_json::to_vec_pretty(value).context("Failed to serialize to JSON"),
+            Self::Ndjson => self.serialize_entry(value),
             Self::Yaml => {
                 let yaml_string =
                     serde_yaml::to_string(value).context("Failed to serialize to YAML")?;
@@ -40,30 +73,280 @@ impl LogFormat {
             }
         }
     }
+
+    /// Serialize one entry of a batch file: a JSON line or a YAML document
+    fn serialize_entry<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            Self::Json | Self::Ndjson => {
+                let mut line = serde_json::to_vec(value).context("Failed to serialize to JSON")?;
+                line.push(b'\n');
+                Ok(line)
+            }
+            Self::Yaml => {
+                let yaml_string =
+                    serde_yaml::to_string(value).context("Failed to serialize to YAML")?;
+                Ok(format!("---\n{}", yaml_string).into_bytes())
+            }
+        }
+    }
+}
+
+/// Compression of request log files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogCompression {
+    /// gzip, adding `.gz` to file names
+    Gzip,
+    /// Zstandard, adding `.zst` to file names
+    Zstd,
+}
+
+impl LogCompression {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+        }
+    }
+
+    /// Compress content written in one go. Batch files receive one gzip
+    /// member or zstd frame per append, which decompressors read as a
+    /// single stream
+    fn compress(self, content: &[u8]) -> Result<Vec<u8>> {
+        use std::io::Write;
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(content)?;
+                Ok(encoder.finish()?)
+            }
+            Self::Zstd => Ok(zstd::encode_all(content, 0)?),
+        }
+    }
 }
 
+/// When to flush batched log entries to disk
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    /// Flush as soon as this many entries are buffered
+    pub size: usize,
+    /// Flush buffered entries at least this often
+    pub interval: Duration,
+}
+
+/// Hands requests to a single writer task through a bounded queue.
+///
+/// Requests that arrive while the queue is full are dropped and counted
+/// instead of piling up in memory.
 #[derive(Debug, Clone)]
 pub struct RequestLogger {
+    sender: mpsc::Sender<LogMessage>,
+    capacity: usize,
+    max_body_bytes: Option<usize>,
+    filter: Arc<LogFilter>,
+    /// Headers whose values are logged as `<redacted>`
+    redacted_headers: Arc<[String]>,
+    counters: Arc<LogCounters>,
+}
+
+/// Logged instead of the values of redacted headers
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug)]
+enum LogMessage {
+    Entry(Box<LoggedRequest>),
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Default)]
+struct LogCounters {
+    written: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl LogCounters {
+    fn record(&self, entries: usize, result: Result<()>) {
+        match result {
+            Ok(()) => self.written.fetch_add(entries as u64, Ordering::Relaxed),
+            Err(e) => {
+                error!("Failed to log request: {}", e);
+                self.failed.fetch_add(entries as u64, Ordering::Relaxed)
+            }
+        };
+    }
+}
+
+/// Snapshot of the request logger's queue and counters
+#[derive(Debug, Clone, Serialize)]
+pub struct LogStats {
+    pub queued: usize,
+    pub capacity: usize,
+    pub written: u64,
+    pub dropped: u64,
+    pub failed: u64,
+}
+
+struct LogWriter {
     base_dir: PathBuf,
     format: LogFormat,
+    batch: Option<BatchOptions>,
+    compression: Option<LogCompression>,
+    counters: Arc<LogCounters>,
 }
 
 impl RequestLogger {
-    pub fn new(base_dir: PathBuf, format: LogFormat) -> Self {
-        Self { base_dir, format }
+    /// Create the logger and spawn its writer task on the current runtime
+    pub fn new(
+        base_dir: PathBuf,
+        format: LogFormat,
+        capacity: usize,
+        batch: Option<BatchOptions>,
+        compression: Option<LogCompression>,
+    ) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, receiver) = mpsc::channel(capacity);
+        let counters = Arc::new(LogCounters::default());
+
+        let writer = LogWriter {
+            base_dir,
+            format,
+            batch,
+            compression,
+            counters: counters.clone(),
+        };
+        tokio::spawn(writer.run(receiver));
+
+        Self {
+            sender,
+            capacity,
+            max_body_bytes: None,
+            filter: Arc::default(),
+            redacted_headers: Arc::new([]),
+            counters,
+        }
     }
 
-    /// Log a request asynchronously. This method spawns a task and never blocks.
-    pub fn log_request_async(&self, logged_request: LoggedRequest) {
-        let logger = self.clone();
-        tokio::spawn(async move {
-            if let Err(e) = logger.log_request(logged_request).await {
-                error!("Failed to log request: {}", e);
+    /// Log bodies larger than `max_body_bytes` only as a note about their size
+    pub fn with_max_body_bytes(mut self, max_body_bytes: Option<usize>) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    /// Log the values of these request and response headers as
+    /// `<redacted>`
+    pub fn with_redacted_headers(mut self, names: Vec<String>) -> Self {
+        self.redacted_headers = names.into();
+        self
+    }
+
+    /// Log only requests passing `filter`
+    pub fn with_filter(mut self, filter: LogFilter) -> Self {
+        self.filter = Arc::new(filter);
+        self
+    }
+
+    /// Whether a request to `path` answered with `status` is logged
+    pub fn accepts(&self, path: &str, status: u16, matched: bool) -> bool {
+        self.filter.accepts(path, status, matched)
+    }
+
+    /// Queue a request for logging. Never blocks; drops the request if the
+    /// writer can't keep up.
+    pub fn log_request_async(&self, mut logged_request: LoggedRequest) {
+        if let Some(max_bytes) = self.max_body_bytes {
+            logged_request.request.body = logged_request
+                .request
+                .body
+                .map(|body| body.truncate(max_bytes));
+            logged_request.response.body = logged_request.response.body.truncate(max_bytes);
+        }
+        redact(&mut logged_request.request.headers, &self.redacted_headers);
+        redact(&mut logged_request.response.headers, &self.redacted_headers);
+
+        match self
+            .sender
+            .try_send(LogMessage::Entry(Box::new(logged_request)))
+        {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.counters.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                // Warn on the first drop and then sparingly, to not flood the log
+                if dropped.is_power_of_two() {
+                    warn!(
+                        "Request log queue is full, {} request(s) dropped so far",
+                        dropped
+                    );
+                }
+            }
+            Err(TrySendError::Closed(_)) => {
+                self.counters.failed.fetch_add(1, Ordering::Relaxed);
             }
-        });
+        }
+    }
+
+    /// Wait until every request queued so far has been written to disk
+    pub async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.sender.send(LogMessage::Flush(done)).await.is_ok() {
+            let _ = flushed.await;
+        }
+    }
+
+    pub fn stats(&self) -> LogStats {
+        LogStats {
+            queued: self.capacity - self.sender.capacity(),
+            capacity: self.capacity,
+            written: self.counters.written.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl LogWriter {
+    async fn run(self, mut receiver: mpsc::Receiver<LogMessage>) {
+        let mut pending = Vec::new();
+        let mut ticker = interval(self.batch.map_or(Duration::from_secs(1), |b| b.interval));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                message = receiver.recv() => match message {
+                    Some(LogMessage::Entry(logged_request)) => match self.batch {
+                        Some(batch) => {
+                            pending.push(*logged_request);
+                            if pending.len() >= batch.size {
+                                self.flush_batch(&mut pending).await;
+                            }
+                        }
+                        None => {
+                            let result = self.log_request(*logged_request).await;
+                            self.counters.record(1, result);
+                        }
+                    },
+                    Some(LogMessage::Flush(done)) => {
+                        self.flush_batch(&mut pending).await;
+                        let _ = done.send(());
+                    }
+                    None => {
+                        self.flush_batch(&mut pending).await;
+                        break;
+                    }
+                },
+                _ = ticker.tick(), if !pending.is_empty() => {
+                    self.flush_batch(&mut pending).await;
+                }
+            }
+        }
     }
 
     async fn log_request(&self, logged_request: LoggedRequest) -> Result<()> {
+        if let LogFormat::Ndjson = self.format {
+            return self
+                .append_entries(NDJSON_FILE, std::slice::from_ref(&logged_request))
+                .await;
+        }
+
         // Build directory path: base_dir/path/METHOD/
         let request_path = logged_request
             .request
@@ -85,24 +368,100 @@ impl RequestLogger {
             .await
             .context("Failed to create log directory")?;
 
-        // Generate filename: timestamp_ulid.extension
+        // Generate filename: timestamp_id.extension
         let filename = format!(
             "{}_{}.{}",
             logged_request.metadata.timestamp,
-            logged_request.metadata.request_id,
+            file_name_safe(&logged_request.metadata.request_id),
             self.format.extension()
         );
 
-        let file_path = dir_path.join(filename);
+        let file_path = dir_path.join(self.compressed_name(filename));
 
         // Serialize and write
-        let content = self.format.serialize(&logged_request)?;
+        let content = self.compress(self.format.serialize(&logged_request)?)?;
         fs::write(&file_path, content)
             .await
             .context("Failed to write log file")?;
 
         Ok(())
     }
+
+    fn compressed_name(&self, filename: String) -> String {
+        match self.compression {
+            Some(compression) => format!("{}.{}", filename, compression.extension()),
+            None => filename,
+        }
+    }
+
+    fn compress(&self, content: Vec<u8>) -> Result<Vec<u8>> {
+        match self.compression {
+            Some(compression) => compression
+                .compress(&content)
+                .context("Failed to compress log file"),
+            None => Ok(content),
+        }
+    }
+
+    /// Append all pending entries to the files of the minutes they were
+    /// logged in, or to the single file of the `ndjson` format
+    async fn flush_batch(&self, pending: &mut Vec<LoggedRequest>) {
+        let mut by_file: BTreeMap<String, Vec<LoggedRequest>> = BTreeMap::new();
+        for logged_request in pending.drain(..) {
+            let file_name = match self.format {
+                LogFormat::Ndjson => NDJSON_FILE.to_string(),
+                // Timestamps look like 2025-01-28T15-30-45.123456Z
+                _ => format!(
+                    "{}.{}",
+                    logged_request
+                        .metadata
+                        .timestamp
+                        .get(..16)
+                        .unwrap_or(&logged_request.metadata.timestamp),
+                    self.format.batch_extension()
+                ),
+            };
+            by_file.entry(file_name).or_default().push(logged_request);
+        }
+
+        for (file_name, entries) in by_file {
+            let count = entries.len();
+            let result = self.append_entries(&file_name, &entries).await;
+            self.counters.record(count, result);
+        }
+    }
+
+    /// Append entries to a file in the log directory. The file is opened
+    /// for every append, so it can be rotated by renaming it
+    async fn append_entries(&self, file_name: &str, entries: &[LoggedRequest]) -> Result<()> {
+        let mut content = Vec::new();
+        for logged_request in entries {
+            content.extend(self.format.serialize_entry(logged_request)?);
+        }
+
+        fs::create_dir_all(&self.base_dir)
+            .await
+            .context("Failed to create log directory")?;
+
+        let content = self.compress(content)?;
+        let file_path = self
+            .base_dir
+            .join(self.compressed_name(file_name.to_string()));
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file_path)
+            .await
+            .context("Failed to open log file")?;
+        file.write_all(&content)
+            .await
+            .context("Failed to write log file")?;
+        // tokio completes writes in the background; wait for them so the
+        // next append to the same file can't overtake this one
+        file.flush().await.context("Failed to write log file")?;
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -116,6 +475,8 @@ pub struct LoggedRequest {
 pub struct RequestMetadata {
     pub timestamp: String,
     pub request_id: String,
+    /// From receiving the request until the response body was sent
+    pub duration_ms: f64,
 }
 
 #[derive(Debug, Serialize)]
@@ -126,36 +487,137 @@ pub struct RequestInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub query: Option<String>,
     pub headers: HashMap<String, String>,
+    /// Address of the client, behind `--trusted-proxy` proxies as they
+    /// forwarded it
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub body: Option<String>,
+    pub client_ip: Option<String>,
+    #[serde(flatten)]
+    pub body: Option<LoggedBody>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub matched_route: Option<String>,
+    /// Repeats an identical request within `--duplicate-window`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub duplicate: bool,
+    /// Arrived out of the order given with `--sequence`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub out_of_sequence: bool,
+    /// ID from `--request-id-header`, logged as `metadata.request_id`
+    /// instead of a new ULID
+    #[serde(skip)]
+    pub request_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ResponseInfo {
     pub status: u16,
     pub headers: HashMap<String, String>,
-    pub body: String,
+    #[serde(flatten)]
+    pub body: LoggedBody,
     pub delay_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fault: Option<Fault>,
+    /// Set once the body has been sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer: Option<TransferStats>,
+}
+
+/// Body as recorded in a log entry.
+///
+/// Content shares its bytes with the response instead of copying them and is
+/// only converted to text when the entry is written. It is serialized as
+/// `body` next to the other fields of the request or response, base64
+/// encoded with `body_encoding: base64` if it isn't UTF-8, and with
+/// `body_truncated` and the original `body_bytes` if it was cut off.
+#[derive(Debug, Clone)]
+pub enum LoggedBody {
+    Content(Bytes),
+    /// The first bytes of a body of `len` bytes
+    Truncated {
+        head: Bytes,
+        len: usize,
+    },
+    /// Placeholder for bodies that aren't logged, e.g. streamed from disk
+    Note(String),
+}
+
+impl LoggedBody {
+    /// Cut content larger than `max_bytes` down to its first `max_bytes`,
+    /// without splitting characters of text
+    fn truncate(self, max_bytes: usize) -> Self {
+        match self {
+            Self::Content(bytes) if bytes.len() > max_bytes => {
+                let mut cut = max_bytes;
+                if let Ok(text) = std::str::from_utf8(&bytes) {
+                    while !text.is_char_boundary(cut) {
+                        cut -= 1;
+                    }
+                }
+                Self::Truncated {
+                    head: bytes.slice(..cut),
+                    len: bytes.len(),
+                }
+            }
+            body => body,
+        }
+    }
+}
+
+impl From<Bytes> for LoggedBody {
+    fn from(bytes: Bytes) -> Self {
+        Self::Content(bytes)
+    }
+}
+
+impl From<&str> for LoggedBody {
+    fn from(text: &str) -> Self {
+        Self::Content(Bytes::copy_from_slice(text.as_bytes()))
+    }
+}
+
+impl From<String> for LoggedBody {
+    fn from(text: String) -> Self {
+        Self::Content(Bytes::from(text))
+    }
+}
+
+impl Serialize for LoggedBody {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        let (content, len) = match self {
+            Self::Content(bytes) => (bytes, None),
+            Self::Truncated { head, len } => (head, Some(*len)),
+            Self::Note(note) => {
+                map.serialize_entry("body", note)?;
+                return map.end();
+            }
+        };
+
+        match std::str::from_utf8(content) {
+            Ok(text) => map.serialize_entry("body", text)?,
+            Err(_) => {
+                map.serialize_entry("body", &STANDARD.encode(content))?;
+                map.serialize_entry("body_encoding", "base64")?;
+            }
+        }
+        if let Some(len) = len {
+            map.serialize_entry("body_truncated", &true)?;
+            map.serialize_entry("body_bytes", &len)?;
+        }
+        map.end()
+    }
 }
 
 /// Extract request information for logging
-pub async fn extract_request_info(
+pub fn extract_request_info(
     method: &Method,
     uri: &Uri,
     headers: &HeaderMap,
-    body: Body,
-) -> Result<RequestInfo> {
-    // Read body
-    let body_bytes = axum::body::to_bytes(body, usize::MAX)
-        .await
-        .context("Failed to read request body")?;
-
-    let body_string = if body_bytes.is_empty() {
+    body_bytes: Bytes,
+) -> RequestInfo {
+    let body = if body_bytes.is_empty() {
         None
     } else {
-        Some(String::from_utf8_lossy(&body_bytes).to_string())
+        Some(LoggedBody::Content(body_bytes))
     };
 
     // Convert headers to HashMap
@@ -169,17 +631,47 @@ pub async fn extract_request_info(
         })
         .collect();
 
-    let request_info = RequestInfo {
+    RequestInfo {
         method: method.to_string(),
         uri: uri.to_string(),
         path: uri.path().to_string(),
         query: uri.query().map(String::from),
         headers: headers_map,
-        body: body_string,
+        client_ip: None,
+        body,
         matched_route: None, // Will be set later if route is found
-    };
+        duplicate: false,
+        out_of_sequence: false,
+        request_id: None,
+    }
+}
 
-    Ok(request_info)
+/// Replace the values of the headers in `names`, compared ignoring case
+fn redact(headers: &mut HashMap<String, String>, names: &[String]) {
+    for (name, value) in headers.iter_mut() {
+        if names
+            .iter()
+            .any(|redacted| redacted.eq_ignore_ascii_case(name))
+        {
+            *value = REDACTED.to_string();
+        }
+    }
+}
+
+/// Request ID for use in a file name; IDs sent by clients may contain
+/// anything
+fn file_name_safe(request_id: &str) -> String {
+    request_id
+        .chars()
+        .take(64)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
 }
 
 /// Create a complete LoggedRequest from all components
@@ -187,6 +679,7 @@ pub fn create_logged_request(
     mut request_info: RequestInfo,
     response_info: ResponseInfo,
     matched_route: Option<String>,
+    duration: Duration,
 ) -> LoggedRequest {
     // Set the matched route
     request_info.matched_route = matched_route;
@@ -194,14 +687,272 @@ pub fn create_logged_request(
     // Generate metadata
     let now = chrono::Utc::now();
     let timestamp = now.format("%Y-%m-%dT%H-%M-%S%.6fZ").to_string();
-    let request_id = ulid::Ulid::new().to_string();
+    let request_id = request_info
+        .request_id
+        .take()
+        .unwrap_or_else(|| ulid::Ulid::new().to_string());
 
     LoggedRequest {
         metadata: RequestMetadata {
             timestamp,
             request_id,
+            duration_ms: logging::duration_ms(duration),
         },
         request: request_info,
         response: response_info,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn logged_request(path: &str) -> LoggedRequest {
+        let request_info = RequestInfo {
+            method: "GET".to_string(),
+            uri: path.to_string(),
+            path: path.to_string(),
+            query: None,
+            headers: HashMap::new(),
+            client_ip: None,
+            body: None,
+            matched_route: None,
+            duplicate: false,
+            out_of_sequence: false,
+            request_id: None,
+        };
+        let response_info = ResponseInfo {
+            status: 200,
+            headers: HashMap::new(),
+            body: LoggedBody::from(""),
+            delay_ms: 0,
+            fault: None,
+            transfer: None,
+        };
+        create_logged_request(request_info, response_info, None, Duration::ZERO)
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_requests() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = RequestLogger::new(
+            temp_dir.path().to_path_buf(),
+            LogFormat::Json,
+            2,
+            None,
+            None,
+        );
+
+        // The writer task can't run before we yield, so the queue fills up
+        for _ in 0..5 {
+            logger.log_request_async(logged_request("/users"));
+        }
+        let stats = logger.stats();
+        assert_eq!(stats.queued, 2);
+        assert_eq!(stats.dropped, 3);
+
+        for _ in 0..50 {
+            if logger.stats().written == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let stats = logger.stats();
+        assert_eq!(stats.written, 2);
+        assert_eq!(stats.queued, 0);
+
+        let logged = std::fs::read_dir(temp_dir.path().join("users/GET"))
+            .unwrap()
+            .count();
+        assert_eq!(logged, 2);
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_appended_to_minute_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let batch = BatchOptions {
+            size: 2,
+            interval: Duration::from_secs(60),
+        };
+        let logger = RequestLogger::new(
+            temp_dir.path().to_path_buf(),
+            LogFormat::Json,
+            10,
+            Some(batch),
+            None,
+        );
+
+        for path in ["/a", "/b", "/c"] {
+            logger.log_request_async(logged_request(path));
+        }
+        logger.flush().await;
+        assert_eq!(logger.stats().written, 3);
+
+        let mut files: Vec<_> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        assert!(!files.is_empty());
+        assert!(files.iter().all(|f| f.extension().unwrap() == "jsonl"));
+
+        let lines: Vec<serde_json::Value> = files
+            .iter()
+            .flat_map(|f| {
+                std::fs::read_to_string(f)
+                    .unwrap()
+                    .lines()
+                    .map(|line| serde_json::from_str(line).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["request"]["path"], "/a");
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_appends_to_one_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = RequestLogger::new(
+            temp_dir.path().to_path_buf(),
+            LogFormat::Ndjson,
+            10,
+            None,
+            None,
+        );
+
+        for path in ["/users", "/users/1", "/"] {
+            logger.log_request_async(logged_request(path));
+        }
+        logger.flush().await;
+        assert_eq!(logger.stats().written, 3);
+
+        let files: Vec<_> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["requests.ndjson"]);
+
+        let content = std::fs::read_to_string(temp_dir.path().join(NDJSON_FILE)).unwrap();
+        let paths: Vec<String> = content
+            .lines()
+            .map(|line| {
+                let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+                entry["request"]["path"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(paths, ["/users", "/users/1", "/"]);
+    }
+
+    #[tokio::test]
+    async fn test_gzip_batches() {
+        use flate2::read::MultiGzDecoder;
+        use std::io::Read;
+
+        for compression in [LogCompression::Gzip, LogCompression::Zstd] {
+            let temp_dir = TempDir::new().unwrap();
+            let batch = BatchOptions {
+                size: 1,
+                interval: Duration::from_secs(60),
+            };
+            let logger = RequestLogger::new(
+                temp_dir.path().to_path_buf(),
+                LogFormat::Json,
+                10,
+                Some(batch),
+                Some(compression),
+            );
+
+            // Each entry is flushed on its own, appending a gzip member or
+            // zstd frame each
+            for path in ["/a", "/b"] {
+                logger.log_request_async(logged_request(path));
+                logger.flush().await;
+            }
+
+            let mut content = String::new();
+            for entry in std::fs::read_dir(temp_dir.path()).unwrap() {
+                let path = entry.unwrap().path();
+                let file = std::fs::File::open(&path).unwrap();
+                match compression {
+                    LogCompression::Gzip => {
+                        assert!(path.to_string_lossy().ends_with(".jsonl.gz"));
+                        MultiGzDecoder::new(file)
+                            .read_to_string(&mut content)
+                            .unwrap();
+                    }
+                    LogCompression::Zstd => {
+                        assert!(path.to_string_lossy().ends_with(".jsonl.zst"));
+                        zstd::Decoder::new(file)
+                            .unwrap()
+                            .read_to_string(&mut content)
+                            .unwrap();
+                    }
+                };
+            }
+            assert_eq!(content.lines().count(), 2);
+        }
+    }
+
+    #[test]
+    fn test_request_ids_in_file_names() {
+        assert_eq!(
+            file_name_safe("01HQKP6J9Z0000000000000000"),
+            "01HQKP6J9Z0000000000000000"
+        );
+        assert_eq!(file_name_safe("../etc/passwd"), "___etc_passwd");
+        assert_eq!(file_name_safe(&"a".repeat(100)).len(), 64);
+    }
+
+    #[test]
+    fn test_redacted_headers() {
+        let mut headers = HashMap::from([
+            ("authorization".to_string(), "Bearer secret".to_string()),
+            ("Set-Cookie".to_string(), "session=1".to_string()),
+            ("accept".to_string(), "*/*".to_string()),
+        ]);
+        redact(
+            &mut headers,
+            &["authorization".to_string(), "set-cookie".to_string()],
+        );
+        assert_eq!(headers["authorization"], "<redacted>");
+        assert_eq!(headers["Set-Cookie"], "<redacted>");
+        assert_eq!(headers["accept"], "*/*");
+    }
+
+    #[test]
+    fn test_large_bodies_are_truncated() {
+        let body = LoggedBody::from("0123456789");
+        assert_eq!(
+            serde_json::to_value(body.clone().truncate(10)).unwrap(),
+            serde_json::json!({"body": "0123456789"})
+        );
+        assert_eq!(
+            serde_json::to_value(body.truncate(4)).unwrap(),
+            serde_json::json!({"body": "0123", "body_truncated": true, "body_bytes": 10})
+        );
+
+        // Characters aren't split
+        let body = LoggedBody::from("aäb");
+        assert_eq!(serde_json::to_value(body.truncate(2)).unwrap()["body"], "a");
+    }
+
+    #[test]
+    fn test_binary_bodies_are_base64_encoded() {
+        let body = LoggedBody::from(Bytes::from_static(&[0xff, 0x00, 0x80]));
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            serde_json::json!({"body": "/wCA", "body_encoding": "base64"})
+        );
+
+        let mut entry = logged_request("/upload");
+        entry.response.body = body.truncate(2);
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["response"]["body"], "/wA=");
+        assert_eq!(json["response"]["body_truncated"], true);
+        assert_eq!(json["response"]["status"], 200);
+        assert!(json["request"].get("body").is_none());
+    }
+}
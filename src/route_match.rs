@@ -0,0 +1,85 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Conditions on the request a route only answers under (`match:`
+//! frontmatter).
+//!
+//! `client_ip` lists networks the client address has to be in, as resolved
+//! behind `--trusted-proxy` proxies. Routes with conditions take precedence
+//! over routes without, so different callers can get different responses
+//! for the same path; requests failing the conditions are answered by the
+//! next matching route, or get a 404.
+
+use crate::forwarded::Network;
+use serde::Deserialize;
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteMatch {
+    /// Networks like `10.0.0.0/8` or single addresses
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub client_ip: Vec<Network>,
+}
+
+fn one_or_many<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Network>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    let networks = match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(network) => vec![network],
+        OneOrMany::Many(networks) => networks,
+    };
+    networks
+        .iter()
+        .map(|network| network.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+impl RouteMatch {
+    /// Whether a request from `client_ip` meets the conditions; unknown
+    /// addresses meet no `client_ip` condition
+    pub fn matches(&self, client_ip: Option<IpAddr>) -> bool {
+        self.client_ip.is_empty()
+            || client_ip.is_some_and(|ip| self.client_ip.iter().any(|network| network.contains(ip)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route_match(yaml: &str) -> RouteMatch {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let internal = route_match("client_ip: [10.0.0.0/8, '::1']");
+        assert!(internal.matches(Some("10.1.2.3".parse().unwrap())));
+        assert!(internal.matches(Some("::1".parse().unwrap())));
+        assert!(!internal.matches(Some("192.0.2.1".parse().unwrap())));
+        assert!(!internal.matches(None));
+
+        let single = route_match("client_ip: 192.0.2.1");
+        assert!(single.matches(Some("::ffff:192.0.2.1".parse().unwrap())));
+        assert!(RouteMatch::default().matches(None));
+    }
+
+    #[test]
+    fn test_invalid_conditions() {
+        assert!(serde_yaml::from_str::<RouteMatch>("client_ip: 10.0.0.0/40").is_err());
+        assert!(serde_yaml::from_str::<RouteMatch>("client: 10.0.0.1").is_err());
+    }
+}
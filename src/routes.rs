@@ -6,12 +6,37 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use crate::frontmatter::{ParsedResponse, parse_frontmatter};
+use crate::frontmatter::{
+    ParsedResponse, ResponseMeta, ResponseMode, parse_frontmatter, parse_frontmatter_header,
+};
+use crate::graphql;
+use crate::includes;
+use crate::interpolate;
+use crate::mime_types;
+use crate::plugin::{self, Plugin};
+use crate::routes_file;
+use crate::scripting::{self, Script};
+use crate::sse;
+use crate::stats;
+use crate::websocket::{self, WsScript};
+use crate::wiremock;
 use anyhow::{Context, Result};
+use bytes::Bytes;
+use clap::ValueEnum;
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Instant, SystemTime};
+use tracing::info;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum HttpMethod {
     Get,
     Post,
@@ -20,10 +45,22 @@ pub enum HttpMethod {
     Patch,
     Head,
     Options,
+    // WebDAV (RFC 4918)
+    Propfind,
+    Proppatch,
+    Mkcol,
+    Copy,
+    Move,
+    Lock,
+    Unlock,
+    /// WebSocket conversation, matched by upgrade requests (`WS.yaml`)
+    Ws,
 }
 
 impl HttpMethod {
-    fn from_str(s: &str) -> Option<Self> {
+    // Unlike `FromStr`, unknown names are no error but no route file
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "get" => Some(Self::Get),
             "post" => Some(Self::Post),
@@ -32,9 +69,37 @@ impl HttpMethod {
             "patch" => Some(Self::Patch),
             "head" => Some(Self::Head),
             "options" => Some(Self::Options),
+            "propfind" => Some(Self::Propfind),
+            "proppatch" => Some(Self::Proppatch),
+            "mkcol" => Some(Self::Mkcol),
+            "copy" => Some(Self::Copy),
+            "move" => Some(Self::Move),
+            "lock" => Some(Self::Lock),
+            "unlock" => Some(Self::Unlock),
+            "ws" => Some(Self::Ws),
             _ => None,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Patch => "PATCH",
+            Self::Head => "HEAD",
+            Self::Options => "OPTIONS",
+            Self::Propfind => "PROPFIND",
+            Self::Proppatch => "PROPPATCH",
+            Self::Mkcol => "MKCOL",
+            Self::Copy => "COPY",
+            Self::Move => "MOVE",
+            Self::Lock => "LOCK",
+            Self::Unlock => "UNLOCK",
+            Self::Ws => websocket::METHOD_NAME,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,6 +108,116 @@ pub struct Route {
     pub path_segments: Vec<PathSegment>,
     pub response: ParsedResponse,
     pub content_type: String,
+    /// Body served from disk instead of `response.body`
+    pub body_file: Option<BodyFile>,
+    /// Script of a WebSocket route
+    pub websocket: Option<Arc<WsScript>>,
+    /// Script computing the response (`GET.rhai`)
+    pub script: Option<Arc<Script>>,
+    /// Plugin transforming the response (`plugin:`)
+    pub plugin: Option<Arc<Plugin>>,
+    /// GraphQL operation answered by this route (`graphql/<Operation>.json`)
+    pub graphql_operation: Option<String>,
+    /// File the route was loaded from
+    pub source: PathBuf,
+    /// Modification time of `source`, served as `Last-Modified`
+    pub modified: Option<SystemTime>,
+    /// Virtual host serving the route (`--vhost`), `None` for the main
+    /// directory
+    pub host: Option<String>,
+    /// Files included into the body (`{{> partials/page.json}}`) and the
+    /// module of `plugin`
+    pub includes: Vec<PathBuf>,
+}
+
+/// Location of a response body that is too large to be kept in memory
+#[derive(Debug, Clone)]
+pub struct BodyFile {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// Options influencing how mock files are turned into routes
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Bodies of files larger than this are streamed from disk per request
+    pub stream_threshold: Option<u64>,
+    /// Content types by lowercase extension, before the built-in ones
+    pub mime_types: HashMap<String, String>,
+}
+
+/// How request paths ending in `/` are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlash {
+    /// `/users/` matches like `/users`
+    #[default]
+    Ignore,
+    /// `/users/` doesn't match `/users`
+    Strict,
+    /// `/users/` is redirected to `/users` with 308
+    Redirect,
+}
+
+/// Options for matching request paths against routes, given globally and
+/// overridden per route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathMatching {
+    pub trailing_slash: TrailingSlash,
+    /// Compare static segments ignoring ASCII case
+    pub case_insensitive: bool,
+    /// Decode `%20` and the like in segments before comparing them
+    pub percent_decode: bool,
+    /// Ignore `;name=value` matrix parameters of segments when comparing
+    /// them
+    pub matrix_params: bool,
+}
+
+impl Default for PathMatching {
+    fn default() -> Self {
+        Self {
+            trailing_slash: TrailingSlash::default(),
+            case_insensitive: false,
+            percent_decode: true,
+            matrix_params: false,
+        }
+    }
+}
+
+/// Segments of a request path as they are compared with routes
+fn request_segments(request_path: &str, options: PathMatching) -> Vec<Cow<'_, str>> {
+    request_path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|segment| {
+            let segment = if options.matrix_params {
+                segment.split(';').next().unwrap_or_default()
+            } else {
+                segment
+            };
+            if options.percent_decode {
+                percent_decode_str(segment).decode_utf8_lossy()
+            } else {
+                Cow::Borrowed(segment)
+            }
+        })
+        .collect()
+}
+
+/// `;name=value` matrix parameters of all segments of a request path,
+/// percent-decoded
+pub fn matrix_params(request_path: &str) -> Vec<(String, String)> {
+    request_path
+        .split('/')
+        .flat_map(|segment| segment.split(';').skip(1))
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            let decode = |text| percent_decode_str(text).decode_utf8_lossy().into_owned();
+            (decode(name), decode(value))
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +226,20 @@ pub enum PathSegment {
     Dynamic(String), // Parameter name
 }
 
+impl PathSegment {
+    /// Segment of a directory name, `[id]` being the parameter `id` and
+    /// `[[id]]` the literal segment `[id]`
+    pub fn parse(segment: &str) -> Self {
+        match segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(escaped) if escaped.starts_with('[') && escaped.ends_with(']') => {
+                Self::Static(escaped.to_string())
+            }
+            Some(param_name) => Self::Dynamic(param_name.to_string()),
+            None => Self::Static(segment.to_string()),
+        }
+    }
+}
+
 impl Route {
     /// Generate a display path for logging (e.g., "/users/:id")
     pub fn display_path(&self) -> String {
@@ -70,12 +259,59 @@ impl Route {
         format!("/{}", parts.join("/"))
     }
 
+    /// Whether answering needs the request body: to match a GraphQL
+    /// operation, echo it, or hand it to a script, plugin or callback
+    pub fn reads_body(&self) -> bool {
+        self.graphql_operation.is_some()
+            || self.response.meta.mode == ResponseMode::Echo
+            || self.script.is_some()
+            || self.plugin.is_some()
+            || !self.response.meta.callbacks.is_empty()
+    }
+
+    /// Whether the response is a server-sent event stream, either by the
+    /// `.sse` extension or by an explicit Content-Type header
+    pub fn is_event_stream(&self) -> bool {
+        let content_type = self
+            .response
+            .meta
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .and_then(|(_, value)| value.first())
+            .unwrap_or(self.content_type.as_str());
+
+        content_type
+            .split(';')
+            .next()
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(sse::CONTENT_TYPE))
+    }
+
     pub fn matches(&self, request_path: &str) -> bool {
-        let request_segments: Vec<&str> = request_path
-            .trim_matches('/')
-            .split('/')
-            .filter(|s| !s.is_empty())
-            .collect();
+        self.matches_with(request_path, PathMatching::default())
+    }
+
+    /// Matching options of the route, falling back to the global ones
+    pub fn path_matching(&self, global: PathMatching) -> PathMatching {
+        let meta = &self.response.meta;
+        PathMatching {
+            trailing_slash: meta.trailing_slash.unwrap_or(global.trailing_slash),
+            case_insensitive: meta.case_insensitive.unwrap_or(global.case_insensitive),
+            ..global
+        }
+    }
+
+    /// Check whether a request path matches with the given global options
+    pub fn matches_with(&self, request_path: &str, global: PathMatching) -> bool {
+        let options = self.path_matching(global);
+        if options.trailing_slash != TrailingSlash::Ignore
+            && request_path.len() > 1
+            && request_path.ends_with('/')
+        {
+            return false;
+        }
+
+        let request_segments = request_segments(request_path, options);
 
         let pattern_len = self.path_segments.len();
 
@@ -90,7 +326,12 @@ impl Route {
         for (segment, pattern) in request_segments.iter().zip(&self.path_segments) {
             match pattern {
                 PathSegment::Static(s) => {
-                    if s != segment {
+                    let equal = if options.case_insensitive {
+                        s.eq_ignore_ascii_case(segment)
+                    } else {
+                        s == segment
+                    };
+                    if !equal {
                         return false;
                     }
                 }
@@ -102,15 +343,150 @@ impl Route {
 
         true
     }
+
+    /// Values of the `[param]` segments in a request path the route matches
+    pub fn params(&self, request_path: &str, global: PathMatching) -> Vec<(String, String)> {
+        request_segments(request_path, self.path_matching(global))
+            .into_iter()
+            .zip(&self.path_segments)
+            .filter_map(|(segment, pattern)| match pattern {
+                PathSegment::Dynamic(name) => Some((name.clone(), segment.into_owned())),
+                PathSegment::Static(_) => None,
+            })
+            .collect()
+    }
 }
 
+/// Frontmatter of streamed files has to fit into this many leading bytes
+const STREAMED_HEADER_LIMIT: u64 = 64 * 1024;
+
+/// Log scan progress every this many files
+const SCAN_PROGRESS_INTERVAL: usize = 5000;
+
 pub fn scan_directory(base_dir: &Path) -> Result<Vec<Route>> {
-    let mut routes = Vec::new();
-    scan_dir_recursive(base_dir, base_dir, &mut routes)?;
+    scan_directory_with(base_dir, &ScanOptions::default())
+}
+
+pub fn scan_directory_with(base_dir: &Path, options: &ScanOptions) -> Result<Vec<Route>> {
+    let started = Instant::now();
+
+    // Walking the tree is cheap compared to reading and parsing the files, so
+    // it stays sequential to keep the discovery order stable
+    let mut files = Vec::new();
+    collect_files(base_dir, &mut files)?;
+
+    let mut routes = parse_files_parallel(base_dir, &files, options)?;
+    stats::deduplicate_bodies(&mut routes);
+
+    info!(
+        "  Scanned {} files in {:.2?}",
+        files.len(),
+        started.elapsed()
+    );
+
     Ok(routes)
 }
 
-fn scan_dir_recursive(base_dir: &Path, current_dir: &Path, routes: &mut Vec<Route>) -> Result<()> {
+/// Update `routes`, as scanned from `base_dir`, for changes at the
+/// `changed` paths by re-parsing only the affected files.
+///
+/// A changed path may name a file or a directory, existing or removed;
+/// paths outside `base_dir` are ignored. Routes of a re-parsed file take the
+/// place of its previous ones, so first-match-wins is unaffected, and routes
+/// of new files are appended. Only routes tagged with `host` are replaced,
+/// and the new ones get the same tag.
+pub fn rescan_paths(
+    base_dir: &Path,
+    host: Option<&str>,
+    changed: &[PathBuf],
+    options: &ScanOptions,
+    routes: &mut Vec<Route>,
+) -> Result<()> {
+    let mut prefixes: Vec<PathBuf> = changed
+        .iter()
+        .filter_map(|path| relative_to(path, base_dir))
+        .map(|relative| base_dir.join(relative))
+        .collect();
+    if prefixes.is_empty() {
+        return Ok(());
+    }
+    // WireMock mappings read their bodies from `__files/`
+    if prefixes
+        .iter()
+        .any(|prefix| prefix.starts_with(base_dir.join(wiremock::FILES_DIR)))
+    {
+        prefixes.push(base_dir.join(wiremock::MAPPINGS_DIR));
+    }
+
+    let started = Instant::now();
+    let changed_path = |path: &PathBuf| prefixes.iter().any(|prefix| path.starts_with(prefix));
+    // Routes including a changed file are re-parsed along with it
+    let affected = |route: &Route| {
+        route.host.as_deref() == host
+            && (changed_path(&route.source) || route.includes.iter().any(changed_path))
+    };
+
+    let mut files = Vec::new();
+    for prefix in &prefixes {
+        if prefix.is_dir() {
+            collect_files(prefix, &mut files)?;
+        } else if prefix.is_file() {
+            files.push(prefix.clone());
+        }
+    }
+    files.extend(
+        routes
+            .iter()
+            .filter(|route| affected(route) && route.source.is_file())
+            .map(|route| route.source.clone()),
+    );
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(file.clone()));
+
+    let mut parsed: HashMap<PathBuf, Vec<Route>> = HashMap::new();
+    for mut route in parse_files_parallel(base_dir, &files, options)? {
+        route.host = host.map(String::from);
+        parsed.entry(route.source.clone()).or_default().push(route);
+    }
+
+    let mut updated = Vec::with_capacity(routes.len());
+    for route in routes.drain(..) {
+        if !affected(&route) {
+            updated.push(route);
+        } else if let Some(new_routes) = parsed.remove(&route.source) {
+            updated.extend(new_routes);
+        }
+    }
+    for file in &files {
+        if let Some(new_routes) = parsed.remove(file) {
+            updated.extend(new_routes);
+        }
+    }
+    *routes = updated;
+
+    info!(
+        "  Rescanned {} files in {:.2?}",
+        files.len(),
+        started.elapsed()
+    );
+
+    Ok(())
+}
+
+/// `path` relative to `dir`, also if notify reports it as absolute or
+/// canonicalized path
+fn relative_to(path: &Path, dir: &Path) -> Option<PathBuf> {
+    [
+        Some(dir.to_path_buf()),
+        std::path::absolute(dir).ok(),
+        fs::canonicalize(dir).ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .find_map(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+}
+
+fn collect_files(current_dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
     let entries = fs::read_dir(current_dir)
         .with_context(|| format!("Failed to read directory: {}", current_dir.display()))?;
 
@@ -119,26 +495,123 @@ fn scan_dir_recursive(base_dir: &Path, current_dir: &Path, routes: &mut Vec<Rout
         let path = entry.path();
 
         if path.is_dir() {
-            scan_dir_recursive(base_dir, &path, routes)?;
-        } else if path.is_file()
-            && let Some(route) = parse_route_file(base_dir, &path)?
-        {
-            routes.push(route);
+            collect_files(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
         }
     }
 
     Ok(())
 }
 
-fn parse_route_file(base_dir: &Path, file_path: &Path) -> Result<Option<Route>> {
+/// Parse all files on a pool of scoped threads.
+///
+/// Routes are returned in the order of `files`, so first-match-wins behaves
+/// exactly as with a sequential scan. If several files fail to parse, the
+/// error of the first one in discovery order is reported.
+fn parse_files_parallel(
+    base_dir: &Path,
+    files: &[PathBuf],
+    options: &ScanOptions,
+) -> Result<Vec<Route>> {
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(files.len().max(1));
+    let next_index = AtomicUsize::new(0);
+    let parsed_count = AtomicUsize::new(0);
+
+    let mut results: Vec<(usize, Result<Vec<Route>>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(index) else {
+                            break;
+                        };
+
+                        results.push((index, parse_file(base_dir, file, options)));
+
+                        let parsed = parsed_count.fetch_add(1, Ordering::Relaxed) + 1;
+                        if parsed.is_multiple_of(SCAN_PROGRESS_INTERVAL) {
+                            info!("  Scanned {}/{} files...", parsed, files.len());
+                        }
+                    }
+                    results
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("scan worker panicked"))
+            .collect()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+
+    let mut routes = Vec::new();
+    for (_, result) in results {
+        routes.extend(result?);
+    }
+
+    Ok(routes)
+}
+
+fn parse_file(base_dir: &Path, file_path: &Path, options: &ScanOptions) -> Result<Vec<Route>> {
+    let mut routes = if wiremock::is_mapping_file(base_dir, file_path) {
+        wiremock::parse_mapping_file(base_dir, file_path)?
+    } else if routes_file::is_routes_file(file_path) {
+        routes_file::parse_routes_file(base_dir, file_path)?
+    } else {
+        parse_route_file(base_dir, file_path, options)?
+            .into_iter()
+            .collect()
+    };
+
+    for route in &mut routes {
+        load_plugin(route)?;
+    }
+    Ok(routes)
+}
+
+/// Compile the plugin a route names, relative to the file defining it; the
+/// route is reloaded whenever the module changes
+fn load_plugin(route: &mut Route) -> Result<()> {
+    let Some(plugin) = &route.response.meta.plugin else {
+        return Ok(());
+    };
+    let path = route.source.parent().unwrap_or(Path::new("")).join(plugin);
+    let loaded = plugin::load(&path).with_context(|| {
+        format!(
+            "Failed to load plugin {} of: {}",
+            path.display(),
+            route.source.display()
+        )
+    })?;
+    route.plugin = Some(Arc::new(loaded));
+    route.includes.push(path);
+    Ok(())
+}
+
+fn parse_route_file(
+    base_dir: &Path,
+    file_path: &Path,
+    options: &ScanOptions,
+) -> Result<Option<Route>> {
     let file_name = file_path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
 
     let extension = file_path.extension().and_then(|s| s.to_str()).unwrap_or("");
 
-    // Parse HTTP method from filename (case-insensitive)
-    let method = match HttpMethod::from_str(file_name) {
-        Some(m) => m,
-        None => return Ok(None), // Not a valid route file
+    // Parse HTTP method from filename (case-insensitive); operation files
+    // inside a `graphql/` directory answer POST requests
+    let graphql_operation = graphql::operation_name(file_path);
+    let method = match (HttpMethod::from_str(file_name), &graphql_operation) {
+        (Some(m), _) => m,
+        (None, Some(_)) => HttpMethod::Post,
+        (None, None) => return Ok(None), // Not a valid route file
     };
 
     // Build path segments from directory structure
@@ -150,44 +623,215 @@ fn parse_route_file(base_dir: &Path, file_path: &Path) -> Result<Option<Route>>
 
     for component in relative_path.components() {
         if let std::path::Component::Normal(os_str) = component {
-            let segment = os_str.to_string_lossy();
-            if segment.starts_with('[') && segment.ends_with(']') {
-                // Dynamic parameter: [id]
-                let param_name = &segment[1..segment.len() - 1];
-                path_segments.push(PathSegment::Dynamic(param_name.to_string()));
-            } else {
-                path_segments.push(PathSegment::Static(segment.to_string()));
-            }
+            path_segments.push(PathSegment::parse(&os_str.to_string_lossy()));
         }
     }
 
     // Determine content type from extension
-    let content_type = match extension {
-        "json" => "application/json",
-        "html" | "htm" => "text/html",
-        "xml" => "application/xml",
-        "txt" => "text/plain",
-        "css" => "text/css",
-        "js" => "application/javascript",
-        _ => "application/octet-stream",
+    let content_type = mime_types::for_extension(extension, &options.mime_types);
+
+    let metadata = fs::metadata(file_path)
+        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+    let file_len = metadata.len();
+    let modified = metadata.modified().ok();
+
+    let is_script = extension.eq_ignore_ascii_case("rhai");
+    if let Some(threshold) = options.stream_threshold
+        && method != HttpMethod::Ws
+        && !is_script
+        && file_len > threshold
+        && let Some((meta, body_file)) = parse_streamed_file(file_path, file_len)?
+    {
+        return Ok(Some(Route {
+            method,
+            path_segments,
+            response: ParsedResponse {
+                meta,
+                body: Bytes::new(),
+            },
+            content_type,
+            body_file: Some(body_file),
+            websocket: None,
+            script: None,
+            plugin: None,
+            graphql_operation,
+            source: file_path.to_path_buf(),
+            modified,
+            host: None,
+            includes: Vec::new(),
+        }));
     }
-    .to_string();
 
     // Read and parse file content
-    let content = fs::read_to_string(file_path)
+    let content = fs::read(file_path)
         .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+    let content = match String::from_utf8(content) {
+        Ok(content) => content,
+        // Binary bodies (images, archives) are sent verbatim; frontmatter
+        // generating a body doesn't apply to them
+        Err(e) if method != HttpMethod::Ws && !is_script => {
+            let content = e.into_bytes();
+            let (meta, body_offset) = parse_leading_frontmatter(file_path, &content)?;
+            let body = Bytes::from(content).slice(body_offset..);
+            return Ok(Some(Route {
+                method,
+                path_segments,
+                response: ParsedResponse { meta, body },
+                content_type,
+                body_file: None,
+                websocket: None,
+                script: None,
+                plugin: None,
+                graphql_operation,
+                source: file_path.to_path_buf(),
+                modified,
+                host: None,
+                includes: Vec::new(),
+            }));
+        }
+        Err(_) => anyhow::bail!("Script is not valid UTF-8: {}", file_path.display()),
+    };
+
+    // Scripts compute the response for each request; their own `${...}`
+    // string interpolation rules out environment interpolation
+    if is_script && method != HttpMethod::Ws {
+        let ParsedResponse { meta, body } = parse_frontmatter(&content)
+            .with_context(|| format!("Failed to parse frontmatter in: {}", file_path.display()))?;
+        let source = std::str::from_utf8(&body).unwrap_or_default();
+        let script = scripting::compile(source)
+            .with_context(|| format!("Failed to compile script: {}", file_path.display()))?;
+        return Ok(Some(Route {
+            method,
+            path_segments,
+            response: ParsedResponse {
+                meta,
+                body: Bytes::new(),
+            },
+            content_type: "text/plain".to_string(),
+            body_file: None,
+            websocket: None,
+            script: Some(Arc::new(script)),
+            plugin: None,
+            graphql_operation,
+            source: file_path.to_path_buf(),
+            modified,
+            host: None,
+            includes: Vec::new(),
+        }));
+    }
 
-    let response = parse_frontmatter(&content)
+    let content = interpolate::interpolate_env(&content, file_path);
+
+    // WebSocket routes hold a conversation script instead of a response
+    if method == HttpMethod::Ws {
+        let script = websocket::parse_script(&content).with_context(|| {
+            format!("Failed to parse WebSocket script: {}", file_path.display())
+        })?;
+        return Ok(Some(Route {
+            method,
+            path_segments,
+            response: ParsedResponse {
+                meta: ResponseMeta::default(),
+                body: Bytes::new(),
+            },
+            content_type,
+            body_file: None,
+            websocket: Some(Arc::new(script)),
+            script: None,
+            plugin: None,
+            graphql_operation: None,
+            source: file_path.to_path_buf(),
+            modified,
+            host: None,
+            includes: Vec::new(),
+        }));
+    }
+
+    let mut response = parse_frontmatter(&content)
         .with_context(|| format!("Failed to parse frontmatter in: {}", file_path.display()))?;
 
+    let mut included = Vec::new();
+    if let Ok(body) = std::str::from_utf8(&response.body)
+        && includes::has_includes(body)
+    {
+        let (body, files) = includes::resolve(base_dir, body)
+            .with_context(|| format!("Failed to resolve includes in: {}", file_path.display()))?;
+        response.body = Bytes::from(body);
+        included = files;
+    }
+    // The body is as new as the newest file it is made of
+    let modified = included
+        .iter()
+        .filter_map(|file| fs::metadata(file).and_then(|m| m.modified()).ok())
+        .chain(modified)
+        .max();
+
     Ok(Some(Route {
         method,
         path_segments,
         response,
         content_type,
+        body_file: None,
+        websocket: None,
+        script: None,
+        plugin: None,
+        graphql_operation,
+        source: file_path.to_path_buf(),
+        modified,
+        host: None,
+        includes: included,
     }))
 }
 
+/// Parse only the frontmatter of a large file and locate its body on disk.
+///
+/// Returns `None` if the body is generated from the frontmatter and the file
+/// therefore has to be parsed as a whole.
+fn parse_streamed_file(
+    file_path: &Path,
+    file_len: u64,
+) -> Result<Option<(ResponseMeta, BodyFile)>> {
+    let mut prefix = Vec::new();
+    fs::File::open(file_path)
+        .and_then(|file| file.take(STREAMED_HEADER_LIMIT).read_to_end(&mut prefix))
+        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+
+    let (meta, body_offset) = parse_leading_frontmatter(file_path, &prefix)?;
+
+    if meta.multistatus.is_some() {
+        return Ok(None);
+    }
+
+    let offset = body_offset as u64;
+    Ok(Some((
+        meta,
+        BodyFile {
+            path: file_path.to_path_buf(),
+            offset,
+            len: file_len - offset,
+        },
+    )))
+}
+
+/// Parse the frontmatter at the start of `content`, whose body may be
+/// binary; only the frontmatter has to be valid text.
+///
+/// Placeholders are only resolved in the frontmatter, the body is sent as it
+/// is on disk. Returns the metadata and the byte offset of the body.
+fn parse_leading_frontmatter(file_path: &Path, content: &[u8]) -> Result<(ResponseMeta, usize)> {
+    let text = match std::str::from_utf8(content) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&content[..e.valid_up_to()]).unwrap(),
+    };
+
+    let (_, body_offset) = parse_frontmatter_header(text)
+        .with_context(|| format!("Failed to parse frontmatter in: {}", file_path.display()))?;
+    let header = interpolate::interpolate_env(&text[..body_offset], file_path);
+    let (meta, _) = parse_frontmatter_header(&header)
+        .with_context(|| format!("Failed to parse frontmatter in: {}", file_path.display()))?;
+    Ok((meta, body_offset))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -257,6 +901,64 @@ status: 200
         assert_eq!(txt_route.content_type, "text/plain");
     }
 
+    #[test]
+    fn test_event_stream_detection() {
+        let temp_dir = TempDir::new().unwrap();
+
+        fs::create_dir(temp_dir.path().join("events")).unwrap();
+        fs::write(temp_dir.path().join("events/GET.sse"), "data: hi\n\n").unwrap();
+        fs::create_dir(temp_dir.path().join("tokens")).unwrap();
+        fs::write(
+            temp_dir.path().join("tokens/POST.txt"),
+            "---\nheaders:\n  content-type: text/event-stream; charset=utf-8\n---\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("GET.json"), "{}").unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        let find = |path: &str| routes.iter().find(|r| r.display_path() == path).unwrap();
+
+        assert_eq!(find("/events").content_type, "text/event-stream");
+        assert!(find("/events").is_event_stream());
+        assert!(find("/tokens").is_event_stream());
+        assert!(!find("/").is_event_stream());
+    }
+
+    #[test]
+    fn test_graphql_operation_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let graphql_dir = temp_dir.path().join("graphql");
+        fs::create_dir(&graphql_dir).unwrap();
+
+        fs::write(graphql_dir.join("POST.json"), "{}").unwrap();
+        fs::write(graphql_dir.join("GetUser.json"), "{}").unwrap();
+        fs::write(
+            graphql_dir.join("GetUser.admin.json"),
+            "---\nvariables:\n  id: \"1\"\n---\n{}",
+        )
+        .unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        assert_eq!(routes.len(), 3);
+        assert!(
+            routes
+                .iter()
+                .all(|r| r.method == HttpMethod::Post && r.display_path() == "/graphql")
+        );
+
+        let operations: Vec<_> = routes
+            .iter()
+            .filter_map(|r| r.graphql_operation.as_deref())
+            .collect();
+        assert_eq!(operations, vec!["GetUser", "GetUser"]);
+
+        let admin = routes
+            .iter()
+            .find(|r| r.response.meta.variables.is_some())
+            .unwrap();
+        assert_eq!(admin.response.meta.variables.as_ref().unwrap()["id"], "1");
+    }
+
     #[test]
     fn test_path_parameters() {
         let temp_dir = TempDir::new().unwrap();
@@ -282,4 +984,278 @@ status: 200
         assert!(!route.matches("/users"));
         assert!(!route.matches("/users/123/extra"));
     }
+
+    #[test]
+    fn test_path_matching_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let users_dir = temp_dir.path().join("Users").join("[id]");
+        fs::create_dir_all(&users_dir).unwrap();
+        fs::write(users_dir.join("GET.json"), "{}").unwrap();
+        fs::write(
+            users_dir.join("DELETE.json"),
+            "---\ntrailing_slash: ignore\ncase_insensitive: false\n---\n",
+        )
+        .unwrap();
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        let get = routes.iter().find(|r| r.method == HttpMethod::Get).unwrap();
+        let delete = routes
+            .iter()
+            .find(|r| r.method == HttpMethod::Delete)
+            .unwrap();
+
+        assert!(get.matches("/Users/Ada/"));
+        assert!(!get.matches("/users/Ada"));
+
+        let strict = PathMatching {
+            trailing_slash: TrailingSlash::Strict,
+            case_insensitive: true,
+            ..Default::default()
+        };
+        assert!(get.matches_with("/users/Ada", strict));
+        assert!(!get.matches_with("/users/Ada/", strict));
+        // Values of parameters keep their case
+        assert_eq!(
+            get.params("/USERS/Ada", strict),
+            [("id".to_string(), "Ada".to_string())]
+        );
+
+        let files = PathMatching {
+            matrix_params: true,
+            ..Default::default()
+        };
+        assert!(get.matches_with("/Users;v=2/1", files));
+        assert!(!get.matches_with("/Users;v=2/1", PathMatching::default()));
+        assert!(get.matches("/%55sers/1"));
+        assert_eq!(
+            get.params("/Users/my%20report;v=2", files),
+            [("id".to_string(), "my report".to_string())]
+        );
+        let raw = PathMatching {
+            percent_decode: false,
+            ..Default::default()
+        };
+        assert_eq!(get.params("/Users/a%2Fb", raw)[0].1, "a%2Fb");
+        assert_eq!(
+            get.params("/Users/a%2Fb", PathMatching::default())[0].1,
+            "a/b"
+        );
+        assert_eq!(
+            matrix_params("/cars;color=dark%20red;used/2024"),
+            [
+                ("color".to_string(), "dark red".to_string()),
+                ("used".to_string(), String::new())
+            ]
+        );
+
+        // Route files override the global options
+        assert!(delete.matches_with("/Users/Ada/", strict));
+        assert!(!delete.matches_with("/users/Ada", strict));
+    }
+
+    #[test]
+    fn test_scan_preserves_discovery_order() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..50 {
+            let dir = temp_dir.path().join(format!("item{}", i));
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join("GET.json"), format!("{}", i)).unwrap();
+        }
+
+        let mut files = Vec::new();
+        collect_files(temp_dir.path(), &mut files).unwrap();
+        let routes = scan_directory(temp_dir.path()).unwrap();
+
+        let expected: Vec<String> = files
+            .iter()
+            .map(|f| fs::read_to_string(f).unwrap())
+            .collect();
+        let actual: Vec<String> = routes
+            .iter()
+            .map(|r| String::from_utf8_lossy(&r.response.body).to_string())
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_rescan_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path();
+        for name in ["a", "b", "c"] {
+            fs::create_dir_all(base.join(name)).unwrap();
+            fs::write(base.join(name).join("GET.json"), name).unwrap();
+        }
+        let mut routes = scan_directory(base).unwrap();
+        let bodies = |routes: &[Route]| -> Vec<String> {
+            routes
+                .iter()
+                .map(|r| String::from_utf8_lossy(&r.response.body).to_string())
+                .collect()
+        };
+        let before = bodies(&routes);
+        let options = ScanOptions::default();
+
+        // A modified file keeps its position, unchanged files are not re-read
+        fs::write(base.join("b/GET.json"), "b2").unwrap();
+        fs::write(base.join("a/GET.json"), "not reloaded").unwrap();
+        let changed = [std::path::absolute(base.join("b/GET.json")).unwrap()];
+        rescan_paths(base, None, &changed, &options, &mut routes).unwrap();
+        let expected: Vec<String> = before
+            .iter()
+            .map(|body| {
+                if body == "b" {
+                    "b2".to_string()
+                } else {
+                    body.clone()
+                }
+            })
+            .collect();
+        assert_eq!(bodies(&routes), expected);
+
+        // Removed directories drop their routes, new files are appended
+        fs::remove_dir_all(base.join("c")).unwrap();
+        fs::create_dir_all(base.join("d/[id]")).unwrap();
+        fs::write(base.join("d/[id]/GET.json"), "d").unwrap();
+        let changed = [base.join("c"), base.join("d"), PathBuf::from("/elsewhere")];
+        rescan_paths(base, None, &changed, &options, &mut routes).unwrap();
+        assert_eq!(routes.len(), 3);
+        assert!(routes.iter().all(|r| !r.source.starts_with(base.join("c"))));
+        assert_eq!(routes[2].display_path(), "/d/:id");
+
+        // Routes of other hosts are left alone
+        rescan_paths(
+            base,
+            Some("api.test"),
+            &[base.join("b")],
+            &options,
+            &mut routes,
+        )
+        .unwrap();
+        assert_eq!(routes.len(), 4);
+        assert_eq!(routes[3].host.as_deref(), Some("api.test"));
+    }
+
+    #[test]
+    fn test_includes() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path();
+        fs::create_dir_all(base.join("partials")).unwrap();
+        fs::create_dir_all(base.join("users")).unwrap();
+        fs::write(base.join("partials/page.json"), "{\"page\": 1}\n").unwrap();
+        fs::write(
+            base.join("users/GET.json"),
+            "---\nstatus: 200\n---\n{\"items\": [], \"meta\": {{> partials/page.json}}}",
+        )
+        .unwrap();
+
+        let mut routes = scan_directory(base).unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(
+            routes[0].response.body,
+            "{\"items\": [], \"meta\": {\"page\": 1}}"
+        );
+        assert_eq!(routes[0].includes, [base.join("partials/page.json")]);
+
+        // Changing the partial reloads the routes including it
+        fs::write(base.join("partials/page.json"), "{\"page\": 2}").unwrap();
+        let changed = [base.join("partials/page.json")];
+        rescan_paths(base, None, &changed, &ScanOptions::default(), &mut routes).unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(
+            routes[0].response.body,
+            "{\"items\": [], \"meta\": {\"page\": 2}}"
+        );
+    }
+
+    #[test]
+    fn test_scan_reports_parse_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("GET.json"), "---\nstatus: 200\n").unwrap();
+
+        assert!(scan_directory(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_large_files_are_streamed() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut content = b"---\nstatus: 201\n---\n".to_vec();
+        content.extend([0xff, 0xfe, 0x00, 0x01].repeat(64));
+        fs::write(temp_dir.path().join("GET.bin"), &content).unwrap();
+        fs::write(temp_dir.path().join("POST.txt"), "small").unwrap();
+
+        let options = ScanOptions {
+            stream_threshold: Some(100),
+            ..Default::default()
+        };
+        let routes = scan_directory_with(temp_dir.path(), &options).unwrap();
+
+        let large = routes.iter().find(|r| r.method == HttpMethod::Get).unwrap();
+        let body_file = large.body_file.as_ref().unwrap();
+        assert_eq!(large.response.meta.status(), 201);
+        assert!(large.response.body.is_empty());
+        assert_eq!(body_file.offset, 20);
+        assert_eq!(body_file.len, 256);
+
+        let small = routes
+            .iter()
+            .find(|r| r.method == HttpMethod::Post)
+            .unwrap();
+        assert!(small.body_file.is_none());
+        assert_eq!(small.response.body, "small");
+    }
+
+    #[test]
+    fn test_webdav_methods() {
+        let temp_dir = TempDir::new().unwrap();
+
+        fs::write(temp_dir.path().join("PROPFIND.xml"), "<multistatus/>").unwrap();
+        fs::write(temp_dir.path().join("mkcol.txt"), "").unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+
+        assert_eq!(routes.len(), 2);
+        assert!(routes.iter().any(|r| r.method == HttpMethod::Propfind));
+        assert!(routes.iter().any(|r| r.method == HttpMethod::Mkcol));
+    }
+
+    #[test]
+    fn test_env_placeholders() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("GET.json"),
+            "---\nheaders:\n  X-Tenant: ${BLENDWERK_TEST_UNSET:-local}\n---\n{\"env\": \"${BLENDWERK_TEST_UNSET:-dev}\"}",
+        )
+        .unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        assert_eq!(routes[0].response.meta.headers["X-Tenant"], *"local");
+        assert_eq!(routes[0].response.body, r#"{"env": "dev"}"#);
+
+        // Unset without fallback: kept as it is instead of failing the scan
+        fs::write(temp_dir.path().join("POST.json"), "${BLENDWERK_TEST_UNSET}").unwrap();
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        let post = routes
+            .iter()
+            .find(|r| r.method == HttpMethod::Post)
+            .unwrap();
+        assert_eq!(post.response.body, "${BLENDWERK_TEST_UNSET}");
+    }
+
+    #[test]
+    fn test_binary_bodies() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut content = b"---\nstatus: 203\n---\n".to_vec();
+        content.extend_from_slice(&[0x89, b'P', b'N', b'G', 0xff, 0x00]);
+        fs::write(temp_dir.path().join("GET.png"), &content).unwrap();
+        fs::write(temp_dir.path().join("POST.bin"), [0xff, 0xfe]).unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        let png = routes.iter().find(|r| r.method == HttpMethod::Get).unwrap();
+        assert_eq!(png.response.meta.status(), 203);
+        assert_eq!(png.response.body, [0x89, b'P', b'N', b'G', 0xff, 0x00][..]);
+        let bin = routes
+            .iter()
+            .find(|r| r.method == HttpMethod::Post)
+            .unwrap();
+        assert_eq!(bin.response.body, [0xff, 0xfe][..]);
+    }
 }
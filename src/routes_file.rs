@@ -0,0 +1,244 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Many routes defined in a single `routes.yaml` instead of one file each.
+//!
+//! Paths are relative to the directory holding the file, so a `routes.yaml`
+//! in `api/` defines routes below `/api`. Its routes are served alongside
+//! the directory-based ones, in the order they are listed.
+
+use crate::frontmatter::{ResponseMeta, build_response};
+use crate::includes;
+use crate::interpolate;
+use crate::routes::{HttpMethod, PathSegment, Route};
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// File names (without directory) of route definition files
+const FILE_NAMES: &[&str] = &["routes.yaml", "routes.yml"];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RoutesFile {
+    routes: Vec<RouteDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteDefinition {
+    method: String,
+    path: String,
+    /// Frontmatter of the response
+    #[serde(default)]
+    meta: Option<ResponseMeta>,
+    /// Defaults to `application/json` for structured bodies and `text/plain`
+    /// for strings
+    content_type: Option<String>,
+    #[serde(default)]
+    body: Body,
+}
+
+/// Body written as text or as YAML structure sent as JSON
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Body {
+    Text(String),
+    Structured(serde_json::Value),
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+/// Check whether a file defines routes rather than being a route file
+pub fn is_routes_file(file_path: &Path) -> bool {
+    file_path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .is_some_and(|name| FILE_NAMES.contains(&name))
+}
+
+/// Parse a `routes.yaml` file into routes
+pub fn parse_routes_file(base_dir: &Path, file_path: &Path) -> Result<Vec<Route>> {
+    let content = fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+    let content = interpolate::interpolate_env(&content, file_path);
+    let routes_file: RoutesFile = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse routes file: {}", file_path.display()))?;
+
+    let modified = fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+
+    // Paths continue the directory structure the file lives in
+    let parent = file_path.parent().unwrap_or(base_dir);
+    let prefix: Vec<PathSegment> = parent
+        .strip_prefix(base_dir)
+        .unwrap_or(Path::new(""))
+        .components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(os_str) => {
+                Some(PathSegment::parse(&os_str.to_string_lossy()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    routes_file
+        .routes
+        .into_iter()
+        .map(|definition| {
+            let display = format!("{} {}", definition.method, definition.path);
+            translate(base_dir, file_path, &prefix, definition)
+                .map(|mut route| {
+                    // The body is as new as the newest file it is made of
+                    route.modified = route
+                        .includes
+                        .iter()
+                        .filter_map(|file| fs::metadata(file).and_then(|m| m.modified()).ok())
+                        .chain(modified)
+                        .max();
+                    route
+                })
+                .with_context(|| format!("Invalid route {} in: {}", display, file_path.display()))
+        })
+        .collect()
+}
+
+fn translate(
+    base_dir: &Path,
+    file_path: &Path,
+    prefix: &[PathSegment],
+    definition: RouteDefinition,
+) -> Result<Route> {
+    let method = HttpMethod::from_str(&definition.method)
+        .filter(|method| *method != HttpMethod::Ws)
+        .ok_or_else(|| anyhow!("unsupported method {}", definition.method))?;
+
+    if !definition.path.starts_with('/') {
+        bail!("path has to start with /");
+    }
+    let mut path_segments = prefix.to_vec();
+    path_segments.extend(
+        definition
+            .path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(PathSegment::parse),
+    );
+
+    let (body, content_type) = match definition.body {
+        Body::Text(text) => (text, "text/plain"),
+        Body::Structured(value) => (value.to_string(), "application/json"),
+    };
+    let content_type = definition
+        .content_type
+        .unwrap_or_else(|| content_type.to_string());
+
+    let mut included = Vec::new();
+    let body = if includes::has_includes(&body) {
+        let (body, files) = includes::resolve(base_dir, &body)?;
+        included = files;
+        body
+    } else {
+        body
+    };
+
+    let response = build_response(definition.meta.unwrap_or_default(), &body)?;
+
+    Ok(Route {
+        method,
+        path_segments,
+        response,
+        content_type,
+        body_file: None,
+        websocket: None,
+        script: None,
+        plugin: None,
+        graphql_operation: None,
+        source: file_path.to_path_buf(),
+        modified: None,
+        host: None,
+        includes: included,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_routes_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path();
+        fs::create_dir_all(base.join("api")).unwrap();
+        let file = base.join("api/routes.yaml");
+        fs::write(
+            &file,
+            r#"
+routes:
+  - method: GET
+    path: /users/[id]
+    body:
+      id: 1
+      tags: [admin]
+  - method: post
+    path: /users
+    meta:
+      status: 201
+      headers:
+        Location: /api/users/2
+  - method: GET
+    path: /
+    content_type: text/html
+    body: <h1>API</h1>
+"#,
+        )
+        .unwrap();
+
+        assert!(is_routes_file(&file));
+        let routes = parse_routes_file(base, &file).unwrap();
+        assert_eq!(routes.len(), 3);
+
+        assert_eq!(routes[0].method, HttpMethod::Get);
+        assert_eq!(routes[0].display_path(), "/api/users/:id");
+        assert_eq!(routes[0].content_type, "application/json");
+        assert_eq!(routes[0].response.body, r#"{"id":1,"tags":["admin"]}"#);
+
+        assert_eq!(routes[1].method, HttpMethod::Post);
+        assert_eq!(routes[1].response.meta.status(), 201);
+        assert_eq!(routes[1].response.body, "");
+
+        assert_eq!(routes[2].display_path(), "/api");
+        assert_eq!(routes[2].content_type, "text/html");
+        assert_eq!(routes[2].response.body, "<h1>API</h1>");
+        assert!(routes.iter().all(|route| route.source == file));
+    }
+
+    #[test]
+    fn test_invalid_routes() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path();
+        let file = base.join("routes.yml");
+
+        fs::write(&file, "routes:\n  - method: FETCH\n    path: /a\n").unwrap();
+        let error = parse_routes_file(base, &file).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid route FETCH /a in:"));
+
+        fs::write(&file, "routes:\n  - method: GET\n    path: a\n").unwrap();
+        assert!(parse_routes_file(base, &file).is_err());
+
+        fs::write(&file, "routes:\n  - method: GET\n    url: /a\n").unwrap();
+        assert!(parse_routes_file(base, &file).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Route files answering from a Rhai script (`GET.rhai`).
+//!
+//! Scripts are compiled when the file is scanned and run for every request
+//! with a `request` object holding `method`, `path`, `params`, `query`,
+//! `headers` (lowercase names), `body` and, for JSON bodies, `json`. A
+//! script returns either a response map `#{status: 201, headers: #{...},
+//! body: ...}` or just the body. String bodies are sent as text, other
+//! values as JSON. Runaway scripts are stopped after a fixed number of
+//! operations.
+
+use crate::frontmatter::HeaderValues;
+use crate::routes::Route;
+use anyhow::{Result, anyhow, bail};
+use axum::http::{HeaderMap, header::CONTENT_TYPE};
+use bytes::Bytes;
+use rhai::{AST, Dynamic, Engine, Scope};
+use serde_json::{Value, json};
+use std::sync::{Arc, LazyLock};
+
+/// Operations a script may run per request
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_expr_depths(64, 64);
+    engine
+});
+
+/// Compiled script of a route file
+#[derive(Debug)]
+pub struct Script(AST);
+
+/// Compile a script, reporting syntax errors with their position
+pub fn compile(source: &str) -> Result<Script> {
+    Ok(Script(ENGINE.compile(source)?))
+}
+
+/// Request as seen by scripts
+#[derive(Debug, Clone)]
+pub struct ScriptRequest {
+    pub method: String,
+    pub path: String,
+    pub params: Vec<(String, String)>,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl ScriptRequest {
+    /// The `request` object of scripts and plugins
+    pub fn to_value(&self) -> Value {
+        let query: Vec<(String, String)> = self
+            .query
+            .as_deref()
+            .and_then(|query| serde_urlencoded::from_str(query).ok())
+            .unwrap_or_default();
+        let headers: serde_json::Map<String, Value> = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    Value::String(String::from_utf8_lossy(value.as_bytes()).into_owned()),
+                )
+            })
+            .collect();
+        json!({
+            "method": self.method,
+            "path": self.path,
+            "params": self
+                .params
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+                .collect::<serde_json::Map<_, _>>(),
+            "query": query
+                .into_iter()
+                .map(|(name, value)| (name, Value::String(value)))
+                .collect::<serde_json::Map<_, _>>(),
+            "headers": headers,
+            "body": String::from_utf8_lossy(&self.body),
+            "json": serde_json::from_slice::<Value>(&self.body).unwrap_or(Value::Null),
+        })
+    }
+}
+
+/// Response produced by a script; unset parts keep those of the route
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptResponse {
+    status: Option<u16>,
+    headers: Vec<(String, String)>,
+    body: Option<Bytes>,
+    content_type: Option<String>,
+}
+
+/// Run the script of a route for a request
+pub async fn run(script: Arc<Script>, request: ScriptRequest) -> Result<ScriptResponse> {
+    tokio::task::spawn_blocking(move || evaluate(&script, &request)).await?
+}
+
+fn evaluate(script: &Script, request: &ScriptRequest) -> Result<ScriptResponse> {
+    let mut scope = Scope::new();
+    scope.push_constant("request", rhai::serde::to_dynamic(request.to_value())?);
+    let result: Dynamic = ENGINE
+        .eval_ast_with_scope(&mut scope, &script.0)
+        .map_err(|e| anyhow!("{}", e))?;
+    response(rhai::serde::from_dynamic(&result)?)
+}
+
+/// Interpret a returned value: an object is a response with `status`,
+/// `headers` and `body`, anything else just the body
+pub fn response(value: Value) -> Result<ScriptResponse> {
+    let Value::Object(mut map) = value else {
+        return Ok(body(value));
+    };
+
+    let mut response = match map.remove("body") {
+        Some(value) => body(value),
+        None => ScriptResponse::default(),
+    };
+    if let Some(status) = map.remove("status") {
+        let status = status
+            .as_u64()
+            .and_then(|status| u16::try_from(status).ok())
+            .filter(|status| (100..1000).contains(status))
+            .ok_or_else(|| anyhow!("status has to be a number between 100 and 999"))?;
+        response.status = Some(status);
+    }
+    if let Some(headers) = map.remove("headers") {
+        let Value::Object(headers) = headers else {
+            bail!("headers have to be a map");
+        };
+        for (name, value) in headers {
+            let value = match value {
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+            if name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()) {
+                response.content_type = Some(value);
+            } else {
+                response.headers.push((name, value));
+            }
+        }
+    }
+    if let Some(key) = map.keys().next() {
+        bail!(
+            "unknown key `{}` in response, expected status, headers or body",
+            key
+        );
+    }
+    Ok(response)
+}
+
+/// Response with just a body, text for strings and JSON for other values
+fn body(value: Value) -> ScriptResponse {
+    let (body, content_type) = match value {
+        Value::Null => (Bytes::new(), None),
+        Value::String(text) => (Bytes::from(text), Some("text/plain")),
+        value => (Bytes::from(value.to_string()), Some("application/json")),
+    };
+    ScriptResponse {
+        body: Some(body),
+        content_type: content_type.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+impl ScriptResponse {
+    /// Replace the route's response with the one of the script
+    pub fn apply(self, route: &mut Route) {
+        let meta = &mut route.response.meta;
+        if let Some(status) = self.status {
+            meta.status = Some(status);
+        }
+        for (name, value) in self.headers {
+            meta.headers.insert(name, HeaderValues::One(value));
+        }
+        if let Some(body) = self.body {
+            meta.chunks = None;
+            meta.events = None;
+            meta.randomize = None;
+            route.content_type = "text/plain".to_string();
+            route.response.body = body;
+            route.body_file = None;
+        }
+        if let Some(content_type) = self.content_type {
+            route.content_type = content_type;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: &str) -> ScriptRequest {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-tenant", "acme".parse().unwrap());
+        ScriptRequest {
+            method: "POST".to_string(),
+            path: "/orders/42".to_string(),
+            params: vec![("id".to_string(), "42".to_string())],
+            query: Some("express=true".to_string()),
+            headers,
+            body: Bytes::from(body.to_string()),
+        }
+    }
+
+    fn run(source: &str, body: &str) -> Result<ScriptResponse> {
+        evaluate(&compile(source)?, &request(body))
+    }
+
+    #[test]
+    fn test_response_map() {
+        let response = run(
+            r#"
+            let total = 0;
+            for item in request.json.items { total += item.price * item.quantity; }
+            #{
+                status: if request.query.express == "true" { 201 } else { 202 },
+                headers: #{ "X-Tenant": request.headers["x-tenant"] },
+                body: #{ id: parse_int(request.params.id), total: total },
+            }
+            "#,
+            r#"{"items": [{"price": 5, "quantity": 2}, {"price": 1, "quantity": 3}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response,
+            ScriptResponse {
+                status: Some(201),
+                headers: vec![("X-Tenant".to_string(), "acme".to_string())],
+                body: Some(Bytes::from(r#"{"id":42,"total":13}"#)),
+                content_type: Some("application/json".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_plain_bodies() {
+        let response = run(r#"`Hello ${request.method} ${request.body}`"#, "world").unwrap();
+        assert_eq!(response.body.unwrap(), "Hello POST world");
+        assert_eq!(response.content_type.as_deref(), Some("text/plain"));
+
+        let response = run("[1, 2]", "").unwrap();
+        assert_eq!(response.body.unwrap(), "[1,2]");
+        assert_eq!(run("()", "").unwrap().body.unwrap(), "");
+        assert_eq!(run("#{ status: 204 }", "").unwrap().body, None);
+    }
+
+    #[test]
+    fn test_script_errors() {
+        assert!(compile("let x = ;").is_err());
+        assert!(run("#{ status: 42 }", "").is_err());
+        assert!(run("#{ stauts: 200 }", "").is_err());
+        assert!(run("loop {}", "").is_err());
+        assert!(run("request.missing.field", "").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Expected order of requests across routes (`--sequence`).
+//!
+//! A sequence file lists requests as `METHOD /path` steps, with `[param]`
+//! segments matching any value. Requests matching one of the steps have to
+//! arrive in the listed order; other requests aren't affected. Requests out
+//! of order are either flagged (served, logged as warning and reported at
+//! `/__blendwerk/sequence`) or rejected with 409. Once the last step was
+//! requested, the sequence starts over.
+
+use anyhow::{Context, bail};
+use axum::http::Method;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// What happens to requests that arrive out of order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Serve them, but warn and report them
+    #[default]
+    Flag,
+    /// Answer them with 409 Conflict
+    Reject,
+}
+
+#[derive(Debug, Deserialize)]
+struct SequenceFile {
+    #[serde(default)]
+    mode: Mode,
+    steps: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    method: String,
+    segments: Vec<String>,
+}
+
+impl Step {
+    fn parse(step: &str) -> anyhow::Result<Self> {
+        let Some((method, path)) = step.trim().split_once(char::is_whitespace) else {
+            bail!("Step {:?} is not of the form `METHOD /path`", step);
+        };
+        Ok(Self {
+            method: method.to_ascii_uppercase(),
+            segments: segments(path.trim()).map(str::to_string).collect(),
+        })
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        let request: Vec<&str> = segments(path).collect();
+        self.method == method.as_str()
+            && request.len() == self.segments.len()
+            && request
+                .iter()
+                .zip(&self.segments)
+                .all(|(segment, pattern)| {
+                    (pattern.starts_with('[') && pattern.ends_with(']')) || pattern == segment
+                })
+    }
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} /{}", self.method, self.segments.join("/"))
+    }
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+#[derive(Debug)]
+pub struct Sequence {
+    steps: Vec<Step>,
+    mode: Mode,
+    progress: Mutex<Progress>,
+}
+
+#[derive(Debug, Default)]
+struct Progress {
+    /// Index of the next expected step
+    position: usize,
+    completed: u64,
+    violations: Vec<Violation>,
+}
+
+/// A request that arrived out of order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub method: String,
+    pub path: String,
+    /// The step that was expected instead
+    pub expected: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Out of sequence: expected {}, got {} {}",
+            self.expected, self.method, self.path
+        )
+    }
+}
+
+/// State of the sequence for `/__blendwerk/sequence`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub mode: Mode,
+    pub steps: usize,
+    /// Number of steps done in the current run
+    pub position: usize,
+    pub expected: String,
+    /// Number of runs through all steps
+    pub completed: u64,
+    pub violations: Vec<Violation>,
+}
+
+impl Sequence {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read sequence file {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid sequence file {}", path.display()))
+    }
+
+    fn parse(content: &str) -> anyhow::Result<Self> {
+        let file: SequenceFile = serde_yaml::from_str(content)?;
+        if file.steps.is_empty() {
+            bail!("The sequence has no steps");
+        }
+        Ok(Self {
+            steps: file
+                .steps
+                .iter()
+                .map(|step| Step::parse(step))
+                .collect::<anyhow::Result<_>>()?,
+            mode: file.mode,
+            progress: Mutex::default(),
+        })
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Advance the sequence with a request, returning the violation if it
+    /// matches a step other than the expected one.
+    ///
+    /// Requests out of order don't advance the sequence.
+    pub fn check(&self, method: &Method, path: &str) -> Option<Violation> {
+        if !self.steps.iter().any(|step| step.matches(method, path)) {
+            return None;
+        }
+
+        let mut progress = self.progress.lock().unwrap();
+        let expected = &self.steps[progress.position];
+        if expected.matches(method, path) {
+            progress.position += 1;
+            if progress.position == self.steps.len() {
+                progress.position = 0;
+                progress.completed += 1;
+            }
+            return None;
+        }
+
+        let violation = Violation {
+            method: method.to_string(),
+            path: path.to_string(),
+            expected: expected.to_string(),
+        };
+        tracing::warn!("{}", violation);
+        progress.violations.push(violation.clone());
+        Some(violation)
+    }
+
+    pub fn report(&self) -> Report {
+        let progress = self.progress.lock().unwrap();
+        Report {
+            mode: self.mode,
+            steps: self.steps.len(),
+            position: progress.position,
+            expected: self.steps[progress.position].to_string(),
+            completed: progress.completed,
+            violations: progress.violations.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYMENT: &str = "
+mode: reject
+steps:
+  - POST /payments
+  - get /payments/[id]
+  - POST /payments/[id]/capture
+";
+
+    #[test]
+    fn test_steps_in_order() {
+        let sequence = Sequence::parse(PAYMENT).unwrap();
+        assert_eq!(sequence.mode(), Mode::Reject);
+
+        // Requests that aren't part of the sequence are always fine
+        assert_eq!(sequence.check(&Method::GET, "/health"), None);
+
+        assert_eq!(sequence.check(&Method::POST, "/payments"), None);
+        assert_eq!(sequence.check(&Method::GET, "/payments/42"), None);
+        assert_eq!(sequence.check(&Method::POST, "/payments/42/capture/"), None);
+        assert_eq!(sequence.report().completed, 1);
+        assert_eq!(sequence.report().expected, "POST /payments");
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let sequence = Sequence::parse(PAYMENT).unwrap();
+        assert_eq!(sequence.check(&Method::POST, "/payments"), None);
+
+        let violation = sequence.check(&Method::POST, "/payments/42/capture");
+        assert_eq!(
+            violation.unwrap().to_string(),
+            "Out of sequence: expected GET /payments/[id], got POST /payments/42/capture"
+        );
+
+        // The sequence stays at the expected step
+        assert_eq!(sequence.check(&Method::GET, "/payments/42"), None);
+        let report = sequence.report();
+        assert_eq!(report.position, 2);
+        assert_eq!(report.violations.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_steps() {
+        assert!(Sequence::parse("steps: []").is_err());
+        assert!(Sequence::parse("steps: [/payments]").is_err());
+        assert_eq!(
+            Sequence::parse("steps: [GET /]").unwrap().mode(),
+            Mode::Flag
+        );
+    }
+}
@@ -6,76 +6,475 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use crate::request_logger::{self, RequestLogger};
-use crate::routes::{HttpMethod, Route};
+use crate::auth::Auth;
+use crate::callbacks::{self, TriggeringRequest};
+use crate::chunks;
+use crate::conditional::Validators;
+use crate::connection::{Connection, ConnectionAcceptor, ConnectionListener};
+use crate::control_headers;
+use crate::cors::Cors;
+use crate::coverage::Coverage;
+use crate::duplicates::Duplicates;
+use crate::echo;
+use crate::fixture_changes::{self, ChangedFixtures};
+use crate::forwarded::{Forwarded, TrustedProxies};
+use crate::frontmatter::{ResponseMode, RouteHttpVersion};
+use crate::graphql::GraphqlRequest;
+use crate::index_listing;
+use crate::logging;
+use crate::mock_server::Stub;
+use crate::mock_server::{ReceivedRequest, ReceivedRequests};
+use crate::oidc::{self, Oidc};
+use crate::openapi;
+use crate::plugin;
+use crate::progression::Progressions;
+use crate::promote;
+use crate::reload_events::ReloadEvents;
+use crate::request_id::RequestIds;
+use crate::request_logger::{self, LoggedBody, RequestLogger};
+use crate::routes::{self, BodyFile, HttpMethod, PathMatching, Route, TrailingSlash};
+use crate::scripting::{self, ScriptRequest};
+use crate::sequence::{self, Sequence};
+use crate::session::Sessions;
+use crate::sse;
+use crate::stats;
+use crate::tags::{TagSelection, Tags};
+use crate::transfer;
+use crate::verification::RequestMatcher;
+use crate::vhost::{self, VirtualHost};
+use crate::websocket;
+use anyhow::Context;
 use axum::{
     Router,
     body::Body,
-    extract::State,
-    http::{HeaderName, HeaderValue, Method, Request, StatusCode, request::Parts},
-    response::Response,
-    routing::any,
+    extract::{ConnectInfo, FromRequestParts, Query, RawQuery, State, ws::WebSocketUpgrade},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode, Uri, Version,
+        header::{
+            ACCEPT, ALLOW, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG,
+            LAST_MODIFIED, LOCATION, SET_COOKIE, UPGRADE, WWW_AUTHENTICATE,
+        },
+        request::Parts,
+    },
+    response::{IntoResponse, Json, Response},
+    routing::{any, get, post},
 };
-use axum_server::{Handle, tls_rustls::RustlsConfig};
+use axum_server::{
+    Handle,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use bytes::Bytes;
+use clap::ValueEnum;
+use serde::Deserialize;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::SeekFrom;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio::net::TcpListener;
 use tokio::sync::{RwLock, watch};
 use tokio::time::sleep;
-use tower_http::trace::{self, TraceLayer};
-use tracing::{Level, info};
+use tokio_util::io::ReaderStream;
+use tracing::Instrument;
 
 pub type SharedRoutes = Arc<RwLock<Vec<Route>>>;
 pub type ShutdownSignal = watch::Receiver<bool>;
 
+/// Whether any of the shared routes reads request bodies (see
+/// [`Route::reads_body`]).
+///
+/// Kept next to [`SharedRoutes`] and updated by everything that changes
+/// them, so requests don't have to look through all routes.
+#[derive(Debug, Clone, Default)]
+pub struct RoutesReadBodies(Arc<AtomicBool>);
+
+impl RoutesReadBodies {
+    /// Flag for the current `routes`, assumed set while they are locked
+    pub fn of(routes: &SharedRoutes) -> Self {
+        let flag = Self::default();
+        match routes.try_read() {
+            Ok(routes) => flag.update(&routes),
+            Err(_) => flag.0.store(true, Ordering::Relaxed),
+        }
+        flag
+    }
+
+    /// Recompute the flag after `routes` changed
+    pub fn update(&self, routes: &[Route]) {
+        self.0
+            .store(routes.iter().any(Route::reads_body), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 pub struct AppState {
     pub routes: SharedRoutes,
+    pub routes_read_bodies: RoutesReadBodies,
     pub request_logger: Option<RequestLogger>,
+    pub shutdown: ShutdownSignal,
+    pub http_version: HttpVersion,
+    pub cors: Option<Cors>,
+    pub limits: RequestLimits,
+    /// Time connections on the HTTP and HTTPS ports wait before being read
+    pub accept_delay: Option<Duration>,
+    pub oidc: Option<Oidc>,
+    /// States of the sessions of `session:` routes
+    pub sessions: Sessions,
+    /// Requests counted per path for `progression:` routes
+    pub progressions: Progressions,
+    /// Tags whose routes are served (`--enable-tags`, `--disable-tags`)
+    pub tags: Tags,
+    /// Conditional GET for routes that don't set `etag:` themselves
+    pub etag: bool,
+    pub error_format: ErrorFormat,
+    /// Flags identical requests within `--duplicate-window`
+    pub duplicates: Option<Duplicates>,
+    /// Expected order of requests given with `--sequence`
+    pub sequence: Option<Sequence>,
+    /// Proxies whose `Forwarded`/`X-Forwarded-*` headers are believed
+    pub trusted_proxies: TrustedProxies,
+    /// Prefix all requests are expected under, e.g. `/mocks`; stripped
+    /// before matching
+    pub base_path: Option<String>,
+    /// Header and policy of correlation IDs
+    pub request_ids: Option<RequestIds>,
+    /// Start of `chaos_schedule` timelines
+    pub started: Instant,
+    /// Routes changed by the last reloads that haven't answered yet, with
+    /// `--mark-changed-fixtures`
+    pub changed_fixtures: Option<Arc<ChangedFixtures>>,
+    /// Answer browsers requesting paths without route file with an HTML
+    /// index of the routes below them
+    pub index_listing: bool,
+    /// Let `X-Blendwerk-Status`/`X-Blendwerk-Delay` override the matched
+    /// route's status and delay
+    pub control_headers: bool,
+    /// Trailing slash and case handling of routes that don't set their own
+    pub path_matching: PathMatching,
+    /// Mock directory `POST /__blendwerk/promote` writes route files to,
+    /// with `--promote-endpoint`
+    pub promote_dir: Option<PathBuf>,
+    /// Hits per route and unmatched requests, with `--coverage`
+    pub coverage: Option<Coverage>,
+    /// Hosts with their own mock directory (`--vhost`)
+    pub vhosts: Vec<VirtualHost>,
+    /// Requests kept for [`crate::MockServer::received_requests`] and
+    /// `/__blendwerk/wait-for-request`
+    pub received_requests: Option<Arc<ReceivedRequests>>,
+    /// Announces hot reloads at `/__blendwerk/events` and to the webhook
+    pub reload_events: Arc<ReloadEvents>,
+}
+
+impl AppState {
+    /// State serving `routes` until `shutdown` fires, with every option
+    /// off; set others with struct update syntax
+    pub fn new(routes: SharedRoutes, shutdown: ShutdownSignal) -> Self {
+        Self {
+            routes_read_bodies: RoutesReadBodies::of(&routes),
+            routes,
+            request_logger: None,
+            shutdown,
+            http_version: HttpVersion::default(),
+            cors: None,
+            limits: RequestLimits::default(),
+            accept_delay: None,
+            oidc: None,
+            sessions: Sessions::default(),
+            progressions: Progressions::default(),
+            tags: Tags::default(),
+            etag: false,
+            error_format: ErrorFormat::default(),
+            duplicates: None,
+            sequence: None,
+            trusted_proxies: TrustedProxies::default(),
+            base_path: None,
+            request_ids: None,
+            started: Instant::now(),
+            changed_fixtures: None,
+            index_listing: false,
+            control_headers: false,
+            path_matching: PathMatching::default(),
+            promote_dir: None,
+            coverage: None,
+            vhosts: Vec::new(),
+            received_requests: None,
+            reload_events: Arc::default(),
+        }
+    }
+
+    /// `--base-path` or the empty string, to prefix absolute paths with
+    fn base_path_or_root(&self) -> &str {
+        self.base_path.as_deref().unwrap_or_default()
+    }
+}
+
+/// Limits on the request head, answered with 414 and 431, and on the body,
+/// answered with 413.
+///
+/// The head limits apply on top of hyper's own limits and can only be lower.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestLimits {
+    /// Longest accepted request target (path and query) in bytes
+    pub max_uri_length: Option<usize>,
+    /// Most accepted bytes of all header names and values together
+    pub max_header_bytes: Option<usize>,
+    /// Most accepted body bytes, by `Content-Length` or as read
+    pub max_body_bytes: Option<usize>,
+}
+
+impl RequestLimits {
+    /// Read the whole request body, refusing bodies over `max_body_bytes`
+    /// with 413 and bodies that fail to arrive with 400
+    async fn read_body(&self, parts: &Parts, body: Body) -> Result<Bytes, ResponseBuilder> {
+        let too_large = |max: usize| {
+            ResponseBuilder::plain_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+                format!("Request body too large: at most {} bytes allowed", max),
+            )
+        };
+
+        let declared = parts
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if let (Some(max), Some(length)) = (self.max_body_bytes, declared)
+            && length > max as u64
+        {
+            return Err(too_large(max));
+        }
+
+        match axum::body::to_bytes(body, self.max_body_bytes.unwrap_or(usize::MAX)).await {
+            Ok(body) => Ok(body),
+            Err(e)
+                if std::error::Error::source(&e)
+                    .is_some_and(|source| source.is::<http_body_util::LengthLimitError>()) =>
+            {
+                Err(too_large(self.max_body_bytes.unwrap_or(usize::MAX)))
+            }
+            Err(e) => {
+                tracing::warn!("Failed to read request body: {}", e);
+                Err(ResponseBuilder::plain_error(
+                    StatusCode::BAD_REQUEST,
+                    "unreadable_body",
+                    format!("Failed to read request body: {}", e),
+                ))
+            }
+        }
+    }
+
+    fn check(&self, parts: &Parts) -> Option<ResponseBuilder> {
+        let uri_length = parts
+            .uri
+            .path_and_query()
+            .map_or(0, |target| target.as_str().len());
+        if let Some(max) = self.max_uri_length
+            && uri_length > max
+        {
+            return Some(ResponseBuilder::plain_error(
+                StatusCode::URI_TOO_LONG,
+                "uri_too_long",
+                format!(
+                    "URI too long: {} bytes, at most {} allowed",
+                    uri_length, max
+                ),
+            ));
+        }
+
+        let header_bytes: usize = parts
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        if let Some(max) = self.max_header_bytes
+            && header_bytes > max
+        {
+            return Some(ResponseBuilder::plain_error(
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                "header_fields_too_large",
+                format!(
+                    "Request header fields too large: {} bytes, at most {} allowed",
+                    header_bytes, max
+                ),
+            ));
+        }
+
+        None
+    }
+}
+
+/// Response extension naming the file a response was served from
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchedRoute(pub PathBuf);
+
+/// HTTP versions the servers answer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum HttpVersion {
+    /// HTTP/1.1 and HTTP/2 (negotiated via ALPN on HTTPS, h2c with prior
+    /// knowledge on HTTP)
+    #[default]
+    Auto,
+    /// HTTP/1.1 only
+    #[value(name = "1.1")]
+    Http1,
+    /// HTTP/2 only
+    #[value(name = "2")]
+    Http2,
+}
+
+impl HttpVersion {
+    fn accepts(self, version: Version) -> bool {
+        match self {
+            // HTTP/3 has its own listener and isn't affected by the setting
+            _ if version == Version::HTTP_3 => true,
+            Self::Auto => true,
+            Self::Http1 => version <= Version::HTTP_11,
+            Self::Http2 => version == Version::HTTP_2,
+        }
+    }
+
+    fn alpn_protocols(self) -> Vec<Vec<u8>> {
+        match self {
+            Self::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            Self::Http1 => vec![b"http/1.1".to_vec()],
+            Self::Http2 => vec![b"h2".to_vec()],
+        }
+    }
+}
+
+/// Body format of the errors blendwerk answers itself (404, 405, 414, ...)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// The message as plain text
+    #[default]
+    Text,
+    /// `{"error": {"status": 404, "code": "route_not_found", "message": "..."}}`
+    Json,
+}
+
+/// Error blendwerk generated itself, with a machine-readable code
+#[derive(Debug, Clone, PartialEq)]
+struct GeneratedError {
+    code: &'static str,
+    message: String,
 }
 
-fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
+pub fn create_router(state: Arc<AppState>) -> Router {
+    let admin = |path: &str| format!("{}/__blendwerk/{}", state.base_path_or_root(), path);
+    let mut router = Router::new()
+        .route(&admin("openapi.json"), get(openapi_handler))
+        .route(&admin("routes"), get(routes_handler))
+        .route(
+            &admin("stubs"),
+            post(add_stub_handler).delete(clear_stubs_handler),
+        )
+        .route(&admin("memory"), get(memory_handler))
+        .route(&admin("request-log"), get(request_log_handler))
+        .route(&admin("duplicates"), get(duplicates_handler))
+        .route(&admin("sequence"), get(sequence_handler))
+        .route(&admin("tags"), get(tags_handler).put(set_tags_handler))
+        .route(
+            &admin("progression"),
+            get(progression_handler).delete(reset_progression_handler),
+        )
+        .route(&admin("coverage"), get(coverage_handler))
+        .route(&admin("events"), get(events_handler));
+    if state.received_requests.is_some() {
+        router = router
+            .route(&admin("wait-for-request"), get(wait_for_request_handler))
+            .route(
+                &admin("requests"),
+                get(requests_handler).delete(clear_requests_handler),
+            );
+    }
+    // Writes into the mock directory, so only served when asked for
+    if state.promote_dir.is_some() {
+        router = router.route(&admin("promote"), post(promote_handler));
+    }
+    router
         .route("/{*path}", any(handler))
         .route("/", any(handler))
         .with_state(state)
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
-                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
-        )
+}
+
+/// Bind a listening socket on all interfaces.
+///
+/// With `reuse_port`, several sockets can be bound to the same port and the
+/// kernel distributes incoming connections between them.
+pub fn bind_listener(port: u16, reuse_port: bool) -> anyhow::Result<std::net::TcpListener> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
+    // Allows restarting while old connections linger in TIME_WAIT, like
+    // tokio's own listeners do. On Windows the option would let another
+    // process bind the port we are listening on.
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+
+    if reuse_port {
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        #[cfg(not(unix))]
+        anyhow::bail!("Sharing a port between listeners requires SO_REUSEPORT (unix only)");
+    }
+
+    socket
+        .bind(&addr.into())
+        .with_context(|| format!("Failed to bind {}", addr))?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+
+    Ok(socket.into())
 }
 
 pub async fn run_http_server(
     state: Arc<AppState>,
-    port: u16,
+    listener: std::net::TcpListener,
     mut shutdown: ShutdownSignal,
 ) -> anyhow::Result<()> {
+    let accept_delay = state.accept_delay;
     let router = create_router(state);
+    let listener = TcpListener::from_std(listener)?;
 
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = TcpListener::bind(&addr).await?;
-
-    info!("HTTP server listening on http://{}", addr);
-
-    axum::serve(listener, router)
-        .with_graceful_shutdown(async move {
-            let _ = shutdown.changed().await;
-        })
-        .await?;
+    axum::serve(
+        ConnectionListener::new(listener, accept_delay),
+        router.into_make_service_with_connect_info::<Connection>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = shutdown.changed().await;
+    })
+    .await?;
 
     Ok(())
 }
 
 pub async fn run_https_server(
     state: Arc<AppState>,
-    port: u16,
+    listener: std::net::TcpListener,
     tls_config: RustlsConfig,
     mut shutdown: ShutdownSignal,
 ) -> anyhow::Result<()> {
+    // Only offer the configured versions during the TLS handshake. The
+    // shared config is updated rather than copied, so certificate reloads
+    // reach this listener
+    let mut server_config = (*tls_config.get_inner()).clone();
+    server_config.alpn_protocols = state.http_version.alpn_protocols();
+    tls_config.reload_from_config(Arc::new(server_config));
+    let acceptor = ConnectionAcceptor {
+        accept_delay: state.accept_delay,
+    };
+
     let router = create_router(state);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
     let handle = Handle::new();
 
     // Spawn task to handle shutdown
@@ -85,9 +484,8 @@ pub async fn run_https_server(
         shutdown_handle.graceful_shutdown(Some(Duration::from_secs(1)));
     });
 
-    info!("HTTPS server listening on https://{}", addr);
-
-    axum_server::bind_rustls(addr, tls_config)
+    axum_server::from_tcp(listener)
+        .acceptor(RustlsAcceptor::new(tls_config).acceptor(acceptor))
         .handle(handle)
         .serve(router.into_make_service())
         .await?;
@@ -95,185 +493,2385 @@ pub async fn run_https_server(
     Ok(())
 }
 
+/// Serve the given listeners on a dedicated thread with its own
+/// single-threaded runtime, so requests never migrate between cores
+pub fn spawn_core_thread(
+    index: usize,
+    state: Arc<AppState>,
+    http_listener: Option<std::net::TcpListener>,
+    https_listener: Option<(std::net::TcpListener, RustlsConfig)>,
+    shutdown: ShutdownSignal,
+) -> anyhow::Result<thread::JoinHandle<()>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    let handle = thread::Builder::new()
+        .name(format!("blendwerk-core-{}", index))
+        .spawn(move || {
+            runtime.block_on(async move {
+                let http = async {
+                    if let Some(listener) = http_listener {
+                        run_http_server(state.clone(), listener, shutdown.clone()).await?;
+                    }
+                    anyhow::Ok(())
+                };
+                let https = async {
+                    if let Some((listener, tls_config)) = https_listener {
+                        run_https_server(state.clone(), listener, tls_config, shutdown.clone())
+                            .await?;
+                    }
+                    anyhow::Ok(())
+                };
+
+                let (http, https) = tokio::join!(http, https);
+                if let Err(e) = http.and(https) {
+                    tracing::error!("Server error on core thread {}: {}", index, e);
+                }
+            });
+        })?;
+
+    Ok(handle)
+}
+
+/// Serve an OpenAPI document generated from the currently loaded routes
+async fn openapi_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let routes = state.routes.read().await;
+    Json(openapi::generate(&routes))
+}
+
+/// List the loaded routes and the files or stubs they come from
+async fn routes_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let routes = state.routes.read().await;
+    let routes: Vec<_> = routes
+        .iter()
+        .map(|route| {
+            serde_json::json!({
+                "method": route.method.as_str(),
+                "path": route.display_path(),
+                "host": route.host,
+                "source": route.source,
+            })
+        })
+        .collect();
+    Json(routes)
+}
+
+#[derive(Debug, Deserialize)]
+struct StubQuery {
+    method: String,
+    path: String,
+    content_type: Option<String>,
+}
+
+/// Answer requests like the posted route file (frontmatter and body), ahead
+/// of all routes, until the stubs are cleared or the tree is fully rescanned
+async fn add_stub_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StubQuery>,
+    body: Bytes,
+) -> Response<Body> {
+    let stub = std::str::from_utf8(&body)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| {
+            Stub::from_route_file(
+                &query.method,
+                &query.path,
+                query
+                    .content_type
+                    .unwrap_or_else(|| "text/plain".to_string()),
+                content,
+            )
+        });
+    match stub {
+        Ok(stub) => {
+            tracing::info!("Added stub {} {}", query.method, query.path);
+            let mut routes = state.routes.write().await;
+            routes.insert(0, stub.into_route());
+            state.routes_read_bodies.update(&routes);
+            StatusCode::CREATED.into_response()
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": format!("{:#}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Remove all stubs added at runtime
+async fn clear_stubs_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut routes = state.routes.write().await;
+    let count = routes.len();
+    routes.retain(|route| !Stub::is_stub(route));
+    state.routes_read_bodies.update(&routes);
+    Json(serde_json::json!({ "removed": count - routes.len() }))
+}
+
+/// Report the memory consumed by the bodies of the loaded routes
+async fn memory_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let routes = state.routes.read().await;
+    Json(stats::memory_report(&routes))
+}
+
+/// Report the request log queue and how many requests were written or dropped
+async fn request_log_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.request_logger {
+        Some(logger) => Json(serde_json::json!({
+            "enabled": true,
+            "stats": logger.stats(),
+        })),
+        None => Json(serde_json::json!({ "enabled": false })),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PromoteOptions {
+    #[serde(default)]
+    force: bool,
+}
+
+/// Write a route file answering like the posted request log entry
+async fn promote_handler(
+    State(state): State<Arc<AppState>>,
+    Query(options): Query<PromoteOptions>,
+    body: Bytes,
+) -> Response<Body> {
+    let Some(directory) = &state.promote_dir else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let written = serde_json::from_slice(&body)
+        .map_err(anyhow::Error::from)
+        .and_then(|entry| promote::promote_entry(&entry, directory, options.force));
+    match written {
+        Ok(file) => {
+            let file = file.strip_prefix(directory).unwrap_or(&file);
+            tracing::info!("Promoted request log entry to {}", file.display());
+            (
+                StatusCode::CREATED,
+                Json(serde_json::json!({ "file": file })),
+            )
+                .into_response()
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": format!("{:#}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Time `/__blendwerk/wait-for-request` waits without `timeout` parameter
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest `timeout` `/__blendwerk/wait-for-request` accepts
+const MAX_WAIT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Answer with the first received request matching the query parameters,
+/// waiting up to `timeout` milliseconds for it to arrive
+async fn wait_for_request_handler(
+    State(state): State<Arc<AppState>>,
+    RawQuery(query): RawQuery,
+) -> Response<Body> {
+    let Some(received) = &state.received_requests else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response()
+    };
+
+    let mut params: Vec<(String, String)> =
+        match serde_urlencoded::from_str(query.as_deref().unwrap_or_default()) {
+            Ok(params) => params,
+            Err(e) => return bad_request(e.to_string()),
+        };
+    let mut timeout = DEFAULT_WAIT_TIMEOUT;
+    if let Some(index) = params.iter().position(|(name, _)| name == "timeout") {
+        match params.remove(index).1.parse() {
+            Ok(millis) => timeout = Duration::from_millis(millis),
+            Err(_) => return bad_request("timeout has to be milliseconds".to_string()),
+        }
+    }
+    let matcher = match RequestMatcher::from_params(&params) {
+        Ok(matcher) => matcher,
+        Err(message) => return bad_request(message),
+    };
+
+    if timeout > MAX_WAIT_TIMEOUT {
+        return bad_request(format!(
+            "timeout may be at most {} ms",
+            MAX_WAIT_TIMEOUT.as_millis()
+        ));
+    }
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut shutdown = state.shutdown.clone();
+    tokio::select! {
+        waited = received.wait_for(&matcher, Some(deadline)) => match waited {
+            Ok(request) => Json(request).into_response(),
+            // Not 408: clients and proxies retry that on their own
+            Err(requests) => (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "error": format!("no request {} within {:?}", matcher, timeout),
+                    "received": requests.len(),
+                })),
+            )
+                .into_response(),
+        },
+        _ = shutdown.wait_for(|stopped| *stopped) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+/// List the received requests, oldest first
+async fn requests_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let requests = state
+        .received_requests
+        .as_ref()
+        .map(|received| received.snapshot())
+        .unwrap_or_default();
+    Json(requests)
+}
+
+/// Forget the requests received so far
+async fn clear_requests_handler(State(state): State<Arc<AppState>>) -> StatusCode {
+    if let Some(received) = &state.received_requests {
+        received.clear();
+    }
+    StatusCode::NO_CONTENT
+}
+
+/// Report the requests repeated within `--duplicate-window`
+async fn duplicates_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.duplicates {
+        Some(duplicates) => Json(serde_json::json!({
+            "enabled": true,
+            "report": duplicates.report(),
+        })),
+        None => Json(serde_json::json!({ "enabled": false })),
+    }
+}
+
+/// Report how often each route answered and the requests none matched
+async fn coverage_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.coverage {
+        Some(coverage) => {
+            let routes = state.routes.read().await;
+            Json(serde_json::json!({
+                "enabled": true,
+                "report": coverage.report(&routes),
+            }))
+        }
+        None => Json(serde_json::json!({ "enabled": false })),
+    }
+}
+
+/// Stream an event for every hot reload until the server shuts down
+async fn events_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [
+            (CONTENT_TYPE, sse::CONTENT_TYPE),
+            (CACHE_CONTROL, "no-cache"),
+        ],
+        state.reload_events.stream(state.shutdown.clone()),
+    )
+}
+
+/// Report the enabled and disabled tags and those used by routes
+async fn tags_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let known: BTreeSet<String> = state
+        .routes
+        .read()
+        .await
+        .iter()
+        .flat_map(|route| route.response.meta.tags.iter().cloned())
+        .collect();
+    let selection = state.tags.selection();
+    Json(serde_json::json!({
+        "enabled": selection.enabled,
+        "disabled": selection.disabled,
+        "known": known,
+    }))
+}
+
+/// Replace the enabled and disabled tags
+async fn set_tags_handler(State(state): State<Arc<AppState>>, body: Bytes) -> Response<Body> {
+    match serde_json::from_slice::<TagSelection>(&body) {
+        Ok(selection) => {
+            tracing::info!(
+                "Tags changed: enabled {:?}, disabled {:?}",
+                selection.enabled,
+                selection.disabled
+            );
+            state.tags.set(selection.clone());
+            Json(selection).into_response()
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Report the requests counted for `progression:` routes
+async fn progression_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "requests": state.progressions.snapshot() }))
+}
+
+#[derive(Debug, Deserialize)]
+struct ResetProgressionQuery {
+    path: Option<String>,
+}
+
+/// Start the progressions of `?path=` (or all paths) over
+async fn reset_progression_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ResetProgressionQuery>,
+) -> impl IntoResponse {
+    let reset = state.progressions.reset(query.path.as_deref());
+    Json(serde_json::json!({ "reset": reset }))
+}
+
+/// Report the progress through the `--sequence` and requests out of order
+async fn sequence_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.sequence {
+        Some(sequence) => Json(serde_json::json!({
+            "enabled": true,
+            "report": sequence.report(),
+        })),
+        None => Json(serde_json::json!({ "enabled": false })),
+    }
+}
+
 /// Response builder that encapsulates both HTTP response and logging info
 struct ResponseBuilder {
     response: Response<Body>,
     info: request_logger::ResponseInfo,
     matched_route: Option<String>,
+    /// The route's `trace_fields`, for the access log
+    trace_fields: BTreeMap<String, String>,
     request_info: Option<request_logger::RequestInfo>,
+    /// Set for errors blendwerk generated itself, to render them per
+    /// `--error-format`
+    error: Option<GeneratedError>,
 }
 
 impl ResponseBuilder {
-    fn method_not_allowed() -> Self {
-        let body = "Method not allowed";
+    /// 405, with an `Allow` header unless `allowed` is empty
+    fn method_not_allowed(allowed: &[HttpMethod]) -> Self {
+        let mut builder = Self::plain_error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            "Method not allowed".to_string(),
+        );
+        if !allowed.is_empty() {
+            let allow = allow_header(allowed);
+            builder
+                .response
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::try_from(&allow).unwrap());
+            builder.info.headers.insert("allow".to_string(), allow);
+        }
+        builder
+    }
+
+    /// Answer to OPTIONS requests for paths without an OPTIONS file
+    fn options(allowed: &[HttpMethod]) -> Self {
+        let allow = allow_header(allowed);
         Self {
             response: Response::builder()
-                .status(StatusCode::METHOD_NOT_ALLOWED)
-                .body(Body::from(body))
+                .status(StatusCode::NO_CONTENT)
+                .header(ALLOW, &allow)
+                .body(Body::empty())
+                .unwrap(),
+            info: request_logger::ResponseInfo {
+                status: 204,
+                headers: [("allow".to_string(), allow)].into(),
+                body: Bytes::new().into(),
+                delay_ms: 0,
+                fault: None,
+                transfer: None,
+            },
+            matched_route: None,
+            trace_fields: BTreeMap::new(),
+            request_info: None,
+            error: None,
+        }
+    }
+
+    fn cors_preflight() -> Self {
+        Self {
+            response: Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .body(Body::empty())
                 .unwrap(),
             info: request_logger::ResponseInfo {
-                status: 405,
+                status: 204,
                 headers: std::collections::HashMap::new(),
-                body: body.to_string(),
+                body: Bytes::new().into(),
                 delay_ms: 0,
+                fault: None,
+                transfer: None,
             },
             matched_route: None,
+            trace_fields: BTreeMap::new(),
             request_info: None,
+            error: None,
         }
     }
 
-    fn not_found(method: &Method, path: &str) -> Self {
-        let body = format!("Route not found: {} {}", method, path);
+    /// Error response with a plain text body
+    fn plain_error(status: StatusCode, code: &'static str, message: String) -> Self {
+        let body = Bytes::from(message.clone());
         Self {
             response: Response::builder()
-                .status(StatusCode::NOT_FOUND)
+                .status(status)
                 .body(Body::from(body.clone()))
                 .unwrap(),
             info: request_logger::ResponseInfo {
-                status: 404,
+                status: status.as_u16(),
                 headers: std::collections::HashMap::new(),
-                body,
+                body: body.into(),
+                delay_ms: 0,
+                fault: None,
+                transfer: None,
+            },
+            matched_route: None,
+            trace_fields: BTreeMap::new(),
+            request_info: None,
+            error: Some(GeneratedError { code, message }),
+        }
+    }
+
+    /// Answer of an endpoint of the built-in OIDC provider
+    fn oidc(reply: oidc::Reply) -> Self {
+        let mut builder = Response::builder().status(reply.status);
+        let mut headers = std::collections::HashMap::new();
+        for (name, value) in reply.headers {
+            if let Ok(text) = value.to_str() {
+                headers.insert(name.as_str().to_string(), text.to_string());
+            }
+            builder = builder.header(name, value);
+        }
+
+        Self {
+            response: builder.body(Body::from(reply.body.clone())).unwrap(),
+            info: request_logger::ResponseInfo {
+                status: reply.status.as_u16(),
+                headers,
+                body: reply.body.into(),
+                delay_ms: 0,
+                fault: None,
+                transfer: None,
+            },
+            matched_route: None,
+            trace_fields: BTreeMap::new(),
+            request_info: None,
+            error: None,
+        }
+    }
+
+    /// Generated page listing the routes below a path without route file
+    fn index_listing(html: String) -> Self {
+        let content_type = "text/html; charset=utf-8";
+        Self {
+            response: Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::from(html.clone()))
+                .unwrap(),
+            info: request_logger::ResponseInfo {
+                status: 200,
+                headers: [("content-type".to_string(), content_type.to_string())].into(),
+                body: html.into(),
                 delay_ms: 0,
+                fault: None,
+                transfer: None,
             },
             matched_route: None,
+            trace_fields: BTreeMap::new(),
             request_info: None,
+            error: None,
         }
     }
 
-    async fn from_route(route: Route) -> Self {
-        // Apply delay if configured
-        if route.response.meta.delay > 0 {
-            sleep(Duration::from_millis(route.response.meta.delay)).await;
+    /// Permanent redirect keeping the method, e.g. to drop a trailing slash
+    fn redirect(location: &str) -> Self {
+        Self {
+            response: Response::builder()
+                .status(StatusCode::PERMANENT_REDIRECT)
+                .header(LOCATION, location)
+                .body(Body::empty())
+                .unwrap(),
+            info: request_logger::ResponseInfo {
+                status: StatusCode::PERMANENT_REDIRECT.as_u16(),
+                headers: [(LOCATION.as_str().to_string(), location.to_string())].into(),
+                body: Bytes::new().into(),
+                delay_ms: 0,
+                fault: None,
+                transfer: None,
+            },
+            matched_route: None,
+            trace_fields: BTreeMap::new(),
+            request_info: None,
+            error: None,
+        }
+    }
+
+    fn http_version_not_supported(version: Version) -> Self {
+        Self::plain_error(
+            StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+            "http_version_not_supported",
+            format!("HTTP version not supported: {:?}", version),
+        )
+    }
+
+    fn not_found(method: &Method, path: &str) -> Self {
+        Self::plain_error(
+            StatusCode::NOT_FOUND,
+            "route_not_found",
+            format!("Route not found: {} {}", method, path),
+        )
+    }
+
+    fn internal_error(message: &str) -> Self {
+        Self::plain_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            message.to_string(),
+        )
+    }
+
+    async fn from_route(route: Route, shutdown: ShutdownSignal) -> Self {
+        // Apply delay if configured, sampled per request for distributions
+        let delay = route.response.meta.delay.sample();
+        if delay > 0 {
+            sleep(Duration::from_millis(delay)).await;
         }
 
         let matched_route = Some(route.display_path());
+        let trace_fields = route.response.meta.trace_fields.clone();
 
         // Build response
         let mut builder = Response::builder()
-            .status(StatusCode::from_u16(route.response.meta.status).unwrap_or(StatusCode::OK));
-
-        // Set content-type from file extension (can be overridden by headers)
-        builder = builder.header("Content-Type", &route.content_type);
+            .status(StatusCode::from_u16(route.response.meta.status()).unwrap_or(StatusCode::OK));
 
         // Collect headers for response info
         let mut response_headers = std::collections::HashMap::new();
-        response_headers.insert("content-type".to_string(), route.content_type.clone());
 
-        // Apply custom headers
-        for (name, value) in &route.response.meta.headers {
-            if let (Ok(header_name), Ok(header_value)) = (
-                HeaderName::try_from(name.as_str()),
-                HeaderValue::try_from(value.as_str()),
-            ) {
-                builder = builder.header(header_name, header_value);
-                response_headers.insert(name.clone(), value.clone());
+        // Set content-type from file extension unless the headers override it
+        let overridden = route
+            .response
+            .meta
+            .headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("content-type"));
+        if !overridden {
+            builder = builder.header("Content-Type", &route.content_type);
+            response_headers.insert("content-type".to_string(), route.content_type.clone());
+        }
+
+        // Apply custom headers, once per value for lists
+        for (name, values) in &route.response.meta.headers {
+            let Ok(header_name) = HeaderName::try_from(name.as_str()) else {
+                continue;
+            };
+            let mut logged = Vec::new();
+            for value in values.iter() {
+                if let Ok(header_value) = HeaderValue::try_from(value) {
+                    builder = builder.header(header_name.clone(), header_value);
+                    logged.push(value);
+                }
+            }
+            if !logged.is_empty() {
+                response_headers.insert(name.clone(), logged.join(", "));
             }
         }
 
-        let response_body = route.response.body.clone();
-        let response = builder.body(Body::from(response_body.clone())).unwrap();
+        let (body, response_body) = match (&route.response.meta.chunks, &route.body_file) {
+            // Scripted chunks are sent without a Content-Length, so HTTP/1.1
+            // clients receive them with chunked transfer encoding
+            (Some(chunks), _) => {
+                let logged = LoggedBody::from(route.response.body.clone());
+                (chunks::into_body(chunks.clone()), logged)
+            }
+            (None, _) if route.is_event_stream() => {
+                // Event streams must not be cached by proxies or the browser
+                let has_cache_control = route
+                    .response
+                    .meta
+                    .headers
+                    .keys()
+                    .any(|name| name.eq_ignore_ascii_case("cache-control"));
+                if !has_cache_control {
+                    builder = builder.header(CACHE_CONTROL, "no-cache");
+                    response_headers.insert("cache-control".to_string(), "no-cache".to_string());
+                }
+                let body = match &route.response.meta.events {
+                    Some(events) => sse::into_body(events.clone()),
+                    None => Body::from(route.response.body.clone()),
+                };
+                let body = if route.response.meta.keep_open {
+                    sse::hold_open(body, shutdown)
+                } else {
+                    body
+                };
+                (body, LoggedBody::from(route.response.body.clone()))
+            }
+            (None, Some(body_file)) => match stream_body_file(body_file).await {
+                Ok(body) => {
+                    builder = builder.header(CONTENT_LENGTH, body_file.len);
+                    let note = format!(
+                        "<{} bytes streamed from {}>",
+                        body_file.len,
+                        body_file.path.display()
+                    );
+                    (body, LoggedBody::Note(note))
+                }
+                Err(e) => {
+                    tracing::error!("Failed to stream response body: {:#}", e);
+                    return Self::internal_error("Failed to read response body");
+                }
+            },
+            (None, None) => {
+                let response_body = match &route.response.meta.randomize {
+                    Some(randomize) => {
+                        // The body was checked to be JSON when it was loaded
+                        let body = serde_json::from_slice(&route.response.body).unwrap_or_default();
+                        Bytes::from(serde_json::to_vec(&randomize.apply(&body)).unwrap())
+                    }
+                    None => route.response.body.clone(),
+                };
+                if route.response.meta.throttle.is_some() {
+                    // Throttled bodies are streamed, announce the full size
+                    builder = builder.header(CONTENT_LENGTH, response_body.len());
+                }
+                // Both the client and the logger share the same bytes
+                (
+                    Body::from(response_body.clone()),
+                    LoggedBody::from(response_body),
+                )
+            }
+        };
+        let body = match &route.response.meta.throttle {
+            Some(throttle) => throttle.apply(body),
+            None => body,
+        };
+        let mut response = builder.body(body).unwrap();
+        response
+            .extensions_mut()
+            .insert(MatchedRoute(route.source.clone()));
 
         Self {
             response,
             info: request_logger::ResponseInfo {
-                status: route.response.meta.status,
+                status: route.response.meta.status(),
                 headers: response_headers,
                 body: response_body,
-                delay_ms: route.response.meta.delay,
+                delay_ms: delay,
+                fault: route.response.meta.fault,
+                transfer: None,
             },
             matched_route,
+            trace_fields,
             request_info: None,
+            error: None,
         }
     }
 
-    fn with_request_info(mut self, request_info: Option<request_logger::RequestInfo>) -> Self {
-        self.request_info = request_info;
-        self
-    }
+    /// Accept a WebSocket upgrade and run the route's script on the connection
+    async fn websocket(route: Route, parts: &mut Parts, shutdown: ShutdownSignal) -> Self {
+        let matched_route = Some(route.display_path());
+        let trace_fields = route.response.meta.trace_fields.clone();
+        let script = route.websocket.unwrap_or_default();
+
+        let upgrade = match WebSocketUpgrade::from_request_parts(parts, &()).await {
+            Ok(upgrade) => upgrade,
+            Err(rejection) => {
+                let response = rejection.into_response();
+                return Self {
+                    info: request_logger::ResponseInfo {
+                        status: response.status().as_u16(),
+                        headers: std::collections::HashMap::new(),
+                        body: LoggedBody::Note("<invalid WebSocket upgrade>".to_string()),
+                        delay_ms: 0,
+                        fault: None,
+                        transfer: None,
+                    },
+                    response,
+                    matched_route,
+                    trace_fields,
+                    request_info: None,
+                    error: None,
+                };
+            }
+        };
 
-    fn log_and_return(self, state: &AppState) -> Response<Body> {
-        // Log if enabled
-        if let (Some(logger), Some(req_info)) = (&state.request_logger, self.request_info) {
-            let logged =
-                request_logger::create_logged_request(req_info, self.info, self.matched_route);
-            logger.log_request_async(logged);
+        let mut response_headers = std::collections::HashMap::new();
+        let headers = script.headers.clone();
+        let mut response = upgrade.on_upgrade(move |socket| async move {
+            websocket::run(&script, socket, shutdown).await;
+        });
+        for (name, value) in &headers {
+            if let (Ok(header_name), Ok(header_value)) = (
+                HeaderName::try_from(name.as_str()),
+                HeaderValue::try_from(value.as_str()),
+            ) {
+                response.headers_mut().insert(header_name, header_value);
+                response_headers.insert(name.clone(), value.clone());
+            }
         }
 
-        self.response
+        Self {
+            info: request_logger::ResponseInfo {
+                status: response.status().as_u16(),
+                headers: response_headers,
+                body: LoggedBody::Note("<WebSocket conversation>".to_string()),
+                delay_ms: 0,
+                fault: None,
+                transfer: None,
+            },
+            response,
+            matched_route,
+            trace_fields,
+            request_info: None,
+            error: None,
+        }
     }
-}
 
-/// Extract request information for logging if enabled
-async fn extract_request_for_logging(
-    state: &AppState,
-    parts: &Parts,
-    body: Body,
-) -> Option<request_logger::RequestInfo> {
-    if state.request_logger.is_none() {
-        return None;
+    /// 401 for a route whose `auth` credentials are missing or wrong
+    fn unauthorized(route: &Route, auth: &Auth) -> Self {
+        let challenge = auth.challenge();
+        let (content_type, body) = match &auth.body {
+            Some(body) => (route.content_type.clone(), Bytes::from(body.clone())),
+            None => (
+                "text/plain".to_string(),
+                Bytes::from_static(b"Unauthorized"),
+            ),
+        };
+
+        let mut response = Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(WWW_AUTHENTICATE, &challenge)
+            .header(CONTENT_TYPE, &content_type)
+            .body(Body::from(body.clone()))
+            .unwrap();
+        response
+            .extensions_mut()
+            .insert(MatchedRoute(route.source.clone()));
+
+        Self {
+            response,
+            info: request_logger::ResponseInfo {
+                status: 401,
+                headers: [
+                    ("www-authenticate".to_string(), challenge),
+                    ("content-type".to_string(), content_type),
+                ]
+                .into(),
+                body: body.into(),
+                delay_ms: 0,
+                fault: None,
+                transfer: None,
+            },
+            matched_route: Some(route.display_path()),
+            trace_fields: route.response.meta.trace_fields.clone(),
+            request_info: None,
+            // Bodies from the route file are sent as they are
+            error: auth.body.is_none().then(|| GeneratedError {
+                code: "unauthorized",
+                message: "Unauthorized".to_string(),
+            }),
+        }
     }
 
-    match request_logger::extract_request_info(&parts.method, &parts.uri, &parts.headers, body)
-        .await
-    {
-        Ok(info) => Some(info),
-        Err(e) => {
-            tracing::error!("Failed to extract request info for logging: {}", e);
-            None
+    /// Drop the body, announcing its size in `Content-Length` if it is known
+    fn without_body(mut self) -> Self {
+        let body = std::mem::replace(self.response.body_mut(), Body::empty());
+        if let Some(length) = http_body::Body::size_hint(&body).exact()
+            && !self.response.headers().contains_key(CONTENT_LENGTH)
+        {
+            self.response
+                .headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(length));
+        }
+        self.info.body = Bytes::new().into();
+        self
+    }
+
+    /// Add the route's validators, and turn the response into a 304 if the
+    /// client's copy is current
+    fn with_validators(mut self, validators: &Validators, parts: &Parts) -> Self {
+        let headers = [
+            (ETAG, Some(&validators.etag)),
+            (LAST_MODIFIED, validators.last_modified.as_ref()),
+        ];
+        for (name, value) in headers {
+            if let Some(value) = value
+                && !self.response.headers().contains_key(&name)
+                && let Ok(header_value) = HeaderValue::try_from(value.as_str())
+            {
+                self.info
+                    .headers
+                    .insert(name.as_str().to_string(), value.clone());
+                self.response.headers_mut().insert(name, header_value);
+            }
+        }
+
+        if self.response.status() == StatusCode::OK && validators.not_modified(&parts.headers) {
+            *self.response.status_mut() = StatusCode::NOT_MODIFIED;
+            *self.response.body_mut() = Body::empty();
+            self.response.headers_mut().remove(CONTENT_LENGTH);
+            self.info.status = 304;
+            self.info.body = Bytes::new().into();
         }
+        self
+    }
+
+    /// Prefix an absolute path in `Location` with the base path, so
+    /// redirects written for the fixture tree stay below it
+    fn with_base_path(mut self, base_path: &str) -> Self {
+        let location = self
+            .response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| value.starts_with('/') && !value.starts_with("//"))
+            .map(|value| format!("{}{}", base_path, value));
+        if let Some(location) = location
+            && let Ok(value) = HeaderValue::try_from(&location)
+        {
+            self.response.headers_mut().insert(LOCATION, value);
+            self.info
+                .headers
+                .insert(LOCATION.as_str().to_string(), location);
+        }
+        self
+    }
+
+    /// Add a `Set-Cookie` header, keeping those set by the route file
+    fn with_set_cookie(mut self, value: HeaderValue) -> Self {
+        if let Ok(text) = value.to_str() {
+            self.info
+                .headers
+                .entry(SET_COOKIE.as_str().to_string())
+                .and_modify(|existing| {
+                    existing.push_str(", ");
+                    existing.push_str(text);
+                })
+                .or_insert_with(|| text.to_string());
+        }
+        self.response.headers_mut().append(SET_COOKIE, value);
+        self
+    }
+
+    /// Mark the first response of a route after a reload changed it
+    fn with_fixture_changed(mut self) -> Self {
+        self.response.headers_mut().insert(
+            HeaderName::from_static(fixture_changes::HEADER),
+            HeaderValue::from_static("true"),
+        );
+        self.info
+            .headers
+            .insert(fixture_changes::HEADER.to_string(), "true".to_string());
+        self
+    }
+
+    /// Send the request's correlation ID back, unless the route file sets
+    /// the header itself
+    fn with_request_id(mut self, header: &HeaderName, id: &str) -> Self {
+        if !self.response.headers().contains_key(header)
+            && let Ok(value) = HeaderValue::try_from(id)
+        {
+            self.response.headers_mut().insert(header.clone(), value);
+            self.info
+                .headers
+                .insert(header.as_str().to_string(), id.to_string());
+        }
+        self
+    }
+
+    /// Add the CORS headers for the request to the response
+    fn with_cors(mut self, cors: &Cors, parts: &Parts) -> Self {
+        let headers = cors.response_headers(&parts.method, &parts.headers, self.response.headers());
+        for (name, value) in headers {
+            if let Ok(text) = value.to_str() {
+                self.info
+                    .headers
+                    .insert(name.as_str().to_string(), text.to_string());
+            }
+            self.response.headers_mut().insert(name, value);
+        }
+        self
     }
+
+    /// Render a generated error as JSON with `--error-format json`
+    fn with_error_format(mut self, format: ErrorFormat) -> Self {
+        let (ErrorFormat::Json, Some(error)) = (format, &self.error) else {
+            return self;
+        };
+
+        let status = self.response.status().as_u16();
+        let body = Bytes::from(
+            serde_json::json!({
+                "error": {
+                    "status": status,
+                    "code": error.code,
+                    "message": error.message,
+                }
+            })
+            .to_string(),
+        );
+        *self.response.body_mut() = Body::from(body.clone());
+        self.response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self.info
+            .headers
+            .insert("content-type".to_string(), "application/json".to_string());
+        self.info.body = body.into();
+        self
+    }
+
+    fn with_request_info(mut self, request_info: Option<request_logger::RequestInfo>) -> Self {
+        self.request_info = request_info;
+        self
+    }
+
+    /// Write the access log line and request log entry once the body has
+    /// been sent, to include how it went over the wire
+    fn log_and_return(self, state: &AppState, request: AccessRequest) -> Response<Body> {
+        let (parts, body) = self.response.into_parts();
+        let status = parts.status.as_u16();
+        let matched_route = self.matched_route;
+        let logger = state
+            .request_logger
+            .clone()
+            .filter(|logger| logger.accepts(&request.path, status, matched_route.is_some()));
+        let request_info = self.request_info;
+        let mut info = self.info;
+        let trace_fields = self.trace_fields;
+        let content_encoding = parts
+            .headers
+            .get(CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok());
+        let body = transfer::observe(body, content_encoding, move |stats| {
+            let duration = request.received.elapsed();
+            logging::Access {
+                method: &request.method,
+                path: &request.path,
+                matched_route: matched_route.as_deref(),
+                status,
+                bytes: stats.transferred_bytes,
+                duration,
+                trace_fields: &trace_fields,
+            }
+            .log();
+
+            if let (Some(logger), Some(request_info)) = (logger, request_info) {
+                info.transfer = Some(stats);
+                let logged = request_logger::create_logged_request(
+                    request_info,
+                    info,
+                    matched_route,
+                    duration,
+                );
+                logger.log_request_async(logged);
+            }
+        });
+
+        Response::from_parts(parts, body)
+    }
+}
+
+/// The request as the client sent it, for the access log
+struct AccessRequest {
+    method: String,
+    path: String,
+    received: Instant,
+}
+
+/// Stream a body from disk without loading it into memory
+async fn stream_body_file(body_file: &BodyFile) -> std::io::Result<Body> {
+    let mut file = File::open(&body_file.path).await?;
+    file.seek(SeekFrom::Start(body_file.offset)).await?;
+
+    Ok(Body::from_stream(ReaderStream::new(
+        file.take(body_file.len),
+    )))
+}
+
+/// Extract request information for logging if enabled
+fn extract_request_for_logging(
+    state: &AppState,
+    parts: &Parts,
+    body: &Bytes,
+) -> Option<request_logger::RequestInfo> {
+    state.request_logger.as_ref()?;
+
+    Some(request_logger::extract_request_info(
+        &parts.method,
+        &parts.uri,
+        &parts.headers,
+        body.clone(),
+    ))
+}
+
+/// Media type of a Content-Type value without parameters, e.g. `text/plain`
+/// for `Text/Plain; charset=utf-8`
+fn media_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Request target without the base path, or `None` if the path isn't below
+/// it
+fn strip_base_path(uri: &Uri, base_path: &str) -> Option<Uri> {
+    let rest = uri.path().strip_prefix(base_path)?;
+    let path = match rest {
+        "" => "/",
+        rest if rest.starts_with('/') => rest,
+        _ => return None,
+    };
+    let target = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(target.parse().ok()?);
+    Uri::from_parts(parts).ok()
 }
 
 /// Parse HTTP method to our internal enum
 fn parse_http_method(method: &Method) -> Option<HttpMethod> {
-    match *method {
-        Method::GET => Some(HttpMethod::Get),
-        Method::POST => Some(HttpMethod::Post),
-        Method::PUT => Some(HttpMethod::Put),
-        Method::DELETE => Some(HttpMethod::Delete),
-        Method::PATCH => Some(HttpMethod::Patch),
-        Method::HEAD => Some(HttpMethod::Head),
-        Method::OPTIONS => Some(HttpMethod::Options),
-        _ => None,
+    // Extension methods such as WebDAV's PROPFIND are only available by name
+    HttpMethod::from_str(method.as_str())
+}
+
+/// Whether the client takes HTML, as browsers navigating to a page do
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| {
+            media_type
+                .trim()
+                .to_ascii_lowercase()
+                .starts_with("text/html")
+        })
+}
+
+/// Whether the request asks to switch to the WebSocket protocol
+fn is_websocket_upgrade(parts: &Parts) -> bool {
+    parts.method == Method::GET
+        && parts
+            .headers
+            .get(UPGRADE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+/// Methods with a route file for the path, in a stable order
+async fn allowed_methods(state: &AppState, host: Option<&str>, path: &str) -> Vec<HttpMethod> {
+    let routes = state.routes.read().await;
+    let mut methods: Vec<HttpMethod> = routes
+        .iter()
+        .filter(|r| {
+            r.method != HttpMethod::Ws
+                && r.host.as_deref() == host
+                && r.matches_with(path, state.path_matching)
+                && state.tags.is_active(&r.response.meta.tags)
+        })
+        .map(|r| r.method.clone())
+        .collect();
+    // HEAD is answered from GET
+    if methods.contains(&HttpMethod::Get) {
+        methods.push(HttpMethod::Head);
     }
+    methods.sort();
+    methods.dedup();
+    methods
 }
 
-/// Find a matching route for the request
-async fn find_matching_route(state: &AppState, method: HttpMethod, path: &str) -> Option<Route> {
+/// Value of the `Allow` header; OPTIONS is always answered
+fn allow_header(allowed: &[HttpMethod]) -> String {
+    let mut methods: Vec<&str> = allowed.iter().map(HttpMethod::as_str).collect();
+    if !allowed.contains(&HttpMethod::Options) {
+        methods.push(HttpMethod::Options.as_str());
+    }
+    methods.join(", ")
+}
+
+/// Path without trailing slash to redirect to, if a route answering it has
+/// `trailing_slash: redirect`
+async fn trailing_slash_redirect(
+    state: &AppState,
+    host: Option<&str>,
+    path: &str,
+) -> Option<String> {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() || trimmed == path {
+        return None;
+    }
     let routes = state.routes.read().await;
     routes
         .iter()
-        .find(|r| r.method == method && r.matches(path))
+        .any(|r| {
+            r.host.as_deref() == host
+                && r.path_matching(state.path_matching).trailing_slash == TrailingSlash::Redirect
+                && state.tags.is_active(&r.response.meta.tags)
+                && r.matches_with(trimmed, state.path_matching)
+        })
+        .then(|| trimmed.to_string())
+}
+
+/// Find a matching route for the request.
+///
+/// GraphQL operation routes only answer requests for their operation, those
+/// requiring specific variables first. Plain routes of the same path act as
+/// fallback for all other requests.
+async fn find_matching_route(
+    state: &AppState,
+    host: Option<&str>,
+    forwarded: &Forwarded,
+    method: HttpMethod,
+    path: &str,
+    body: &[u8],
+) -> Option<Route> {
+    let routes = state.routes.read().await;
+    let mut candidates: Vec<&Route> = routes
+        .iter()
+        .filter(|r| {
+            r.method == method
+                && r.host.as_deref() == host
+                && r.matches_with(path, state.path_matching)
+                && state.tags.is_active(&r.response.meta.tags)
+                && r.response
+                    .meta
+                    .route_match
+                    .as_ref()
+                    .is_none_or(|m| m.matches(forwarded.client_ip))
+        })
+        .collect();
+    // Enabled tagged routes shadow the untagged ones, and routes with
+    // conditions those without
+    candidates.sort_by_key(|r| {
+        (
+            r.response.meta.tags.is_empty(),
+            r.response.meta.route_match.is_none(),
+        )
+    });
+
+    if candidates.iter().any(|r| r.graphql_operation.is_some())
+        && let Some(request) = GraphqlRequest::parse(body)
+    {
+        let operation_route = |with_variables: bool| {
+            candidates.iter().find(|r| {
+                let variables = r.response.meta.variables.as_ref();
+                variables.is_some() == with_variables
+                    && r.graphql_operation
+                        .as_deref()
+                        .is_some_and(|operation| request.matches(operation, variables))
+            })
+        };
+
+        if let Some(route) = operation_route(true).or_else(|| operation_route(false)) {
+            return Some((*route).clone());
+        }
+    }
+
+    candidates
+        .into_iter()
+        .find(|r| r.graphql_operation.is_none())
         .cloned()
 }
 
-async fn handler(State(state): State<Arc<AppState>>, request: Request<Body>) -> Response<Body> {
-    let (parts, body) = request.into_parts();
+async fn handler(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(connection): ConnectInfo<Connection>,
+    request: Request<Body>,
+) -> Response<Body> {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        // Filled in from the route's `trace_fields`
+        trace_fields = tracing::field::Empty,
+    );
+    serve(state, connection, request).instrument(span).await
+}
+
+async fn serve(
+    state: Arc<AppState>,
+    connection: Connection,
+    request: Request<Body>,
+) -> Response<Body> {
+    let access = AccessRequest {
+        method: request.method().to_string(),
+        path: request.uri().path().to_string(),
+        received: Instant::now(),
+    };
+    let (mut parts, body) = request.into_parts();
+
+    // The body is read once, for logging as well as for matching, and only
+    // if anything looks at it
+    let (body, body_rejection) = if reads_bodies(&state) {
+        match state.limits.read_body(&parts, body).await {
+            Ok(body) => (body, None),
+            Err(rejection) => (Bytes::new(), Some(rejection)),
+        }
+    } else {
+        match state.limits.read_body(&parts, Body::empty()).await {
+            Ok(_) => (Bytes::new(), None),
+            Err(rejection) => (Bytes::new(), Some(rejection)),
+        }
+    };
+
+    // Logged with the path the client requested
+    let request_info = extract_request_for_logging(&state, &parts, &body);
+
+    let forwarded = state
+        .trusted_proxies
+        .resolve(&parts.uri, &parts.headers, &connection);
+
+    let below_base_path = match &state.base_path {
+        Some(base_path) => match strip_base_path(&parts.uri, base_path) {
+            Some(uri) => {
+                parts.uri = uri;
+                true
+            }
+            None => false,
+        },
+        None => true,
+    };
+
+    let duplicate = state
+        .duplicates
+        .as_ref()
+        .is_some_and(|duplicates| duplicates.check(&parts.method, &parts.uri, &body));
+
+    let violation = state
+        .sequence
+        .as_ref()
+        .and_then(|sequence| sequence.check(&parts.method, parts.uri.path()));
+
+    let request_id = match &state.request_ids {
+        Some(ids) => ids.resolve(&parts.headers),
+        None => Ok(None),
+    };
+
+    let request_info = request_info.map(|mut info| {
+        info.client_ip = forwarded.client_ip.map(|ip| ip.to_string());
+        info.duplicate = duplicate;
+        info.out_of_sequence = violation.is_some();
+        info.request_id = request_id.clone().ok().flatten();
+        info
+    });
+
+    let response_builder = match (&state.sequence, violation) {
+        _ if let Some(rejection) = body_rejection => rejection,
+        _ if !below_base_path => ResponseBuilder::not_found(&parts.method, parts.uri.path()),
+        _ if let Err(missing) = &request_id => ResponseBuilder::plain_error(
+            StatusCode::BAD_REQUEST,
+            "request_id_required",
+            missing.to_string(),
+        ),
+        (Some(sequence), Some(violation)) if sequence.mode() == sequence::Mode::Reject => {
+            ResponseBuilder::plain_error(
+                StatusCode::CONFLICT,
+                "out_of_sequence",
+                violation.to_string(),
+            )
+        }
+        _ => respond(&state, &connection, &forwarded, &mut parts, &body).await,
+    };
+    if let Some(coverage) = &state.coverage
+        && response_builder.matched_route.is_none()
+        && matches!(
+            response_builder.response.status(),
+            StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED
+        )
+    {
+        coverage.miss(&access.method, &access.path);
+    }
+    if let Some(received) = &state.received_requests {
+        received.record(ReceivedRequest {
+            method: access.method.clone(),
+            path: access.path.clone(),
+            query: parts.uri.query().map(String::from),
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            matched_route: response_builder.matched_route.clone(),
+            status: response_builder.response.status().as_u16(),
+        });
+    }
+    let mut response_builder = response_builder.with_error_format(state.error_format);
+    if let (Some(ids), Ok(Some(id))) = (&state.request_ids, &request_id) {
+        response_builder = response_builder.with_request_id(ids.header(), id);
+    }
+    if let Some(cors) = &state.cors {
+        response_builder = response_builder.with_cors(cors, &parts);
+    }
+
+    response_builder
+        .with_request_info(request_info)
+        .log_and_return(&state, access)
+}
+
+/// Record the route's `trace_fields` on the current request span as
+/// `key=value` pairs
+fn record_trace_fields(route: &Route) {
+    let fields = &route.response.meta.trace_fields;
+    if fields.is_empty() {
+        return;
+    }
 
-    // Extract request information for logging
-    let request_info = extract_request_for_logging(&state, &parts, body).await;
+    let formatted: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    tracing::Span::current().record("trace_fields", formatted.join(" "));
+}
+
+/// Whether anything reads request bodies: logging, duplicate detection,
+/// request history, the OIDC provider or a route
+fn reads_bodies(state: &AppState) -> bool {
+    state.request_logger.is_some()
+        || state.duplicates.is_some()
+        || state.received_requests.is_some()
+        || state.oidc.is_some()
+        || state.routes_read_bodies.get()
+}
+
+async fn respond(
+    state: &AppState,
+    connection: &Connection,
+    forwarded: &Forwarded,
+    parts: &mut Parts,
+    body: &Bytes,
+) -> ResponseBuilder {
+    // Clients speaking a disabled HTTP version (e.g. h2c prior knowledge
+    // with `--http-version 1.1`) get a 505
+    if !state.http_version.accepts(parts.version) {
+        return ResponseBuilder::http_version_not_supported(parts.version);
+    }
+
+    if let Some(rejection) = state.limits.check(parts) {
+        return rejection;
+    }
+
+    let host = vhost::select(&state.vhosts, &forwarded.host);
 
     // Parse HTTP method
     let method = match parse_http_method(&parts.method) {
-        Some(m) => m,
-        None => {
-            return ResponseBuilder::method_not_allowed()
-                .with_request_info(request_info)
-                .log_and_return(&state);
+        // WS is a route file type, not a method clients may send; like other
+        // methods without files, it is only a 405 on paths that exist
+        Some(HttpMethod::Ws) | None => {
+            let path = parts.uri.path();
+            let allowed = allowed_methods(state, host, path).await;
+            return if allowed.is_empty() {
+                ResponseBuilder::not_found(&parts.method, path)
+            } else {
+                ResponseBuilder::method_not_allowed(&allowed)
+            };
         }
+        Some(m) => m,
     };
 
+    // Upgrade requests are answered by a WebSocket route if there is one;
+    // otherwise they fall through to the regular routes of the path
+    if is_websocket_upgrade(parts) {
+        let path = parts.uri.path().to_string();
+        if let Some(route) =
+            find_matching_route(state, host, forwarded, HttpMethod::Ws, &path, &[]).await
+        {
+            record_trace_fields(&route);
+            if let Some(coverage) = &state.coverage {
+                coverage.hit(&route);
+            }
+            return ResponseBuilder::websocket(route, parts, state.shutdown.clone()).await;
+        }
+    }
+
     // Find matching route
     let path = parts.uri.path();
-    let route = find_matching_route(&state, method, path).await;
+    let mut route = find_matching_route(state, host, forwarded, method.clone(), path, body).await;
 
-    // Build and return response
-    let response_builder = match route {
-        Some(route) => ResponseBuilder::from_route(route).await,
-        None => ResponseBuilder::not_found(&parts.method, path),
-    };
+    // Without a HEAD file, HEAD is answered like GET, minus the body
+    let head_from_get = route.is_none() && method == HttpMethod::Head;
+    if head_from_get {
+        route = find_matching_route(state, host, forwarded, HttpMethod::Get, path, body).await;
+    }
 
-    response_builder
-        .with_request_info(request_info)
-        .log_and_return(&state)
+    // The OIDC provider answers its endpoints unless a route file does
+    if route.is_none()
+        && let Some(oidc) = &state.oidc
+        && let Some(reply) = oidc.respond(
+            &parts.method,
+            &parts.uri,
+            &parts.headers,
+            body,
+            &format!("{}{}", forwarded.origin(), state.base_path_or_root()),
+        )
+    {
+        return ResponseBuilder::oidc(reply);
+    }
+
+    match route {
+        Some(mut route) => {
+            record_trace_fields(&route);
+            if let Some(coverage) = &state.coverage {
+                coverage.hit(&route);
+            }
+            if let Some(schedule) = &route.response.meta.chaos_schedule
+                && let Some(phase) = schedule.phase_at(state.started.elapsed())
+            {
+                phase.clone().apply(&mut route);
+            }
+            if let Some(progression) = &route.response.meta.progression {
+                let count = state.progressions.advance(parts.method.as_str(), path);
+                progression.step_at(count).clone().apply(&mut route);
+            }
+            if state.control_headers {
+                match control_headers::Overrides::from_headers(&parts.headers) {
+                    Ok(overrides) => overrides.apply(&mut route),
+                    Err(message) => {
+                        return ResponseBuilder::plain_error(
+                            StatusCode::BAD_REQUEST,
+                            "invalid_control_header",
+                            message,
+                        );
+                    }
+                }
+            }
+            if route.response.meta.mode == ResponseMode::Echo {
+                let client = echo::Client::new(connection, forwarded, parts.version);
+                echo::apply(&mut route, parts, body, client);
+            }
+            let http_version = route.response.meta.http_version;
+            if let Some(required) = http_version
+                && !required.accepts(parts.version)
+            {
+                return ResponseBuilder::plain_error(
+                    StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+                    "http_version_not_supported",
+                    format!(
+                        "{} requires {:?}, got {:?}",
+                        route.display_path(),
+                        required.version(),
+                        parts.version
+                    ),
+                );
+            }
+            if let Some(auth) = &route.response.meta.auth
+                && !auth.accepts(&parts.headers)
+            {
+                return ResponseBuilder::unauthorized(&route, auth);
+            }
+            if let Some(required) = &route.response.meta.require_content_type {
+                let content_type = parts
+                    .headers
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(media_type);
+                if !content_type
+                    .as_deref()
+                    .is_some_and(|actual| required.iter().any(|media| media_type(media) == actual))
+                {
+                    return ResponseBuilder::plain_error(
+                        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                        "unsupported_media_type",
+                        format!(
+                            "{} requires Content-Type {}, got {}",
+                            route.display_path(),
+                            required.iter().collect::<Vec<_>>().join(" or "),
+                            content_type.as_deref().unwrap_or("none")
+                        ),
+                    );
+                }
+            }
+            let set_cookie = match &route.response.meta.session {
+                Some(session) => match state.sessions.apply(session, &parts.headers) {
+                    Ok(set_cookie) => set_cookie,
+                    Err(rejection) => {
+                        return ResponseBuilder::plain_error(
+                            StatusCode::UNAUTHORIZED,
+                            "session_required",
+                            rejection.to_string(),
+                        );
+                    }
+                },
+                None => None,
+            };
+            if route.script.is_some() || route.plugin.is_some() {
+                let request = ScriptRequest {
+                    method: parts.method.to_string(),
+                    path: path.to_string(),
+                    params: route.params(path, state.path_matching),
+                    query: parts.uri.query().map(str::to_string),
+                    headers: parts.headers.clone(),
+                    body: body.clone(),
+                };
+                if let Some(script) = route.script.clone() {
+                    match scripting::run(script, request.clone()).await {
+                        Ok(response) => response.apply(&mut route),
+                        Err(e) => {
+                            tracing::warn!("Script {} failed: {:#}", route.source.display(), e);
+                            return ResponseBuilder::plain_error(
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                "script_error",
+                                format!("{:#}", e),
+                            );
+                        }
+                    }
+                }
+                if let Some(plugin) = route.plugin.clone() {
+                    match plugin::run(plugin, &request, &route).await {
+                        Ok(response) => response.apply(&mut route),
+                        Err(e) => {
+                            tracing::warn!("Plugin of {} failed: {:#}", route.source.display(), e);
+                            return ResponseBuilder::plain_error(
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                "plugin_error",
+                                format!("{:#}", e),
+                            );
+                        }
+                    }
+                }
+            }
+            // The fault replaces the response on the wire once it is written
+            if let Some(fault) = route.response.meta.fault {
+                connection.fault.arm(fault);
+            }
+            let validators = (matches!(method, HttpMethod::Get | HttpMethod::Head)
+                && route.response.meta.etag.unwrap_or(state.etag)
+                && route.response.meta.randomize.is_none()
+                && route.response.meta.mode == ResponseMode::File
+                && route.script.is_none()
+                && route.plugin.is_none()
+                && !route.is_event_stream())
+            .then(|| Validators::for_route(&route));
+            let changed = state
+                .changed_fixtures
+                .as_ref()
+                .is_some_and(|changes| changes.take(&route));
+            let callbacks = (!route.response.meta.callbacks.is_empty()).then(|| {
+                (
+                    route.response.meta.callbacks.clone(),
+                    TriggeringRequest {
+                        method: parts.method.to_string(),
+                        path: path.to_string(),
+                        query: parts.uri.query().map(str::to_string),
+                        headers: parts.headers.clone(),
+                        body: body.clone(),
+                        params: route.params(path, state.path_matching),
+                        matrix: if state.path_matching.matrix_params {
+                            routes::matrix_params(path)
+                        } else {
+                            Vec::new()
+                        },
+                    },
+                )
+            });
+            let mut response_builder =
+                ResponseBuilder::from_route(route, state.shutdown.clone()).await;
+            // Sent once the route's delay has passed, like the response
+            if let Some((callbacks, request)) = callbacks {
+                callbacks::fire(&callbacks, request, state.shutdown.clone());
+            }
+            if changed {
+                response_builder = response_builder.with_fixture_changed();
+            }
+            if let Some(set_cookie) = set_cookie {
+                response_builder = response_builder.with_set_cookie(set_cookie);
+            }
+            if let Some(validators) = &validators {
+                response_builder = response_builder.with_validators(validators, parts);
+            }
+            if let Some(base_path) = &state.base_path {
+                response_builder = response_builder.with_base_path(base_path);
+            }
+            // HTTP/1.1 connections answer in the version of the response
+            if http_version == Some(RouteHttpVersion::Http10) {
+                *response_builder.response.version_mut() = Version::HTTP_10;
+            }
+            if head_from_get {
+                response_builder.without_body()
+            } else {
+                response_builder
+            }
+        }
+        // Preflights are answered for every path unless an OPTIONS file
+        // takes care of them
+        None if state.cors.is_some() && Cors::is_preflight(&parts.method, &parts.headers) => {
+            ResponseBuilder::cors_preflight()
+        }
+        None => {
+            if let Some(location) = trailing_slash_redirect(state, host, path).await {
+                let location = match parts.uri.query() {
+                    Some(query) => format!("{}?{}", location, query),
+                    None => location,
+                };
+                let redirect = ResponseBuilder::redirect(&location);
+                return match &state.base_path {
+                    Some(base_path) => redirect.with_base_path(base_path),
+                    None => redirect,
+                };
+            }
+            // A method that has files for the path but didn't match (e.g. an
+            // unknown GraphQL operation) is still a 404
+            let allowed = allowed_methods(state, host, path).await;
+            if allowed.is_empty()
+                && state.index_listing
+                && matches!(method, HttpMethod::Get | HttpMethod::Head)
+                && accepts_html(&parts.headers)
+                && let Some(html) = index_listing::render(
+                    &state
+                        .routes
+                        .read()
+                        .await
+                        .iter()
+                        .filter(|r| r.host.as_deref() == host)
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    path,
+                    state.base_path_or_root(),
+                )
+            {
+                ResponseBuilder::index_listing(html)
+            } else if allowed.is_empty() || allowed.contains(&method) {
+                ResponseBuilder::not_found(&parts.method, path)
+            } else if method == HttpMethod::Options {
+                ResponseBuilder::options(&allowed)
+            } else {
+                ResponseBuilder::method_not_allowed(&allowed)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::FaultTrigger;
+    use crate::routes;
+    use std::fs;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+    use tracing::field::Field;
+    use tracing::span;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    fn router(temp_dir: &TempDir, limits: RequestLimits) -> Router {
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        let routes = routes::scan_directory(temp_dir.path()).unwrap();
+        create_router(Arc::new(AppState {
+            limits,
+            ..AppState::new(Arc::new(RwLock::new(routes)), shutdown_rx)
+        }))
+    }
+
+    async fn send(router: &Router, method: &str, path: &str) -> Response<Body> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("user-agent", "blendwerk-test")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(Connection::with_trigger(
+                FaultTrigger::default(),
+            )));
+        router.clone().oneshot(request).await.unwrap()
+    }
+
+    fn users_tree() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let users_dir = temp_dir.path().join("users");
+        fs::create_dir_all(&users_dir).unwrap();
+        fs::write(users_dir.join("GET.json"), "[]").unwrap();
+        fs::write(users_dir.join("POST.json"), "{}").unwrap();
+        temp_dir
+    }
+
+    #[tokio::test]
+    async fn test_options_and_405_list_allowed_methods() {
+        let temp_dir = users_tree();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let response = send(&router, "OPTIONS", "/users").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[ALLOW], "GET, POST, HEAD, OPTIONS");
+
+        let response = send(&router, "DELETE", "/users").await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET, POST, HEAD, OPTIONS");
+
+        let response = send(&router, "OPTIONS", "/posts").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Methods blendwerk doesn't know get the same treatment
+        let response = send(&router, "PURGE", "/users").await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET, POST, HEAD, OPTIONS");
+
+        let response = send(&router, "PURGE", "/posts").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_head_is_answered_from_get() {
+        let temp_dir = users_tree();
+        fs::write(
+            temp_dir.path().join("users/GET.json"),
+            "---\nstatus: 203\nheaders:\n  X-Total-Count: \"2\"\n---\n[1, 2]",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let response = send(&router, "HEAD", "/users").await;
+        assert_eq!(response.status(), StatusCode::NON_AUTHORITATIVE_INFORMATION);
+        assert_eq!(response.headers()["x-total-count"], "2");
+        assert_eq!(response.headers()[CONTENT_LENGTH], "6");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn test_strip_base_path() {
+        let strip =
+            |uri: &str| strip_base_path(&uri.parse().unwrap(), "/mocks").map(|uri| uri.to_string());
+        assert_eq!(
+            strip("/mocks/users?page=2").as_deref(),
+            Some("/users?page=2")
+        );
+        assert_eq!(strip("/mocks").as_deref(), Some("/"));
+        assert_eq!(strip("/mocks/").as_deref(), Some("/"));
+        assert_eq!(strip("/mocksy/users"), None);
+        assert_eq!(strip("/users"), None);
+    }
+
+    #[tokio::test]
+    async fn test_json_error_format() {
+        let allowed = [HttpMethod::Get];
+        let response = ResponseBuilder::method_not_allowed(&allowed)
+            .with_error_format(ErrorFormat::Json)
+            .response;
+        assert_eq!(response.headers()[ALLOW], "GET, OPTIONS");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            error,
+            serde_json::json!({
+                "error": {
+                    "status": 405,
+                    "code": "method_not_allowed",
+                    "message": "Method not allowed",
+                }
+            })
+        );
+
+        let response = ResponseBuilder::not_found(&Method::GET, "/missing")
+            .with_error_format(ErrorFormat::Text)
+            .response;
+        assert!(!response.headers().contains_key(CONTENT_TYPE));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "Route not found: GET /missing");
+    }
+
+    #[tokio::test]
+    async fn test_session_cookies() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("login")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("profile")).unwrap();
+        fs::write(
+            temp_dir.path().join("login/POST.json"),
+            "---\nheaders:\n  Set-Cookie: [theme=dark, lang=en]\nsession:\n  set: logged-in\n---\n{}",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("profile/GET.json"),
+            "---\nsession:\n  requires: logged-in\n---\n{}",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let response = send(&router, "POST", "/login").await;
+        let cookies: Vec<&str> = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(cookies.len(), 3);
+        assert!(cookies[..2].contains(&"theme=dark"));
+        assert!(cookies[2].starts_with("session="));
+
+        let response = send(&router, "GET", "/profile").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_progression() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("jobs/[id]")).unwrap();
+        fs::write(
+            temp_dir.path().join("jobs/[id]/GET.json"),
+            "---\nprogression:\n  steps:\n    - times: 2\n      status: 202\n    - {}\n---\n{}",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let statuses = async |path| {
+            let mut statuses = Vec::new();
+            for _ in 0..3 {
+                statuses.push(send(&router, "GET", path).await.status().as_u16());
+            }
+            statuses
+        };
+        assert_eq!(statuses("/jobs/1").await, [202, 202, 200]);
+        assert_eq!(statuses("/jobs/2").await, [202, 202, 200]);
+
+        let response = send(&router, "DELETE", "/__blendwerk/progression?path=/jobs/1").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, r#"{"reset":1}"#);
+        assert_eq!(statuses("/jobs/1").await, [202, 202, 200]);
+        assert_eq!(
+            send(&router, "GET", "/jobs/2").await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_echo() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("debug")).unwrap();
+        fs::write(
+            temp_dir.path().join("debug/POST.txt"),
+            "---\nmode: echo\nstatus: 202\n---\n",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/debug?tag=a&tag=b")
+            .header("x-trace", "1")
+            .body(Body::from("hello"))
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(Connection::with_trigger(
+                FaultTrigger::default(),
+            )));
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let echo: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(echo["method"], "POST");
+        assert_eq!(echo["path"], "/debug");
+        assert_eq!(echo["query"]["tag"], serde_json::json!(["a", "b"]));
+        assert_eq!(echo["headers"]["x-trace"], "1");
+        assert_eq!(echo["body"], "hello");
+        assert_eq!(echo["client"]["protocol"], "http/1.1");
+        assert_eq!(echo["route"]["path"], "/debug");
+        assert_eq!(echo["route"]["tags"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_script() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("orders/[id]")).unwrap();
+        fs::write(
+            temp_dir.path().join("orders/[id]/POST.rhai"),
+            "---\nheaders:\n  X-Mock: script\n---\n\
+             if request.json == () { return #{ status: 400, body: \"JSON expected\" }; }\n\
+             #{ status: 201, headers: #{ Location: `/orders/${request.params.id}` },\n\
+                body: #{ id: request.params.id, items: request.json.items.len() } }\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("orders/GET.rhai"), "throw \"broken\";").unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let post = |body: &'static str| {
+            let mut request = Request::builder()
+                .method("POST")
+                .uri("/orders/7")
+                .body(Body::from(body))
+                .unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(Connection::with_trigger(
+                    FaultTrigger::default(),
+                )));
+            router.clone().oneshot(request)
+        };
+        let response = post(r#"{"items": [1, 2]}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["location"], "/orders/7");
+        assert_eq!(response.headers()["x-mock"], "script");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, r#"{"id":"7","items":2}"#);
+
+        let response = post("not json").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+
+        assert_eq!(
+            send(&router, "GET", "/orders").await.status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("items")).unwrap();
+        fs::write(
+            temp_dir.path().join("items/GET.json"),
+            "---\nplugin: ../plugins/tag.wat\n---\n[1, 2]",
+        )
+        .unwrap();
+        // Writes `{"status":203,"headers":{"x-plugin":"tag"}}` to stdout
+        let output = r#"{"status":203,"headers":{"x-plugin":"tag"}}"#;
+        fs::create_dir_all(temp_dir.path().join("plugins")).unwrap();
+        fs::write(
+            temp_dir.path().join("plugins/tag.wat"),
+            format!(
+                r#"(module
+                  (import "wasi_snapshot_preview1" "fd_write"
+                    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                  (memory (export "memory") 1)
+                  (data (i32.const 0) "\10\00\00\00\{:02x}\00\00\00")
+                  (data (i32.const 16) "{}")
+                  (func (export "_start")
+                    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))"#,
+                output.len(),
+                output.replace('"', "\\\""),
+            ),
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let response = send(&router, "GET", "/items").await;
+        assert_eq!(response.status(), StatusCode::NON_AUTHORITATIVE_INFORMATION);
+        assert_eq!(response.headers()["x-plugin"], "tag");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "[1, 2]");
+    }
+
+    #[tokio::test]
+    async fn test_match_client_ip() {
+        let temp_dir = users_tree();
+        fs::write(
+            temp_dir.path().join("routes.yaml"),
+            "routes:\n  - {method: GET, path: /users, meta: {status: 403, match: {client_ip: 192.0.2.0/24}}}",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let status = async |peer: &str| {
+            let mut connection = Connection::with_trigger(FaultTrigger::default());
+            connection.remote_addr = Some(SocketAddr::new(peer.parse().unwrap(), 40000));
+            let mut request = Request::builder()
+                .uri("/users")
+                .body(Body::empty())
+                .unwrap();
+            request.extensions_mut().insert(ConnectInfo(connection));
+            router.clone().oneshot(request).await.unwrap().status()
+        };
+        assert_eq!(status("192.0.2.7").await, StatusCode::FORBIDDEN);
+        assert_eq!(status("198.51.100.1").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_trailing_slash_redirect() {
+        let temp_dir = users_tree();
+        fs::create_dir_all(temp_dir.path().join("orders")).unwrap();
+        fs::write(
+            temp_dir.path().join("orders/GET.json"),
+            "---\ntrailing_slash: redirect\n---\n[]",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let response = send(&router, "GET", "/orders/?page=2").await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/orders?page=2");
+        assert_eq!(
+            send(&router, "GET", "/orders").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&router, "GET", "/users/").await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tags() {
+        let temp_dir = users_tree();
+        fs::write(
+            temp_dir.path().join("routes.yaml"),
+            "routes:\n  - {method: GET, path: /users, meta: {status: 500, tags: [errors]}}\n  - {method: GET, path: /beta, meta: {tags: [v2]}}",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        assert_eq!(
+            send(&router, "GET", "/users").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&router, "GET", "/beta").await.status(),
+            StatusCode::NOT_FOUND
+        );
+
+        let mut request = Request::builder()
+            .method("PUT")
+            .uri("/__blendwerk/tags")
+            .body(Body::from(
+                r#"{"enabled": ["errors", "v2"], "disabled": ["v2"]}"#,
+            ))
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(Connection::with_trigger(
+                FaultTrigger::default(),
+            )));
+        assert_eq!(
+            router.clone().oneshot(request).await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        assert_eq!(
+            send(&router, "GET", "/users").await.status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            send(&router, "GET", "/beta").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        let response = send(&router, "GET", "/__blendwerk/tags").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            r#"{"disabled":["v2"],"enabled":["errors","v2"],"known":["errors","v2"]}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_content_type_header_overrides_extension() {
+        let temp_dir = users_tree();
+        fs::write(
+            temp_dir.path().join("users/GET.json"),
+            "---\nheaders:\n  content-type: application/vnd.api+json\n---\n[]",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let response = send(&router, "GET", "/users").await;
+        let content_types: Vec<_> = response.headers().get_all(CONTENT_TYPE).iter().collect();
+        assert_eq!(content_types, ["application/vnd.api+json"]);
+
+        let response = send(&router, "POST", "/users").await;
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    }
+
+    #[tokio::test]
+    async fn test_require_content_type() {
+        let temp_dir = users_tree();
+        fs::write(
+            temp_dir.path().join("users/POST.json"),
+            "---\nstatus: 201\nrequire_content_type: application/json\n---\n{}",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+        let post = |content_type: &'static str| {
+            let mut request = Request::builder().method("POST").uri("/users");
+            if !content_type.is_empty() {
+                request = request.header(CONTENT_TYPE, content_type);
+            }
+            let mut request = request.body(Body::from("{}")).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(Connection::with_trigger(
+                    FaultTrigger::default(),
+                )));
+            router.clone().oneshot(request)
+        };
+
+        let response = post("Application/JSON; charset=utf-8").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = post("text/plain").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            "/users requires Content-Type application/json, got text/plain"
+        );
+
+        let response = post("").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_get() {
+        let temp_dir = users_tree();
+        fs::write(
+            temp_dir.path().join("users/GET.json"),
+            "---\netag: true\n---\n[]",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let response = send(&router, "GET", "/users").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[ETAG].clone();
+        assert!(response.headers().contains_key(LAST_MODIFIED));
+
+        let mut request = Request::builder()
+            .uri("/users")
+            .header("if-none-match", etag)
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(Connection::with_trigger(
+                FaultTrigger::default(),
+            )));
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        // Only GET and HEAD responses carry validators
+        let response = send(&router, "POST", "/users").await;
+        assert!(!response.headers().contains_key(ETAG));
+    }
+
+    #[tokio::test]
+    async fn test_request_limits() {
+        let temp_dir = users_tree();
+        let strict = router(
+            &temp_dir,
+            RequestLimits {
+                max_uri_length: Some(16),
+                max_header_bytes: Some(20),
+                max_body_bytes: None,
+            },
+        );
+
+        // "user-agent" + "blendwerk-test" is 24 bytes
+        let response = send(&strict, "GET", "/users").await;
+        assert_eq!(
+            response.status(),
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        );
+
+        let response = send(&strict, "GET", "/users?page=1&size=100").await;
+        assert_eq!(response.status(), StatusCode::URI_TOO_LONG);
+
+        let relaxed = router(
+            &temp_dir,
+            RequestLimits {
+                max_uri_length: Some(16),
+                max_header_bytes: Some(24),
+                max_body_bytes: None,
+            },
+        );
+        let response = send(&relaxed, "GET", "/users?page=1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_max_body_bytes() {
+        let temp_dir = users_tree();
+        fs::create_dir_all(temp_dir.path().join("echo")).unwrap();
+        fs::write(
+            temp_dir.path().join("echo/POST.json"),
+            "---\nmode: echo\n---\n",
+        )
+        .unwrap();
+        let router = router(
+            &temp_dir,
+            RequestLimits {
+                max_body_bytes: Some(8),
+                ..RequestLimits::default()
+            },
+        );
+        let post = |path: &str, length: Option<usize>, body: &'static str| {
+            let mut request = Request::builder().method("POST").uri(path);
+            if let Some(length) = length {
+                request = request.header(CONTENT_LENGTH, length);
+            }
+            let mut request = request.body(Body::from(body)).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(Connection::with_trigger(
+                    FaultTrigger::default(),
+                )));
+            router.clone().oneshot(request)
+        };
+
+        // Refused by its declared length, before anything is read
+        let response = post("/users", Some(9), "123456789").await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Refused while reading a body of unknown length
+        let response = post("/echo", None, "123456789").await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            "Request body too large: at most 8 bytes allowed".as_bytes()
+        );
+
+        let response = post("/echo", None, "12345678").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_promote_stays_in_mock_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let mocks = temp_dir.path().join("mocks");
+        fs::create_dir_all(&mocks).unwrap();
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        let router = create_router(Arc::new(AppState {
+            promote_dir: Some(mocks.clone()),
+            ..AppState::new(Arc::default(), shutdown_rx)
+        }));
+        let promote = |path: &str| {
+            let entry = serde_json::json!({
+                "request": {"method": "GET", "path": path},
+                "response": {"status": 200, "body": "escaped"},
+            });
+            let mut request = Request::builder()
+                .method("POST")
+                .uri("/__blendwerk/promote")
+                .body(Body::from(entry.to_string()))
+                .unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(Connection::with_trigger(
+                    FaultTrigger::default(),
+                )));
+            router.clone().oneshot(request)
+        };
+
+        for path in ["/../escaped", "/a/%2E%2E/%2e%2e/escaped", "/..%2Fescaped"] {
+            let response = promote(path).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", path);
+        }
+        assert!(!temp_dir.path().join("escaped").exists());
+        assert_eq!(fs::read_dir(&mocks).unwrap().count(), 0);
+
+        let response = promote("/users").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(mocks.join("users/GET.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_routes_read_bodies_follows_stubs() {
+        let temp_dir = users_tree();
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        let routes = routes::scan_directory(temp_dir.path()).unwrap();
+        let state = Arc::new(AppState::new(Arc::new(RwLock::new(routes)), shutdown_rx));
+        let router = create_router(state.clone());
+        assert!(!state.routes_read_bodies.get());
+
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/__blendwerk/stubs?method=POST&path=/debug")
+            .body(Body::from("---\nmode: echo\n---\n"))
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(Connection::with_trigger(
+                FaultTrigger::default(),
+            )));
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(state.routes_read_bodies.get());
+
+        send(&router, "DELETE", "/__blendwerk/stubs").await;
+        assert!(!state.routes_read_bodies.get());
+    }
+
+    #[tokio::test]
+    async fn test_trace_fields_on_request_span() {
+        let temp_dir = users_tree();
+        fs::write(
+            temp_dir.path().join("users/GET.json"),
+            "---\ntrace_fields:\n  case: \"42\"\n---\n[]",
+        )
+        .unwrap();
+        let router = router(&temp_dir, RequestLimits::default());
+
+        let recorded = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(RecordedTraceFields(recorded.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        send(&router, "GET", "/users").await;
+        send(&router, "POST", "/users").await;
+        assert_eq!(*recorded.lock().unwrap(), vec!["case=42"]);
+    }
+
+    /// Collects the values recorded as `trace_fields` on spans
+    struct RecordedTraceFields(Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for RecordedTraceFields {
+        fn on_record(&self, _span: &span::Id, values: &span::Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut |field: &Field, value: &dyn std::fmt::Debug| {
+                if field.name() == "trace_fields" {
+                    let value = format!("{:?}", value);
+                    self.0
+                        .lock()
+                        .unwrap()
+                        .push(value.trim_matches('"').to_string());
+                }
+            });
+        }
+    }
 }
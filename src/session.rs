@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Cookie-keyed sessions from the `session:` frontmatter block.
+//!
+//! A session is a named state stored under the value of a cookie. Routes
+//! move sessions into a state (issuing the cookie if the client has none),
+//! require a state, or end the session. Sessions live in memory and survive
+//! reloads of the mock directory, but not restarts.
+
+use axum::http::{HeaderMap, HeaderValue, header::COOKIE};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_COOKIE: &str = "session";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Session {
+    /// Name of the cookie holding the session ID
+    #[serde(default = "default_cookie")]
+    pub cookie: String,
+    /// State the session must be in; other requests get a 401
+    #[serde(default)]
+    pub requires: Option<String>,
+    /// State the session is put into
+    #[serde(default)]
+    pub set: Option<String>,
+    /// Forget the session and expire its cookie
+    #[serde(default)]
+    pub end: bool,
+}
+
+fn default_cookie() -> String {
+    DEFAULT_COOKIE.to_string()
+}
+
+/// Why a request was refused by a route's `session:` requirements
+#[derive(Debug, PartialEq)]
+pub struct Rejection {
+    pub required: String,
+    pub actual: Option<String>,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.actual {
+            Some(actual) => write!(
+                f,
+                "Session is {}, but {} is required",
+                actual, self.required
+            ),
+            None => write!(f, "Session {} is required", self.required),
+        }
+    }
+}
+
+/// States of all sessions, by session ID
+#[derive(Debug, Clone, Default)]
+pub struct Sessions(Arc<Mutex<HashMap<String, String>>>);
+
+impl Sessions {
+    /// Check the request against the route's session block and update the
+    /// session.
+    ///
+    /// Returns the `Set-Cookie` value to send, if the cookie changes.
+    pub fn apply(
+        &self,
+        session: &Session,
+        headers: &HeaderMap,
+    ) -> Result<Option<HeaderValue>, Rejection> {
+        let id = cookie(headers, &session.cookie);
+        let mut states = self.0.lock().unwrap();
+        let current = id.as_ref().and_then(|id| states.get(id)).cloned();
+
+        if let Some(required) = &session.requires
+            && current.as_ref() != Some(required)
+        {
+            return Err(Rejection {
+                required: required.clone(),
+                actual: current,
+            });
+        }
+
+        if session.end {
+            if let Some(id) = &id {
+                states.remove(id);
+            }
+            return Ok(set_cookie(&format!(
+                "{}=; Path=/; Max-Age=0",
+                session.cookie
+            )));
+        }
+
+        let Some(state) = &session.set else {
+            return Ok(None);
+        };
+        // IDs sent by the client are adopted, so tests can pick their own
+        match id {
+            Some(id) => {
+                states.insert(id, state.clone());
+                Ok(None)
+            }
+            None => {
+                let id = format!("{:032x}", rand::random::<u128>());
+                states.insert(id.clone(), state.clone());
+                Ok(set_cookie(&format!(
+                    "{}={}; Path=/; HttpOnly; SameSite=Lax",
+                    session.cookie, id
+                )))
+            }
+        }
+    }
+}
+
+fn set_cookie(value: &str) -> Option<HeaderValue> {
+    HeaderValue::try_from(value).ok()
+}
+
+/// Value of the cookie `name` in the request's `Cookie` headers
+fn cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(cookie, _)| *cookie == name)
+        .map(|(_, value)| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(yaml: &str) -> Session {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn with_cookie(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::try_from(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_login_flow() {
+        let sessions = Sessions::default();
+        let login = session("set: logged-in");
+        let profile = session("requires: logged-in");
+        let logout = session("end: true");
+
+        assert_eq!(
+            sessions.apply(&profile, &HeaderMap::new()),
+            Err(Rejection {
+                required: "logged-in".to_string(),
+                actual: None,
+            })
+        );
+
+        let set_cookie = sessions.apply(&login, &HeaderMap::new()).unwrap().unwrap();
+        let pair = set_cookie.to_str().unwrap().split(';').next().unwrap();
+        let headers = with_cookie(&format!("theme=dark; {}", pair));
+        assert_eq!(sessions.apply(&profile, &headers), Ok(None));
+
+        let expired = sessions.apply(&logout, &headers).unwrap().unwrap();
+        assert!(expired.to_str().unwrap().contains("Max-Age=0"));
+        assert!(sessions.apply(&profile, &headers).is_err());
+    }
+
+    #[test]
+    fn test_client_chosen_id_and_cookie_name() {
+        let sessions = Sessions::default();
+        let headers = with_cookie("sid=test-1");
+
+        let checkout = session("cookie: sid\nset: cart");
+        assert_eq!(sessions.apply(&checkout, &headers), Ok(None));
+
+        let pay = session("cookie: sid\nrequires: cart\nset: paid");
+        assert_eq!(sessions.apply(&pay, &headers), Ok(None));
+        assert_eq!(
+            sessions.apply(&pay, &headers),
+            Err(Rejection {
+                required: "cart".to_string(),
+                actual: Some("paid".to_string()),
+            })
+        );
+    }
+}
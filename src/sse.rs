@@ -0,0 +1,128 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::delay::Delay;
+use crate::server::ShutdownSignal;
+use axum::body::Body;
+use bytes::Bytes;
+use futures_util::{StreamExt, stream};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::time::sleep;
+
+pub const CONTENT_TYPE: &str = "text/event-stream";
+
+/// A single server-sent event of a scripted stream
+#[derive(Debug, Clone, Deserialize)]
+pub struct SseEvent {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub event: Option<String>,
+    #[serde(default)]
+    pub data: String,
+    #[serde(default)]
+    pub retry: Option<u64>,
+    /// Pause before this event is sent, in milliseconds or as a distribution
+    #[serde(default)]
+    pub delay: Delay,
+}
+
+impl SseEvent {
+    /// Render the event in the `text/event-stream` wire format
+    pub fn render(&self) -> String {
+        let mut rendered = String::new();
+        if let Some(id) = &self.id {
+            rendered.push_str(&format!("id: {}\n", id));
+        }
+        if let Some(event) = &self.event {
+            rendered.push_str(&format!("event: {}\n", event));
+        }
+        if let Some(retry) = self.retry {
+            rendered.push_str(&format!("retry: {}\n", retry));
+        }
+        // Every line of multi-line data needs its own field
+        for line in self.data.split('\n') {
+            rendered.push_str(&format!("data: {}\n", line));
+        }
+        rendered.push('\n');
+        rendered
+    }
+}
+
+/// All events rendered back to back, used wherever the full body is needed
+pub fn full_body(events: &[SseEvent]) -> String {
+    events.iter().map(SseEvent::render).collect()
+}
+
+/// Build a body that sends each event after its own delay
+pub fn into_body(events: Vec<SseEvent>) -> Body {
+    let scripted = stream::unfold(events.into_iter(), |mut events| async move {
+        let event = events.next()?;
+
+        let delay = event.delay.sample();
+        if delay > 0 {
+            sleep(Duration::from_millis(delay)).await;
+        }
+
+        Some((Ok::<_, Infallible>(Bytes::from(event.render())), events))
+    });
+
+    Body::from_stream(scripted)
+}
+
+/// Keep the stream open after the body is sent until the server shuts
+/// down, like a real event source waiting for new events
+pub fn hold_open(body: Body, mut shutdown: ShutdownSignal) -> Body {
+    let hold = stream::once(async move {
+        let _ = shutdown.wait_for(|stopped| *stopped).await;
+    })
+    .filter_map(|_| async { None });
+
+    Body::from_stream(body.into_data_stream().chain(hold))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::watch;
+
+    #[test]
+    fn test_render_event() {
+        let event: SseEvent = serde_yaml::from_str(
+            r#"
+id: "1"
+event: token
+data: "first\nsecond"
+retry: 3000
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            event.render(),
+            "id: 1\nevent: token\nretry: 3000\ndata: first\ndata: second\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_stays_open_until_shutdown() {
+        let events: Vec<SseEvent> = serde_yaml::from_str("- data: a\n- data: b\n").unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let mut stream = hold_open(into_body(events), shutdown_rx).into_data_stream();
+        assert_eq!(stream.next().await.unwrap().unwrap(), "data: a\n\n");
+        assert_eq!(stream.next().await.unwrap().unwrap(), "data: b\n\n");
+
+        let pending = tokio::time::timeout(Duration::from_millis(50), stream.next()).await;
+        assert!(pending.is_err());
+
+        shutdown_tx.send(true).unwrap();
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,100 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::routes::Route;
+use bytes::Bytes;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Memory consumed by the response bodies of the loaded routes
+#[derive(Debug, Serialize)]
+pub struct MemoryReport {
+    /// Sum of all body sizes as if every route owned its body
+    pub total_body_bytes: usize,
+    /// Bytes actually allocated after deduplication
+    pub unique_body_bytes: usize,
+    pub routes: Vec<RouteMemory>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteMemory {
+    pub method: String,
+    pub path: String,
+    pub body_bytes: usize,
+    /// Whether the body allocation is shared with other routes
+    pub shared: bool,
+}
+
+/// Make routes with identical bodies share a single allocation
+pub fn deduplicate_bodies(routes: &mut [Route]) {
+    let mut bodies: HashSet<Bytes> = HashSet::new();
+
+    for route in routes {
+        match bodies.get(&route.response.body) {
+            Some(existing) => route.response.body = existing.clone(),
+            None => {
+                bodies.insert(route.response.body.clone());
+            }
+        }
+    }
+}
+
+pub fn memory_report(routes: &[Route]) -> MemoryReport {
+    // Bodies are shared by reference, so identical allocations are detected
+    // by their address rather than their content
+    let mut allocations: HashMap<(usize, usize), usize> = HashMap::new();
+    for route in routes {
+        *allocations
+            .entry(allocation(&route.response.body))
+            .or_default() += 1;
+    }
+
+    MemoryReport {
+        total_body_bytes: routes.iter().map(|r| r.response.body.len()).sum(),
+        unique_body_bytes: allocations.keys().map(|(_, len)| len).sum(),
+        routes: routes
+            .iter()
+            .map(|route| RouteMemory {
+                method: route.method.as_str().to_string(),
+                path: route.display_path(),
+                body_bytes: route.response.body.len(),
+                shared: allocations[&allocation(&route.response.body)] > 1,
+            })
+            .collect(),
+    }
+}
+
+fn allocation(body: &Bytes) -> (usize, usize) {
+    (body.as_ptr() as usize, body.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scan_directory;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_identical_bodies_are_shared() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["a", "b"] {
+            let dir = temp_dir.path().join(name);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join("GET.json"), r#"{"shared": true}"#).unwrap();
+        }
+        fs::write(temp_dir.path().join("GET.json"), r#"{"unique": true}"#).unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        let report = memory_report(&routes);
+
+        assert_eq!(report.total_body_bytes, 16 + 16 + 16);
+        assert_eq!(report.unique_body_bytes, 16 + 16);
+        assert_eq!(report.routes.iter().filter(|r| r.shared).count(), 2);
+    }
+}
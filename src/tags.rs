@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Route activation by `tags:` (`--enable-tags`, `--disable-tags`).
+//!
+//! Routes without tags are always served. Tagged routes are only served
+//! while one of their tags is enabled and none is disabled, and then take
+//! precedence over untagged routes of the same path, so an `errors` set can
+//! shadow the happy path for one test run. The selection can be replaced at
+//! runtime through `/__blendwerk/tags`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::{Arc, RwLock};
+
+/// Enabled and disabled tags
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TagSelection {
+    #[serde(default)]
+    pub enabled: BTreeSet<String>,
+    #[serde(default)]
+    pub disabled: BTreeSet<String>,
+}
+
+impl TagSelection {
+    /// Whether a route with `tags` is served
+    pub fn is_active(&self, tags: &[String]) -> bool {
+        tags.is_empty()
+            || (tags.iter().any(|tag| self.enabled.contains(tag))
+                && !tags.iter().any(|tag| self.disabled.contains(tag)))
+    }
+}
+
+/// Tag selection shared by all requests, changeable at runtime
+#[derive(Debug, Clone, Default)]
+pub struct Tags(Arc<RwLock<TagSelection>>);
+
+impl Tags {
+    pub fn new(selection: TagSelection) -> Self {
+        Self(Arc::new(RwLock::new(selection)))
+    }
+
+    pub fn selection(&self) -> TagSelection {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, selection: TagSelection) {
+        *self.0.write().unwrap() = selection;
+    }
+
+    /// Whether a route with `tags` is served
+    pub fn is_active(&self, tags: &[String]) -> bool {
+        self.0.read().unwrap().is_active(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_active() {
+        let selection = TagSelection {
+            enabled: ["errors".to_string(), "v2".to_string()].into(),
+            disabled: ["slow".to_string()].into(),
+        };
+        assert!(selection.is_active(&[]));
+        assert!(selection.is_active(&tags(&["errors"])));
+        assert!(selection.is_active(&tags(&["legacy", "v2"])));
+        assert!(!selection.is_active(&tags(&["legacy"])));
+        assert!(!selection.is_active(&tags(&["errors", "slow"])));
+        assert!(!TagSelection::default().is_active(&tags(&["errors"])));
+    }
+
+    #[test]
+    fn test_runtime_toggle() {
+        let tags = Tags::default();
+        assert!(!tags.is_active(&self::tags(&["errors"])));
+        tags.set(serde_json::from_str(r#"{"enabled": ["errors"]}"#).unwrap());
+        assert!(tags.is_active(&self::tags(&["errors"])));
+        assert!(serde_json::from_str::<TagSelection>(r#"{"enable": ["errors"]}"#).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use axum::body::Body;
+use bytes::Bytes;
+use futures_util::{StreamExt, stream};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Interval between chunks when the bandwidth allows more than one byte
+const TICK: Duration = Duration::from_millis(100);
+
+/// Bandwidth limit parsed from strings like `16kbps`, `2mbps` or `500bps`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Throttle {
+    bytes_per_second: f64,
+}
+
+impl TryFrom<String> for Throttle {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let lower = value.trim().to_lowercase();
+        let (number, multiplier) = if let Some(number) = lower.strip_suffix("gbps") {
+            (number, 1_000_000_000.0)
+        } else if let Some(number) = lower.strip_suffix("mbps") {
+            (number, 1_000_000.0)
+        } else if let Some(number) = lower.strip_suffix("kbps") {
+            (number, 1_000.0)
+        } else if let Some(number) = lower.strip_suffix("bps") {
+            (number, 1.0)
+        } else {
+            return Err(format!(
+                "invalid throttle '{}', expected e.g. 16kbps, 2mbps or 500bps",
+                value
+            ));
+        };
+
+        let bits_per_second = number
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite() && *n > 0.0)
+            .ok_or_else(|| format!("invalid throttle rate '{}'", value))?
+            * multiplier;
+
+        Ok(Self {
+            bytes_per_second: bits_per_second / 8.0,
+        })
+    }
+}
+
+impl Throttle {
+    /// Size of a single chunk and the pause that keeps chunks at the limit
+    fn pacing(&self) -> (usize, Duration) {
+        let chunk_size = (self.bytes_per_second * TICK.as_secs_f64()).max(1.0) as usize;
+        let interval = Duration::from_secs_f64(chunk_size as f64 / self.bytes_per_second);
+        (chunk_size, interval)
+    }
+
+    /// Wrap a body so it trickles out no faster than the configured rate
+    pub fn apply(&self, body: Body) -> Body {
+        let (chunk_size, interval) = self.pacing();
+        let state = (body.into_data_stream(), Bytes::new(), true);
+
+        let paced = stream::unfold(state, move |(mut inner, mut pending, first)| async move {
+            if pending.is_empty() {
+                match inner.next().await? {
+                    Ok(bytes) => pending = bytes,
+                    Err(e) => return Some((Err(e), (inner, pending, first))),
+                }
+            }
+
+            if !first {
+                sleep(interval).await;
+            }
+
+            let chunk = pending.split_to(chunk_size.min(pending.len()));
+            Some((Ok(chunk), (inner, pending, false)))
+        });
+
+        Body::from_stream(paced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn parse(value: &str) -> Result<Throttle, String> {
+        Throttle::try_from(value.to_string())
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(parse("16kbps").unwrap().bytes_per_second, 2_000.0);
+        assert_eq!(parse("2 Mbps").unwrap().bytes_per_second, 250_000.0);
+        assert_eq!(parse("800bps").unwrap().bytes_per_second, 100.0);
+        assert!(parse("16").is_err());
+        assert!(parse("0kbps").is_err());
+        assert!(parse("fastkbps").is_err());
+    }
+
+    #[test]
+    fn test_slow_rates_send_single_bytes() {
+        let (chunk_size, interval) = parse("4bps").unwrap().pacing();
+        assert_eq!(chunk_size, 1);
+        assert_eq!(interval, Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_body_is_paced() {
+        // 1000 bytes per second: 100 byte chunks every 100ms
+        let throttle = parse("8kbps").unwrap();
+        let body = throttle.apply(Body::from(vec![b'x'; 300]));
+
+        let started = Instant::now();
+        let chunks: Vec<Bytes> = body
+            .into_data_stream()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.len() == 100));
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -8,21 +8,196 @@
 
 use anyhow::{Context, Result};
 use axum_server::tls_rustls::RustlsConfig;
-use rcgen::{CertifiedKey, generate_simple_self_signed};
-use std::path::Path;
+use chrono::{Datelike, Days, Utc};
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    Issuer, KeyPair, KeyUsagePurpose, date_time_ymd,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-pub async fn create_self_signed_config() -> Result<RustlsConfig> {
-    let subject_alt_names = vec![
-        "localhost".to_string(),
-        "127.0.0.1".to_string(),
-        "::1".to_string(),
-    ];
+const CA_CERT_FILE: &str = "ca.pem";
+const CA_KEY_FILE: &str = "ca-key.pem";
+const CA_NAME: &str = "blendwerk local CA";
 
-    let CertifiedKey { cert, signing_key } = generate_simple_self_signed(subject_alt_names)
-        .context("Failed to generate self-signed certificate")?;
+/// Names the generated server certificate is always valid for
+const SUBJECT_ALT_NAMES: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
 
-    let cert_pem = cert.pem();
-    let key_pem = signing_key.serialize_pem();
+/// Subject and alternative names of the self-signed mode's server
+/// certificate
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerNames {
+    common_name: String,
+    alt_names: Vec<String>,
+}
+
+impl ServerNames {
+    /// Localhost plus `extra` names (`--cert-san`), with `common_name`
+    /// (`--cert-cn`) as subject; the subject is covered by the alternative
+    /// names too, since clients only check those
+    pub fn new(extra: &[String], common_name: Option<&str>) -> Self {
+        let common_name = common_name.unwrap_or(SUBJECT_ALT_NAMES[0]).to_string();
+        let mut alt_names: Vec<String> = SUBJECT_ALT_NAMES.map(String::from).to_vec();
+        for name in extra.iter().chain([&common_name]) {
+            if !alt_names.contains(name) {
+                alt_names.push(name.clone());
+            }
+        }
+        Self {
+            common_name,
+            alt_names,
+        }
+    }
+}
+
+impl Default for ServerNames {
+    fn default() -> Self {
+        Self::new(&[], None)
+    }
+}
+
+/// Directory keeping the local CA across restarts: `$XDG_STATE_HOME/blendwerk`,
+/// `~/.local/state/blendwerk` or `%LOCALAPPDATA%\blendwerk`
+pub fn default_state_dir() -> Option<PathBuf> {
+    let non_empty = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    non_empty("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .or_else(|| non_empty("LOCALAPPDATA").map(PathBuf::from))
+        .map(|dir| dir.join("blendwerk"))
+}
+
+/// Certificate authority signing the self-signed mode's server certificates.
+///
+/// Clients that trust the CA once accept the certificates of all later
+/// starts.
+pub struct LocalCa {
+    pub cert_pem: String,
+    key: KeyPair,
+}
+
+impl LocalCa {
+    /// Load the CA from `dir`, generating and storing it on first use
+    pub fn load_or_create(dir: &Path) -> Result<Self> {
+        let cert_file = dir.join(CA_CERT_FILE);
+        let key_file = dir.join(CA_KEY_FILE);
+        if cert_file.exists() && key_file.exists() {
+            let cert_pem = std::fs::read_to_string(&cert_file)
+                .with_context(|| format!("Failed to read {}", cert_file.display()))?;
+            let key_pem = std::fs::read_to_string(&key_file)
+                .with_context(|| format!("Failed to read {}", key_file.display()))?;
+            let key = KeyPair::from_pem(&key_pem)
+                .with_context(|| format!("Invalid CA key in {}", key_file.display()))?;
+            return Ok(Self { cert_pem, key });
+        }
+
+        let ca = Self::generate()?;
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create state directory {}", dir.display()))?;
+        write_private(&key_file, &ca.key.serialize_pem())
+            .with_context(|| format!("Failed to write {}", key_file.display()))?;
+        std::fs::write(&cert_file, &ca.cert_pem)
+            .with_context(|| format!("Failed to write {}", cert_file.display()))?;
+        Ok(ca)
+    }
+
+    /// Generate a CA that is only kept in memory
+    pub fn generate() -> Result<Self> {
+        let key = KeyPair::generate().context("Failed to generate CA key")?;
+        let mut params = ca_params();
+        set_validity(&mut params, 10 * 365);
+        let cert = params
+            .self_signed(&key)
+            .context("Failed to generate CA certificate")?;
+        Ok(Self {
+            cert_pem: cert.pem(),
+            key,
+        })
+    }
+
+    /// Issue a server certificate for `names`, returning the PEM of the
+    /// chain (server certificate and CA) and of the server key
+    pub fn issue(&self, names: &ServerNames) -> Result<(String, String)> {
+        let key = KeyPair::generate().context("Failed to generate server key")?;
+        // Names that parse as IP addresses become IP address entries
+        let mut params = CertificateParams::new(names.alt_names.clone())
+            .context("Invalid subject alternative names")?;
+        params
+            .distinguished_name
+            .push(DnType::CommonName, names.common_name.as_str());
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.use_authority_key_identifier_extension = true;
+        // Clients reject long-lived server certificates even from local CAs
+        set_validity(&mut params, 365);
+
+        // The CA's parameters are fixed, so the issuer can be rebuilt from
+        // them and the stored key without parsing the certificate
+        let issuer = Issuer::new(ca_params(), &self.key);
+        let cert = params
+            .signed_by(&key, &issuer)
+            .context("Failed to sign server certificate")?;
+        Ok((
+            format!("{}{}", cert.pem(), self.cert_pem),
+            key.serialize_pem(),
+        ))
+    }
+}
+
+/// Parameters of the CA certificate, minus its validity
+fn ca_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CommonName, CA_NAME);
+    name.push(DnType::OrganizationName, "blendwerk");
+    params.distinguished_name = name;
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    params
+}
+
+/// Make a certificate valid from yesterday for `days` days
+fn set_validity(params: &mut CertificateParams, days: u64) {
+    let now = Utc::now();
+    let ymd = |date: chrono::DateTime<Utc>| {
+        date_time_ymd(date.year(), date.month() as u8, date.day() as u8)
+    };
+    params.not_before = ymd(now - Days::new(1));
+    params.not_after = ymd(now + Days::new(days));
+}
+
+/// Write a file only the current user can read
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(content.as_bytes())
+}
+
+/// TLS config with a server certificate from the local CA in `state_dir`.
+///
+/// Falls back to a CA that only lives as long as the process if there is no
+/// state directory or it can't be used.
+pub async fn create_self_signed_config(
+    state_dir: Option<&Path>,
+    names: &ServerNames,
+) -> Result<RustlsConfig> {
+    let ca = match state_dir.map(|dir| (dir, LocalCa::load_or_create(dir))) {
+        Some((dir, Ok(ca))) => {
+            tracing::info!("  Using local CA from {}", dir.display());
+            ca
+        }
+        Some((_, Err(e))) => {
+            tracing::warn!("Using a temporary CA: {:#}", e);
+            LocalCa::generate()?
+        }
+        None => {
+            tracing::warn!("Using a temporary CA: no state directory");
+            LocalCa::generate()?
+        }
+    };
+    let (cert_pem, key_pem) = ca.issue(names)?;
 
     RustlsConfig::from_pem(cert_pem.into_bytes(), key_pem.into_bytes())
         .await
@@ -40,3 +215,101 @@ pub async fn load_custom_config(cert_file: &Path, key_file: &Path) -> Result<Rus
             )
         })
 }
+
+/// Load changed certificate files into a running config, keeping the ALPN
+/// protocols the listeners set up
+pub async fn reload_custom_config(
+    tls_config: &RustlsConfig,
+    cert_file: &Path,
+    key_file: &Path,
+) -> Result<()> {
+    let alpn_protocols = tls_config.get_inner().alpn_protocols.clone();
+    let reloaded = load_custom_config(cert_file, key_file).await?;
+    let mut server_config = (*reloaded.get_inner()).clone();
+    server_config.alpn_protocols = alpn_protocols;
+    tls_config.reload_from_config(Arc::new(server_config));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ca_is_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("state");
+        let ca = LocalCa::load_or_create(&dir).unwrap();
+        let again = LocalCa::load_or_create(&dir).unwrap();
+        assert_eq!(ca.cert_pem, again.cert_pem);
+
+        let (chain, _) = again.issue(&ServerNames::default()).unwrap();
+        assert_eq!(chain.matches("BEGIN CERTIFICATE").count(), 2);
+        assert!(chain.ends_with(&ca.cert_pem));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join(CA_KEY_FILE))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_alpn() {
+        let temp_dir = TempDir::new().unwrap();
+        let cert_file = temp_dir.path().join("cert.pem");
+        let key_file = temp_dir.path().join("key.pem");
+        let ca = LocalCa::generate().unwrap();
+        let write = |(chain, key): (String, String)| {
+            std::fs::write(&cert_file, chain).unwrap();
+            std::fs::write(&key_file, key).unwrap();
+        };
+
+        write(ca.issue(&ServerNames::default()).unwrap());
+        let tls_config = load_custom_config(&cert_file, &key_file).await.unwrap();
+        let mut server_config = (*tls_config.get_inner()).clone();
+        server_config.alpn_protocols = vec![b"h2".to_vec()];
+        tls_config.reload_from_config(Arc::new(server_config));
+        let before = tls_config.get_inner();
+
+        write(ca.issue(&ServerNames::default()).unwrap());
+        reload_custom_config(&tls_config, &cert_file, &key_file)
+            .await
+            .unwrap();
+        assert!(!Arc::ptr_eq(&before, &tls_config.get_inner()));
+        assert_eq!(tls_config.get_inner().alpn_protocols, vec![b"h2".to_vec()]);
+
+        // A certificate without its key leaves the config alone
+        std::fs::write(&cert_file, ca.issue(&ServerNames::default()).unwrap().0).unwrap();
+        let current = tls_config.get_inner();
+        assert!(
+            reload_custom_config(&tls_config, &cert_file, &key_file)
+                .await
+                .is_err()
+        );
+        assert!(Arc::ptr_eq(&current, &tls_config.get_inner()));
+    }
+
+    #[test]
+    fn test_server_names() {
+        let names = ServerNames::new(&["api.test".to_string(), "::1".to_string()], None);
+        assert_eq!(names.common_name, "localhost");
+        assert_eq!(
+            names.alt_names,
+            ["localhost", "127.0.0.1", "::1", "api.test"]
+        );
+
+        let names = ServerNames::new(&["10.0.0.5".to_string()], Some("mock.internal"));
+        assert_eq!(names.common_name, "mock.internal");
+        assert_eq!(
+            names.alt_names,
+            ["localhost", "127.0.0.1", "::1", "10.0.0.5", "mock.internal"]
+        );
+        assert!(LocalCa::generate().unwrap().issue(&names).is_ok());
+    }
+}
@@ -0,0 +1,253 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Transfer statistics of response bodies for the request log.
+//!
+//! The body is observed while it is sent, so throttled, chunked and streamed
+//! bodies are measured as they went out. Bodies with a `gzip` or `deflate`
+//! `Content-Encoding` are decoded on the side to report their original size.
+
+use axum::body::Body;
+use bytes::Bytes;
+use flate2::write::{MultiGzDecoder, ZlibDecoder};
+use http_body::{Frame, SizeHint};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TransferStats {
+    /// Body bytes sent, as encoded on the wire (before transfer encoding)
+    pub transferred_bytes: u64,
+    /// Number of body chunks the server wrote
+    pub chunks: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_encoding: Option<String>,
+    /// Size after decoding the content encoding, if it is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded_bytes: Option<u64>,
+    /// `decoded_bytes / transferred_bytes`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_ratio: Option<f64>,
+    /// Whether the whole body was sent; `false` if the client went away or a
+    /// fault replaced the response
+    pub complete: bool,
+}
+
+/// Wrap `body` to call `on_done` with its transfer statistics once it has
+/// been sent completely or dropped
+pub fn observe(
+    body: Body,
+    content_encoding: Option<&str>,
+    on_done: impl FnOnce(TransferStats) + Send + 'static,
+) -> Body {
+    let encoding = content_encoding.map(|encoding| encoding.trim().to_ascii_lowercase());
+    let decoder = match encoding.as_deref() {
+        Some("gzip") | Some("x-gzip") => Some(Decoder::Gzip(MultiGzDecoder::new(ByteCounter(0)))),
+        Some("deflate") => Some(Decoder::Deflate(ZlibDecoder::new(ByteCounter(0)))),
+        _ => None,
+    };
+
+    Body::new(ObservedBody {
+        inner: body,
+        stats: TransferStats {
+            content_encoding: encoding.filter(|encoding| encoding != "identity"),
+            ..Default::default()
+        },
+        decoder,
+        on_done: Some(Box::new(on_done)),
+    })
+}
+
+/// Counts the decoded bytes instead of keeping them
+struct ByteCounter(u64);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Decoder {
+    Gzip(MultiGzDecoder<ByteCounter>),
+    Deflate(ZlibDecoder<ByteCounter>),
+}
+
+impl Decoder {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Gzip(decoder) => decoder.write_all(data),
+            Self::Deflate(decoder) => decoder.write_all(data),
+        }
+    }
+
+    fn finish(self) -> io::Result<u64> {
+        match self {
+            Self::Gzip(decoder) => decoder.finish().map(|counter| counter.0),
+            Self::Deflate(decoder) => decoder.finish().map(|counter| counter.0),
+        }
+    }
+}
+
+type OnDone = Box<dyn FnOnce(TransferStats) + Send>;
+
+struct ObservedBody {
+    inner: Body,
+    stats: TransferStats,
+    /// `None` without a known encoding and for bodies that failed to decode
+    decoder: Option<Decoder>,
+    on_done: Option<OnDone>,
+}
+
+impl ObservedBody {
+    fn finish(&mut self) {
+        let Some(on_done) = self.on_done.take() else {
+            return;
+        };
+
+        // A partially sent body can't be decoded to its full size
+        let decoded = match (self.stats.complete, self.decoder.take()) {
+            (true, Some(decoder)) => decoder.finish().ok(),
+            _ => None,
+        };
+        self.stats.decoded_bytes = decoded;
+        self.stats.compression_ratio =
+            decoded
+                .filter(|_| self.stats.transferred_bytes > 0)
+                .map(|decoded| {
+                    let ratio = decoded as f64 / self.stats.transferred_bytes as f64;
+                    (ratio * 100.0).round() / 100.0
+                });
+
+        on_done(std::mem::take(&mut self.stats));
+    }
+}
+
+impl http_body::Body for ObservedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    self.stats.transferred_bytes += data.len() as u64;
+                    self.stats.chunks += 1;
+                    if let Some(decoder) = &mut self.decoder
+                        && decoder.write(data).is_err()
+                    {
+                        self.decoder = None;
+                    }
+                }
+                // Servers stop polling bodies that announce their end
+                if self.inner.is_end_stream() {
+                    self.stats.complete = true;
+                    self.finish();
+                }
+            }
+            Poll::Ready(None) => {
+                self.stats.complete = true;
+                self.finish();
+            }
+            Poll::Ready(Some(Err(_))) => self.finish(),
+            Poll::Pending => {}
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for ObservedBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use futures_util::stream;
+    use http_body_util::BodyExt;
+    use std::sync::{Arc, Mutex};
+
+    async fn transfer(body: Body, encoding: Option<&str>) -> TransferStats {
+        let result = Arc::new(Mutex::new(None));
+        let sink = result.clone();
+        let body = observe(body, encoding, move |stats| {
+            *sink.lock().unwrap() = Some(stats);
+        });
+
+        body.collect().await.unwrap();
+        result.lock().unwrap().take().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_counts_chunks_and_bytes() {
+        let chunks = vec![
+            Ok::<_, io::Error>(Bytes::from("hello ")),
+            Ok(Bytes::from("world")),
+        ];
+        let stats = transfer(Body::from_stream(stream::iter(chunks)), None).await;
+
+        assert_eq!(stats.transferred_bytes, 11);
+        assert_eq!(stats.chunks, 2);
+        assert_eq!(stats.content_encoding, None);
+        assert_eq!(stats.decoded_bytes, None);
+        assert!(stats.complete);
+    }
+
+    #[tokio::test]
+    async fn test_reports_compression_ratio_of_gzip_bodies() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b'a'; 4000]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let compressed_len = compressed.len() as u64;
+
+        let stats = transfer(Body::from(compressed), Some("gzip")).await;
+        assert_eq!(stats.transferred_bytes, compressed_len);
+        assert_eq!(stats.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(stats.decoded_bytes, Some(4000));
+        assert!(stats.compression_ratio.unwrap() > 10.0);
+
+        // Claimed but not actually compressed
+        let stats = transfer(Body::from("plain"), Some("gzip")).await;
+        assert_eq!(stats.decoded_bytes, None);
+        assert!(stats.complete);
+    }
+
+    #[test]
+    fn test_dropped_body_is_incomplete() {
+        let result = Arc::new(Mutex::new(None));
+        let sink = result.clone();
+        drop(observe(Body::from("never sent"), None, move |stats| {
+            *sink.lock().unwrap() = Some(stats);
+        }));
+
+        let stats = result.lock().unwrap().take().unwrap();
+        assert!(!stats.complete);
+        assert_eq!(stats.transferred_bytes, 0);
+    }
+}
@@ -0,0 +1,407 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Assertions on the requests a [`MockServer`](crate::MockServer) received.
+//!
+//! ```no_run
+//! # async fn example(server: blendwerk::MockServer) -> anyhow::Result<()> {
+//! use blendwerk::RequestMatcher;
+//! use std::time::Duration;
+//!
+//! server
+//!     .received("POST", "/orders")
+//!     .with_json_body(&serde_json::json!({"item": "book"}))
+//!     .times(2);
+//!
+//! let matcher = RequestMatcher::new("GET", "/orders/[id]");
+//! server.wait_for_request(matcher, Duration::from_secs(5)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::mock_server::ReceivedRequest;
+use crate::routes::PathSegment;
+use std::fmt;
+
+/// Criteria a received request has to meet, all of them at once
+#[derive(Debug, Clone)]
+pub struct RequestMatcher {
+    method: String,
+    path: String,
+    segments: Vec<PathSegment>,
+    headers: Vec<(String, String)>,
+    query: Vec<(String, String)>,
+    body: Option<Body>,
+}
+
+#[derive(Debug, Clone)]
+enum Body {
+    Exact(Vec<u8>),
+    Json(serde_json::Value),
+    Containing(String),
+}
+
+impl RequestMatcher {
+    /// Requests with this method to this path; `[param]` segments match any
+    /// value, like in route file paths
+    pub fn new(method: impl AsRef<str>, path: &str) -> Self {
+        Self {
+            method: method.as_ref().to_ascii_uppercase(),
+            path: path.to_string(),
+            segments: path
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(PathSegment::parse)
+                .collect(),
+            headers: Vec::new(),
+            query: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Carrying the header with exactly this value
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Carrying the query parameter with this (decoded) value
+    pub fn with_query_param(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// With exactly this body
+    pub fn with_body(mut self, body: impl AsRef<[u8]>) -> Self {
+        self.body = Some(Body::Exact(body.as_ref().to_vec()));
+        self
+    }
+
+    /// With a JSON body equal to `body`, regardless of formatting and key
+    /// order
+    pub fn with_json_body(mut self, body: &serde_json::Value) -> Self {
+        self.body = Some(Body::Json(body.clone()));
+        self
+    }
+
+    /// With a body containing `text`
+    pub fn with_body_containing(mut self, text: &str) -> Self {
+        self.body = Some(Body::Containing(text.to_string()));
+        self
+    }
+
+    /// Matcher from query parameters of `/__blendwerk/wait-for-request`:
+    /// `method` and `path`, any number of `header=Name:Value` and
+    /// `query=name=value`, and one of `body`, `body_json` or `body_contains`
+    pub fn from_params(params: &[(String, String)]) -> Result<Self, String> {
+        let param = |key: &str| {
+            params
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+                .ok_or_else(|| format!("missing parameter `{}`", key))
+        };
+        let mut matcher = Self::new(param("method")?, param("path")?);
+
+        for (name, value) in params {
+            matcher = match name.as_str() {
+                "method" | "path" => matcher,
+                "header" => {
+                    let (name, value) = value
+                        .split_once(':')
+                        .ok_or_else(|| format!("expected header=Name:Value, got `{}`", value))?;
+                    matcher.with_header(name.trim(), value.trim())
+                }
+                "query" => {
+                    let (name, value) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("expected query=name=value, got `{}`", value))?;
+                    matcher.with_query_param(name, value)
+                }
+                "body" => matcher.with_body(value),
+                "body_json" => matcher.with_json_body(
+                    &serde_json::from_str(value)
+                        .map_err(|e| format!("body_json is no valid JSON: {}", e))?,
+                ),
+                "body_contains" => matcher.with_body_containing(value),
+                other => return Err(format!("unknown parameter `{}`", other)),
+            };
+        }
+
+        Ok(matcher)
+    }
+
+    pub fn matches(&self, request: &ReceivedRequest) -> bool {
+        request.method.eq_ignore_ascii_case(&self.method)
+            && self.matches_path(&request.path)
+            && self.headers.iter().all(|(name, value)| {
+                request
+                    .headers
+                    .get_all(name.as_str())
+                    .iter()
+                    .any(|actual| actual == value.as_str())
+            })
+            && self.matches_query(request.query.as_deref().unwrap_or_default())
+            && self.body.as_ref().is_none_or(|body| match body {
+                Body::Exact(expected) => request.body == expected.as_slice(),
+                Body::Json(expected) => serde_json::from_slice::<serde_json::Value>(&request.body)
+                    .is_ok_and(|actual| actual == *expected),
+                Body::Containing(text) => {
+                    String::from_utf8_lossy(&request.body).contains(text.as_str())
+                }
+            })
+    }
+
+    fn matches_path(&self, path: &str) -> bool {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        segments.len() == self.segments.len()
+            && segments
+                .iter()
+                .zip(&self.segments)
+                .all(|(segment, pattern)| match pattern {
+                    PathSegment::Static(name) => name == segment,
+                    PathSegment::Dynamic(_) => true,
+                })
+    }
+
+    fn matches_query(&self, query: &str) -> bool {
+        if self.query.is_empty() {
+            return true;
+        }
+        let params: Vec<(String, String)> = serde_urlencoded::from_str(query).unwrap_or_default();
+        self.query.iter().all(|expected| params.contains(expected))
+    }
+}
+
+impl fmt::Display for RequestMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        for (name, value) in &self.query {
+            write!(f, " with query {}={}", name, value)?;
+        }
+        for (name, value) in &self.headers {
+            write!(f, " with header {}: {}", name, value)?;
+        }
+        match &self.body {
+            Some(Body::Exact(body)) => write!(f, " with body {:?}", String::from_utf8_lossy(body)),
+            Some(Body::Json(body)) => write!(f, " with JSON body {}", body),
+            Some(Body::Containing(text)) => write!(f, " with body containing {:?}", text),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Requests received so far, narrowed down by a [`RequestMatcher`]; the
+/// count assertions panic with a list of all received requests
+#[derive(Debug)]
+pub struct Received {
+    matcher: RequestMatcher,
+    requests: Vec<ReceivedRequest>,
+}
+
+impl Received {
+    pub(crate) fn new(matcher: RequestMatcher, requests: Vec<ReceivedRequest>) -> Self {
+        Self { matcher, requests }
+    }
+
+    pub fn with_header(self, name: &str, value: &str) -> Self {
+        self.map(|matcher| matcher.with_header(name, value))
+    }
+
+    pub fn with_query_param(self, name: &str, value: &str) -> Self {
+        self.map(|matcher| matcher.with_query_param(name, value))
+    }
+
+    pub fn with_body(self, body: impl AsRef<[u8]>) -> Self {
+        self.map(|matcher| matcher.with_body(body))
+    }
+
+    pub fn with_json_body(self, body: &serde_json::Value) -> Self {
+        self.map(|matcher| matcher.with_json_body(body))
+    }
+
+    pub fn with_body_containing(self, text: &str) -> Self {
+        self.map(|matcher| matcher.with_body_containing(text))
+    }
+
+    fn map(self, f: impl FnOnce(RequestMatcher) -> RequestMatcher) -> Self {
+        Self {
+            matcher: f(self.matcher),
+            requests: self.requests,
+        }
+    }
+
+    /// The matching requests, in order of arrival
+    pub fn requests(&self) -> Vec<&ReceivedRequest> {
+        self.requests
+            .iter()
+            .filter(|request| self.matcher.matches(request))
+            .collect()
+    }
+
+    pub fn count(&self) -> usize {
+        self.requests().len()
+    }
+
+    /// Assert exactly `expected` matching requests
+    #[track_caller]
+    pub fn times(&self, expected: usize) {
+        self.check(self.count() == expected, &format!("{}", expected));
+    }
+
+    #[track_caller]
+    pub fn once(&self) {
+        self.times(1);
+    }
+
+    #[track_caller]
+    pub fn never(&self) {
+        self.times(0);
+    }
+
+    #[track_caller]
+    pub fn at_least(&self, expected: usize) {
+        self.check(self.count() >= expected, &format!("at least {}", expected));
+    }
+
+    #[track_caller]
+    fn check(&self, ok: bool, expected: &str) {
+        if !ok {
+            panic!(
+                "expected {} requests {}, got {}\n{}",
+                expected,
+                self.matcher,
+                self.count(),
+                summary(&self.requests)
+            );
+        }
+    }
+}
+
+/// One line per request for failure messages
+pub(crate) fn summary(requests: &[ReceivedRequest]) -> String {
+    if requests.is_empty() {
+        return "no requests received".to_string();
+    }
+    let lines: Vec<String> = requests
+        .iter()
+        .map(|request| {
+            let query = request
+                .query
+                .as_ref()
+                .map(|query| format!("?{}", query))
+                .unwrap_or_default();
+            format!(
+                "  {} {}{} -> {}",
+                request.method, request.path, query, request.status
+            )
+        })
+        .collect();
+    format!("received requests:\n{}", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use bytes::Bytes;
+
+    fn request(method: &str, path: &str, query: Option<&str>, body: &str) -> ReceivedRequest {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-tenant", "acme".parse().unwrap());
+        ReceivedRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.map(String::from),
+            headers,
+            body: Bytes::from(body.to_string()),
+            matched_route: None,
+            status: 200,
+        }
+    }
+
+    #[test]
+    fn test_matcher() {
+        let order = request(
+            "POST",
+            "/orders",
+            Some("dry_run=true"),
+            r#"{"b": 1, "a": [2]}"#,
+        );
+
+        assert!(RequestMatcher::new("post", "/orders").matches(&order));
+        assert!(!RequestMatcher::new("GET", "/orders").matches(&order));
+        assert!(!RequestMatcher::new("POST", "/orders/1").matches(&order));
+        assert!(
+            RequestMatcher::new("POST", "/orders")
+                .with_json_body(&serde_json::json!({"a": [2], "b": 1}))
+                .with_header("X-Tenant", "acme")
+                .with_query_param("dry_run", "true")
+                .matches(&order)
+        );
+        assert!(
+            !RequestMatcher::new("POST", "/orders")
+                .with_header("x-tenant", "other")
+                .matches(&order)
+        );
+        assert!(
+            RequestMatcher::new("POST", "/orders")
+                .with_body_containing("\"b\"")
+                .matches(&order)
+        );
+
+        let item = request("GET", "/orders/42/items", None, "");
+        assert!(RequestMatcher::new("GET", "/orders/[id]/items").matches(&item));
+    }
+
+    #[test]
+    fn test_from_params() {
+        let params =
+            |query: &str| serde_urlencoded::from_str::<Vec<(String, String)>>(query).unwrap();
+        let order = request("POST", "/orders", Some("dry_run=true"), r#"{"b": 1}"#);
+
+        let matcher = RequestMatcher::from_params(&params(
+            "method=POST&path=/orders&header=X-Tenant:%20acme&query=dry_run=true&body_json=%7B%22b%22:1%7D",
+        ))
+        .unwrap();
+        assert!(matcher.matches(&order));
+        assert_eq!(
+            matcher.to_string(),
+            "POST /orders with query dry_run=true with header X-Tenant: acme with JSON body {\"b\":1}"
+        );
+
+        assert_eq!(
+            RequestMatcher::from_params(&params("path=/orders")).unwrap_err(),
+            "missing parameter `method`"
+        );
+        assert!(RequestMatcher::from_params(&params("method=GET&path=/&header=x")).is_err());
+        assert!(RequestMatcher::from_params(&params("method=GET&path=/&color=red")).is_err());
+    }
+
+    #[test]
+    fn test_count_assertions() {
+        let requests = vec![
+            request("POST", "/orders", None, "{}"),
+            request("POST", "/orders", None, "{}"),
+            request("GET", "/orders", None, ""),
+        ];
+        let received = Received::new(RequestMatcher::new("POST", "/orders"), requests);
+        received.times(2);
+        received.at_least(1);
+        received.with_body("[]").never();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 1 requests GET /orders with header x-tenant: other, got 0")]
+    fn test_failure_message() {
+        let requests = vec![request("GET", "/orders", None, "")];
+        Received::new(RequestMatcher::new("GET", "/orders"), requests)
+            .with_header("x-tenant", "other")
+            .once();
+    }
+}
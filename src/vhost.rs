@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Host header based virtual hosts (`--vhost HOST=DIR`).
+//!
+//! Each virtual host has its own mock directory. Requests whose host (the
+//! `Host` header or HTTP/2 authority, without port) names a virtual host are
+//! only matched against its routes, all others against the main directory.
+
+use crate::routes::{Route, ScanOptions, rescan_paths, scan_directory_with};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::info;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VirtualHost {
+    /// Lower case host name
+    pub host: String,
+    pub directory: PathBuf,
+}
+
+impl FromStr for VirtualHost {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (host, directory) = input
+            .split_once('=')
+            .ok_or_else(|| format!("expected HOST=DIR, got `{}`", input))?;
+        let host = host.trim();
+        if host.is_empty() || directory.is_empty() {
+            return Err(format!("expected HOST=DIR, got `{}`", input));
+        }
+        Ok(Self {
+            host: host.to_ascii_lowercase(),
+            directory: PathBuf::from(directory),
+        })
+    }
+}
+
+/// The virtual host a request to `authority` (`host[:port]`) is served by
+pub fn select<'a>(vhosts: &'a [VirtualHost], authority: &str) -> Option<&'a str> {
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => authority,
+    };
+    vhosts
+        .iter()
+        .find(|vhost| vhost.host.eq_ignore_ascii_case(host))
+        .map(|vhost| vhost.host.as_str())
+}
+
+/// Scan the main directory and the directories of all virtual hosts
+pub fn scan(directory: &Path, vhosts: &[VirtualHost], options: &ScanOptions) -> Result<Vec<Route>> {
+    let mut routes = scan_directory_with(directory, options)?;
+    for vhost in vhosts {
+        let mut vhost_routes = scan_directory_with(&vhost.directory, options)?;
+        info!("  {} routes for {}", vhost_routes.len(), vhost.host);
+        for route in &mut vhost_routes {
+            route.host = Some(vhost.host.clone());
+        }
+        routes.extend(vhost_routes);
+    }
+    Ok(routes)
+}
+
+/// Update routes from [`scan`] for changes at the `changed` paths, which may
+/// lie in any of the directories
+pub fn rescan(
+    directory: &Path,
+    vhosts: &[VirtualHost],
+    options: &ScanOptions,
+    changed: &[PathBuf],
+    routes: &mut Vec<Route>,
+) -> Result<()> {
+    rescan_paths(directory, None, changed, options, routes)?;
+    for vhost in vhosts {
+        rescan_paths(
+            &vhost.directory,
+            Some(&vhost.host),
+            changed,
+            options,
+            routes,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        let vhost: VirtualHost = "API.example.test=./mocks/api".parse().unwrap();
+        assert_eq!(vhost.host, "api.example.test");
+        assert_eq!(vhost.directory, PathBuf::from("./mocks/api"));
+        assert!("api.example.test".parse::<VirtualHost>().is_err());
+        assert!("=./mocks".parse::<VirtualHost>().is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let vhosts: Vec<VirtualHost> = vec!["api.example.test=a".parse().unwrap()];
+        assert_eq!(
+            select(&vhosts, "api.example.test"),
+            Some("api.example.test")
+        );
+        assert_eq!(
+            select(&vhosts, "API.example.test:8080"),
+            Some("api.example.test")
+        );
+        assert_eq!(select(&vhosts, "auth.example.test"), None);
+        assert_eq!(select(&vhosts, "[::1]:8080"), None);
+    }
+
+    #[test]
+    fn test_scan() {
+        let temp_dir = TempDir::new().unwrap();
+        let main = temp_dir.path().join("main");
+        let api = temp_dir.path().join("api");
+        fs::create_dir_all(&main).unwrap();
+        fs::create_dir_all(&api).unwrap();
+        fs::write(main.join("GET.json"), "{}").unwrap();
+        fs::write(api.join("GET.json"), "[]").unwrap();
+
+        let vhosts = vec![VirtualHost {
+            host: "api.example.test".to_string(),
+            directory: api,
+        }];
+        let routes = scan(&main, &vhosts, &ScanOptions::default()).unwrap();
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].host, None);
+        assert_eq!(routes[1].host.as_deref(), Some("api.example.test"));
+    }
+}
@@ -6,55 +6,116 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use crate::routes::scan_directory;
-use crate::server::{SharedRoutes, ShutdownSignal};
-use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::PathBuf;
+use crate::fixture_changes::ChangedFixtures;
+use crate::http3;
+use crate::reload_events::{ReloadEvent, ReloadEvents};
+use crate::routes::ScanOptions;
+use crate::server::{RoutesReadBodies, SharedRoutes, ShutdownSignal};
+use crate::tls;
+use crate::vhost::{self, VirtualHost};
+use axum_server::tls_rustls::RustlsConfig;
+use notify::{Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::time::sleep;
 use tracing::{error, info};
 
+/// Told about every successful route reload
+pub struct ReloadListeners {
+    /// Marks routes for `--mark-changed-fixtures`
+    pub changed_fixtures: Option<Arc<ChangedFixtures>>,
+    pub events: Arc<ReloadEvents>,
+    pub routes_read_bodies: RoutesReadBodies,
+}
+
+/// Reload routes whenever files in the mock directories change, polling
+/// every `poll_interval` instead of relying on file system events if given
 pub async fn watch_directory(
     dir: PathBuf,
+    vhosts: Vec<VirtualHost>,
+    options: ScanOptions,
+    poll_interval: Option<Duration>,
     routes: SharedRoutes,
+    listeners: ReloadListeners,
     mut shutdown: ShutdownSignal,
 ) -> anyhow::Result<()> {
     let (tx, mut rx) = mpsc::channel(100);
 
-    let mut watcher = RecommendedWatcher::new(
-        move |res: Result<Event, notify::Error>| {
-            if let Ok(event) = res {
-                if event.kind.is_modify() || event.kind.is_create() || event.kind.is_remove() {
-                    let _ = tx.blocking_send(());
-                }
-            }
-        },
-        notify::Config::default(),
-    )?;
+    let handler = move |res: Result<Event, notify::Error>| {
+        if let Ok(event) = res
+            && (event.kind.is_modify()
+                || event.kind.is_create()
+                || event.kind.is_remove()
+                || event.need_rescan())
+        {
+            let _ = tx.blocking_send(event);
+        }
+    };
+    let mut watcher: Box<dyn Watcher + Send> = match poll_interval {
+        Some(interval) => Box::new(PollWatcher::new(
+            handler,
+            notify::Config::default().with_poll_interval(interval),
+        )?),
+        None => Box::new(RecommendedWatcher::new(handler, notify::Config::default())?),
+    };
+    let how = match poll_interval {
+        Some(interval) => format!("polling every {:?}", interval),
+        None => "for changes".to_string(),
+    };
 
     watcher.watch(&dir, RecursiveMode::Recursive)?;
-
-    info!("  Watching {} for changes", dir.display());
+    info!("  Watching {} {}", dir.display(), how);
+    for vhost in &vhosts {
+        watcher.watch(&vhost.directory, RecursiveMode::Recursive)?;
+        info!("  Watching {} {}", vhost.directory.display(), how);
+    }
 
     // Keep watcher alive and process events
     loop {
         tokio::select! {
-            Some(()) = rx.recv() => {
+            Some(event) = rx.recv() => {
                 // Debounce: wait a bit for multiple rapid changes
                 sleep(Duration::from_millis(100)).await;
 
-                // Drain any additional events
-                while rx.try_recv().is_ok() {}
+                // Collect the paths of any additional events
+                let mut events = vec![event];
+                while let Ok(event) = rx.try_recv() {
+                    events.push(event);
+                }
 
-                // Rebuild routes
-                match scan_directory(&dir) {
+                // Re-parse the changed files only, unless notify lost track
+                // of what changed
+                let result = if events.iter().any(Event::need_rescan) {
+                    vhost::scan(&dir, &vhosts, &options)
+                } else {
+                    let changed: Vec<PathBuf> =
+                        events.into_iter().flat_map(|event| event.paths).collect();
+                    let mut new_routes = routes.read().await.clone();
+                    vhost::rescan(&dir, &vhosts, &options, &changed, &mut new_routes)
+                        .map(|()| new_routes)
+                };
+
+                match result {
                     Ok(new_routes) => {
                         let count = new_routes.len();
                         let mut routes_guard = routes.write().await;
+                        let changed = listeners
+                            .changed_fixtures
+                            .as_ref()
+                            .map(|changes| changes.record(&routes_guard, &new_routes));
+                        let event = ReloadEvent::between(&routes_guard, &new_routes);
                         *routes_guard = new_routes;
+                        listeners.routes_read_bodies.update(&routes_guard);
                         drop(routes_guard);
-                        info!("  Reloaded {} routes", count);
+                        listeners.events.publish(event);
+                        match changed {
+                            Some(changed) => {
+                                info!("  Reloaded {} routes, {} changed", count, changed)
+                            }
+                            None => info!("  Reloaded {} routes", count),
+                        }
                     }
                     Err(e) => {
                         error!("  Error reloading routes: {}", e);
@@ -69,3 +130,77 @@ pub async fn watch_directory(
 
     Ok(())
 }
+
+/// Reload custom certificate files into the running HTTPS (and HTTP/3)
+/// listeners whenever they change.
+///
+/// The directories containing the files are watched rather than the files
+/// themselves, since certificate tools usually replace them by renaming. A
+/// pair that fails to load, e.g. because only the certificate was written so
+/// far, leaves the previous certificate in place until the next change.
+pub async fn watch_certificates(
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    tls: RustlsConfig,
+    http3: Option<quinn::Endpoint>,
+    mut shutdown: ShutdownSignal,
+) -> anyhow::Result<()> {
+    let (tx, mut rx) = mpsc::channel(100);
+
+    let files = [absolute(&cert_file), absolute(&key_file)];
+    let watched = files.clone();
+    let mut watcher = RecommendedWatcher::new(
+        move |res: Result<Event, notify::Error>| {
+            if let Ok(event) = res
+                && (event.kind.is_modify() || event.kind.is_create())
+                && event.paths.iter().any(|path| watched.contains(path))
+            {
+                let _ = tx.blocking_send(());
+            }
+        },
+        notify::Config::default(),
+    )?;
+
+    for file in &files {
+        if let Some(dir) = file.parent() {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+    }
+
+    info!(
+        "  Watching {} and {} for changes",
+        cert_file.display(),
+        key_file.display()
+    );
+
+    loop {
+        tokio::select! {
+            Some(()) = rx.recv() => {
+                // Debounce: certificate and key are usually written together
+                sleep(Duration::from_millis(100)).await;
+                while rx.try_recv().is_ok() {}
+
+                if let Err(e) = tls::reload_custom_config(&tls, &cert_file, &key_file).await {
+                    error!("  Error reloading certificate: {:#}", e);
+                    continue;
+                }
+                if let Some(endpoint) = &http3
+                    && let Err(e) = http3::reload(endpoint, &tls)
+                {
+                    error!("  Error reloading HTTP/3 certificate: {}", e);
+                }
+                info!("  Reloaded certificate from {}", cert_file.display());
+            }
+            _ = shutdown.changed() => {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Notify reports absolute paths, which relative arguments have to match
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
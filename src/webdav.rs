@@ -0,0 +1,201 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use anyhow::{Result, bail};
+use axum::http::StatusCode;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Namespaces CalDAV and CardDAV clients expect under their usual prefixes,
+/// declared when used unless `namespaces` maps the prefix differently
+const WELL_KNOWN_NAMESPACES: &[(&str, &str)] = &[
+    ("C", "urn:ietf:params:xml:ns:caldav"),
+    ("CARD", "urn:ietf:params:xml:ns:carddav"),
+    ("CS", "http://calendarserver.org/ns/"),
+    ("A", "http://apple.com/ns/ical/"),
+];
+
+/// One `<D:response>` element of a WebDAV multistatus body
+#[derive(Debug, Clone, Deserialize)]
+pub struct MultistatusEntry {
+    pub href: String,
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub props: BTreeMap<String, String>,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+/// Render a `207 Multi-Status` XML body from the given entries.
+///
+/// Property names without a namespace prefix are placed in the `DAV:`
+/// namespace. Other prefixes are declared from `namespaces`, falling back
+/// to the CalDAV and CardDAV ones; unknown prefixes are an error, as the
+/// XML would be invalid. Property values are inserted verbatim, so nested
+/// XML such as `<D:collection/>` can be used for `resourcetype`.
+pub fn render_multistatus(
+    entries: &[MultistatusEntry],
+    namespaces: &BTreeMap<String, String>,
+) -> Result<String> {
+    let mut declarations = BTreeMap::new();
+    for (prefix, uri) in namespaces {
+        declarations.insert(prefix.as_str(), uri.as_str());
+    }
+    for name in entries.iter().flat_map(|entry| entry.props.keys()) {
+        let Some((prefix, _)) = name.split_once(':') else {
+            continue;
+        };
+        if prefix == "D" || declarations.contains_key(prefix) {
+            continue;
+        }
+        match WELL_KNOWN_NAMESPACES
+            .iter()
+            .find(|(known, _)| *known == prefix)
+        {
+            Some((known, uri)) => {
+                declarations.insert(known, uri);
+            }
+            None => bail!(
+                "namespace prefix `{}` of property `{}` is not declared in `namespaces`",
+                prefix,
+                name
+            ),
+        }
+    }
+    // Values may use well-known prefixes without any property doing so
+    let values: Vec<&String> = entries
+        .iter()
+        .flat_map(|entry| entry.props.values())
+        .collect();
+    for (prefix, uri) in WELL_KNOWN_NAMESPACES {
+        let tag = format!("<{}:", prefix);
+        if !declarations.contains_key(prefix) && values.iter().any(|value| value.contains(&tag)) {
+            declarations.insert(prefix, uri);
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<D:multistatus xmlns:D=\"DAV:\"");
+    for (prefix, uri) in &declarations {
+        xml.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape_attribute(uri)));
+    }
+    xml.push_str(">\n");
+
+    for entry in entries {
+        xml.push_str("  <D:response>\n");
+        xml.push_str(&format!("    <D:href>{}</D:href>\n", escape(&entry.href)));
+        xml.push_str("    <D:propstat>\n");
+        xml.push_str("      <D:prop>\n");
+
+        for (name, value) in &entry.props {
+            let name = if name.contains(':') {
+                name.clone()
+            } else {
+                format!("D:{}", name)
+            };
+
+            if value.is_empty() {
+                xml.push_str(&format!("        <{}/>\n", name));
+            } else {
+                xml.push_str(&format!("        <{}>{}</{}>\n", name, value, name));
+            }
+        }
+
+        xml.push_str("      </D:prop>\n");
+        xml.push_str(&format!(
+            "      <D:status>HTTP/1.1 {}</D:status>\n",
+            status_line(entry.status)
+        ));
+        xml.push_str("    </D:propstat>\n");
+        xml.push_str("  </D:response>\n");
+    }
+
+    xml.push_str("</D:multistatus>\n");
+    Ok(xml)
+}
+
+fn status_line(status: u16) -> String {
+    let reason = StatusCode::from_u16(status)
+        .ok()
+        .and_then(|code| code.canonical_reason())
+        .unwrap_or("");
+
+    format!("{} {}", status, reason).trim_end().to_string()
+}
+
+fn escape_attribute(text: &str) -> String {
+    escape(text).replace('"', "&quot;")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_multistatus() {
+        let entries = vec![
+            MultistatusEntry {
+                href: "/files/".to_string(),
+                status: 200,
+                props: BTreeMap::from([
+                    ("displayname".to_string(), "files".to_string()),
+                    ("resourcetype".to_string(), "<D:collection/>".to_string()),
+                ]),
+            },
+            MultistatusEntry {
+                href: "/files/a&b.txt".to_string(),
+                status: 404,
+                props: BTreeMap::from([("C:calendar-data".to_string(), String::new())]),
+            },
+        ];
+
+        let xml = render_multistatus(&entries, &BTreeMap::new()).unwrap();
+
+        assert!(xml.contains(
+            "<D:multistatus xmlns:D=\"DAV:\" xmlns:C=\"urn:ietf:params:xml:ns:caldav\">"
+        ));
+        assert!(xml.contains("<D:displayname>files</D:displayname>"));
+        assert!(xml.contains("<D:resourcetype><D:collection/></D:resourcetype>"));
+        assert!(xml.contains("<D:href>/files/a&amp;b.txt</D:href>"));
+        assert!(xml.contains("<C:calendar-data/>"));
+        assert!(xml.contains("<D:status>HTTP/1.1 404 Not Found</D:status>"));
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let entries = vec![MultistatusEntry {
+            href: "/contacts/".to_string(),
+            status: 200,
+            props: BTreeMap::from([
+                ("X:color".to_string(), "red".to_string()),
+                (
+                    "resourcetype".to_string(),
+                    "<D:collection/><CARD:addressbook/>".to_string(),
+                ),
+            ]),
+        }];
+        let namespaces = BTreeMap::from([("X".to_string(), "urn:example".to_string())]);
+
+        let xml = render_multistatus(&entries, &namespaces).unwrap();
+        assert!(xml.contains(
+            "xmlns:D=\"DAV:\" xmlns:CARD=\"urn:ietf:params:xml:ns:carddav\" xmlns:X=\"urn:example\">"
+        ));
+
+        let error = render_multistatus(&entries, &BTreeMap::new()).unwrap_err();
+        assert!(error.to_string().contains("prefix `X`"));
+    }
+}
@@ -0,0 +1,267 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::delay::Delay;
+use crate::server::ShutdownSignal;
+use anyhow::{Context, Result};
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures_util::StreamExt;
+use futures_util::stream::{self, BoxStream, SelectAll};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// File stem of WebSocket route files (`WS.yaml`)
+pub const METHOD_NAME: &str = "WS";
+
+/// Scripted WebSocket conversation defined in a `WS.yaml` file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WsScript {
+    /// Additional headers sent with the handshake response
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Steps performed right after the connection is established
+    #[serde(default)]
+    pub on_connect: Vec<WsStep>,
+    /// Replies to incoming messages; the first matching rule wins
+    #[serde(default)]
+    pub messages: Vec<WsRule>,
+    /// Messages sent periodically, independent of incoming messages
+    #[serde(default)]
+    pub push: Vec<WsPush>,
+}
+
+/// A single action: wait, then send a message and/or close the connection
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WsStep {
+    #[serde(default)]
+    pub delay: Delay,
+    #[serde(default)]
+    pub send: Option<String>,
+    #[serde(default)]
+    pub close: Option<WsClose>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WsRule {
+    /// Text the incoming message has to match; JSON is compared
+    /// structurally. Without `expect`, the rule matches any message.
+    #[serde(default)]
+    pub expect: Option<String>,
+    #[serde(default)]
+    pub reply: Vec<WsStep>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WsPush {
+    /// Interval between pushes in milliseconds
+    pub every: u64,
+    pub send: String,
+    /// Stop after this many pushes
+    #[serde(default)]
+    pub times: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WsClose {
+    #[serde(default = "default_close_code")]
+    pub code: u16,
+    #[serde(default)]
+    pub reason: String,
+}
+
+fn default_close_code() -> u16 {
+    1000
+}
+
+/// Close code sent when blendwerk shuts down during a conversation
+const GOING_AWAY: u16 = 1001;
+
+pub fn parse_script(content: &str) -> Result<WsScript> {
+    if content.trim().is_empty() {
+        return Ok(WsScript::default());
+    }
+    serde_yaml::from_str(content).context("Failed to parse WebSocket script")
+}
+
+impl WsRule {
+    fn matches(&self, message: &str) -> bool {
+        let Some(expected) = &self.expect else {
+            return true;
+        };
+        if expected == message {
+            return true;
+        }
+
+        // Compare JSON structurally, so formatting differences don't matter
+        match (
+            serde_json::from_str::<serde_json::Value>(expected),
+            serde_json::from_str::<serde_json::Value>(message),
+        ) {
+            (Ok(expected), Ok(actual)) => expected == actual,
+            _ => false,
+        }
+    }
+}
+
+/// Whether the conversation continues after a step
+enum Flow {
+    Continue,
+    Closed,
+}
+
+/// Run the script on an established connection until either side closes it
+/// or the server shuts down
+pub async fn run(script: &WsScript, mut socket: WebSocket, mut shutdown: ShutdownSignal) {
+    if let Flow::Closed = perform(&script.on_connect, &mut socket).await {
+        return;
+    }
+
+    let mut pushes: SelectAll<BoxStream<'static, String>> =
+        stream::select_all(script.push.iter().map(push_stream));
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text.to_string(),
+                    Some(Ok(Message::Binary(bytes))) => String::from_utf8_lossy(&bytes).into_owned(),
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    // Pings are answered automatically
+                    Some(Ok(_)) => continue,
+                };
+
+                if let Some(rule) = script.messages.iter().find(|rule| rule.matches(&text))
+                    && let Flow::Closed = perform(&rule.reply, &mut socket).await
+                {
+                    return;
+                }
+            }
+            Some(text) = pushes.next() => {
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    return;
+                }
+            }
+            _ = async { let _ = shutdown.wait_for(|stopped| *stopped).await; } => {
+                let _ = close(&mut socket, GOING_AWAY, "server shutting down").await;
+                return;
+            }
+        }
+    }
+}
+
+async fn perform(steps: &[WsStep], socket: &mut WebSocket) -> Flow {
+    for step in steps {
+        let delay = step.delay.sample();
+        if delay > 0 {
+            sleep(Duration::from_millis(delay)).await;
+        }
+
+        if let Some(text) = &step.send
+            && socket
+                .send(Message::Text(text.as_str().into()))
+                .await
+                .is_err()
+        {
+            return Flow::Closed;
+        }
+
+        if let Some(frame) = &step.close {
+            let _ = close(socket, frame.code, &frame.reason).await;
+            return Flow::Closed;
+        }
+    }
+
+    Flow::Continue
+}
+
+async fn close(socket: &mut WebSocket, code: u16, reason: &str) -> Result<(), axum::Error> {
+    socket
+        .send(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        })))
+        .await
+}
+
+fn push_stream(push: &WsPush) -> BoxStream<'static, String> {
+    let interval = Duration::from_millis(push.every.max(1));
+    let text = push.send.clone();
+
+    let ticks = stream::unfold((), move |_| async move {
+        sleep(interval).await;
+        Some(((), ()))
+    });
+
+    match push.times {
+        Some(times) => ticks
+            .take(times as usize)
+            .map(move |_| text.clone())
+            .boxed(),
+        None => ticks.map(move |_| text.clone()).boxed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let script = parse_script(
+            r#"
+on_connect:
+  - send: '{"type": "welcome"}'
+messages:
+  - expect: '{"type": "ping"}'
+    reply:
+      - send: '{"type": "pong"}'
+  - expect: bye
+    reply:
+      - send: see you
+        delay: 100
+      - close: { code: 4000, reason: done }
+push:
+  - every: 1000
+    send: tick
+    times: 3
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(script.on_connect.len(), 1);
+        assert_eq!(script.messages.len(), 2);
+        let close = script.messages[1].reply[1].close.as_ref().unwrap();
+        assert_eq!(close.code, 4000);
+        assert_eq!(script.push[0].times, Some(3));
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let json_rule = WsRule {
+            expect: Some(r#"{"type": "ping", "id": 1}"#.to_string()),
+            reply: vec![],
+        };
+        assert!(json_rule.matches(r#"{"id":1,"type":"ping"}"#));
+        assert!(!json_rule.matches(r#"{"id":2,"type":"ping"}"#));
+
+        let text_rule = WsRule {
+            expect: Some("hello".to_string()),
+            reply: vec![],
+        };
+        assert!(text_rule.matches("hello"));
+        assert!(!text_rule.matches("hello!"));
+
+        let catch_all = WsRule {
+            expect: None,
+            reply: vec![],
+        };
+        assert!(catch_all.matches("anything"));
+    }
+}
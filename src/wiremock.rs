@@ -0,0 +1,289 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Translation of WireMock stub mappings into blendwerk routes.
+//!
+//! Stub files are read from the `mappings/` directory in the mock root, and
+//! `bodyFileName` references are resolved against `__files/`, mirroring
+//! WireMock's own layout.
+
+use crate::delay::Delay;
+use crate::frontmatter::{HeaderValues, ParsedResponse, ResponseMeta};
+use crate::routes::{HttpMethod, PathSegment, Route};
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+pub const MAPPINGS_DIR: &str = "mappings";
+pub const FILES_DIR: &str = "__files";
+
+/// Methods a WireMock `ANY` stub is expanded to
+const ANY_METHODS: &[HttpMethod] = &[
+    HttpMethod::Get,
+    HttpMethod::Post,
+    HttpMethod::Put,
+    HttpMethod::Delete,
+    HttpMethod::Patch,
+    HttpMethod::Head,
+    HttpMethod::Options,
+];
+
+/// A mapping file holds either a single stub or a `mappings` array
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MappingFile {
+    Many { mappings: Vec<StubMapping> },
+    Single(Box<StubMapping>),
+}
+
+#[derive(Debug, Deserialize)]
+struct StubMapping {
+    request: StubRequest,
+    #[serde(default)]
+    response: StubResponse,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StubRequest {
+    #[serde(default = "default_method")]
+    method: String,
+    url: Option<String>,
+    url_path: Option<String>,
+    url_pattern: Option<String>,
+    url_path_pattern: Option<String>,
+}
+
+fn default_method() -> String {
+    "ANY".to_string()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StubResponse {
+    status: Option<u16>,
+    #[serde(default)]
+    headers: HashMap<String, Value>,
+    body: Option<String>,
+    json_body: Option<Value>,
+    body_file_name: Option<String>,
+    fixed_delay_milliseconds: Option<u64>,
+}
+
+/// Check whether a file is a WireMock stub rather than a blendwerk route file
+pub fn is_mapping_file(base_dir: &Path, file_path: &Path) -> bool {
+    let is_json = file_path.extension().and_then(|s| s.to_str()) == Some("json");
+    let stem = file_path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+
+    is_json
+        && file_path.parent() == Some(base_dir.join(MAPPINGS_DIR).as_path())
+        && HttpMethod::from_str(stem).is_none()
+}
+
+/// Parse a WireMock mapping file into routes.
+///
+/// Stubs using features blendwerk cannot express (regex URL patterns,
+/// unknown methods) are skipped with a warning instead of failing the scan.
+pub fn parse_mapping_file(base_dir: &Path, file_path: &Path) -> Result<Vec<Route>> {
+    let content = fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+    let mapping_file: MappingFile = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse WireMock mapping: {}", file_path.display()))?;
+
+    let mappings = match mapping_file {
+        MappingFile::Many { mappings } => mappings,
+        MappingFile::Single(mapping) => vec![*mapping],
+    };
+
+    let mut routes = Vec::new();
+    for mapping in mappings {
+        match translate(base_dir, file_path, mapping) {
+            Ok(translated) => routes.extend(translated),
+            Err(e) => warn!("Skipping WireMock stub in {}: {}", file_path.display(), e),
+        }
+    }
+
+    Ok(routes)
+}
+
+fn translate(base_dir: &Path, file_path: &Path, mapping: StubMapping) -> Result<Vec<Route>> {
+    let request = mapping.request;
+    let response = mapping.response;
+
+    if request.url_pattern.is_some() || request.url_path_pattern.is_some() {
+        anyhow::bail!("regex URL patterns are not supported");
+    }
+
+    let url = request
+        .url
+        .or(request.url_path)
+        .ok_or_else(|| anyhow::anyhow!("stub has no url or urlPath"))?;
+    let path = url.split('?').next().unwrap_or("/");
+    let path_segments: Vec<PathSegment> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| PathSegment::Static(s.to_string()))
+        .collect();
+
+    let methods = if request.method.eq_ignore_ascii_case("ANY") {
+        ANY_METHODS.to_vec()
+    } else {
+        vec![
+            HttpMethod::from_str(&request.method)
+                .ok_or_else(|| anyhow::anyhow!("unsupported method {}", request.method))?,
+        ]
+    };
+
+    let (body, content_type) = if let Some(json_body) = &response.json_body {
+        (json_body.to_string(), "application/json")
+    } else if let Some(file_name) = &response.body_file_name {
+        let body_path = base_dir.join(FILES_DIR).join(file_name);
+        let body = fs::read_to_string(&body_path)
+            .with_context(|| format!("failed to read body file {}", body_path.display()))?;
+        (body, "text/plain")
+    } else {
+        (response.body.clone().unwrap_or_default(), "text/plain")
+    };
+
+    let headers = response
+        .headers
+        .iter()
+        .map(|(name, value)| (name.clone(), header_value(value)))
+        .collect();
+
+    let meta = ResponseMeta {
+        status: Some(response.status.unwrap_or(200)),
+        headers,
+        delay: Delay::Fixed(response.fixed_delay_milliseconds.unwrap_or(0)),
+        ..ResponseMeta::default()
+    };
+
+    Ok(methods
+        .into_iter()
+        .map(|method| Route {
+            method,
+            path_segments: path_segments.clone(),
+            response: ParsedResponse {
+                meta: meta.clone(),
+                body: Bytes::from(body.clone()),
+            },
+            content_type: content_type.to_string(),
+            body_file: None,
+            websocket: None,
+            script: None,
+            plugin: None,
+            graphql_operation: None,
+            source: file_path.to_path_buf(),
+            modified: fs::metadata(file_path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            host: None,
+            includes: Vec::new(),
+        })
+        .collect())
+}
+
+/// Header values of a mapping; arrays send the header once per element
+fn header_value(value: &Value) -> HeaderValues {
+    let text = |value: &Value| match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    match value {
+        Value::Array(values) => HeaderValues::Many(values.iter().map(text).collect()),
+        other => HeaderValues::One(text(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::scan_directory;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_single_and_multiple_mappings() {
+        let temp_dir = TempDir::new().unwrap();
+        let mappings = temp_dir.path().join(MAPPINGS_DIR);
+        let files = temp_dir.path().join(FILES_DIR);
+        fs::create_dir_all(&mappings).unwrap();
+        fs::create_dir_all(&files).unwrap();
+
+        fs::write(
+            mappings.join("user.json"),
+            r#"{
+                "request": { "method": "GET", "url": "/users/1?ignored=true" },
+                "response": {
+                    "status": 201,
+                    "jsonBody": { "id": 1 },
+                    "headers": { "X-Custom": "value" },
+                    "fixedDelayMilliseconds": 50
+                }
+            }"#,
+        )
+        .unwrap();
+        fs::write(
+            mappings.join("more.json"),
+            r#"{
+                "mappings": [
+                    {
+                        "request": { "method": "POST", "urlPath": "/upload" },
+                        "response": { "bodyFileName": "upload.txt" }
+                    },
+                    {
+                        "request": { "method": "GET", "urlPattern": "/regex/.*" },
+                        "response": { "status": 200 }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        fs::write(files.join("upload.txt"), "uploaded").unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        assert_eq!(routes.len(), 2);
+
+        let user = routes
+            .iter()
+            .find(|r| r.display_path() == "/users/1")
+            .unwrap();
+        assert_eq!(user.method, HttpMethod::Get);
+        assert_eq!(user.response.meta.status(), 201);
+        assert_eq!(user.response.meta.delay, 50);
+        assert_eq!(user.response.body, r#"{"id":1}"#);
+        assert_eq!(user.content_type, "application/json");
+
+        let upload = routes
+            .iter()
+            .find(|r| r.display_path() == "/upload")
+            .unwrap();
+        assert_eq!(upload.method, HttpMethod::Post);
+        assert_eq!(upload.response.body, "uploaded");
+    }
+
+    #[test]
+    fn test_any_method_expands() {
+        let temp_dir = TempDir::new().unwrap();
+        let mappings = temp_dir.path().join(MAPPINGS_DIR);
+        fs::create_dir_all(&mappings).unwrap();
+        fs::write(
+            mappings.join("any.json"),
+            r#"{ "request": { "url": "/health" }, "response": { "body": "ok" } }"#,
+        )
+        .unwrap();
+
+        let routes = scan_directory(temp_dir.path()).unwrap();
+        assert_eq!(routes.len(), ANY_METHODS.len());
+        assert!(routes.iter().all(|r| r.response.body == "ok"));
+    }
+}
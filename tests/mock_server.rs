@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) 2025 Jakob Westhoff <jakob@westhoffswelt.de>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use blendwerk::{MockServer, RequestMatcher, Stub};
+use std::fs;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Send an HTTP/1.1 request and return the raw response
+async fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn test_directory_stubs_and_received_requests() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("users/[id]")).unwrap();
+    fs::write(temp_dir.path().join("users/[id]/GET.json"), r#"{"id": 1}"#).unwrap();
+
+    let server = MockServer::builder()
+        .directory(temp_dir.path())
+        .stub(
+            Stub::post("/users")
+                .status(201)
+                .json(&serde_json::json!({"id": 2})),
+        )
+        .start()
+        .await
+        .unwrap();
+    assert!(server.url().starts_with("http://127.0.0.1:"));
+
+    let response = request(server.address(), "GET", "/users/1?full=1", "").await;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with(r#"{"id": 1}"#));
+
+    let response = request(server.address(), "POST", "/users", "{}").await;
+    assert!(response.starts_with("HTTP/1.1 201"));
+    assert!(response.contains("content-type: application/json"));
+
+    // Stubs added later win over route files
+    server
+        .stub(Stub::get("/users/[id]").status(503).body("down"))
+        .await;
+    let response = request(server.address(), "GET", "/users/7", "").await;
+    assert!(response.starts_with("HTTP/1.1 503"));
+
+    let received = server.received_requests();
+    assert_eq!(received.len(), 3);
+    assert_eq!(received[0].path, "/users/1");
+    assert_eq!(received[0].query.as_deref(), Some("full=1"));
+    assert_eq!(received[0].matched_route.as_deref(), Some("/users/:id"));
+    assert_eq!(received[1].method, "POST");
+    assert_eq!(received[1].body, "{}");
+    assert_eq!(received[2].status, 503);
+
+    server.clear_received_requests();
+    assert!(server.received_requests().is_empty());
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_assertions_and_waiting() {
+    let server = MockServer::builder()
+        .stub(Stub::post("/orders").status(201))
+        .start()
+        .await
+        .unwrap();
+
+    let address = server.address();
+    let client = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        request(address, "POST", "/orders?dry_run=1", r#"{"item": "book"}"#).await;
+        request(address, "POST", "/orders", r#"{ "item":"book" }"#).await;
+    });
+
+    let matcher = RequestMatcher::new("POST", "/orders").with_query_param("dry_run", "1");
+    let first = server
+        .wait_for_request(matcher, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(first.status, 201);
+    client.await.unwrap();
+
+    server
+        .received("POST", "/orders")
+        .with_json_body(&serde_json::json!({"item": "book"}))
+        .times(2);
+    server.received("GET", "/orders").never();
+
+    let missing = RequestMatcher::new("DELETE", "/orders/[id]");
+    let error = server
+        .wait_for_request(missing, Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("POST /orders?dry_run=1 -> 201"));
+}
+
+#[tokio::test]
+async fn test_wait_for_request_endpoint() {
+    let server = MockServer::builder()
+        .stub(Stub::post("/orders").status(201))
+        .start()
+        .await
+        .unwrap();
+    let address = server.address();
+
+    let waiting = tokio::spawn(async move {
+        request(
+            address,
+            "GET",
+            "/__blendwerk/wait-for-request?method=POST&path=/orders&body_contains=book&timeout=5000",
+            "",
+        )
+        .await
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    request(address, "POST", "/orders", r#"{"item": "book"}"#).await;
+
+    let response = waiting.await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let waited: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(waited["method"], "POST");
+    assert_eq!(waited["body"], r#"{"item": "book"}"#);
+    assert_eq!(waited["status"], 201);
+
+    // Admin requests are not recorded, and the history can be cleared
+    let response = request(address, "DELETE", "/__blendwerk/requests", "").await;
+    assert!(response.starts_with("HTTP/1.1 204"));
+    assert!(server.received_requests().is_empty());
+
+    let response = request(
+        address,
+        "GET",
+        "/__blendwerk/wait-for-request?method=POST&path=/orders&timeout=50",
+        "",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 404"));
+    assert!(response.contains(r#""received":0"#));
+
+    let response = request(address, "GET", "/__blendwerk/wait-for-request?path=/", "").await;
+    assert!(response.starts_with("HTTP/1.1 400"));
+
+    let response = request(
+        address,
+        "GET",
+        "/__blendwerk/wait-for-request?method=POST&path=/orders&timeout=18446744073709551615",
+        "",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 400"));
+    assert!(response.contains("timeout may be at most 3600000 ms"));
+}